@@ -19,5 +19,33 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_prost_build::configure()
         .file_descriptor_set_path(out_dir.join("games_descriptor.bin"))
         .compile_protos(&["proto/games.proto"], &["proto/"])?;
+
+    tonic_prost_build::configure()
+        .file_descriptor_set_path(out_dir.join("content_descriptor.bin"))
+        .compile_protos(&["proto/content.proto"], &["proto/"])?;
+
+    tonic_prost_build::configure()
+        .file_descriptor_set_path(out_dir.join("tracking_descriptor.bin"))
+        .compile_protos(&["proto/tracking.proto"], &["proto/"])?;
+
+    tonic_prost_build::configure()
+        .file_descriptor_set_path(out_dir.join("news_descriptor.bin"))
+        .compile_protos(&["proto/news.proto"], &["proto/"])?;
+
+    tonic_prost_build::configure()
+        .file_descriptor_set_path(out_dir.join("challenges_descriptor.bin"))
+        .compile_protos(&["proto/challenges.proto"], &["proto/"])?;
+
+    tonic_prost_build::configure()
+        .file_descriptor_set_path(out_dir.join("ladder_descriptor.bin"))
+        .compile_protos(&["proto/ladder.proto"], &["proto/"])?;
+
+    tonic_prost_build::configure()
+        .file_descriptor_set_path(out_dir.join("notifications_descriptor.bin"))
+        .compile_protos(&["proto/notifications.proto"], &["proto/"])?;
+
+    tonic_prost_build::configure()
+        .file_descriptor_set_path(out_dir.join("nat_descriptor.bin"))
+        .compile_protos(&["proto/nat.proto"], &["proto/"])?;
     Ok(())
 }
@@ -14,3 +14,31 @@ pub mod games {
     tonic::include_proto!("games"); // The string specified here must match the proto package name
     pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("games_descriptor");
 }
+pub mod content {
+    tonic::include_proto!("content"); // The string specified here must match the proto package name
+    pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("content_descriptor");
+}
+pub mod tracking {
+    tonic::include_proto!("tracking"); // The string specified here must match the proto package name
+    pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("tracking_descriptor");
+}
+pub mod news {
+    tonic::include_proto!("news"); // The string specified here must match the proto package name
+    pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("news_descriptor");
+}
+pub mod challenges {
+    tonic::include_proto!("challenges"); // The string specified here must match the proto package name
+    pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("challenges_descriptor");
+}
+pub mod ladder {
+    tonic::include_proto!("ladder"); // The string specified here must match the proto package name
+    pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("ladder_descriptor");
+}
+pub mod notifications {
+    tonic::include_proto!("notifications"); // The string specified here must match the proto package name
+    pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("notifications_descriptor");
+}
+pub mod nat {
+    tonic::include_proto!("nat"); // The string specified here must match the proto package name
+    pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("nat_descriptor");
+}
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::net::SocketAddr;
 
@@ -18,12 +19,215 @@ pub struct DebugConfig {
     pub force_joins: bool,
 }
 
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[serde(default)]
+pub struct GameSessionConfig {
+    /// Seconds between two runs of the stale game session reaper. 0 disables the reaper.
+    pub reaper_interval: u64,
+}
+
+impl Default for GameSessionConfig {
+    fn default() -> Self {
+        Self { reaper_interval: 60 }
+    }
+}
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[serde(default)]
+pub struct MatchmakingConfig {
+    /// Maximum number of sessions returned by a search. 0 returns all.
+    pub max_results: usize,
+    /// Weight for preferring sessions that are close to being full.
+    pub free_slots_weight: f64,
+    /// Weight for preferring recently created sessions.
+    pub age_weight: f64,
+    /// Weight for preferring hosts with an address close to the searching player.
+    pub region_weight: f64,
+    /// Weight for preferring sessions with players of similar skill.
+    pub skill_weight: f64,
+    /// Sessions older than this many seconds get no age bonus.
+    pub max_age: u64,
+    /// Skill difference at which sessions get no skill bonus.
+    pub skill_range: f64,
+    /// Stat board of the stat used as skill rating.
+    pub skill_board: u32,
+    /// Stat used as skill rating, summed over the contexts of the stat board. Players that didn't write it are unrated.
+    pub skill_stat: u32,
+    /// Failed NAT traversals in a row after which two players aren't matched anymore. 0 disables it, like enabling the
+    /// relay does, since relayed players reach each other anyway.
+    pub unreachable_after: u32,
+    /// Seconds after the last failed NAT traversal of two players after which their failures are forgotten.
+    pub unreachable_for: u64,
+}
+
+impl Default for MatchmakingConfig {
+    fn default() -> Self {
+        Self {
+            max_results: 20,
+            free_slots_weight: 1.0,
+            age_weight: 0.5,
+            region_weight: 1.0,
+            skill_weight: 0.0,
+            max_age: 30 * 60,
+            skill_range: 500.0,
+            skill_board: 0,
+            skill_stat: 0,
+            unreachable_after: 2,
+            unreachable_for: 24 * 60 * 60,
+        }
+    }
+}
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[serde(default)]
+pub struct UserStorageConfig {
+    /// Number of contents a player can store per content type.
+    pub slots: u32,
+    /// Maximum size of a single content in bytes.
+    pub max_content_size: u32,
+    /// Seconds a client has to finish an upload, abandoned uploads are discarded afterwards. 0 keeps them forever.
+    pub upload_timeout: u64,
+    /// Seconds between two cleanups of abandoned uploads.
+    pub cleanup_interval: u64,
+    /// Whether moderators can use `UserStorageAdminProtocol` from within the game. Its protocol id 54 is inferred from
+    /// the id of `UserStorageProtocol` and not confirmed by a capture yet.
+    pub admin_protocol: bool,
+}
+
+impl Default for UserStorageConfig {
+    fn default() -> Self {
+        Self {
+            slots: 10,
+            max_content_size: 1024 * 1024,
+            upload_timeout: 60 * 60,
+            cleanup_interval: 10 * 60,
+            admin_protocol: false,
+        }
+    }
+}
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[serde(default)]
+pub struct StatsHistoryConfig {
+    /// Days of stats history to keep, ladder seasons that started earlier can't be computed exactly anymore. 0 keeps
+    /// the history forever.
+    pub retention_days: u32,
+    /// Seconds between two cleanups of the stats history.
+    pub cleanup_interval: u64,
+}
+
+impl Default for StatsHistoryConfig {
+    fn default() -> Self {
+        Self {
+            retention_days: 365,
+            cleanup_interval: 24 * 60 * 60,
+        }
+    }
+}
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[serde(default)]
+pub struct TrackingConfig {
+    /// Days to keep received tracking tags. 0 keeps them forever.
+    pub retention_days: u32,
+    /// Maximum number of tracking tags to keep, the oldest are deleted first. 0 for no limit.
+    pub max_tags: u32,
+    /// Seconds between two cleanups of old tracking tags.
+    pub cleanup_interval: u64,
+}
+
+impl Default for TrackingConfig {
+    fn default() -> Self {
+        Self {
+            retention_days: 30,
+            max_tags: 1_000_000,
+            cleanup_interval: 60 * 60,
+        }
+    }
+}
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[serde(default)]
+pub struct RelayConfig {
+    /// Whether players get a relay station URL, for peers they can't reach directly.
+    pub enabled: bool,
+    /// Address the relay ports are bound to.
+    pub listen: IpAddr,
+    /// Address of the relay in the station URLs, reachable by all players.
+    pub public_address: IpAddr,
+    /// First relay port. Each relayed player gets its own port, following this one. 0 lets the OS pick the ports.
+    pub first_port: u16,
+    /// Maximum number of players relayed at once.
+    pub max_allocations: u16,
+    /// Bytes per second a player can send through the relay. 0 disables the cap.
+    pub max_bytes_per_sec: u32,
+    /// Seconds without traffic or registration after which a player's relay port is released.
+    pub idle_timeout: u64,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            public_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            first_port: 21200,
+            max_allocations: 64,
+            max_bytes_per_sec: 64 * 1024,
+            idle_timeout: 120,
+        }
+    }
+}
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PrivilegeConfig {
+    pub id: u32,
+    pub description: String,
+    /// Whether every user has the privilege unless it's revoked.
+    #[serde(default = "default_true")]
+    pub default: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_privileges() -> Vec<PrivilegeConfig> {
+    vec![PrivilegeConfig {
+        id: 1,
+        description: String::from("PlayOnline"),
+        default: true,
+    }]
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
     #[serde(flatten)]
     pub quazal: quazal::Config,
     pub api_server: SocketAddr,
     pub debug: DebugConfig,
+    #[serde(default)]
+    pub game_sessions: GameSessionConfig,
+    #[serde(default)]
+    pub matchmaking: MatchmakingConfig,
+    #[serde(default)]
+    pub user_storage: UserStorageConfig,
+    #[serde(default)]
+    pub tracking: TrackingConfig,
+    #[serde(default)]
+    pub stats_history: StatsHistoryConfig,
+    #[serde(default)]
+    pub relay: RelayConfig,
+    /// Privileges known to the server. Users can be granted or denied each of them with the admin api.
+    #[serde(default = "default_privileges")]
+    pub privileges: Vec<PrivilegeConfig>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -110,6 +314,13 @@ impl Default for Config {
             api_server: "0.0.0.0:50051".parse().unwrap(),
             quazal: quazal_config,
             debug: DebugConfig::default(),
+            game_sessions: GameSessionConfig::default(),
+            matchmaking: MatchmakingConfig::default(),
+            user_storage: UserStorageConfig::default(),
+            tracking: TrackingConfig::default(),
+            stats_history: StatsHistoryConfig::default(),
+            relay: RelayConfig::default(),
+            privileges: default_privileges(),
         }
     }
 }
@@ -45,11 +45,7 @@ pub mod ubi_account_management_service;
 pub mod ubi_authentication;
 #[cfg(feature = "uplay_win_service")]
 pub mod uplay_win_service;
-#[cfg(feature = "user_account_management_service")]
-pub mod user_account_management_service;
 #[cfg(feature = "user_storage")]
 pub mod user_storage;
 #[cfg(feature = "user_storage_admin")]
 pub mod user_storage_admin;
-#[cfg(feature = "web_notifications_storage_service")]
-pub mod web_notifications_storage_service;
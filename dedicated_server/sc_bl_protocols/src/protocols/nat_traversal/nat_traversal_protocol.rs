@@ -29,6 +29,8 @@ pub enum NatTraversalProtocolMethod {
     RequestProbeInitiation = 1u32,
     InitiateProbe = 2u32,
     RequestProbeInitiationExt = 3u32,
+    ReportNatTraversalResult = 4u32,
+    ReportNatProperties = 5u32,
 }
 #[derive(Debug, ToStream, FromStream)]
 pub struct RequestProbeInitiationRequest {
@@ -49,6 +51,21 @@ pub struct RequestProbeInitiationExtRequest {
 }
 #[derive(Debug, ToStream, FromStream)]
 pub struct RequestProbeInitiationExtResponse;
+#[derive(Debug, ToStream, FromStream)]
+pub struct ReportNatTraversalResultRequest {
+    pub cid: u32,
+    pub result: bool,
+}
+#[derive(Debug, ToStream, FromStream)]
+pub struct ReportNatTraversalResultResponse;
+#[derive(Debug, ToStream, FromStream)]
+pub struct ReportNatPropertiesRequest {
+    pub natmapping: u32,
+    pub natfiltering: u32,
+    pub rtt: u32,
+}
+#[derive(Debug, ToStream, FromStream)]
+pub struct ReportNatPropertiesResponse;
 pub struct NatTraversalProtocolServer<T: NatTraversalProtocolServerTrait<CI>, CI>(T, ::std::marker::PhantomData<CI>);
 impl<T: NatTraversalProtocolServerTrait<CI>, CI> NatTraversalProtocolServer<T, CI> {
     pub fn new(implementation: T) -> Self {
@@ -63,7 +80,7 @@ impl<T: NatTraversalProtocolServerTrait<CI>, CI> Protocol<CI> for NatTraversalPr
         "NatTraversalProtocol".to_string()
     }
     fn num_methods(&self) -> u32 {
-        3u32
+        5u32
     }
     fn handle(
         &self,
@@ -98,6 +115,20 @@ impl<T: NatTraversalProtocolServerTrait<CI>, CI> Protocol<CI> for NatTraversalPr
                 debug!(logger, "Response: {:?}", resp);
                 Ok(resp?.to_bytes())
             }
+            Some(NatTraversalProtocolMethod::ReportNatTraversalResult) => {
+                let req = ReportNatTraversalResultRequest::from_bytes(&request.parameters)?;
+                debug!(logger, "Request: {:?}", req);
+                let resp = self.0.report_nat_traversal_result(logger, ctx, ci, req, client_registry, socket);
+                debug!(logger, "Response: {:?}", resp);
+                Ok(resp?.to_bytes())
+            }
+            Some(NatTraversalProtocolMethod::ReportNatProperties) => {
+                let req = ReportNatPropertiesRequest::from_bytes(&request.parameters)?;
+                debug!(logger, "Request: {:?}", req);
+                let resp = self.0.report_nat_properties(logger, ctx, ci, req, client_registry, socket);
+                debug!(logger, "Response: {:?}", resp);
+                Ok(resp?.to_bytes())
+            }
         }
     }
     fn method_name(&self, method_id: u32) -> Option<String> {
@@ -142,6 +173,30 @@ pub trait NatTraversalProtocolServerTrait<CI> {
         warn!(logger, "Method {}.{} not implemented", "NatTraversalProtocol", stringify!(request_probe_initiation_ext));
         Err(quazal::rmc::Error::UnimplementedMethod)
     }
+    fn report_nat_traversal_result(
+        &self,
+        logger: &Logger,
+        ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: ReportNatTraversalResultRequest,
+        client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<ReportNatTraversalResultResponse, Error> {
+        warn!(logger, "Method {}.{} not implemented", "NatTraversalProtocol", stringify!(report_nat_traversal_result));
+        Err(quazal::rmc::Error::UnimplementedMethod)
+    }
+    fn report_nat_properties(
+        &self,
+        logger: &Logger,
+        ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: ReportNatPropertiesRequest,
+        client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<ReportNatPropertiesResponse, Error> {
+        warn!(logger, "Method {}.{} not implemented", "NatTraversalProtocol", stringify!(report_nat_properties));
+        Err(quazal::rmc::Error::UnimplementedMethod)
+    }
 }
 pub struct NatTraversalProtocolClient<CI>(::std::marker::PhantomData<CI>);
 impl<CI> NatTraversalProtocolClient<CI> {
@@ -162,7 +217,7 @@ impl<CI> ClientProtocol<CI> for NatTraversalProtocolClient<CI> {
         "NatTraversalProtocol".to_string()
     }
     fn num_methods(&self) -> u32 {
-        3u32
+        5u32
     }
     fn method_name(&self, method_id: u32) -> Option<String> {
         NatTraversalProtocolMethod::try_from(method_id).ok().map(|e| format!("{:?}", e))
@@ -197,4 +252,26 @@ impl<CI> NatTraversalProtocolClient<CI> {
         self.send(logger, ctx, ci, NatTraversalProtocolMethod::RequestProbeInitiationExt as u32, request.to_bytes());
         Err(quazal::rmc::Error::UnimplementedMethod)
     }
+    pub fn report_nat_traversal_result(
+        &self,
+        logger: &Logger,
+        ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: ReportNatTraversalResultRequest,
+    ) -> Result<ReportNatTraversalResultResponse, Error> {
+        warn!(logger, "Method {}.{} not implemented", "NatTraversalProtocol", stringify!(report_nat_traversal_result));
+        self.send(logger, ctx, ci, NatTraversalProtocolMethod::ReportNatTraversalResult as u32, request.to_bytes());
+        Err(quazal::rmc::Error::UnimplementedMethod)
+    }
+    pub fn report_nat_properties(
+        &self,
+        logger: &Logger,
+        ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: ReportNatPropertiesRequest,
+    ) -> Result<ReportNatPropertiesResponse, Error> {
+        warn!(logger, "Method {}.{} not implemented", "NatTraversalProtocol", stringify!(report_nat_properties));
+        self.send(logger, ctx, ci, NatTraversalProtocolMethod::ReportNatProperties as u32, request.to_bytes());
+        Err(quazal::rmc::Error::UnimplementedMethod)
+    }
 }
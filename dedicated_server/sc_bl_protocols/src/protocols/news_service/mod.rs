@@ -1,3 +1,2 @@
 // AUTOGENERATED with quazal-tools
-pub mod news_protocol;
 pub mod types;
@@ -1,4 +1,2 @@
 // AUTOGENERATED with quazal-tools
-pub mod notification_protocol;
-pub mod remote_log_device_protocol;
 pub mod types;
@@ -1,3 +0,0 @@
-// AUTOGENERATED with quazal-tools
-pub mod types;
-pub mod user_account_management_protocol;
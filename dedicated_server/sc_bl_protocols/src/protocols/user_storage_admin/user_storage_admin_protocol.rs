@@ -26,7 +26,7 @@ use super::super::user_storage::types::UserContentKey;
 use super::super::user_storage::types::UserContentURL;
 #[allow(unused)]
 use super::types::*;
-pub const USER_STORAGE_ADMIN_PROTOCOL_ID: u16 = todo!();
+pub const USER_STORAGE_ADMIN_PROTOCOL_ID: u16 = 54u16;
 #[derive(Debug, TryFromPrimitive)]
 #[repr(u32)]
 pub enum UserStorageAdminProtocolMethod {
@@ -1,3 +0,0 @@
-// AUTOGENERATED with quazal-tools
-pub mod types;
-pub mod web_notifications_storage_protocol;
@@ -0,0 +1,658 @@
+//! Implements the `AccountManagementProtocolServer` for changing and looking up accounts.
+//!
+//! Accounts are created with the `Users` gRPC service, so only the methods to manage an existing
+//! account and to look up other players are implemented.
+
+use std::sync::Arc;
+
+use quazal::prudp::ClientRegistry;
+use quazal::rmc::result::RendezVousError;
+use quazal::rmc::types::Any;
+use quazal::rmc::types::Data;
+use quazal::rmc::types::DateTime;
+use quazal::rmc::types::QResult;
+use quazal::rmc::Error;
+use quazal::rmc::Protocol;
+use quazal::ClientInfo;
+use quazal::Context;
+use slog::Logger;
+
+use crate::login_required;
+use crate::protocols::account_management_service::account_management_protocol::AccountManagementProtocolServer;
+use crate::protocols::account_management_service::account_management_protocol::AccountManagementProtocolServerTrait;
+use crate::protocols::account_management_service::account_management_protocol::ChangePasswordRequest;
+use crate::protocols::account_management_service::account_management_protocol::ChangePasswordResponse;
+use crate::protocols::account_management_service::account_management_protocol::FindByNameLikeRequest;
+use crate::protocols::account_management_service::account_management_protocol::FindByNameLikeResponse;
+use crate::protocols::account_management_service::account_management_protocol::GetAccountDataRequest;
+use crate::protocols::account_management_service::account_management_protocol::GetAccountDataResponse;
+use crate::protocols::account_management_service::account_management_protocol::GetLastConnectionStatsRequest;
+use crate::protocols::account_management_service::account_management_protocol::GetLastConnectionStatsResponse;
+use crate::protocols::account_management_service::account_management_protocol::GetMultiplePublicDataRequest;
+use crate::protocols::account_management_service::account_management_protocol::GetMultiplePublicDataResponse;
+use crate::protocols::account_management_service::account_management_protocol::GetNameRequest;
+use crate::protocols::account_management_service::account_management_protocol::GetNameResponse;
+use crate::protocols::account_management_service::account_management_protocol::GetPrivateDataRequest;
+use crate::protocols::account_management_service::account_management_protocol::GetPrivateDataResponse;
+use crate::protocols::account_management_service::account_management_protocol::GetPublicDataRequest;
+use crate::protocols::account_management_service::account_management_protocol::GetPublicDataResponse;
+use crate::protocols::account_management_service::account_management_protocol::GetStatusRequest;
+use crate::protocols::account_management_service::account_management_protocol::GetStatusResponse;
+use crate::protocols::account_management_service::account_management_protocol::RetrieveAccountRequest;
+use crate::protocols::account_management_service::account_management_protocol::RetrieveAccountResponse;
+use crate::protocols::account_management_service::account_management_protocol::UpdateAccountEmailRequest;
+use crate::protocols::account_management_service::account_management_protocol::UpdateAccountEmailResponse;
+use crate::protocols::account_management_service::account_management_protocol::UpdateAccountNameRequest;
+use crate::protocols::account_management_service::account_management_protocol::UpdateAccountNameResponse;
+use crate::protocols::account_management_service::account_management_protocol::UpdateCustomDataRequest;
+use crate::protocols::account_management_service::account_management_protocol::UpdateCustomDataResponse;
+use crate::protocols::account_management_service::account_management_protocol::UpdateStatusRequest;
+use crate::protocols::account_management_service::account_management_protocol::UpdateStatusResponse;
+use crate::protocols::account_management_service::types::AccountData;
+use crate::protocols::account_management_service::types::BasicAccountInfo;
+use crate::storage::Account;
+use crate::storage::Storage;
+
+/// Converts a sqlite timestamp, using 0 if there is none.
+fn datetime(timestamp: Option<&str>) -> DateTime {
+    timestamp.and_then(|ts| ts.parse().ok()).unwrap_or_default()
+}
+
+/// Converts stored custom data, using an empty `Any` if there is none.
+fn custom_data(data: Option<(String, Vec<u8>)>) -> Any<Data, String> {
+    data.map_or_else(|| Any::new(String::new(), Vec::new()), |(type_name, data)| Any::new(type_name, data))
+}
+
+fn to_account_data(account: Account) -> AccountData {
+    let creation_date = datetime(Some(&account.created_at));
+    AccountData {
+        pid: account.id,
+        str_name: account.username,
+        ui_groups: 0,
+        str_email: account.email,
+        dt_creation_date: creation_date,
+        dt_effective_date: creation_date,
+        str_not_effective_msg: String::new(),
+        dt_expiry_date: DateTime::default(),
+        str_expired_msg: String::new(),
+    }
+}
+
+/// Implementation of the `AccountManagementProtocolServerTrait` backed by the users table.
+struct AccountManagementProtocolServerImpl {
+    storage: Arc<Storage>,
+}
+
+impl AccountManagementProtocolServerImpl {
+    fn account(&self, logger: &Logger, user_id: u32) -> Result<Account, Error> {
+        let account = rmc_err!(self.storage.find_account(user_id), logger, "Error loading account")?;
+        account.ok_or_else(|| {
+            warn!(logger, "Account {user_id} not found");
+            Error::AccessDenied
+        })
+    }
+
+    fn public_data(&self, logger: &Logger, user_id: u32) -> Result<Any<Data, String>, Error> {
+        let data = rmc_err!(self.storage.find_public_data(user_id), logger, "Error loading public data")?;
+        Ok(custom_data(data))
+    }
+
+    fn private_data(&self, logger: &Logger, user_id: u32) -> Result<Any<Data, String>, Error> {
+        let data = rmc_err!(self.storage.find_private_data(user_id), logger, "Error loading private data")?;
+        Ok(custom_data(data))
+    }
+}
+
+impl<CI> AccountManagementProtocolServerTrait<CI> for AccountManagementProtocolServerImpl {
+    /// Handles the `ChangePassword` request, changing the password of the client's account.
+    ///
+    /// This function requires the client to be logged in.
+    fn change_password(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: ChangePasswordRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<ChangePasswordResponse, Error> {
+        let user_id = login_required(ci)?;
+        if request.str_new_key.is_empty() {
+            return Ok(ChangePasswordResponse { return_value: false });
+        }
+        rmc_err!(self.storage.change_password(user_id, &request.str_new_key), logger, "Error changing password")?;
+        info!(logger, "User {user_id} changed their password");
+        Ok(ChangePasswordResponse { return_value: true })
+    }
+
+    /// Handles the `GetName` request, returning the name of a player.
+    ///
+    /// This function requires the client to be logged in.
+    fn get_name(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetNameRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetNameResponse, Error> {
+        login_required(ci)?;
+        let name = rmc_err!(self.storage.find_username_by_user_id(request.id_principal), logger, "Error loading name")?;
+        let Some(str_name) = name else {
+            warn!(logger, "Account {} not found", request.id_principal);
+            return Err(Error::AccessDenied);
+        };
+        Ok(GetNameResponse { str_name })
+    }
+
+    /// Handles the `GetAccountData` request, returning the account of the client.
+    ///
+    /// This function requires the client to be logged in.
+    fn get_account_data(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        _request: GetAccountDataRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetAccountDataResponse, Error> {
+        let user_id = login_required(ci)?;
+        let account = self.account(logger, user_id)?;
+        Ok(GetAccountDataResponse {
+            return_value: QResult::Ok,
+            o_account_data: to_account_data(account),
+        })
+    }
+
+    /// Handles the `GetPrivateData` request, returning the private custom data of the client.
+    ///
+    /// This function requires the client to be logged in.
+    fn get_private_data(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        _request: GetPrivateDataRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetPrivateDataResponse, Error> {
+        let user_id = login_required(ci)?;
+        Ok(GetPrivateDataResponse {
+            return_value: true,
+            o_data: self.private_data(logger, user_id)?,
+        })
+    }
+
+    /// Handles the `GetPublicData` request, returning the public custom data of a player.
+    ///
+    /// This function requires the client to be logged in.
+    fn get_public_data(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetPublicDataRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetPublicDataResponse, Error> {
+        login_required(ci)?;
+        Ok(GetPublicDataResponse {
+            return_value: true,
+            o_data: self.public_data(logger, request.id_principal)?,
+        })
+    }
+
+    /// Handles the `GetMultiplePublicData` request, returning the public custom data of several players in request order.
+    ///
+    /// This function requires the client to be logged in.
+    fn get_multiple_public_data(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetMultiplePublicDataRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetMultiplePublicDataResponse, Error> {
+        login_required(ci)?;
+        let o_data = request
+            .lst_principals
+            .into_iter()
+            .map(|user_id| self.public_data(logger, user_id))
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(GetMultiplePublicDataResponse { return_value: true, o_data })
+    }
+
+    /// Handles the `UpdateAccountName` request, renaming the client's account.
+    ///
+    /// This function requires the client to be logged in. Fails if the name is empty or taken.
+    fn update_account_name(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: UpdateAccountNameRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<UpdateAccountNameResponse, Error> {
+        let user_id = login_required(ci)?;
+        let name = request.str_name.trim();
+        if name.is_empty() {
+            return Ok(UpdateAccountNameResponse {
+                return_value: QResult::Error(quazal::rmc::result::Error::RendezVous(RendezVousError::InvalidUsername)),
+            });
+        }
+        if !rmc_err!(self.storage.rename_user(user_id, name), logger, "Error renaming user")? {
+            return Ok(UpdateAccountNameResponse {
+                return_value: QResult::Error(quazal::rmc::result::Error::RendezVous(RendezVousError::UsernameAlreadyExists)),
+            });
+        }
+        info!(logger, "User {user_id} renamed to {name}");
+        Ok(UpdateAccountNameResponse { return_value: QResult::Ok })
+    }
+
+    /// Handles the `UpdateAccountEmail` request, changing the e-mail address of the client's account.
+    ///
+    /// This function requires the client to be logged in.
+    fn update_account_email(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: UpdateAccountEmailRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<UpdateAccountEmailResponse, Error> {
+        let user_id = login_required(ci)?;
+        // the generated field name is misleading, it's the new e-mail address
+        rmc_err!(self.storage.update_user_email(user_id, request.str_name.trim()), logger, "Error updating e-mail")?;
+        Ok(UpdateAccountEmailResponse { return_value: QResult::Ok })
+    }
+
+    /// Handles the `UpdateCustomData` request, storing the public and private custom data of the client.
+    ///
+    /// This function requires the client to be logged in.
+    fn update_custom_data(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: UpdateCustomDataRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<UpdateCustomDataResponse, Error> {
+        let user_id = login_required(ci)?;
+        rmc_err!(
+            self.storage.update_custom_data(
+                user_id,
+                (request.o_public_data.type_name(), request.o_public_data.data()),
+                (request.o_private_data.type_name(), request.o_private_data.data()),
+            ),
+            logger,
+            "Error updating custom data"
+        )?;
+        Ok(UpdateCustomDataResponse { return_value: QResult::Ok })
+    }
+
+    /// Handles the `UpdateStatus` request, setting the status text of the client.
+    ///
+    /// This function requires the client to be logged in.
+    fn update_status(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: UpdateStatusRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<UpdateStatusResponse, Error> {
+        let user_id = login_required(ci)?;
+        rmc_err!(self.storage.update_user_status(user_id, &request.str_status), logger, "Error updating status")?;
+        Ok(UpdateStatusResponse)
+    }
+
+    /// Handles the `GetStatus` request, returning the status text of a player.
+    ///
+    /// This function requires the client to be logged in.
+    fn get_status(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetStatusRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetStatusResponse, Error> {
+        login_required(ci)?;
+        let account = self.account(logger, request.id_principal)?;
+        Ok(GetStatusResponse { str_status: account.status })
+    }
+
+    /// Handles the `GetLastConnectionStats` request, returning when a player connected and disconnected.
+    ///
+    /// This function requires the client to be logged in. Unknown times are 0.
+    fn get_last_connection_stats(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetLastConnectionStatsRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetLastConnectionStatsResponse, Error> {
+        login_required(ci)?;
+        let account = self.account(logger, request.id_principal)?;
+        Ok(GetLastConnectionStatsResponse {
+            dt_last_session_login: datetime(account.previous_login.as_deref()),
+            dt_last_session_logout: datetime(account.last_logout.as_deref()),
+            dt_current_session_login: datetime(account.current_login.as_deref()),
+        })
+    }
+
+    /// Handles the `RetrieveAccount` request, returning the account and custom data of the client.
+    ///
+    /// This function requires the client to be logged in.
+    fn retrieve_account(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        _request: RetrieveAccountRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<RetrieveAccountResponse, Error> {
+        let user_id = login_required(ci)?;
+        let account = self.account(logger, user_id)?;
+        Ok(RetrieveAccountResponse {
+            o_account_data: to_account_data(account),
+            o_public_data: self.public_data(logger, user_id)?,
+            o_private_data: self.private_data(logger, user_id)?,
+        })
+    }
+
+    /// Handles the `FindByNameLike` request, searching players by a SQL `LIKE` pattern on their name.
+    ///
+    /// This function requires the client to be logged in.
+    fn find_by_name_like(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: FindByNameLikeRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<FindByNameLikeResponse, Error> {
+        login_required(ci)?;
+        let users = rmc_err!(
+            self.storage
+                .find_users_by_name_like(&request.str_like, request.result_range.offset, request.result_range.size),
+            logger,
+            "Error searching users"
+        )?;
+        Ok(FindByNameLikeResponse {
+            plst_accounts: users.into_iter().map(|(pid_owner, str_name)| BasicAccountInfo { pid_owner, str_name }).collect(),
+        })
+    }
+}
+
+/// Creates a new boxed `AccountManagementProtocolServer` instance.
+pub fn new_protocol<T: 'static>(storage: Arc<Storage>) -> Box<dyn Protocol<T>> {
+    Box::new(AccountManagementProtocolServer::new(AccountManagementProtocolServerImpl { storage }))
+}
+
+#[cfg(test)]
+mod tests {
+    use quazal::rmc::basic::FromStream;
+    use quazal::rmc::basic::ToStream;
+    use quazal::rmc::types::ResultRange;
+
+    use super::*;
+    use crate::protocols::account_management_service::account_management_protocol::AccountManagementProtocolMethod;
+    use crate::protocols::account_management_service::account_management_protocol::GetStatusRequest;
+    use crate::protocols::account_management_service::account_management_protocol::UpdateStatusRequest;
+    use crate::test_util;
+
+    const USER_ID: u32 = 1000;
+
+    fn storage() -> Arc<Storage> {
+        Arc::new(Storage::in_memory().unwrap())
+    }
+
+    /// `UpdateCustomData` with public data `PublicData [AA BB]` and private data `PrivateData [CC]`
+    fn custom_data_request() -> Vec<u8> {
+        UpdateCustomDataRequest {
+            o_public_data: Any::new(String::from("PublicData"), vec![0xaa, 0xbb]),
+            o_private_data: Any::new(String::from("PrivateData"), vec![0xcc]),
+        }
+        .to_bytes()
+    }
+
+    #[test]
+    fn requires_login() {
+        let prot = new_protocol::<()>(storage());
+        let resp = test_util::call(&*prot, None, AccountManagementProtocolMethod::GetAccountData as u32, &[]);
+        assert!(matches!(resp, Err(Error::AccessDenied)));
+    }
+
+    #[test]
+    fn change_password() {
+        let storage = storage();
+        let prot = new_protocol::<()>(Arc::clone(&storage));
+        let change = |str_new_key: &str| {
+            let request = ChangePasswordRequest { str_new_key: str_new_key.into() };
+            let resp = test_util::call(&*prot, Some(USER_ID), AccountManagementProtocolMethod::ChangePassword as u32, &request.to_bytes()).unwrap();
+            ChangePasswordResponse::from_bytes(&resp).unwrap().return_value
+        };
+        assert!(change("secret"));
+        assert!(matches!(storage.login_user("Foo", "secret"), Ok(Ok(USER_ID))));
+        assert!(!change(""));
+    }
+
+    #[test]
+    fn get_name() {
+        let prot = new_protocol::<()>(storage());
+        let get_name = |id_principal| {
+            test_util::call(
+                &*prot,
+                Some(USER_ID),
+                AccountManagementProtocolMethod::GetName as u32,
+                &GetNameRequest { id_principal }.to_bytes(),
+            )
+        };
+        let resp = get_name(1001).unwrap();
+        assert_eq!(GetNameResponse::from_bytes(&resp).unwrap().str_name, "AAAABBBB");
+        assert!(matches!(get_name(12345), Err(Error::AccessDenied)));
+    }
+
+    #[test]
+    fn get_account_data() {
+        let storage = storage();
+        let prot = new_protocol::<()>(Arc::clone(&storage));
+        storage.update_user_email(USER_ID, "foo@example.com").unwrap();
+        let resp = test_util::call(
+            &*prot,
+            Some(USER_ID),
+            AccountManagementProtocolMethod::GetAccountData as u32,
+            &GetAccountDataRequest.to_bytes(),
+        )
+        .unwrap();
+        let resp = GetAccountDataResponse::from_bytes(&resp).unwrap();
+        assert!(matches!(resp.return_value, QResult::Ok));
+        assert_eq!(resp.o_account_data.pid, USER_ID);
+        assert_eq!(resp.o_account_data.str_name, "Foo");
+        assert_eq!(resp.o_account_data.str_email, "foo@example.com");
+        assert_ne!(resp.o_account_data.dt_creation_date.0, 0);
+    }
+
+    #[test]
+    fn get_private_data() {
+        let prot = new_protocol::<()>(storage());
+        let get_private_data = || {
+            let resp = test_util::call(
+                &*prot,
+                Some(USER_ID),
+                AccountManagementProtocolMethod::GetPrivateData as u32,
+                &GetPrivateDataRequest.to_bytes(),
+            )
+            .unwrap();
+            GetPrivateDataResponse::from_bytes(&resp).unwrap()
+        };
+        let resp = get_private_data();
+        assert!(resp.return_value);
+        assert!(resp.o_data.type_name().is_empty());
+        assert!(resp.o_data.data().is_empty());
+
+        test_util::call(&*prot, Some(USER_ID), AccountManagementProtocolMethod::UpdateCustomData as u32, &custom_data_request()).unwrap();
+        let resp = get_private_data();
+        assert!(resp.return_value);
+        assert_eq!(resp.o_data.type_name(), "PrivateData");
+        assert_eq!(resp.o_data.data(), b"\xcc");
+    }
+
+    #[test]
+    fn get_public_data() {
+        let prot = new_protocol::<()>(storage());
+        test_util::call(&*prot, Some(USER_ID), AccountManagementProtocolMethod::UpdateCustomData as u32, &custom_data_request()).unwrap();
+        let request = GetPublicDataRequest { id_principal: USER_ID };
+        let resp = test_util::call(&*prot, Some(USER_ID), AccountManagementProtocolMethod::GetPublicData as u32, &request.to_bytes()).unwrap();
+        let resp = GetPublicDataResponse::from_bytes(&resp).unwrap();
+        assert!(resp.return_value);
+        assert_eq!(resp.o_data.type_name(), "PublicData");
+        assert_eq!(resp.o_data.data(), b"\xaa\xbb");
+    }
+
+    #[test]
+    fn get_multiple_public_data() {
+        let prot = new_protocol::<()>(storage());
+        test_util::call(&*prot, Some(USER_ID), AccountManagementProtocolMethod::UpdateCustomData as u32, &custom_data_request()).unwrap();
+        let request = GetMultiplePublicDataRequest {
+            lst_principals: vec![USER_ID, 1001],
+        };
+        let resp = test_util::call(&*prot, Some(USER_ID), AccountManagementProtocolMethod::GetMultiplePublicData as u32, &request.to_bytes()).unwrap();
+        let resp = GetMultiplePublicDataResponse::from_bytes(&resp).unwrap();
+        assert!(resp.return_value);
+        assert_eq!(resp.o_data.len(), 2);
+        assert_eq!(resp.o_data[0].type_name(), "PublicData");
+        assert_eq!(resp.o_data[0].data(), b"\xaa\xbb");
+        assert_eq!(resp.o_data[1].type_name(), "");
+        assert!(resp.o_data[1].data().is_empty());
+    }
+
+    #[test]
+    fn update_account_name() {
+        let storage = storage();
+        let prot = new_protocol::<()>(Arc::clone(&storage));
+        let update = |str_name: &str| {
+            let request = UpdateAccountNameRequest { str_name: str_name.into() };
+            let resp = test_util::call(&*prot, Some(USER_ID), AccountManagementProtocolMethod::UpdateAccountName as u32, &request.to_bytes()).unwrap();
+            UpdateAccountNameResponse::from_bytes(&resp).unwrap().return_value
+        };
+        assert!(matches!(update("Bar"), QResult::Ok));
+        assert_eq!(storage.find_username_by_user_id(USER_ID).unwrap().as_deref(), Some("Bar"));
+
+        // taken by user 1001
+        assert!(matches!(
+            update("AAAABBBB"),
+            QResult::Error(quazal::rmc::result::Error::RendezVous(RendezVousError::UsernameAlreadyExists))
+        ));
+        assert_eq!(storage.find_username_by_user_id(USER_ID).unwrap().as_deref(), Some("Bar"));
+    }
+
+    #[test]
+    fn update_account_email() {
+        let storage = storage();
+        let prot = new_protocol::<()>(Arc::clone(&storage));
+        let request = UpdateAccountEmailRequest {
+            str_name: String::from("foo@example.com"),
+        };
+        let resp = test_util::call(&*prot, Some(USER_ID), AccountManagementProtocolMethod::UpdateAccountEmail as u32, &request.to_bytes()).unwrap();
+        assert!(matches!(UpdateAccountEmailResponse::from_bytes(&resp).unwrap().return_value, QResult::Ok));
+        assert_eq!(storage.find_account(USER_ID).unwrap().unwrap().email, "foo@example.com");
+    }
+
+    #[test]
+    fn update_custom_data() {
+        let storage = storage();
+        let prot = new_protocol::<()>(Arc::clone(&storage));
+        let resp = test_util::call(&*prot, Some(USER_ID), AccountManagementProtocolMethod::UpdateCustomData as u32, &custom_data_request()).unwrap();
+        assert!(matches!(UpdateCustomDataResponse::from_bytes(&resp).unwrap().return_value, QResult::Ok));
+        assert_eq!(storage.find_public_data(USER_ID).unwrap(), Some((String::from("PublicData"), vec![0xaa, 0xbb])));
+        assert_eq!(storage.find_private_data(USER_ID).unwrap(), Some((String::from("PrivateData"), vec![0xcc])));
+    }
+
+    #[test]
+    fn update_and_get_status() {
+        let prot = new_protocol::<()>(storage());
+        let request = UpdateStatusRequest {
+            str_status: String::from("In a game"),
+        };
+        let resp = test_util::call(&*prot, Some(USER_ID), AccountManagementProtocolMethod::UpdateStatus as u32, &request.to_bytes()).unwrap();
+        assert!(resp.is_empty());
+        let request = GetStatusRequest { id_principal: USER_ID };
+        let resp = test_util::call(&*prot, Some(USER_ID), AccountManagementProtocolMethod::GetStatus as u32, &request.to_bytes()).unwrap();
+        assert_eq!(GetStatusResponse::from_bytes(&resp).unwrap().str_status, "In a game");
+    }
+
+    #[test]
+    fn get_last_connection_stats() {
+        let storage = storage();
+        let prot = new_protocol::<()>(Arc::clone(&storage));
+        let get_stats = || {
+            let request = GetLastConnectionStatsRequest { id_principal: USER_ID };
+            let resp = test_util::call(&*prot, Some(USER_ID), AccountManagementProtocolMethod::GetLastConnectionStats as u32, &request.to_bytes()).unwrap();
+            GetLastConnectionStatsResponse::from_bytes(&resp).unwrap()
+        };
+        let resp = get_stats();
+        assert_eq!((resp.dt_last_session_login.0, resp.dt_last_session_logout.0, resp.dt_current_session_login.0), (0, 0, 0));
+
+        storage.create_user_session(USER_ID, &[1]).unwrap();
+        storage.delete_user_session(USER_ID).unwrap();
+        storage.create_user_session(USER_ID, &[2]).unwrap();
+        let resp = get_stats();
+        assert_ne!(resp.dt_last_session_login.0, 0);
+        assert_ne!(resp.dt_last_session_logout.0, 0);
+        assert_ne!(resp.dt_current_session_login.0, 0);
+    }
+
+    #[test]
+    fn retrieve_account() {
+        let prot = new_protocol::<()>(storage());
+        test_util::call(&*prot, Some(USER_ID), AccountManagementProtocolMethod::UpdateCustomData as u32, &custom_data_request()).unwrap();
+        let resp = test_util::call(
+            &*prot,
+            Some(USER_ID),
+            AccountManagementProtocolMethod::RetrieveAccount as u32,
+            &RetrieveAccountRequest.to_bytes(),
+        )
+        .unwrap();
+        let resp = RetrieveAccountResponse::from_bytes(&resp).unwrap();
+        assert_eq!(resp.o_account_data.pid, USER_ID);
+        assert_eq!(resp.o_account_data.str_name, "Foo");
+        assert_eq!(resp.o_public_data.type_name(), "PublicData");
+        assert_eq!(resp.o_private_data.data(), b"\xcc");
+    }
+
+    #[test]
+    fn find_by_name_like() {
+        let prot = new_protocol::<()>(storage());
+        let find = |str_like: &str, offset, size| {
+            let request = FindByNameLikeRequest {
+                ui_groups: 0,
+                str_like: str_like.into(),
+                result_range: ResultRange { offset, size },
+            };
+            let resp = test_util::call(&*prot, Some(USER_ID), AccountManagementProtocolMethod::FindByNameLike as u32, &request.to_bytes()).unwrap();
+            FindByNameLikeResponse::from_bytes(&resp)
+                .unwrap()
+                .plst_accounts
+                .into_iter()
+                .map(|a| (a.pid_owner, a.str_name))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(find("A%", 0, 10), [(1001, String::from("AAAABBBB"))]);
+        // "%" matches all players but not the system accounts
+        assert_eq!(find("%", 1, 1), [(1000, String::from("Foo"))]);
+    }
+}
@@ -1,15 +1,22 @@
 //! This module defines and implements the gRPC services for the dedicated server,
-//! including Friends, Users, Misc, UsersAdmin, and GamesAdmin services.
+//! including the `Friends`, `Users` and `Misc` services and the admin services `UsersAdmin`, `GamesAdmin`, `ContentAdmin`,
+//! `TrackingAdmin`, `NewsAdmin`, `ChallengesAdmin`, `LadderAdmin`, `NotificationsAdmin` and `NatAdmin`.
 
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::future::Future;
 use std::net::SocketAddr;
+use std::sync::mpsc::Sender;
 use std::sync::Arc;
 
-use quazal::rmc::types::Property;
-use quazal::rmc::types::QList;
+use quazal::rmc::types::DateTime;
 use quazal::rmc::types::StationURL;
+use server_api::challenges;
+use server_api::challenges::challenges_admin_server::ChallengesAdmin;
+use server_api::challenges::challenges_admin_server::ChallengesAdminServer;
+use server_api::content;
+use server_api::content::content_admin_server::ContentAdmin;
+use server_api::content::content_admin_server::ContentAdminServer;
 use server_api::friends;
 use server_api::friends::friends_server::Friends;
 use server_api::friends::friends_server::FriendsServer;
@@ -17,9 +24,24 @@ use server_api::friends::Friend;
 use server_api::games;
 use server_api::games::games_admin_server::GamesAdmin;
 use server_api::games::games_admin_server::GamesAdminServer;
+use server_api::ladder;
+use server_api::ladder::ladder_admin_server::LadderAdmin;
+use server_api::ladder::ladder_admin_server::LadderAdminServer;
 use server_api::misc;
 use server_api::misc::misc_server::Misc;
 use server_api::misc::misc_server::MiscServer;
+use server_api::nat;
+use server_api::nat::nat_admin_server::NatAdmin;
+use server_api::nat::nat_admin_server::NatAdminServer;
+use server_api::news;
+use server_api::news::news_admin_server::NewsAdmin;
+use server_api::news::news_admin_server::NewsAdminServer;
+use server_api::notifications;
+use server_api::notifications::notifications_admin_server::NotificationsAdmin;
+use server_api::notifications::notifications_admin_server::NotificationsAdminServer;
+use server_api::tracking;
+use server_api::tracking::tracking_admin_server::TrackingAdmin;
+use server_api::tracking::tracking_admin_server::TrackingAdminServer;
 use server_api::users;
 use server_api::users::users_admin_server::UsersAdmin;
 use server_api::users::users_admin_server::UsersAdminServer;
@@ -31,14 +53,33 @@ use sodiumoxide::base64;
 use sodiumoxide::crypto::secretbox;
 use sodiumoxide::crypto::secretbox::Key;
 use sodiumoxide::crypto::secretbox::Nonce;
+use tonic::transport::server::Router;
 use tonic::transport::Server;
 use tonic::Request;
 use tonic::Response;
 use tonic::Status;
 
 use crate::config::DebugConfig;
+use crate::config::MatchmakingConfig;
+use crate::game_session::attribute_ids;
+use crate::ladder::standings;
+use crate::ladder::LadderConfig;
+use crate::ladder::Season;
+use crate::ladder::LADDER_PATH;
+use crate::overlord_challenge::unix_now;
+use crate::overlord_challenge::ChallengeCatalogue;
+use crate::overlord_challenge::Schedule;
+use crate::overlord_challenge::CATALOGUE_PATH;
+use crate::overlord_news::NewsEntry;
+use crate::overlord_news::NewsFeed;
+use crate::storage::Ban;
+use crate::storage::Content;
 use crate::storage::LoginError;
+use crate::storage::NotificationKind;
 use crate::storage::Storage;
+use crate::storage::TrackingFilter;
+use crate::storage::TrackingInterval;
+use crate::strings::Strings;
 
 /// Implements the `Friends` gRPC service.
 pub struct MyFriends {
@@ -154,6 +195,7 @@ impl Users for MyUsers {
     ///
     /// Authenticates the user against the storage and generates an authorization token upon successful login.
     async fn login(&self, request: Request<users::LoginRequest>) -> Result<Response<users::LoginResponse>, Status> {
+        let ip = request.remote_addr().map(|a| a.ip().to_string());
         let request = request.into_inner();
         let username = request.username;
         let password = request.password;
@@ -169,6 +211,17 @@ impl Users for MyUsers {
             LoginError::NotFound => Status::not_found("Unknown user"),
         })?;
 
+        let ban = self
+            .storage
+            .find_active_ban_async(Some(user_id), None, ip.as_deref())
+            .await
+            .map_err(|e| Status::internal(format!("Login error: {e:?}")))?;
+        if let Some(ban) = ban {
+            warn!(self.logger, "Login of banned user {username} denied (ban {})", ban.id);
+            let locale = self.storage.find_user_locale_async(user_id).await.unwrap_or_default();
+            return Err(banned(&ban, locale.as_deref()));
+        }
+
         let user_id = format!("{user_id}");
         let n = secretbox::gen_nonce();
         let c = secretbox::seal(user_id.as_bytes(), &n, &self.key);
@@ -188,11 +241,22 @@ impl Users for MyUsers {
     ///
     /// Registers a new user in the storage, handling potential conflicts like duplicate usernames or Ubisoft IDs.
     async fn register(&self, request: Request<users::RegisterRequest>) -> Result<Response<users::RegisterResponse>, Status> {
+        let ip = request.remote_addr().map(|a| a.ip().to_string());
         let request = request.into_inner();
         let username = request.username;
         let password = request.password;
         let ubi_id = request.ubi_id;
 
+        let ban = self
+            .storage
+            .find_active_ban_async(None, Some(&ubi_id), ip.as_deref())
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        if let Some(ban) = ban {
+            warn!(self.logger, "Registration of {username} ({ubi_id}) denied (ban {})", ban.id);
+            return Err(banned(&ban, None));
+        }
+
         let error = if let Err(err) = self.storage.register_user_async(&username, &password, Some(&ubi_id)).await {
             match err.downcast::<sqlx::Error>() {
                 Ok(sqlx::Error::Database(db_err)) => {
@@ -212,6 +276,25 @@ impl Users for MyUsers {
     }
 }
 
+/// Parses an optional "YYYY-MM-DD HH:MM:SS" timestamp, empty for none.
+fn parse_time(time: &str, error: &str) -> Result<Option<String>, Status> {
+    if time.is_empty() {
+        return Ok(None);
+    }
+    let time: DateTime = time.parse().map_err(|_| Status::invalid_argument(error))?;
+    Ok(Some(time.to_string()))
+}
+
+/// Parses an optional "YYYY-MM-DD HH:MM:SS" expiration date, empty for none.
+fn parse_expiration(expires_at: &str) -> Result<Option<String>, Status> {
+    parse_time(expires_at, "Invalid expiration date")
+}
+
+/// Creates the error returned to banned users.
+fn banned(ban: &Ban, locale: Option<&str>) -> Status {
+    Status::permission_denied(Strings::default().ban_message(locale, ban))
+}
+
 /// Implements the `Misc` gRPC service.
 pub struct MyMisc {
     logger: Logger,
@@ -287,6 +370,28 @@ impl Misc for MyMisc {
 pub struct MyUsersAdmin {
     logger: Logger,
     storage: Arc<Storage>,
+    /// Kick channels of the secure servers.
+    kick: Vec<Sender<u32>>,
+}
+
+impl MyUsersAdmin {
+    /// Looks up a user by their Ubisoft ID.
+    async fn find_user(&self, ubi_id: &str) -> Result<crate::storage::User, Status> {
+        match self.storage.find_user_by_ubi_id_async(ubi_id).await {
+            Ok(Some(user)) => Ok(user),
+            Ok(None) => Err(Status::not_found("User not found")),
+            Err(_) => Err(Status::invalid_argument("Invalid ID")),
+        }
+    }
+
+    /// Disconnects a user from every secure server.
+    fn kick_user(&self, user_id: u32) {
+        for kick in &self.kick {
+            if kick.send(user_id).is_err() {
+                error!(self.logger, "Secure server stopped, can't kick user {user_id}");
+            }
+        }
+    }
 }
 
 #[tonic::async_trait]
@@ -382,6 +487,124 @@ impl UsersAdmin for MyUsersAdmin {
             Err(e) => Err(Status::internal(format!("{e:?}"))),
         }
     }
+
+    /// Handles requests to ban a user.
+    ///
+    /// The ban covers the user's account and Ubisoft ID, and optionally the address they last logged in from.
+    /// The user is kicked if they are online.
+    async fn ban(&self, request: Request<users::BanRequest>) -> Result<Response<users::BanResponse>, Status> {
+        let request = request.into_inner();
+        let user = self.find_user(&request.id).await?;
+        let expires_at = parse_expiration(&request.expires_at)?;
+        let id = self
+            .storage
+            .ban_user_async(user.id, &request.reason, &request.banned_by, expires_at.as_deref(), request.ban_ip)
+            .await
+            .map_err(|e| Status::internal(format!("{e:?}")))?;
+        self.kick_user(user.id);
+        Ok(Response::new(users::BanResponse { id }))
+    }
+
+    /// Handles requests to lift a ban.
+    async fn unban(&self, request: Request<users::UnbanRequest>) -> Result<Response<users::UnbanResponse>, Status> {
+        let ban_id = request.into_inner().id;
+        match self.storage.unban_async(ban_id).await {
+            Ok(true) => {
+                warn!(self.logger, "Lifted ban {ban_id}");
+                Ok(Response::new(users::UnbanResponse {}))
+            }
+            Ok(false) => Err(Status::not_found("Ban not found")),
+            Err(e) => Err(Status::internal(format!("{e:?}"))),
+        }
+    }
+
+    /// Handles requests to list active bans.
+    async fn list_bans(&self, request: Request<users::ListBansRequest>) -> Result<Response<users::ListBansResponse>, Status> {
+        let _request = request.into_inner();
+        let bans = self.storage.list_bans_async().await.map_err(|e| Status::internal(format!("{e:?}")))?;
+        Ok(Response::new(users::ListBansResponse {
+            bans: bans
+                .into_iter()
+                .map(|b| users::Ban {
+                    id: b.id,
+                    user_id: b.ubi_id.unwrap_or_default(),
+                    username: b.username.unwrap_or_default(),
+                    ip: b.ip.unwrap_or_default(),
+                    reason: b.reason,
+                    banned_by: b.banned_by,
+                    created_at: b.created_at,
+                    expires_at: b.expires_at.unwrap_or_default(),
+                })
+                .collect(),
+        }))
+    }
+
+    /// Handles requests to disconnect a user from the game.
+    async fn kick(&self, request: Request<users::KickRequest>) -> Result<Response<users::KickResponse>, Status> {
+        let user = self.find_user(&request.into_inner().id).await?;
+        warn!(self.logger, "Kicking user {user:?}");
+        self.kick_user(user.id);
+        Ok(Response::new(users::KickResponse {}))
+    }
+
+    /// Handles requests to list the privileges of a user.
+    async fn list_privileges(&self, request: Request<users::ListPrivilegesRequest>) -> Result<Response<users::ListPrivilegesResponse>, Status> {
+        let user = self.find_user(&request.into_inner().id).await?;
+        let privileges = self.storage.list_user_privileges_async(user.id).await.map_err(|e| Status::internal(format!("{e:?}")))?;
+        Ok(Response::new(users::ListPrivilegesResponse {
+            privileges: privileges
+                .into_iter()
+                .map(|p| users::Privilege {
+                    granted: p.is_granted(),
+                    overridden: p.granted.is_some(),
+                    id: p.id,
+                    description: p.description,
+                    is_default: p.is_default,
+                    expires_at: p.expires_at.unwrap_or_default(),
+                })
+                .collect(),
+        }))
+    }
+
+    /// Handles requests to grant a privilege to a user or to revoke it.
+    ///
+    /// Changes apply the next time the game requests the privileges, usually on login.
+    async fn set_privilege(&self, request: Request<users::SetPrivilegeRequest>) -> Result<Response<users::SetPrivilegeResponse>, Status> {
+        let request = request.into_inner();
+        let user = self.find_user(&request.id).await?;
+        let expires_at = parse_expiration(&request.expires_at)?;
+        let description = if request.description.is_empty() {
+            format!("Privilege {}", request.privilege_id)
+        } else {
+            request.description
+        };
+        self.storage
+            .set_user_privilege_async(user.id, request.privilege_id, &description, request.granted, expires_at.as_deref())
+            .await
+            .map_err(|e| Status::internal(format!("{e:?}")))?;
+        warn!(
+            self.logger,
+            "{} privilege {} for user {}",
+            if request.granted { "Granted" } else { "Revoked" },
+            request.privilege_id,
+            user.id
+        );
+        Ok(Response::new(users::SetPrivilegeResponse {}))
+    }
+
+    /// Handles requests to reset a privilege of a user to the default.
+    async fn reset_privilege(&self, request: Request<users::ResetPrivilegeRequest>) -> Result<Response<users::ResetPrivilegeResponse>, Status> {
+        let request = request.into_inner();
+        let user = self.find_user(&request.id).await?;
+        match self.storage.reset_user_privilege_async(user.id, request.privilege_id).await {
+            Ok(true) => {
+                warn!(self.logger, "Reset privilege {} for user {}", request.privilege_id, user.id);
+                Ok(Response::new(users::ResetPrivilegeResponse {}))
+            }
+            Ok(false) => Err(Status::not_found("Privilege not overridden")),
+            Err(e) => Err(Status::internal(format!("{e:?}"))),
+        }
+    }
 }
 
 /// Implements the `GamesAdmin` gRPC service for administrative game session management.
@@ -406,17 +629,10 @@ impl GamesAdmin for MyGamesAdmin {
             games: sessions
                 .into_iter()
                 .map(|s| {
-                    let attributes: QList<Property> = s
-                        .attributes
-                        .parse()
-                        .inspect_err(|e| {
-                            error!(self.logger, "Error parsing game type: {e}");
-                        })
-                        .unwrap_or_default();
-                    let attributes: HashMap<u32, u32> = attributes.0.into_iter().map(|p| (p.id, p.value)).collect();
+                    let attributes: HashMap<u32, u32> = s.attributes.iter().copied().collect();
 
                     // just guessing that 105 gives me what I want... ¯\_(ツ)_/¯
-                    let game_type = match attributes.get(&105) {
+                    let game_type = match attributes.get(&attribute_ids::GAME_TYPE) {
                         None => String::from("Lobby"),
                         Some(&1) => String::from("SvM"),
                         Some(&2) => String::from("Coop"),
@@ -428,6 +644,10 @@ impl GamesAdmin for MyGamesAdmin {
                         creator: s.participants.iter().find(|p| p.user_id == s.creator_id).unwrap().name.clone(),
                         participants: s.participants.into_iter().filter(|p| p.user_id != s.creator_id).map(|p| p.name).collect(),
                         game_type,
+                        map_id: attributes.get(&attribute_ids::MAP).copied().unwrap_or_default(),
+                        game_mode: attributes.get(&attribute_ids::GAME_MODE).copied().unwrap_or_default(),
+                        is_svm: attributes.get(&attribute_ids::SVM).is_some_and(|&v| v != 0),
+                        slots: attributes.get(&attribute_ids::SLOTS).copied().unwrap_or_default(),
                     }
                 })
                 .collect(),
@@ -450,6 +670,650 @@ impl GamesAdmin for MyGamesAdmin {
     }
 }
 
+/// Implements the `ContentAdmin` gRPC service for moderating user contents.
+struct MyContentAdmin {
+    logger: Logger,
+    storage: Arc<Storage>,
+}
+
+impl MyContentAdmin {
+    /// Looks up the internal user ID of a Ubisoft ID.
+    async fn user_id(&self, ubi_id: &str) -> Result<u32, Status> {
+        match self.storage.find_user_by_ubi_id_async(ubi_id).await {
+            Ok(Some(user)) => Ok(user.id),
+            Ok(None) => Err(Status::not_found("User not found")),
+            Err(_) => Err(Status::invalid_argument("Invalid ID")),
+        }
+    }
+
+    /// Converts a stored content, adding its owner's name and pending reports.
+    async fn to_content(&self, content: Content) -> Result<content::Content, Status> {
+        let owner = self
+            .storage
+            .find_user_by_id_async(content.owner_id)
+            .await
+            .map_err(|e| Status::internal(format!("{e:?}")))?
+            .map(|u| u.username)
+            .unwrap_or_default();
+        let reports = self.storage.list_content_reports_async(content.id).await.map_err(|e| Status::internal(format!("{e:?}")))?;
+        Ok(content::Content {
+            id: content.id,
+            type_id: content.type_id,
+            owner,
+            size: content.size,
+            updated_at: content.updated_at,
+            reports: reports
+                .into_iter()
+                .map(|r| content::Report {
+                    reporter: r.reporter_name,
+                    reason: r.reason,
+                    created_at: r.created_at,
+                    reporter_id: r.reporter_id.to_string(),
+                })
+                .collect(),
+            banned: content.banned,
+            verified: content.verified,
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl ContentAdmin for MyContentAdmin {
+    /// Handles requests to list reported contents waiting for moderation.
+    async fn list_reported(&self, request: Request<content::ListReportedRequest>) -> Result<Response<content::ListReportedResponse>, Status> {
+        let request = request.into_inner();
+        let type_id = (request.type_id != 0).then_some(request.type_id);
+        let (db_contents, _total) = self.storage.list_contents_to_moderate_async(type_id, request.threshold, 0, u32::MAX).await.map_err(|e| {
+            error!(self.logger, "Error listing reported contents: {e}");
+            Status::internal(format!("{e:?}"))
+        })?;
+
+        let mut contents = Vec::with_capacity(db_contents.len());
+        for c in db_contents {
+            contents.push(self.to_content(c).await?);
+        }
+        Ok(Response::new(content::ListReportedResponse { contents }))
+    }
+
+    /// Handles requests to accept a content and close its reports.
+    async fn verify(&self, request: Request<content::VerifyRequest>) -> Result<Response<content::VerifyResponse>, Status> {
+        let content_id = request.into_inner().id;
+        match self.storage.moderate_content_async(content_id, false).await {
+            Ok(true) => {
+                info!(self.logger, "Verified content {content_id}");
+                Ok(Response::new(content::VerifyResponse {}))
+            }
+            Ok(false) => Err(Status::not_found("Content not found")),
+            Err(e) => Err(Status::internal(format!("{e:?}"))),
+        }
+    }
+
+    /// Handles requests to hide a content from other players and close its reports.
+    async fn ban(&self, request: Request<content::BanRequest>) -> Result<Response<content::BanResponse>, Status> {
+        let content_id = request.into_inner().id;
+        match self.storage.moderate_content_async(content_id, true).await {
+            Ok(true) => {
+                warn!(self.logger, "Banned content {content_id}");
+                Ok(Response::new(content::BanResponse {}))
+            }
+            Ok(false) => Err(Status::not_found("Content not found")),
+            Err(e) => Err(Status::internal(format!("{e:?}"))),
+        }
+    }
+
+    /// Handles requests to delete a content.
+    async fn delete(&self, request: Request<content::DeleteRequest>) -> Result<Response<content::DeleteResponse>, Status> {
+        let content_id = request.into_inner().id;
+        match self.storage.delete_content_by_id_async(content_id).await {
+            Ok(true) => {
+                warn!(self.logger, "Deleted content {content_id}");
+                Ok(Response::new(content::DeleteResponse {}))
+            }
+            Ok(false) => Err(Status::not_found("Content not found")),
+            Err(e) => Err(Status::internal(format!("{e:?}"))),
+        }
+    }
+
+    /// Handles requests to ban a user from saving contents.
+    async fn ban_user(&self, request: Request<content::BanUserRequest>) -> Result<Response<content::BanUserResponse>, Status> {
+        let request = request.into_inner();
+        let user_id = self.user_id(&request.user_id).await?;
+        let expires_at = parse_expiration(&request.expires_at)?;
+        self.storage
+            .ban_content_user_async(user_id, request.type_id, &request.reason, expires_at.as_deref(), request.ban_contents)
+            .await
+            .map_err(|e| Status::internal(format!("{e:?}")))?;
+        Ok(Response::new(content::BanUserResponse {}))
+    }
+
+    /// Handles requests to lift the ban of a user.
+    async fn unban_user(&self, request: Request<content::UnbanUserRequest>) -> Result<Response<content::UnbanUserResponse>, Status> {
+        let request = request.into_inner();
+        let user_id = self.user_id(&request.user_id).await?;
+        match self.storage.unban_content_user_async(user_id, request.type_id).await {
+            Ok(true) => {
+                warn!(self.logger, "Unbanned user {user_id} from content type {}", request.type_id);
+                Ok(Response::new(content::UnbanUserResponse {}))
+            }
+            Ok(false) => Err(Status::not_found("Ban not found")),
+            Err(e) => Err(Status::internal(format!("{e:?}"))),
+        }
+    }
+
+    /// Handles requests to list the users banned from saving contents.
+    async fn list_banned_users(&self, request: Request<content::ListBannedUsersRequest>) -> Result<Response<content::ListBannedUsersResponse>, Status> {
+        let request = request.into_inner();
+        let (bans, _total) = self
+            .storage
+            .list_content_bans_async(request.type_id, 0, u32::MAX)
+            .await
+            .map_err(|e| Status::internal(format!("{e:?}")))?;
+        Ok(Response::new(content::ListBannedUsersResponse {
+            users: bans
+                .into_iter()
+                .map(|b| content::BannedUser {
+                    user_id: b.ubi_id,
+                    username: b.username,
+                    type_id: b.type_id,
+                    reason: b.reason,
+                    created_at: b.created_at,
+                    expires_at: b.expires_at.unwrap_or_default(),
+                })
+                .collect(),
+        }))
+    }
+}
+
+/// Implements the `TrackingAdmin` gRPC service for querying the received tracking tags.
+pub struct MyTrackingAdmin {
+    logger: Logger,
+    storage: Arc<Storage>,
+}
+
+impl MyTrackingAdmin {
+    /// Converts the user and the time range of a filter, returning `(user_id, since, until)`.
+    async fn parse_filter(&self, filter: &tracking::Filter) -> Result<(Option<u32>, Option<String>, Option<String>), Status> {
+        let user_id = if filter.user_id.is_empty() {
+            None
+        } else {
+            match self.storage.find_user_by_ubi_id_async(&filter.user_id).await {
+                Ok(Some(user)) => Some(user.id),
+                Ok(None) => return Err(Status::not_found("User not found")),
+                Err(_) => return Err(Status::invalid_argument("Invalid ID")),
+            }
+        };
+        Ok((user_id, parse_time(&filter.since, "Invalid time")?, parse_time(&filter.until, "Invalid time")?))
+    }
+}
+
+#[tonic::async_trait]
+impl TrackingAdmin for MyTrackingAdmin {
+    /// Handles requests to count the received tags.
+    async fn aggregate(&self, request: Request<tracking::AggregateRequest>) -> Result<Response<tracking::AggregateResponse>, Status> {
+        let request = request.into_inner();
+        let interval = match request.interval() {
+            tracking::Interval::None => TrackingInterval::None,
+            tracking::Interval::Hour => TrackingInterval::Hour,
+            tracking::Interval::Day => TrackingInterval::Day,
+        };
+        let filter = request.filter.unwrap_or_default();
+        let (user_id, since, until) = self.parse_filter(&filter).await?;
+        let filter = TrackingFilter {
+            tag: (!filter.tag.is_empty()).then_some(filter.tag.as_str()),
+            user_id,
+            since: since.as_deref(),
+            until: until.as_deref(),
+        };
+        let counts = self
+            .storage
+            .aggregate_tracking_tags_async(&filter, request.by_tag, request.by_user, interval)
+            .await
+            .map_err(|e| {
+                error!(self.logger, "Error aggregating tracking tags: {e}");
+                Status::internal(format!("{e:?}"))
+            })?;
+        Ok(Response::new(tracking::AggregateResponse {
+            counts: counts
+                .into_iter()
+                .map(|c| tracking::Count {
+                    tag: c.tag.unwrap_or_default(),
+                    user_id: c.ubi_id.unwrap_or_default(),
+                    username: c.username.unwrap_or_default(),
+                    time: c.time.unwrap_or_default(),
+                    count: c.count,
+                })
+                .collect(),
+        }))
+    }
+
+    /// Handles requests to list the latest received tags.
+    async fn list(&self, request: Request<tracking::ListRequest>) -> Result<Response<tracking::ListResponse>, Status> {
+        let request = request.into_inner();
+        let filter = request.filter.unwrap_or_default();
+        let (user_id, since, until) = self.parse_filter(&filter).await?;
+        let filter = TrackingFilter {
+            tag: (!filter.tag.is_empty()).then_some(filter.tag.as_str()),
+            user_id,
+            since: since.as_deref(),
+            until: until.as_deref(),
+        };
+        let tags = self.storage.list_tracking_tags_async(&filter, request.limit).await.map_err(|e| {
+            error!(self.logger, "Error listing tracking tags: {e}");
+            Status::internal(format!("{e:?}"))
+        })?;
+        Ok(Response::new(tracking::ListResponse {
+            tags: tags
+                .into_iter()
+                .map(|t| tracking::Tag {
+                    id: t.id,
+                    tag: t.tag,
+                    user_id: t.ubi_id.unwrap_or_default(),
+                    username: t.username.unwrap_or_default(),
+                    attributes: t.attributes,
+                    delta_time: t.delta_time,
+                    received_at: t.received_at,
+                })
+                .collect(),
+        }))
+    }
+}
+
+/// Implements the `NewsAdmin` gRPC service for managing the in-game news.
+pub struct MyNewsAdmin {
+    logger: Logger,
+    feed: NewsFeed,
+}
+
+#[tonic::async_trait]
+impl NewsAdmin for MyNewsAdmin {
+    /// Handles requests to list all news items.
+    async fn list(&self, _request: Request<news::ListRequest>) -> Result<Response<news::ListResponse>, Status> {
+        let items = self
+            .feed
+            .entries(&self.logger)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|e| news::NewsItem {
+                id: e.id,
+                locale: e.locale,
+                title: e.title,
+                link: e.link,
+                description: e.description,
+                source: e.source,
+                publish_at: e.publish_at,
+                expires_at: e.expires_at,
+            })
+            .collect();
+        Ok(Response::new(news::ListResponse { items }))
+    }
+
+    /// Handles requests to publish a news item.
+    async fn publish(&self, request: Request<news::PublishRequest>) -> Result<Response<news::PublishResponse>, Status> {
+        let item = request.into_inner().item.ok_or_else(|| Status::invalid_argument("Missing news item"))?;
+        let mut entry = NewsEntry {
+            id: item.id,
+            locale: item.locale,
+            title: item.title,
+            link: item.link,
+            description: item.description,
+            publish_at: parse_time(&item.publish_at, "Invalid publish date")?.unwrap_or_default(),
+            expires_at: parse_expiration(&item.expires_at)?.unwrap_or_default(),
+            ..NewsEntry::default()
+        };
+        if !item.source.is_empty() {
+            entry.source = item.source;
+        }
+        let id = self.feed.publish(entry).map_err(|e| {
+            error!(self.logger, "Error publishing news: {e}");
+            Status::internal(format!("{e:?}"))
+        })?;
+        info!(self.logger, "Published news item {id}");
+        Ok(Response::new(news::PublishResponse { id }))
+    }
+
+    /// Handles requests to retract a news item.
+    async fn retract(&self, request: Request<news::RetractRequest>) -> Result<Response<news::RetractResponse>, Status> {
+        let id = request.into_inner().id;
+        match self.feed.retract(id) {
+            Ok(true) => {
+                info!(self.logger, "Retracted news item {id}");
+                Ok(Response::new(news::RetractResponse {}))
+            }
+            Ok(false) => Err(Status::not_found("News item not found")),
+            Err(e) => Err(Status::internal(format!("{e:?}"))),
+        }
+    }
+}
+
+/// Implements the `ChallengesAdmin` gRPC service for managing the online challenges.
+pub struct MyChallengesAdmin {
+    logger: Logger,
+    storage: Arc<Storage>,
+}
+
+impl MyChallengesAdmin {
+    fn catalogue(&self) -> Result<ChallengeCatalogue, Status> {
+        ChallengeCatalogue::load(&self.logger, std::path::Path::new(CATALOGUE_PATH)).ok_or_else(|| Status::failed_precondition("No challenge catalogue"))
+    }
+
+    async fn find_user(&self, user_id: &str) -> Result<u32, Status> {
+        match self.storage.find_user_by_ubi_id_async(user_id).await {
+            Ok(Some(user)) => Ok(user.id),
+            Ok(None) => Err(Status::not_found("User not found")),
+            Err(_) => Err(Status::invalid_argument("Invalid ID")),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl ChallengesAdmin for MyChallengesAdmin {
+    /// Handles requests to list the active challenges.
+    async fn list_active(&self, _request: Request<challenges::ListActiveRequest>) -> Result<Response<challenges::ListActiveResponse>, Status> {
+        let catalogue = self.catalogue()?;
+        let challenges = catalogue
+            .active(unix_now())
+            .into_iter()
+            .map(|c| {
+                let (schedule, start_time, end_time) = match c.definition.schedule {
+                    Schedule::Permanent => (challenges::Schedule::Permanent, String::new(), String::new()),
+                    Schedule::Daily => (challenges::Schedule::Daily, c.start_time.to_string(), c.end_time.to_string()),
+                    Schedule::Weekly => (challenges::Schedule::Weekly, c.start_time.to_string(), c.end_time.to_string()),
+                    Schedule::Community { .. } => (challenges::Schedule::Community, c.start_time.to_string(), c.end_time.to_string()),
+                };
+                challenges::Challenge {
+                    id: c.definition.id,
+                    schedule: schedule.into(),
+                    start_time,
+                    end_time,
+                    target: c.definition.target(),
+                }
+            })
+            .collect();
+        Ok(Response::new(challenges::ListActiveResponse { challenges }))
+    }
+
+    /// Handles requests to list the progress of a user.
+    async fn list_progress(&self, request: Request<challenges::ListProgressRequest>) -> Result<Response<challenges::ListProgressResponse>, Status> {
+        let user_id = self.find_user(&request.into_inner().user_id).await?;
+        let progress = self.storage.list_challenge_progress_async(user_id).await.map_err(|e| {
+            error!(self.logger, "Error listing challenge progress: {e}");
+            Status::internal(format!("{e:?}"))
+        })?;
+        Ok(Response::new(challenges::ListProgressResponse {
+            progress: progress
+                .into_iter()
+                .map(|p| challenges::Progress {
+                    challenge_id: p.challenge_id,
+                    period: p.period,
+                    progress: p.progress,
+                    completed_at: p.completed_at.unwrap_or_default(),
+                    updated_at: p.updated_at,
+                })
+                .collect(),
+        }))
+    }
+
+    /// Handles requests to set the progress of a user in an active challenge.
+    async fn set_progress(&self, request: Request<challenges::SetProgressRequest>) -> Result<Response<challenges::SetProgressResponse>, Status> {
+        let request = request.into_inner();
+        let user_id = self.find_user(&request.user_id).await?;
+        let catalogue = self.catalogue()?;
+        let (period, target) = catalogue
+            .active(unix_now())
+            .into_iter()
+            .find(|c| c.definition.id == request.challenge_id)
+            .map(|c| (c.period(), c.definition.target()))
+            .ok_or_else(|| Status::not_found("Challenge not active"))?;
+        let completed = self
+            .storage
+            .update_challenge_progress_async(user_id, request.challenge_id, &period, request.progress, target)
+            .await
+            .map_err(|e| {
+                error!(self.logger, "Error setting challenge progress: {e}");
+                Status::internal(format!("{e:?}"))
+            })?;
+        info!(self.logger, "Set progress of user {user_id} in challenge {} to {}", request.challenge_id, request.progress);
+        Ok(Response::new(challenges::SetProgressResponse { completed }))
+    }
+}
+
+/// Implements the `LadderAdmin` gRPC service for inspecting the seasonal ladder.
+pub struct MyLadderAdmin {
+    logger: Logger,
+    storage: Arc<Storage>,
+}
+
+impl MyLadderAdmin {
+    fn config(&self) -> Result<LadderConfig, Status> {
+        LadderConfig::load(&self.logger, std::path::Path::new(LADDER_PATH)).ok_or_else(|| Status::failed_precondition("No ladder configuration"))
+    }
+}
+
+/// Converts a season to its gRPC representation.
+fn season(season: &Season, running: Option<&Season>) -> ladder::Season {
+    ladder::Season {
+        id: season.id,
+        name: season.name.clone(),
+        start: season.start.clone(),
+        end: season.end.clone(),
+        running: running.is_some_and(|r| r.id == season.id),
+    }
+}
+
+#[tonic::async_trait]
+impl LadderAdmin for MyLadderAdmin {
+    /// Handles requests to list the seasons.
+    async fn list_seasons(&self, _request: Request<ladder::ListSeasonsRequest>) -> Result<Response<ladder::ListSeasonsResponse>, Status> {
+        let config = self.config()?;
+        let running = config.active_season(DateTime::from_unix_timestamp(unix_now()));
+        Ok(Response::new(ladder::ListSeasonsResponse {
+            seasons: config.seasons.iter().map(|s| season(s, running)).collect(),
+        }))
+    }
+
+    /// Handles requests to list the placements of the players in a season.
+    async fn list_standings(&self, request: Request<ladder::ListStandingsRequest>) -> Result<Response<ladder::ListStandingsResponse>, Status> {
+        let request = request.into_inner();
+        let config = self.config()?;
+        let running = config.active_season(DateTime::from_unix_timestamp(unix_now()));
+        let selected = if request.season_id == 0 {
+            running.ok_or_else(|| Status::not_found("No season running"))?
+        } else {
+            config
+                .seasons
+                .iter()
+                .find(|s| s.id == request.season_id)
+                .ok_or_else(|| Status::not_found("Season not found"))?
+        };
+        let mut placements = standings(&self.storage, &config, selected).await.map_err(|e| {
+            error!(self.logger, "Error computing standings: {e}");
+            Status::internal(format!("{e:?}"))
+        })?;
+        if request.limit > 0 {
+            placements.truncate(usize::try_from(request.limit).unwrap_or(usize::MAX));
+        }
+        let mut result = Vec::with_capacity(placements.len());
+        for placement in placements {
+            let user = self.storage.find_user_by_id_async(placement.user_id).await.map_err(|e| {
+                error!(self.logger, "Error finding user: {e}");
+                Status::internal(format!("{e:?}"))
+            })?;
+            let Some(user) = user else {
+                continue;
+            };
+            result.push(ladder::Placement {
+                user_id: user.ubi_id,
+                username: user.username,
+                points: placement.points,
+                rank: placement.rank,
+            });
+        }
+        Ok(Response::new(ladder::ListStandingsResponse {
+            season: Some(season(selected, running)),
+            placements: result,
+        }))
+    }
+}
+
+/// Implements the `NotificationsAdmin` gRPC service for messaging players.
+pub struct MyNotificationsAdmin {
+    logger: Logger,
+    storage: Arc<Storage>,
+}
+
+impl MyNotificationsAdmin {
+    async fn find_user(&self, user_id: &str) -> Result<u32, Status> {
+        match self.storage.find_user_by_ubi_id_async(user_id).await {
+            Ok(Some(user)) => Ok(user.id),
+            Ok(None) => Err(Status::not_found("User not found")),
+            Err(_) => Err(Status::invalid_argument("Invalid ID")),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl NotificationsAdmin for MyNotificationsAdmin {
+    /// Handles requests to send an admin message to a user.
+    async fn send(&self, request: Request<notifications::SendRequest>) -> Result<Response<notifications::SendResponse>, Status> {
+        let request = request.into_inner();
+        if request.text.is_empty() {
+            return Err(Status::invalid_argument("Empty message"));
+        }
+        let user_id = self.find_user(&request.user_id).await?;
+        let id = self
+            .storage
+            .add_notification_async(user_id, None, NotificationKind::AdminMessage, (0, 0), &request.text)
+            .await
+            .map_err(|e| {
+                error!(self.logger, "Error adding notification: {e}");
+                Status::internal(format!("{e:?}"))
+            })?;
+        info!(self.logger, "Sent message {id} to user {user_id}");
+        Ok(Response::new(notifications::SendResponse { id }))
+    }
+
+    /// Handles requests to list the notifications of a user.
+    async fn list(&self, request: Request<notifications::ListRequest>) -> Result<Response<notifications::ListResponse>, Status> {
+        let request = request.into_inner();
+        let user_id = self.find_user(&request.user_id).await?;
+        let limit = if request.limit == 0 { 100 } else { request.limit };
+        let stored = self.storage.list_notifications_async(user_id, limit).await.map_err(|e| {
+            error!(self.logger, "Error listing notifications: {e}");
+            Status::internal(format!("{e:?}"))
+        })?;
+        let mut result = Vec::with_capacity(stored.len());
+        for notification in stored {
+            let sender_id = match notification.sender_id {
+                Some(sender_id) => self
+                    .storage
+                    .find_user_by_id_async(sender_id)
+                    .await
+                    .map_err(|e| Status::internal(format!("{e:?}")))?
+                    .map(|u| u.ubi_id)
+                    .unwrap_or_default(),
+                None => String::new(),
+            };
+            let kind = match notification.kind {
+                NotificationKind::GameInvite => notifications::Kind::GameInvite,
+                NotificationKind::FriendRequest => notifications::Kind::FriendRequest,
+                NotificationKind::ClanInvite => notifications::Kind::ClanInvite,
+                NotificationKind::AdminMessage => notifications::Kind::AdminMessage,
+            };
+            result.push(notifications::Notification {
+                id: notification.id,
+                kind: kind.into(),
+                sender_id,
+                text: notification.text,
+                created_at: notification.created_at,
+                read_at: notification.read_at.unwrap_or_default(),
+            });
+        }
+        Ok(Response::new(notifications::ListResponse { notifications: result }))
+    }
+}
+
+/// Implements the `NatAdmin` gRPC service for NAT traversal statistics.
+pub struct MyNatAdmin {
+    logger: Logger,
+    storage: Arc<Storage>,
+    /// Failures in a row after which a pair isn't matched, 0 if disabled.
+    unreachable_after: u32,
+    /// Seconds after which the failures of a pair are forgotten.
+    unreachable_for: u64,
+}
+
+impl MyNatAdmin {
+    async fn ubi_id(&self, user_id: u32) -> Result<String, Status> {
+        Ok(self
+            .storage
+            .find_user_by_id_async(user_id)
+            .await
+            .map_err(|e| Status::internal(format!("{e:?}")))?
+            .map(|u| u.ubi_id)
+            .unwrap_or_default())
+    }
+}
+
+#[tonic::async_trait]
+impl NatAdmin for MyNatAdmin {
+    /// Handles requests for the NAT traversal statistics.
+    async fn get_stats(&self, _request: Request<nat::GetStatsRequest>) -> Result<Response<nat::GetStatsResponse>, Status> {
+        let stats = self.storage.nat_stats_async(self.unreachable_after, self.unreachable_for).await.map_err(|e| {
+            error!(self.logger, "Error getting NAT stats: {e}");
+            Status::internal(format!("{e:?}"))
+        })?;
+        let reported = stats.successes + stats.failures;
+        Ok(Response::new(nat::GetStatsResponse {
+            pairs: stats.pairs,
+            probes: stats.probes,
+            successes: stats.successes,
+            failures: stats.failures,
+            success_rate: if reported == 0 { 0.0 } else { f64::from(stats.successes) / f64::from(reported) },
+            unreachable_pairs: if self.unreachable_after == 0 { 0 } else { stats.unreachable },
+            reported_properties: stats.reported_properties,
+        }))
+    }
+
+    /// Handles requests to list the NAT traversal results of pairs.
+    async fn list_results(&self, request: Request<nat::ListResultsRequest>) -> Result<Response<nat::ListResultsResponse>, Status> {
+        let request = request.into_inner();
+        let user_id = if request.user_id.is_empty() {
+            None
+        } else {
+            match self.storage.find_user_by_ubi_id_async(&request.user_id).await {
+                Ok(Some(user)) => Some(user.id),
+                Ok(None) => return Err(Status::not_found("User not found")),
+                Err(_) => return Err(Status::invalid_argument("Invalid ID")),
+            }
+        };
+        let limit = if request.limit == 0 { 100 } else { request.limit };
+        let stored = self.storage.list_nat_results_async(user_id, limit, self.unreachable_for).await.map_err(|e| {
+            error!(self.logger, "Error listing NAT results: {e}");
+            Status::internal(format!("{e:?}"))
+        })?;
+        let mut results = Vec::with_capacity(stored.len());
+        for result in stored {
+            // the requested user first
+            let (user, peer) = if user_id == Some(result.user_b) {
+                (result.user_b, result.user_a)
+            } else {
+                (result.user_a, result.user_b)
+            };
+            results.push(nat::Result {
+                user_id: self.ubi_id(user).await?,
+                peer_id: self.ubi_id(peer).await?,
+                probes: result.probes,
+                successes: result.successes,
+                failures: result.failures,
+                failure_streak: result.failure_streak,
+                unreachable: self.unreachable_after > 0 && result.failure_streak >= self.unreachable_after,
+                updated_at: result.updated_at,
+            });
+        }
+        Ok(Response::new(nat::ListResultsResponse { results }))
+    }
+}
+
 /// Creates an authenticated gRPC service.
 ///
 /// This function wraps a gRPC service with an interceptor that validates
@@ -521,17 +1385,93 @@ fn base32(data: &[u8]) -> String {
     s
 }
 
+/// Adds the administrative services, authenticated with a key printed on startup.
+fn add_admin_services(builder: Router, logger: Logger, storage: Arc<Storage>, kick: Vec<Sender<u32>>, matchmaking: MatchmakingConfig) -> Router {
+    warn!(logger, "Enabling admin services");
+    let preshared = base32(&secretbox::gen_key().0);
+    println!("Admin Key: {preshared}");
+    builder
+        .add_service(preshared_authentication(
+            UsersAdminServer::new(MyUsersAdmin {
+                logger: logger.clone(),
+                storage: Arc::clone(&storage),
+                kick,
+            }),
+            preshared.clone(),
+        ))
+        .add_service(preshared_authentication(
+            GamesAdminServer::new(MyGamesAdmin {
+                logger: logger.clone(),
+                storage: Arc::clone(&storage),
+            }),
+            preshared.clone(),
+        ))
+        .add_service(preshared_authentication(
+            ContentAdminServer::new(MyContentAdmin {
+                logger: logger.clone(),
+                storage: Arc::clone(&storage),
+            }),
+            preshared.clone(),
+        ))
+        .add_service(preshared_authentication(
+            TrackingAdminServer::new(MyTrackingAdmin {
+                logger: logger.clone(),
+                storage: Arc::clone(&storage),
+            }),
+            preshared.clone(),
+        ))
+        .add_service(preshared_authentication(
+            NewsAdminServer::new(MyNewsAdmin {
+                logger: logger.clone(),
+                feed: NewsFeed::new("data/news.json"),
+            }),
+            preshared.clone(),
+        ))
+        .add_service(preshared_authentication(
+            ChallengesAdminServer::new(MyChallengesAdmin {
+                logger: logger.clone(),
+                storage: Arc::clone(&storage),
+            }),
+            preshared.clone(),
+        ))
+        .add_service(preshared_authentication(
+            LadderAdminServer::new(MyLadderAdmin {
+                logger: logger.clone(),
+                storage: Arc::clone(&storage),
+            }),
+            preshared.clone(),
+        ))
+        .add_service(preshared_authentication(
+            NotificationsAdminServer::new(MyNotificationsAdmin {
+                logger: logger.clone(),
+                storage: Arc::clone(&storage),
+            }),
+            preshared.clone(),
+        ))
+        .add_service(preshared_authentication(
+            NatAdminServer::new(MyNatAdmin {
+                logger,
+                storage,
+                unreachable_after: matchmaking.unreachable_after,
+                unreachable_for: matchmaking.unreachable_for,
+            }),
+            preshared,
+        ))
+}
+
 /// Starts the gRPC server, binding to the specified address and registering services.
 ///
 /// This function initializes the server, sets up reflection services, and registers
 /// the Friends, Users, and Misc gRPC services. Optionally, it enables and registers
-/// administrative services (UsersAdmin and GamesAdmin) if `enable_admin_services` is true.
+/// the administrative services of `add_admin_services` if `enable_admin_services` is true.
 pub async fn start_server(
     logger: Logger,
     storage: Arc<Storage>,
     server_addr: SocketAddr,
     debug_config: Arc<DebugConfig>,
     enable_admin_services: bool,
+    kick: Vec<Sender<u32>>,
+    matchmaking: MatchmakingConfig,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let key = secretbox::gen_key();
     info!(logger, "Listening on {server_addr}");
@@ -579,18 +1519,7 @@ pub async fn start_server(
         }));
 
     let builder = if enable_admin_services {
-        warn!(logger, "Enabling admin services");
-        let preshared = base32(&secretbox::gen_key().0);
-        println!("Admin Key: {preshared}");
-        builder
-            .add_service(preshared_authentication(
-                UsersAdminServer::new(MyUsersAdmin {
-                    logger: logger.clone(),
-                    storage: Arc::clone(&storage),
-                }),
-                preshared.clone(),
-            ))
-            .add_service(preshared_authentication(GamesAdminServer::new(MyGamesAdmin { logger, storage }), preshared))
+        add_admin_services(builder, logger, storage, kick, matchmaking)
     } else {
         builder
     };
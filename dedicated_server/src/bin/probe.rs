@@ -0,0 +1,61 @@
+//! A liveness probe for the Quazal services of the dedicated server.
+//!
+//! It connects to the secure service without logging in, calls the `HealthProtocol` and the `MonitoringProtocol`, prints
+//! the results and exits with an error if the service doesn't answer or its database is unreachable.
+
+use std::time::Duration;
+
+use eyre::bail;
+use quazal::prudp::client::Client;
+use quazal::rmc::basic::FromStream;
+use quazal::Context;
+use sc_bl_protocols::health_service::health_protocol::HealthProtocolMethod;
+use sc_bl_protocols::health_service::health_protocol::PingDatabaseResponse;
+use sc_bl_protocols::health_service::health_protocol::HEALTH_PROTOCOL_ID;
+use sc_bl_protocols::health_service::monitoring_protocol::GetClusterMembersResponse;
+use sc_bl_protocols::health_service::monitoring_protocol::MonitoringProtocolMethod;
+use sc_bl_protocols::health_service::monitoring_protocol::PingDaemonResponse;
+use sc_bl_protocols::health_service::monitoring_protocol::MONITORING_PROTOCOL_ID;
+
+#[derive(argh::FromArgs, PartialEq, Debug)]
+/// probe a quazal service of the dedicated server
+struct Args {
+    /// seconds to wait for each reply.
+    #[argh(option, short = 't', default = "5")]
+    timeout: u64,
+
+    /// access key of the service, the one of the game by default.
+    #[argh(option, short = 'k')]
+    access_key: Option<String>,
+
+    /// address of the secure service, like `127.0.0.1:21127`.
+    #[argh(positional)]
+    address: String,
+}
+
+/// Main entry point of the probe.
+pub fn main() -> color_eyre::Result<()> {
+    color_eyre::install()?;
+    let args: Args = argh::from_env();
+
+    let mut ctx = Context::splinter_cell_blacklist();
+    if let Some(access_key) = args.access_key {
+        ctx.access_key = access_key.into_bytes();
+    }
+    let mut client = Client::connect(ctx, args.address.as_str(), Duration::from_secs(args.timeout))?;
+
+    let daemon = PingDaemonResponse::from_bytes(&client.call(MONITORING_PROTOCOL_ID, MonitoringProtocolMethod::PingDaemon as u32, Vec::new())?)?;
+    let database = PingDatabaseResponse::from_bytes(&client.call(HEALTH_PROTOCOL_ID, HealthProtocolMethod::PingDatabase as u32, Vec::new())?)?;
+    let members = GetClusterMembersResponse::from_bytes(&client.call(MONITORING_PROTOCOL_ID, MonitoringProtocolMethod::GetClusterMembers as u32, Vec::new())?)?;
+    client.close()?;
+
+    println!("daemon: {}", if daemon.return_value { "ok" } else { "failing" });
+    println!("database: {}", if database.return_value { "ok" } else { "unreachable" });
+    for value in members.str_values {
+        println!("{value}");
+    }
+    if !daemon.return_value || !database.return_value {
+        bail!("{} is unhealthy", args.address);
+    }
+    Ok(())
+}
@@ -1,4 +1,16 @@
 //! Implements the `ChallengeHelperProtocolServer` for handling challenge-related requests.
+//!
+//! Friend challenges compare the stats of a player with those of their friends. Which stats are compared is read from
+//! `data/friend_challenges.json`: each challenge type names a stat of a stat board, with the board context being the
+//! map. For each map the player gets the friend that's closest to them among those with a better value. The generated
+//! challenges are kept until the end of the day, or until the player asks for them with other friends.
+//!
+//! The online challenges are the active ones of the challenge catalogue of the `OverlordChallengeProtocol`.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
 
 use quazal::prudp::ClientRegistry;
 use quazal::rmc::types::QList;
@@ -6,33 +18,244 @@ use quazal::rmc::Error;
 use quazal::rmc::Protocol;
 use quazal::ClientInfo;
 use quazal::Context;
+use sc_bl_protocols::challenge_helper_service::types::FriendChallenge;
+use sc_bl_protocols::challenge_helper_service::types::OnlineChallenge;
+use serde::Deserialize;
 use slog::Logger;
 
+use crate::data_file::DataFile;
 use crate::login_required;
+use crate::overlord_challenge::player_challenges;
+use crate::overlord_challenge::unix_now;
+use crate::overlord_challenge::ChallengeCatalogue;
+use crate::overlord_challenge::CATALOGUE_PATH;
 use crate::protocols::challenge_helper_service::challenge_helper_protocol::ChallengeHelperProtocolServer;
 use crate::protocols::challenge_helper_service::challenge_helper_protocol::ChallengeHelperProtocolServerTrait;
 use crate::protocols::challenge_helper_service::challenge_helper_protocol::GenerateFriendChallengesRequest;
 use crate::protocols::challenge_helper_service::challenge_helper_protocol::GenerateFriendChallengesResponse;
+use crate::protocols::challenge_helper_service::challenge_helper_protocol::GenerateMyFriendChallengesRequest;
+use crate::protocols::challenge_helper_service::challenge_helper_protocol::GenerateMyFriendChallengesResponse;
+use crate::protocols::challenge_helper_service::challenge_helper_protocol::GetOnlineChallengesRequest;
+use crate::protocols::challenge_helper_service::challenge_helper_protocol::GetOnlineChallengesResponse;
+use crate::storage::Storage;
+use crate::strings::Strings;
+
+const DAY: u64 = 24 * 60 * 60;
+/// Most friends whose stats are compared, the others are ignored.
+const MAX_FRIENDS: usize = 100;
+
+#[derive(Debug, Default, Deserialize)]
+struct FriendChallengeDefinitions {
+    #[serde(default)]
+    challenges: Vec<FriendChallengeDefinition>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FriendChallengeDefinition {
+    challenge_type: u32,
+    board_id: u32,
+    stat_id: u32,
+    /// Whether a higher value beats a lower one, like a score. Otherwise lower is better, like a time.
+    #[serde(default = "default_true")]
+    higher_is_better: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// A generated challenge, kept in the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct GeneratedChallenge {
+    challenge_type: u32,
+    map_id: u32,
+    friend_to_beat_pid: u32,
+    value_on_hand: i64,
+    value_to_beat: i64,
+}
+
+impl From<GeneratedChallenge> for FriendChallenge {
+    fn from(c: GeneratedChallenge) -> Self {
+        let to_u32 = |value: i64| u32::try_from(value.max(0)).unwrap_or(u32::MAX);
+        Self {
+            challenge_type: c.challenge_type,
+            map_id: c.map_id,
+            friend_to_beat_pid: c.friend_to_beat_pid,
+            value_on_hand: to_u32(c.value_on_hand),
+            value_to_beat: to_u32(c.value_to_beat),
+        }
+    }
+}
+
+/// Challenges of a player: the day they were generated on, the sorted friends they were generated with and the
+/// challenges.
+type CacheEntry = (u64, Vec<u32>, Vec<GeneratedChallenge>);
 
 /// Implementation of the `ChallengeHelperProtocolServerTrait`.
-struct ChallengeHelperProtocolServerImpl;
+struct ChallengeHelperProtocolServerImpl {
+    storage: Arc<Storage>,
+    definitions: DataFile<FriendChallengeDefinitions>,
+    catalogue: DataFile<ChallengeCatalogue>,
+    strings: Strings,
+    /// Challenges generated today per player.
+    cache: Mutex<HashMap<u32, CacheEntry>>,
+}
+
+impl ChallengeHelperProtocolServerImpl {
+    /// Loads the friend challenge definitions, there are none if the file is missing or invalid.
+    fn definitions(&self, logger: &Logger) -> Arc<FriendChallengeDefinitions> {
+        match self.definitions.load() {
+            Ok(definitions) => definitions.unwrap_or_default(),
+            Err(e) => {
+                error!(logger, "Invalid friend challenges {}: {e}", self.definitions.path().display());
+                Arc::default()
+            }
+        }
+    }
+
+    /// Compares the stats of a player with those of their friends.
+    fn generate(&self, logger: &Logger, target_pid: u32, friend_pids: &[u32]) -> Result<Vec<GeneratedChallenge>, Error> {
+        let mut user_ids = friend_pids.to_vec();
+        user_ids.push(target_pid);
+        let mut challenges = Vec::new();
+        for definition in &self.definitions(logger).challenges {
+            let stats = rmc_err!(
+                self.storage.list_player_stats(&user_ids, definition.board_id, definition.stat_id),
+                logger,
+                "error listing stats"
+            )?;
+            // own value and the values of the friends per map
+            let mut maps = BTreeMap::<u32, (Option<i64>, Vec<(u32, i64)>)>::new();
+            for stat in stats {
+                let (own, friends) = maps.entry(stat.context_id).or_default();
+                if stat.user_id == target_pid {
+                    *own = Some(stat.value);
+                } else {
+                    friends.push((stat.user_id, stat.value));
+                }
+            }
+            for (map_id, (own, friends)) in maps {
+                let own = match own {
+                    Some(own) => own,
+                    // nothing to beat yet
+                    None if definition.higher_is_better => 0,
+                    None => continue,
+                };
+                let friend = friends
+                    .into_iter()
+                    .filter(|(_, value)| if definition.higher_is_better { *value > own } else { *value < own })
+                    .min_by_key(|(_, value)| value.abs_diff(own));
+                if let Some((friend_to_beat_pid, value_to_beat)) = friend {
+                    challenges.push(GeneratedChallenge {
+                        challenge_type: definition.challenge_type,
+                        map_id,
+                        friend_to_beat_pid,
+                        value_on_hand: own,
+                        value_to_beat,
+                    });
+                }
+            }
+        }
+        Ok(challenges)
+    }
+
+    /// Returns the challenges generated today for a player and their friends, generating them if there are none yet.
+    fn cached(&self, logger: &Logger, target_pid: u32, friend_pids: Vec<u32>) -> Result<QList<FriendChallenge>, Error> {
+        let mut friend_pids = friend_pids.into_iter().filter(|pid| *pid != target_pid).collect::<Vec<_>>();
+        friend_pids.sort_unstable();
+        friend_pids.dedup();
+        if friend_pids.len() > MAX_FRIENDS {
+            warn!(logger, "Comparing the stats of {target_pid} with only {MAX_FRIENDS} of {} friends", friend_pids.len());
+            friend_pids.truncate(MAX_FRIENDS);
+        }
+
+        let day = unix_now() / DAY;
+        let cached = self
+            .cache
+            .lock()
+            .unwrap()
+            .get(&target_pid)
+            .filter(|(d, friends, _)| *d == day && *friends == friend_pids)
+            .map(|(_, _, c)| c.clone());
+        let challenges = if let Some(challenges) = cached {
+            challenges
+        } else {
+            let challenges = self.generate(logger, target_pid, &friend_pids)?;
+            let mut cache = self.cache.lock().unwrap();
+            cache.retain(|_, (d, _, _)| *d == day);
+            cache.insert(target_pid, (day, friend_pids, challenges.clone()));
+            challenges
+        };
+        Ok(challenges.into_iter().map(FriendChallenge::from).collect())
+    }
+}
 
 impl<CI> ChallengeHelperProtocolServerTrait<CI> for ChallengeHelperProtocolServerImpl {
-    /// Handles the `GenerateFriendChallenges` request.
+    /// Handles the `GenerateFriendChallenges` request, returning a challenge per challenge type and map where a friend
+    /// did better than the target player.
     ///
-    /// This function requires the client to be logged in. It currently returns an empty list of challenges.
+    /// This function requires the client to be logged in. The challenges are generated once per day.
     fn generate_friend_challenges(
         &self,
-        _logger: &Logger,
+        logger: &Logger,
         _ctx: &Context,
         ci: &mut ClientInfo<CI>,
-        _request: GenerateFriendChallengesRequest,
+        request: GenerateFriendChallengesRequest,
         _client_registry: &ClientRegistry<CI>,
         _socket: &std::net::UdpSocket,
     ) -> Result<GenerateFriendChallengesResponse, Error> {
-        // Ensure the client is logged in.
         login_required(&*ci)?;
-        Ok(GenerateFriendChallengesResponse { result: QList::default() })
+        Ok(GenerateFriendChallengesResponse {
+            result: self.cached(logger, request.target_pid, request.friend_pids.0)?,
+        })
+    }
+
+    /// Handles the `GenerateMyFriendChallenges` request, generating the friend challenges for the calling player.
+    ///
+    /// This function requires the client to be logged in.
+    fn generate_my_friend_challenges(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GenerateMyFriendChallengesRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GenerateMyFriendChallengesResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        Ok(GenerateMyFriendChallengesResponse {
+            result: self.cached(logger, user_id, request.friend_pids.0)?,
+        })
+    }
+
+    /// Handles the `GetOnlineChallenges` request, returning the active challenges of the catalogue and whether the
+    /// player completed them.
+    ///
+    /// This function requires the client to be logged in.
+    fn get_online_challenges(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        _request: GetOnlineChallengesRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetOnlineChallengesResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let challenges = player_challenges(logger, &self.storage, &self.strings, &self.catalogue, user_id, unix_now())?;
+        Ok(GetOnlineChallengesResponse {
+            online_challenges: challenges
+                .unwrap_or_default()
+                .into_iter()
+                .map(|c| OnlineChallenge {
+                    challenge_id: c.id,
+                    static_data: c.xml,
+                    start_time: c.start_time,
+                    end_time: c.end_time,
+                    is_complete: c.is_complete,
+                })
+                .collect::<QList<_>>(),
+        })
     }
 }
 
@@ -40,6 +263,103 @@ impl<CI> ChallengeHelperProtocolServerTrait<CI> for ChallengeHelperProtocolServe
 ///
 /// This function is typically used to register the challenge helper protocol
 /// with the server's protocol dispatcher.
-pub fn new_protocol<T: 'static>() -> Box<dyn Protocol<T>> {
-    Box::new(ChallengeHelperProtocolServer::new(ChallengeHelperProtocolServerImpl))
+pub fn new_protocol<T: 'static>(storage: Arc<Storage>) -> Box<dyn Protocol<T>> {
+    Box::new(ChallengeHelperProtocolServer::new(ChallengeHelperProtocolServerImpl {
+        storage,
+        definitions: DataFile::new("data/friend_challenges.json"),
+        catalogue: DataFile::new(CATALOGUE_PATH),
+        strings: Strings::default(),
+        cache: Mutex::default(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use quazal::rmc::basic::FromStream;
+
+    use super::*;
+    use crate::protocols::challenge_helper_service::challenge_helper_protocol::ChallengeHelperProtocolMethod;
+    use crate::test_util;
+
+    const DEFINITIONS: &str = r#"{"challenges": [
+        {"challenge_type": 1, "board_id": 10, "stat_id": 100},
+        {"challenge_type": 2, "board_id": 10, "stat_id": 101, "higher_is_better": false}
+    ]}"#;
+
+    /// `GenerateFriendChallenges` for pid 1000 with the friends 1001 and 1002
+    const GENERATE: &[u8] = b"\xe8\x03\x00\x00\x02\x00\x00\x00\xe9\x03\x00\x00\xea\x03\x00\x00";
+
+    fn generate(prot: &dyn Protocol<()>) -> Vec<FriendChallenge> {
+        let resp = test_util::call(prot, Some(1000), ChallengeHelperProtocolMethod::GenerateFriendChallenges as u32, GENERATE).unwrap();
+        GenerateFriendChallengesResponse::from_bytes(&resp).unwrap().result.0
+    }
+
+    #[test]
+    fn friend_challenges() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let path = std::env::temp_dir().join(format!("friend_challenges_{}.json", std::process::id()));
+        std::fs::write(&path, DEFINITIONS).unwrap();
+        let prot = ChallengeHelperProtocolServer::new(ChallengeHelperProtocolServerImpl {
+            storage: Arc::clone(&storage),
+            definitions: DataFile::new(&path),
+            catalogue: DataFile::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../data/challenges.json")),
+            strings: Strings::default(),
+            cache: Mutex::default(),
+        });
+
+        // scores on map 5, the closest better friend is the one to beat
+        storage.store_player_stats(1000, 10, 5, &[(100, 10), (101, 60)]).unwrap();
+        storage.store_player_stats(1001, 10, 5, &[(100, 30), (101, 50)]).unwrap();
+        storage.store_player_stats(1002, 10, 5, &[(100, 20), (101, 70)]).unwrap();
+        // only a friend played map 6
+        storage.store_player_stats(1001, 10, 6, &[(100, 5), (101, 5)]).unwrap();
+
+        let challenges = generate(&prot)
+            .into_iter()
+            .map(|c| (c.challenge_type, c.map_id, c.friend_to_beat_pid, c.value_on_hand, c.value_to_beat))
+            .collect::<Vec<_>>();
+        assert_eq!(challenges, [(1, 5, 1002, 10, 20), (1, 6, 1001, 0, 5), (2, 5, 1001, 60, 50)]);
+
+        // cached for the day
+        storage.store_player_stats(1000, 10, 5, &[(100, 40)]).unwrap();
+        assert_eq!(generate(&prot).len(), 3);
+        // the same challenges for the calling player, without the pid in the request
+        let resp = test_util::call(&prot, Some(1000), ChallengeHelperProtocolMethod::GenerateMyFriendChallenges as u32, &GENERATE[4..]).unwrap();
+        assert_eq!(GenerateMyFriendChallengesResponse::from_bytes(&resp).unwrap().result.len(), 3);
+        // generated again with other friends
+        let resp = test_util::call(
+            &prot,
+            Some(1000),
+            ChallengeHelperProtocolMethod::GenerateMyFriendChallenges as u32,
+            b"\x01\x00\x00\x00\xe9\x03\x00\x00",
+        )
+        .unwrap();
+        assert_eq!(GenerateMyFriendChallengesResponse::from_bytes(&resp).unwrap().result.len(), 2);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn online_challenges() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let catalogue_file = DataFile::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../data/challenges.json"));
+        let catalogue = catalogue_file.load().unwrap().unwrap();
+        let prot = ChallengeHelperProtocolServer::new(ChallengeHelperProtocolServerImpl {
+            storage: Arc::clone(&storage),
+            definitions: DataFile::new(""),
+            catalogue: catalogue_file,
+            strings: Strings::default(),
+            cache: Mutex::default(),
+        });
+        let active = catalogue.active(unix_now());
+        let (period, target) = (active[0].period(), active[0].definition.target());
+        test_util::block_on(storage.update_challenge_progress_async(1000, active[0].definition.id, &period, target, target)).unwrap();
+
+        let resp = test_util::call(&prot, Some(1000), ChallengeHelperProtocolMethod::GetOnlineChallenges as u32, &[]).unwrap();
+        let challenges = GetOnlineChallengesResponse::from_bytes(&resp).unwrap().online_challenges.0;
+        assert_eq!(
+            challenges.iter().map(|c| (c.challenge_id, c.is_complete)).collect::<Vec<_>>(),
+            active.iter().enumerate().map(|(i, c)| (c.definition.id, i == 0)).collect::<Vec<_>>()
+        );
+        assert_eq!(challenges[0].start_time, active[0].start_time);
+    }
 }
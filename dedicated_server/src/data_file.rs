@@ -0,0 +1,83 @@
+//! JSON data files that are read again once they change.
+//!
+//! Data files like the news or the Uplay catalogue are edited while the server runs. They're parsed again when their
+//! modification time or size changes, so requests don't read and parse them each time.
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use serde::de::DeserializeOwned;
+
+/// The modification time and size of a file.
+type Version = (SystemTime, u64);
+
+/// A JSON data file and the content loaded last.
+pub struct DataFile<T> {
+    path: PathBuf,
+    cache: Mutex<Option<(Version, Arc<T>)>>,
+}
+
+impl<T: DeserializeOwned> DataFile<T> {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            cache: Mutex::new(None),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the content of the file, `None` if it's missing.
+    ///
+    /// The file is only parsed again if it changed since the last call.
+    pub fn load(&self) -> eyre::Result<Option<Arc<T>>> {
+        let mut cache = self.cache.lock().unwrap();
+        let version = match std::fs::metadata(&self.path) {
+            Ok(metadata) => (metadata.modified()?, metadata.len()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                *cache = None;
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if let Some((loaded, content)) = &*cache {
+            if *loaded == version {
+                return Ok(Some(Arc::clone(content)));
+            }
+        }
+        *cache = None;
+        let file = std::fs::File::open(&self.path)?;
+        let content = Arc::new(serde_json::from_reader(std::io::BufReader::new(file))?);
+        *cache = Some((version, Arc::clone(&content)));
+        Ok(Some(content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reload() {
+        let path = std::env::temp_dir().join(format!("data_file_{}.json", std::process::id()));
+        let file = DataFile::<Vec<u32>>::new(&path);
+        assert!(file.load().unwrap().is_none());
+
+        std::fs::write(&path, "[1, 2]").unwrap();
+        let loaded = file.load().unwrap().unwrap();
+        assert_eq!(*loaded, [1, 2]);
+        assert!(Arc::ptr_eq(&loaded, &file.load().unwrap().unwrap()));
+
+        std::fs::write(&path, "[1, 2, 3]").unwrap();
+        assert_eq!(*file.load().unwrap().unwrap(), [1, 2, 3]);
+        std::fs::write(&path, "[1,").unwrap();
+        assert!(file.load().is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!(file.load().unwrap().is_none());
+    }
+}
@@ -3,6 +3,10 @@
 use std::sync::Arc;
 
 use quazal::prudp::ClientRegistry;
+use quazal::rmc::types::DateTime;
+use quazal::rmc::types::Property;
+use quazal::rmc::types::QList;
+use quazal::rmc::types::ResultRange;
 use quazal::rmc::Error;
 use quazal::rmc::Protocol;
 use quazal::ClientInfo;
@@ -16,32 +20,144 @@ use slog::Logger;
 use crate::login_required;
 use crate::protocols::game_session_service::game_session_protocol::AbandonSessionRequest;
 use crate::protocols::game_session_service::game_session_protocol::AbandonSessionResponse;
+use crate::protocols::game_session_service::game_session_protocol::AcceptInvitationRequest;
+use crate::protocols::game_session_service::game_session_protocol::AcceptInvitationResponse;
 use crate::protocols::game_session_service::game_session_protocol::AddParticipantsRequest;
 use crate::protocols::game_session_service::game_session_protocol::AddParticipantsResponse;
+use crate::protocols::game_session_service::game_session_protocol::CancelInvitationRequest;
+use crate::protocols::game_session_service::game_session_protocol::CancelInvitationResponse;
 use crate::protocols::game_session_service::game_session_protocol::CreateSessionRequest;
 use crate::protocols::game_session_service::game_session_protocol::CreateSessionResponse;
+use crate::protocols::game_session_service::game_session_protocol::DeclineInvitationRequest;
+use crate::protocols::game_session_service::game_session_protocol::DeclineInvitationResponse;
 use crate::protocols::game_session_service::game_session_protocol::DeleteSessionRequest;
 use crate::protocols::game_session_service::game_session_protocol::DeleteSessionResponse;
 use crate::protocols::game_session_service::game_session_protocol::GameSessionProtocolServer;
 use crate::protocols::game_session_service::game_session_protocol::GameSessionProtocolServerTrait;
+use crate::protocols::game_session_service::game_session_protocol::GetInvitationReceivedCountRequest;
+use crate::protocols::game_session_service::game_session_protocol::GetInvitationReceivedCountResponse;
+use crate::protocols::game_session_service::game_session_protocol::GetInvitationSentCountRequest;
+use crate::protocols::game_session_service::game_session_protocol::GetInvitationSentCountResponse;
+use crate::protocols::game_session_service::game_session_protocol::GetInvitationsReceivedRequest;
+use crate::protocols::game_session_service::game_session_protocol::GetInvitationsReceivedResponse;
+use crate::protocols::game_session_service::game_session_protocol::GetInvitationsSentRequest;
+use crate::protocols::game_session_service::game_session_protocol::GetInvitationsSentResponse;
+use crate::protocols::game_session_service::game_session_protocol::GetParticipantCountRequest;
+use crate::protocols::game_session_service::game_session_protocol::GetParticipantCountResponse;
+use crate::protocols::game_session_service::game_session_protocol::GetParticipantsRequest;
+use crate::protocols::game_session_service::game_session_protocol::GetParticipantsResponse;
+use crate::protocols::game_session_service::game_session_protocol::GetSessionRequest;
+use crate::protocols::game_session_service::game_session_protocol::GetSessionResponse;
 use crate::protocols::game_session_service::game_session_protocol::LeaveSessionRequest;
 use crate::protocols::game_session_service::game_session_protocol::LeaveSessionResponse;
+use crate::protocols::game_session_service::game_session_protocol::MigrateSessionRequest;
+use crate::protocols::game_session_service::game_session_protocol::MigrateSessionResponse;
 use crate::protocols::game_session_service::game_session_protocol::RegisterUrLsRequest;
 use crate::protocols::game_session_service::game_session_protocol::RegisterUrLsResponse;
+use crate::protocols::game_session_service::game_session_protocol::SearchSessionsRequest;
+use crate::protocols::game_session_service::game_session_protocol::SearchSessionsResponse;
 use crate::protocols::game_session_service::game_session_protocol::SearchSessionsWithParticipantsRequest;
 use crate::protocols::game_session_service::game_session_protocol::SearchSessionsWithParticipantsResponse;
+use crate::protocols::game_session_service::game_session_protocol::SendInvitationRequest;
+use crate::protocols::game_session_service::game_session_protocol::SendInvitationResponse;
 use crate::protocols::game_session_service::game_session_protocol::SplitSessionRequest;
 use crate::protocols::game_session_service::game_session_protocol::SplitSessionResponse;
 use crate::protocols::game_session_service::game_session_protocol::UpdateSessionRequest;
 use crate::protocols::game_session_service::game_session_protocol::UpdateSessionResponse;
+use crate::protocols::game_session_service::types::GameSessionInvitationReceived;
+use crate::protocols::game_session_service::types::GameSessionInvitationSent;
 use crate::protocols::game_session_service::types::GameSessionKey;
+use crate::protocols::game_session_service::types::GameSessionParticipant;
 use crate::protocols::game_session_service::types::GameSessionSearchResult;
 use crate::protocols::game_session_service::types::GameSessionSearchWithParticipantsResult;
+use crate::relay::Relay;
+use crate::secure::client_station_urls;
+use crate::storage::GameSession;
+use crate::storage::Invite;
+use crate::storage::InviteStatus;
 use crate::storage::Storage;
+use crate::strings::Strings;
+
+/// Known game session attribute ids.
+///
+/// See <https://github.com/GitHubProUser67/MultiServer3/blob/dc189cfac27589356a52d2ad64c31c8a124c68f7/SpecializedServers/QuazalServer/RDVServices/DDL/Models/GameSessionService/GameSession.cs#L15>
+pub(crate) mod attribute_ids {
+    /// Map id
+    pub const MAP: u32 = 101;
+    /// Game mode
+    pub const GAME_MODE: u32 = 102;
+    /// Non-zero for Spies vs. Mercs sessions, 0 for coop
+    pub const SVM: u32 = 103;
+    /// 1 for `SvM`, 2 for coop (guessed)
+    pub const GAME_TYPE: u32 = 105;
+    /// Number of player slots
+    pub const SLOTS: u32 = 112;
+}
+
+/// Maximum number of players invited with one `SendInvitation`, far more than a session has slots.
+const MAX_INVITATION_RECIPIENTS: usize = 64;
+
+/// Converts the sqlite creation timestamp of an invite into a quazal `DateTime`.
+fn invite_creation_time(invite: &Invite) -> DateTime {
+    invite.created.as_deref().and_then(|ts| ts.parse().ok()).unwrap_or_default()
+}
+
+/// Returns the game session key an invite belongs to.
+///
+/// Only invites with a session are returned by the storage queries used here, so missing keys fall back to 0.
+fn invite_session_key(invite: &Invite) -> GameSessionKey {
+    GameSessionKey {
+        type_id: invite.session_type.unwrap_or_default(),
+        session_id: invite.session_id.unwrap_or_default(),
+    }
+}
+
+/// Builds the search result the game expects for a stored session.
+fn to_search_result(session: &GameSession) -> GameSessionSearchResult {
+    GameSessionSearchResult {
+        session_key: GameSessionKey {
+            type_id: session.session_type,
+            session_id: session.session_id,
+        },
+        host_pid: session.creator_id,
+        host_urls: session
+            .participants
+            .iter()
+            .filter(|p| p.user_id == session.creator_id)
+            .flat_map(|p| p.station_urls.iter())
+            .filter_map(|u| u.parse().ok())
+            .collect(),
+        attributes: to_properties(&session.attributes),
+    }
+}
+
+/// Converts stored `(attribute id, value)` pairs into the properties sent to the game.
+pub(crate) fn to_properties(attributes: &[(u32, u32)]) -> QList<Property> {
+    attributes.iter().map(|&(id, value)| Property { id, value }).collect()
+}
+
+/// Applies a `ResultRange` to a list of results.
+fn apply_range<T>(items: Vec<T>, range: &ResultRange) -> impl Iterator<Item = T> {
+    items.into_iter().skip(range.offset as usize).take(range.size as usize)
+}
 
 /// Implementation of the `GameSessionProtocolServerTrait` for handling game session operations.
 struct GameSessionProtocolServerImpl {
     storage: Arc<Storage>,
+    relay: Option<Arc<Relay>>,
+    strings: Strings,
+}
+
+impl GameSessionProtocolServerImpl {
+    /// Returns the message of a received invite, a localized default one if the sender didn't write any.
+    fn invite_message(&self, logger: &Logger, locale: Option<&str>, invite: Invite) -> Result<String, Error> {
+        if !invite.message.is_empty() {
+            return Ok(invite.message);
+        }
+        let sender = rmc_err!(self.storage.find_user_by_id(invite.sender), logger, "error finding invite sender")?;
+        let sender = sender.map(|u| u.username).unwrap_or_default();
+        Ok(self.strings.get(locale, "invite.default", &[("sender", &sender)]))
+    }
 }
 
 impl<CI> GameSessionProtocolServerTrait<CI> for GameSessionProtocolServerImpl {
@@ -62,21 +178,9 @@ impl<CI> GameSessionProtocolServerTrait<CI> for GameSessionProtocolServerImpl {
         // Ensure the client is logged in.
         let user_id = login_required(&*ci)?;
 
-        let attributes = request
-            .game_session
-            .attributes
-            .0
-            .into_iter()
-            /*
-            101 => map
-            102 => game mode
-            https://github.com/GitHubProUser67/MultiServer3/blob/dc189cfac27589356a52d2ad64c31c8a124c68f7/SpecializedServers/QuazalServer/RDVServices/DDL/Models/GameSessionService/GameSession.cs#L15
-             */
-            .map(|p| format!("{} => {}", p.id, p.value))
-            .collect::<Vec<_>>()
-            .join(";");
+        let attributes = request.game_session.attributes.0.iter().map(|p| (p.id, p.value)).collect::<Vec<_>>();
         let session_id = rmc_err!(
-            self.storage.create_game_session(user_id, request.game_session.type_id, attributes),
+            self.storage.create_game_session(user_id, request.game_session.type_id, &attributes),
             logger,
             "error creating game session"
         )?;
@@ -103,24 +207,12 @@ impl<CI> GameSessionProtocolServerTrait<CI> for GameSessionProtocolServerImpl {
         // Ensure the client is logged in.
         login_required(&*ci)?;
         info!(logger, "Client updates session: {:?}", request);
-        let attributes = request
-            .game_session_update
-            .attributes
-            .0
-            .into_iter()
-            /*
-            101 => map
-            102 => game mode
-            https://github.com/GitHubProUser67/MultiServer3/blob/dc189cfac27589356a52d2ad64c31c8a124c68f7/SpecializedServers/QuazalServer/RDVServices/DDL/Models/GameSessionService/GameSession.cs#L15
-             */
-            .map(|p| format!("{} => {}", p.id, p.value))
-            .collect::<Vec<_>>()
-            .join(";");
+        let attributes = request.game_session_update.attributes.0.iter().map(|p| (p.id, p.value)).collect::<Vec<_>>();
         rmc_err!(
             self.storage.update_game_session(
                 request.game_session_update.session_key.type_id,
                 request.game_session_update.session_key.session_id,
-                attributes,
+                &attributes,
             ),
             logger,
             "error updating game session"
@@ -154,23 +246,129 @@ impl<CI> GameSessionProtocolServerTrait<CI> for GameSessionProtocolServerImpl {
         Ok(DeleteSessionResponse)
     }
 
-    /// Handles the `LeaveSession` request.
+    /// Handles the `MigrateSession` request, moving the host role to another participant.
+    ///
+    /// This function requires the client to be logged in. The host calling it hands the session to the longest-joined
+    /// remaining participant. A participant calling it takes over the session, but only once the host is logged out.
+    /// The session key stays the same.
+    fn migrate_session(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: MigrateSessionRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<MigrateSessionResponse, Error> {
+        // Ensure the client is logged in.
+        let user_id = login_required(&*ci)?;
+        info!(logger, "Client migrates session: {:?}", request);
+        let key = request.game_session_key;
+        let Some(session) = rmc_err!(self.storage.find_game_session(key.type_id, key.session_id), logger, "error getting game session")? else {
+            warn!(logger, "Session {} not found", key.session_id);
+            return Err(Error::AccessDenied);
+        };
+
+        let new_host = if session.creator_id == user_id {
+            session.participants.iter().map(|p| p.user_id).find(|&pid| pid != user_id)
+        } else if rmc_err!(self.storage.has_user_session(session.creator_id), logger, "error checking the host")? {
+            warn!(logger, "User {user_id} can't take over session {} from its logged in host", key.session_id);
+            return Err(Error::AccessDenied);
+        } else {
+            Some(user_id)
+        };
+        if let Some(new_host) = new_host {
+            if rmc_err!(
+                self.storage.migrate_game_session_host(key.type_id, key.session_id, new_host),
+                logger,
+                "error migrating game session"
+            )? {
+                info!(logger, "Session {} is now hosted by {new_host}", key.session_id);
+            } else {
+                warn!(logger, "User {new_host} is not a participant of session {}", key.session_id);
+            }
+        } else {
+            warn!(logger, "No participant left to migrate session {} to", key.session_id);
+        }
+
+        Ok(MigrateSessionResponse { game_session_key_migrated: key })
+    }
+
+    /// Handles the `LeaveSession` request, removing the client from a game session.
     ///
-    /// This function requires the client to be logged in. It currently returns an empty response.
+    /// This function requires the client to be logged in. The host role moves to another participant
+    /// if the host leaves, and the session is destroyed once it is empty.
     fn leave_session(
         &self,
-        _logger: &Logger,
+        logger: &Logger,
         _ctx: &Context,
         ci: &mut ClientInfo<CI>,
-        _request: LeaveSessionRequest,
+        request: LeaveSessionRequest,
         _client_registry: &ClientRegistry<CI>,
         _socket: &std::net::UdpSocket,
     ) -> Result<LeaveSessionResponse, Error> {
         // Ensure the client is logged in.
-        login_required(&*ci)?;
+        let user_id = login_required(&*ci)?;
+        info!(logger, "Client leaves session: {:?}", request);
+        rmc_err!(
+            self.storage.leave_game_session(request.game_session_key.session_id, user_id),
+            logger,
+            "error leaving session"
+        )?;
         Ok(LeaveSessionResponse)
     }
 
+    /// Handles the `GetSession` request, returning a single game session.
+    ///
+    /// This function requires the client to be logged in.
+    fn get_session(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetSessionRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetSessionResponse, Error> {
+        // Ensure the client is logged in.
+        login_required(&*ci)?;
+        let key = request.game_session_key;
+        let Some(session) = rmc_err!(self.storage.find_game_session(key.type_id, key.session_id), logger, "error getting game session")? else {
+            warn!(logger, "Session {} not found", key.session_id);
+            return Err(Error::AccessDenied);
+        };
+        Ok(GetSessionResponse {
+            search_result: to_search_result(&session),
+        })
+    }
+
+    /// Handles the `SearchSessions` request, searching for game sessions.
+    ///
+    /// This function requires the client to be logged in. A session matches if all query
+    /// parameters are present in its attributes with the same value.
+    fn search_sessions(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: SearchSessionsRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<SearchSessionsResponse, Error> {
+        // Ensure the client is logged in.
+        let user_id = login_required(&*ci)?;
+        info!(logger, "Client searches for session: {:?}", request);
+        let filters = request.game_session_query.parameters.0.iter().map(|p| (p.id, p.value)).collect::<Vec<_>>();
+        let sessions = rmc_err!(
+            self.storage.search_sessions(request.game_session_query.type_id, Some(user_id), &filters),
+            logger,
+            "error searching game sessions"
+        )?;
+        Ok(SearchSessionsResponse {
+            search_results: sessions.iter().map(to_search_result).collect(),
+        })
+    }
+
     /// Handles the `AddParticipants` request, adding participants to a game session.
     ///
     /// This function requires the client to be logged in.
@@ -223,24 +421,333 @@ impl<CI> GameSessionProtocolServerTrait<CI> for GameSessionProtocolServerImpl {
         Ok(RemoveParticipantsResponse)
     }
 
+    /// Handles the `GetParticipantCount` request.
+    ///
+    /// This function requires the client to be logged in.
+    fn get_participant_count(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetParticipantCountRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetParticipantCountResponse, Error> {
+        // Ensure the client is logged in.
+        login_required(&*ci)?;
+        let key = request.game_session_key;
+        let session = rmc_err!(self.storage.find_game_session(key.type_id, key.session_id), logger, "error getting game session")?;
+        #[allow(clippy::cast_possible_truncation)]
+        Ok(GetParticipantCountResponse {
+            count: session.map_or(0, |s| s.participants.len() as u32),
+        })
+    }
+
+    /// Handles the `GetParticipants` request, listing the participants of a game session.
+    ///
+    /// This function requires the client to be logged in.
+    fn get_participants(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetParticipantsRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetParticipantsResponse, Error> {
+        // Ensure the client is logged in.
+        login_required(&*ci)?;
+        let key = request.game_session_key;
+        let participants = rmc_err!(self.storage.find_game_session(key.type_id, key.session_id), logger, "error getting game session")?
+            .map(|s| s.participants)
+            .unwrap_or_default();
+        Ok(GetParticipantsResponse {
+            participants: apply_range(participants, &request.result_range)
+                .map(|participant| GameSessionParticipant {
+                    pid: participant.user_id,
+                    station_urls: participant.station_urls.iter().filter_map(|u| u.parse().ok()).collect(),
+                    name: participant.name,
+                })
+                .collect(),
+        })
+    }
+
+    /// Handles the `SendInvitation` request, inviting players into a game session.
+    ///
+    /// This function requires the client to be logged in as host or participant of the session. The invites are
+    /// stored for the recipients and are also picked up by the overlay.
+    fn send_invitation(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: SendInvitationRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<SendInvitationResponse, Error> {
+        // Ensure the client is logged in.
+        let user_id = login_required(&*ci)?;
+        info!(logger, "Client sends invitation: {:?}", request);
+        let invitation = request.invitation;
+        let key = &invitation.session_key;
+        if invitation.recipient_pids.0.len() > MAX_INVITATION_RECIPIENTS {
+            warn!(logger, "User {user_id} invites {} players at once", invitation.recipient_pids.0.len());
+            return Err(Error::AccessDenied);
+        }
+        let session = rmc_err!(self.storage.find_game_session(key.type_id, key.session_id), logger, "error getting game session")?;
+        if !session.is_some_and(|s| s.creator_id == user_id || s.participants.iter().any(|p| p.user_id == user_id)) {
+            warn!(logger, "User {user_id} invites into session {} without being in it", key.session_id);
+            return Err(Error::AccessDenied);
+        }
+        rmc_err!(
+            self.storage.add_session_invites(
+                user_id,
+                invitation.session_key.type_id,
+                invitation.session_key.session_id,
+                &invitation.recipient_pids.0,
+                &invitation.message,
+            ),
+            logger,
+            "error sending invitation"
+        )?;
+        Ok(SendInvitationResponse)
+    }
+
+    /// Handles the `GetInvitationReceivedCount` request.
+    ///
+    /// This function requires the client to be logged in. Only pending invites are counted.
+    fn get_invitation_received_count(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetInvitationReceivedCountRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetInvitationReceivedCountResponse, Error> {
+        // Ensure the client is logged in.
+        let user_id = login_required(&*ci)?;
+        let invites = rmc_err!(
+            self.storage.list_invites_received(user_id, request.game_session_type_id),
+            logger,
+            "error listing received invitations"
+        )?;
+        #[allow(clippy::cast_possible_truncation)]
+        Ok(GetInvitationReceivedCountResponse { count: invites.len() as u32 })
+    }
+
+    /// Handles the `GetInvitationsReceived` request, listing pending invites for the client.
+    ///
+    /// This function requires the client to be logged in.
+    fn get_invitations_received(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetInvitationsReceivedRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetInvitationsReceivedResponse, Error> {
+        // Ensure the client is logged in.
+        let user_id = login_required(&*ci)?;
+        let invites = rmc_err!(
+            self.storage.list_invites_received(user_id, request.game_session_type_id),
+            logger,
+            "error listing received invitations"
+        )?;
+        let locale = rmc_err!(self.storage.find_user_locale(user_id), logger, "error finding locale")?;
+        Ok(GetInvitationsReceivedResponse {
+            invitations: apply_range(invites, &request.result_range)
+                .map(|invite| {
+                    Ok(GameSessionInvitationReceived {
+                        session_key: invite_session_key(&invite),
+                        sender_pid: invite.sender,
+                        creation_time: invite_creation_time(&invite),
+                        message: self.invite_message(logger, locale.as_deref(), invite)?,
+                    })
+                })
+                .collect::<Result<_, Error>>()?,
+        })
+    }
+
+    /// Handles the `GetInvitationSentCount` request.
+    ///
+    /// This function requires the client to be logged in. Only pending invites are counted.
+    fn get_invitation_sent_count(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetInvitationSentCountRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetInvitationSentCountResponse, Error> {
+        // Ensure the client is logged in.
+        let user_id = login_required(&*ci)?;
+        let invites = rmc_err!(
+            self.storage.list_invites_sent(user_id, request.game_session_type_id),
+            logger,
+            "error listing sent invitations"
+        )?;
+        #[allow(clippy::cast_possible_truncation)]
+        Ok(GetInvitationSentCountResponse { count: invites.len() as u32 })
+    }
+
+    /// Handles the `GetInvitationsSent` request, listing pending invites sent by the client.
+    ///
+    /// This function requires the client to be logged in.
+    fn get_invitations_sent(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetInvitationsSentRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetInvitationsSentResponse, Error> {
+        // Ensure the client is logged in.
+        let user_id = login_required(&*ci)?;
+        let invites = rmc_err!(
+            self.storage.list_invites_sent(user_id, request.game_session_type_id),
+            logger,
+            "error listing sent invitations"
+        )?;
+        Ok(GetInvitationsSentResponse {
+            invitations: apply_range(invites, &request.result_range)
+                .map(|invite| GameSessionInvitationSent {
+                    session_key: invite_session_key(&invite),
+                    recipient_pid: invite.receiver,
+                    creation_time: invite_creation_time(&invite),
+                    message: invite.message,
+                })
+                .collect(),
+        })
+    }
+
+    /// Handles the `AcceptInvitation` request.
+    ///
+    /// This function requires the client to be logged in. Joining the session itself is done by the game.
+    fn accept_invitation(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: AcceptInvitationRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<AcceptInvitationResponse, Error> {
+        // Ensure the client is logged in.
+        let user_id = login_required(&*ci)?;
+        info!(logger, "Client accepts invitation: {:?}", request);
+        let invitation = request.game_session_invitation;
+        if rmc_err!(
+            self.storage.update_invite_status(
+                invitation.sender_pid,
+                user_id,
+                invitation.session_key.type_id,
+                invitation.session_key.session_id,
+                InviteStatus::Accepted,
+            ),
+            logger,
+            "error accepting invitation"
+        )? == 0
+        {
+            warn!(logger, "No pending invitation found");
+        }
+        Ok(AcceptInvitationResponse)
+    }
+
+    /// Handles the `DeclineInvitation` request.
+    ///
+    /// This function requires the client to be logged in.
+    fn decline_invitation(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: DeclineInvitationRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<DeclineInvitationResponse, Error> {
+        // Ensure the client is logged in.
+        let user_id = login_required(&*ci)?;
+        info!(logger, "Client declines invitation: {:?}", request);
+        let invitation = request.game_session_invitation;
+        if rmc_err!(
+            self.storage.update_invite_status(
+                invitation.sender_pid,
+                user_id,
+                invitation.session_key.type_id,
+                invitation.session_key.session_id,
+                InviteStatus::Declined,
+            ),
+            logger,
+            "error declining invitation"
+        )? == 0
+        {
+            warn!(logger, "No pending invitation found");
+        }
+        Ok(DeclineInvitationResponse)
+    }
+
+    /// Handles the `CancelInvitation` request, withdrawing an invite sent by the client.
+    ///
+    /// This function requires the client to be logged in.
+    fn cancel_invitation(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: CancelInvitationRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<CancelInvitationResponse, Error> {
+        // Ensure the client is logged in.
+        let user_id = login_required(&*ci)?;
+        info!(logger, "Client cancels invitation: {:?}", request);
+        let invitation = request.game_session_invitation;
+        if rmc_err!(
+            self.storage.update_invite_status(
+                user_id,
+                invitation.recipient_pid,
+                invitation.session_key.type_id,
+                invitation.session_key.session_id,
+                InviteStatus::Cancelled,
+            ),
+            logger,
+            "error cancelling invitation"
+        )? == 0
+        {
+            warn!(logger, "No pending invitation found");
+        }
+        Ok(CancelInvitationResponse)
+    }
+
     /// Handles the `AbandonSession` request.
     ///
-    /// This function requires the client to be logged in. It currently returns an empty response.
+    /// This function requires the client to be logged in. It behaves like `LeaveSession`.
     fn abandon_session(
         &self,
-        _logger: &Logger,
+        logger: &Logger,
         _ctx: &Context,
         ci: &mut ClientInfo<CI>,
-        _request: AbandonSessionRequest,
+        request: AbandonSessionRequest,
         _client_registry: &ClientRegistry<CI>,
         _socket: &std::net::UdpSocket,
     ) -> Result<AbandonSessionResponse, Error> {
         // Ensure the client is logged in.
-        login_required(&*ci)?;
+        let user_id = login_required(&*ci)?;
+        info!(logger, "Client abandons session: {:?}", request);
+        rmc_err!(
+            self.storage.leave_game_session(request.game_session_key.session_id, user_id),
+            logger,
+            "error abandoning session"
+        )?;
         Ok(AbandonSessionResponse)
     }
 
-    /// Handles the `RegisterUrLs` request, registering client URLs.
+    /// Handles the `RegisterUrLs` request, registering client URLs merged with the observed public address and the relay
+    /// URL.
     ///
     /// This function requires the client to be logged in.
     fn register_urls(
@@ -255,8 +762,9 @@ impl<CI> GameSessionProtocolServerTrait<CI> for GameSessionProtocolServerImpl {
         // Ensure the client is logged in.
         let user_id = login_required(&*ci)?;
         info!(logger, "Client registers urls: {:?}", request);
+        let urls = client_station_urls(logger, ci, user_id, &request.station_urls, "15", self.relay.as_ref());
         rmc_err!(
-            self.storage.register_urls(user_id, request.station_urls.0.into_iter().map(|su| su.to_string()).collect()),
+            self.storage.register_urls(user_id, urls.into_iter().map(|su| su.to_string()).collect()),
             logger,
             "error adding participants"
         )?;
@@ -302,7 +810,7 @@ impl<CI> GameSessionProtocolServerTrait<CI> for GameSessionProtocolServerImpl {
                             },
                             host_pid: host.user_id,
                             host_urls: host.station_urls.clone().try_into().unwrap(),
-                            attributes: session.attributes.as_str().parse().unwrap(),
+                            attributes: to_properties(&session.attributes),
                         },
                         participant_ids: session.participants.into_iter().map(|p| p.user_id).collect(),
                     }
@@ -350,6 +858,155 @@ impl<CI> GameSessionProtocolServerTrait<CI> for GameSessionProtocolServerImpl {
 ///
 /// This function is typically used to register the game session protocol
 /// with the server's protocol dispatcher.
-pub fn new_protocol<T: 'static>(storage: Arc<Storage>) -> Box<dyn Protocol<T>> {
-    Box::new(GameSessionProtocolServer::new(GameSessionProtocolServerImpl { storage }))
+pub fn new_protocol<T: 'static>(storage: Arc<Storage>, relay: Option<Arc<Relay>>) -> Box<dyn Protocol<T>> {
+    Box::new(GameSessionProtocolServer::new(GameSessionProtocolServerImpl {
+        storage,
+        relay,
+        strings: Strings::default(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use quazal::rmc::basic::ToStream;
+
+    use super::*;
+    use crate::protocols::game_session_service::game_session_protocol::GameSessionProtocolMethod;
+    use crate::protocols::game_session_service::types::GameSessionInvitation;
+    use crate::test_util;
+
+    #[test]
+    fn migrate_session() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        // 1000 hosts the seeded game session without being logged in
+        storage.add_participants(1, 1, vec![1001], vec![]).unwrap();
+        let prot = new_protocol::<()>(Arc::clone(&storage), None);
+        let request = MigrateSessionRequest {
+            game_session_key: GameSessionKey { type_id: 1, session_id: 1 },
+        }
+        .to_bytes();
+        let migrate = |user_id| test_util::call(&*prot, Some(user_id), GameSessionProtocolMethod::MigrateSession as u32, &request);
+
+        migrate(1001).unwrap();
+        assert_eq!(storage.find_game_session(1, 1).unwrap().unwrap().creator_id, 1001);
+
+        storage.create_user_session(1001, b"key").unwrap();
+        assert!(matches!(migrate(1000), Err(Error::AccessDenied)));
+        assert_eq!(storage.find_game_session(1, 1).unwrap().unwrap().creator_id, 1001);
+    }
+
+    #[test]
+    fn unknown_session() {
+        let prot = new_protocol::<()>(Arc::new(Storage::in_memory().unwrap()), None);
+        let game_session_key = || GameSessionKey { type_id: 1, session_id: 42 };
+        let request = GetSessionRequest {
+            game_session_key: game_session_key(),
+        }
+        .to_bytes();
+        let resp = test_util::call(&*prot, Some(1000), GameSessionProtocolMethod::GetSession as u32, &request);
+        assert!(matches!(resp, Err(Error::AccessDenied)));
+        let request = MigrateSessionRequest {
+            game_session_key: game_session_key(),
+        }
+        .to_bytes();
+        let resp = test_util::call(&*prot, Some(1000), GameSessionProtocolMethod::MigrateSession as u32, &request);
+        assert!(matches!(resp, Err(Error::AccessDenied)));
+    }
+
+    #[test]
+    fn send_invitation() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        // 1000 hosts the seeded game session
+        storage.add_participants(1, 1, vec![1001], vec![]).unwrap();
+        let prot = new_protocol::<()>(Arc::clone(&storage), None);
+        let invite = |user_id, recipient_pids: Vec<u32>| {
+            let request = SendInvitationRequest {
+                invitation: GameSessionInvitation {
+                    session_key: GameSessionKey { type_id: 1, session_id: 1 },
+                    recipient_pids: QList(recipient_pids),
+                    message: String::from("join"),
+                },
+            };
+            test_util::call(&*prot, Some(user_id), GameSessionProtocolMethod::SendInvitation as u32, &request.to_bytes())
+        };
+
+        invite(1000, vec![1002]).unwrap();
+        invite(1001, vec![1002]).unwrap();
+        let invites = storage.list_invites_received(1002, 1).unwrap();
+        assert_eq!(invites.iter().map(|i| i.sender).collect::<Vec<_>>(), [1000, 1001]);
+        assert!(invites.iter().all(|i| i.message == "join"));
+
+        // not in the session
+        assert!(matches!(invite(1002, vec![1000]), Err(Error::AccessDenied)));
+        assert!(matches!(invite(1000, vec![1002; MAX_INVITATION_RECIPIENTS + 1]), Err(Error::AccessDenied)));
+        assert_eq!(storage.list_invites_received(1002, 1).unwrap().len(), 2);
+        assert!(storage.list_invites_received(1000, 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn search_sessions_by_attributes() {
+        let storage = Storage::in_memory().unwrap();
+        let first = storage.create_game_session(1001, 1, &[(1, 5), (2, 7)]).unwrap();
+        let second = storage.create_game_session(1001, 1, &[(1, 5), (2, 8)]).unwrap();
+        storage.add_participants(1, first, vec![1001, 1002], vec![]).unwrap();
+        storage.register_urls(1001, vec![String::from("prudp:/address=1.2.3.4")]).unwrap();
+        storage.register_urls(1002, vec![String::from("prudp:/address=5.6.7.8")]).unwrap();
+        let search = |filters: &[(u32, u32)]| {
+            let mut ids: Vec<u32> = storage.search_sessions(1, None, filters).unwrap().iter().map(|s| s.session_id).collect();
+            ids.sort_unstable();
+            ids
+        };
+
+        assert_eq!(search(&[]), [1, first, second]);
+        assert_eq!(search(&[(1, 5)]), [first, second]);
+        assert_eq!(search(&[(1, 5), (2, 8)]), [second]);
+        assert!(search(&[(1, 5), (2, 9)]).is_empty());
+
+        let sessions = storage.search_sessions(1, Some(1002), &[(2, 7)]).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].attributes, [(1, 5), (2, 7)]);
+        let participants: Vec<_> = sessions[0].participants.iter().map(|p| (p.user_id, p.station_urls.clone())).collect();
+        // the searching player doesn't get their own urls
+        assert_eq!(participants, [(1001, vec![String::from("prudp:/address=1.2.3.4")]), (1002, vec![])]);
+    }
+
+    #[test]
+    fn host_leaves() {
+        let storage = Storage::in_memory().unwrap();
+        // 1000 hosts the seeded game session
+        storage.add_participants(1, 1, vec![1002, 1001], vec![]).unwrap();
+        let host = || storage.find_game_session(1, 1).unwrap().map(|s| s.creator_id);
+
+        // the earliest remaining participant takes over
+        storage.leave_game_session(1, 1000).unwrap();
+        assert_eq!(host(), Some(1002));
+        storage.leave_game_session(1, 1001).unwrap();
+        assert_eq!(host(), Some(1002));
+        // disconnecting leaves all sessions
+        storage.create_user_session(1002, b"key").unwrap();
+        storage.delete_user_session(1002).unwrap();
+        assert_eq!(host(), None);
+    }
+
+    #[test]
+    fn reap_sessions() {
+        let storage = Storage::in_memory().unwrap();
+        // 1000 hosts the seeded game session without being logged in
+        storage.create_user_session(1001, b"key").unwrap();
+        storage.add_participants(1, 1, vec![1001], vec![]).unwrap();
+        let offline = storage.create_game_session(1002, 1, &[]).unwrap();
+        storage.add_participants(1, offline, vec![1002], vec![]).unwrap();
+        let fresh = storage.create_game_session(1002, 1, &[]).unwrap();
+        storage.age_game_session(1, 120).unwrap();
+        storage.age_game_session(offline, 120).unwrap();
+
+        assert_eq!(storage.reap_game_sessions().unwrap(), 1);
+        let session = storage.find_game_session(1, 1).unwrap().unwrap();
+        assert_eq!(session.creator_id, 1001);
+        assert_eq!(session.participants.iter().map(|p| p.user_id).collect::<Vec<_>>(), [1001]);
+        assert!(storage.find_game_session(1, offline).unwrap().is_none());
+        // new sessions get some time to add their participants
+        assert!(storage.find_game_session(1, fresh).unwrap().is_some());
+        assert_eq!(storage.reap_game_sessions().unwrap(), 0);
+    }
 }
@@ -3,10 +3,10 @@
 
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::sync::Arc;
 
 use quazal::prudp::ClientRegistry;
-use quazal::rmc::types::Property;
 use quazal::rmc::types::QList;
 use quazal::rmc::Error;
 use quazal::rmc::Protocol;
@@ -14,7 +14,12 @@ use quazal::ClientInfo;
 use quazal::Context;
 use slog::Logger;
 
+use crate::config::MatchmakingConfig;
+use crate::game_session::attribute_ids;
+use crate::game_session::to_properties;
 use crate::login_required;
+use crate::matchmaking;
+use crate::matchmaking::Searcher;
 use crate::protocols::game_session_ex_service::game_session_ex_protocol::GameSessionExProtocolServer;
 use crate::protocols::game_session_ex_service::game_session_ex_protocol::GameSessionExProtocolServerTrait;
 use crate::protocols::game_session_ex_service::game_session_ex_protocol::SearchSessionsRequest;
@@ -28,13 +33,14 @@ use crate::storage::Storage;
 /// Implementation of the `GameSessionExProtocolServerTrait` for extended game session operations.
 struct GameSessionExProtocolServerImpl {
     storage: Arc<Storage>,
+    matchmaking: MatchmakingConfig,
 }
 
 impl<CI> GameSessionExProtocolServerTrait<CI> for GameSessionExProtocolServerImpl {
     /// Handles the `SearchSessions` request, providing extended search capabilities for game sessions.
     ///
     /// This function requires the client to be logged in. It filters sessions based on
-    /// various attributes and returns detailed session information.
+    /// various attributes, ranks them for matchmaking and returns detailed session information.
     fn search_sessions(
         &self,
         logger: &Logger,
@@ -46,13 +52,8 @@ impl<CI> GameSessionExProtocolServerTrait<CI> for GameSessionExProtocolServerImp
     ) -> Result<SearchSessionsResponse, Error> {
         #![allow(clippy::unreadable_literal)]
 
-        login_required(&*ci)?;
+        let user_id = login_required(&*ci)?;
         info!(logger, "Client searches for session: {:?}", request);
-        let sessions = rmc_err!(
-            self.storage.search_sessions(request.game_session_query.type_id, ci.user_id),
-            logger,
-            "Error searching game sessions"
-        )?;
         // search svm
         // 103 => 2165463540
         // 106 => 3564829
@@ -79,27 +80,46 @@ impl<CI> GameSessionExProtocolServerTrait<CI> for GameSessionExProtocolServerImp
         // 112 => might be number of players? (svm) first is set to 4, but 8 after opening and closening the match settings
         let mut req_attrs = request.game_session_query.parameters.0.into_iter().map(|p| (p.id, p.value)).collect::<HashMap<_, _>>();
         // if not set assume 0 (required for keeping coop and svm apart)
-        if let Entry::Vacant(entry) = req_attrs.entry(103) {
+        if let Entry::Vacant(entry) = req_attrs.entry(attribute_ids::SVM) {
             entry.insert(0);
         }
-        let sessions: Vec<_> = sessions
-            .into_iter()
-            .filter(|session| {
-                let sess_attrs: QList<Property> = session.attributes.parse().unwrap();
-                let sess_attrs = sess_attrs.0.into_iter().map(|p| (p.id, p.value)).collect::<HashMap<_, _>>();
-                for (id, value) in &req_attrs {
-                    if *id == 112 {
-                        // search request says 1 but in the session create request it says 2.
-                        // no idea what they mean, but we would like to find those sessions, so skip this check
-                        continue;
-                    }
-                    if sess_attrs.get(id) != Some(value) {
-                        return false;
-                    }
-                }
-                true
-            })
-            .collect();
+        // search request says 1 but in the session create request it says 2.
+        // no idea what they mean, free slots are taken care of by the matchmaking instead
+        req_attrs.remove(&attribute_ids::SLOTS);
+        let filters = req_attrs.into_iter().collect::<Vec<_>>();
+        let sessions = rmc_err!(
+            self.storage.search_sessions(request.game_session_query.type_id, Some(user_id), &filters),
+            logger,
+            "Error searching game sessions"
+        )?;
+
+        let skills = if self.matchmaking.skill_weight > 0.0 {
+            let mut user_ids: Vec<u32> = sessions.iter().flat_map(|s| s.participants.iter().map(|p| p.user_id)).collect();
+            user_ids.push(user_id);
+            rmc_err!(
+                self.storage.find_skill_ratings(self.matchmaking.skill_board, self.matchmaking.skill_stat, &user_ids),
+                logger,
+                "Error getting skill ratings"
+            )?
+        } else {
+            HashMap::new()
+        };
+        let unreachable = if self.matchmaking.unreachable_after > 0 {
+            rmc_err!(
+                self.storage
+                    .find_unreachable_peers(user_id, self.matchmaking.unreachable_after, self.matchmaking.unreachable_for),
+                logger,
+                "Error getting unreachable peers"
+            )?
+        } else {
+            HashSet::new()
+        };
+        let searcher = Searcher {
+            address: ci.address().ip(),
+            skill: skills.get(&user_id).copied(),
+            unreachable,
+        };
+        let sessions = matchmaking::rank(&self.matchmaking, &searcher, sessions, &skills);
         info!(logger, "Found sessions {sessions:?}");
         Ok(SearchSessionsResponse {
             search_results: QList(
@@ -119,7 +139,7 @@ impl<CI> GameSessionExProtocolServerTrait<CI> for GameSessionExProtocolServerImp
                                 .flat_map(|p| p.station_urls.iter())
                                 .map(|u| u.parse().unwrap())
                                 .collect(),
-                            attributes: session.attributes.parse().unwrap(),
+                            attributes: to_properties(&session.attributes),
                         },
                         participants: QList(
                             session
@@ -139,6 +159,6 @@ impl<CI> GameSessionExProtocolServerTrait<CI> for GameSessionExProtocolServerImp
     }
 }
 
-pub fn new_protocol<T: 'static>(storage: Arc<Storage>) -> Box<dyn Protocol<T>> {
-    Box::new(GameSessionExProtocolServer::new(GameSessionExProtocolServerImpl { storage }))
+pub fn new_protocol<T: 'static>(storage: Arc<Storage>, matchmaking: MatchmakingConfig) -> Box<dyn Protocol<T>> {
+    Box::new(GameSessionExProtocolServer::new(GameSessionExProtocolServerImpl { storage, matchmaking }))
 }
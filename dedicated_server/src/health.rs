@@ -0,0 +1,133 @@
+//! Implements the `HealthProtocol` for liveness checks.
+//!
+//! The protocol is registered on the secure server. The pings answer without login, so that probes can check the
+//! service port, while the sanity check and its fixes touch the whole database and are reserved to moderators.
+
+use std::sync::Arc;
+
+use quazal::prudp::ClientRegistry;
+use quazal::rmc::Error;
+use quazal::rmc::Protocol;
+use quazal::ClientInfo;
+use quazal::Context;
+use slog::Logger;
+
+use crate::moderator_required;
+use crate::protocols::health_service::health_protocol::FixSanityErrorsRequest;
+use crate::protocols::health_service::health_protocol::FixSanityErrorsResponse;
+use crate::protocols::health_service::health_protocol::HealthProtocolServer;
+use crate::protocols::health_service::health_protocol::HealthProtocolServerTrait;
+use crate::protocols::health_service::health_protocol::PingDaemonRequest;
+use crate::protocols::health_service::health_protocol::PingDaemonResponse;
+use crate::protocols::health_service::health_protocol::PingDatabaseRequest;
+use crate::protocols::health_service::health_protocol::PingDatabaseResponse;
+use crate::protocols::health_service::health_protocol::RunSanityCheckRequest;
+use crate::protocols::health_service::health_protocol::RunSanityCheckResponse;
+use crate::storage::Storage;
+
+/// Implementation of the `HealthProtocolServerTrait`.
+struct HealthProtocolServerImpl {
+    storage: Arc<Storage>,
+}
+
+impl<CI> HealthProtocolServerTrait<CI> for HealthProtocolServerImpl {
+    /// Handles the `PingDaemon` request, the server answering is all that's checked.
+    fn ping_daemon(
+        &self,
+        _logger: &Logger,
+        _ctx: &Context,
+        _ci: &mut ClientInfo<CI>,
+        _request: PingDaemonRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<PingDaemonResponse, Error> {
+        Ok(PingDaemonResponse { return_value: true })
+    }
+
+    /// Handles the `PingDatabase` request, checking that the database answers queries.
+    fn ping_database(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        _ci: &mut ClientInfo<CI>,
+        _request: PingDatabaseRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<PingDatabaseResponse, Error> {
+        let return_value = self.storage.ping().map_err(|e| error!(logger, "Database unreachable: {e}")).is_ok();
+        Ok(PingDatabaseResponse { return_value })
+    }
+
+    /// Handles the `RunSanityCheck` request, running the integrity check of the database.
+    ///
+    /// This function requires the client to be a moderator.
+    fn run_sanity_check(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        _request: RunSanityCheckRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<RunSanityCheckResponse, Error> {
+        moderator_required(logger, &self.storage, ci)?;
+        let return_value = match self.storage.check_integrity() {
+            Ok(consistent) => {
+                if !consistent {
+                    crit!(logger, "Database integrity check failed");
+                }
+                consistent
+            }
+            Err(e) => {
+                error!(logger, "Error checking the database: {e}");
+                false
+            }
+        };
+        Ok(RunSanityCheckResponse { return_value })
+    }
+
+    /// Handles the `FixSanityErrors` request, removing offline participants and empty game sessions right away instead
+    /// of waiting for the periodic cleanup.
+    ///
+    /// This function requires the client to be a moderator.
+    fn fix_sanity_errors(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        _request: FixSanityErrorsRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<FixSanityErrorsResponse, Error> {
+        moderator_required(logger, &self.storage, ci)?;
+        let return_value = self.storage.reap_game_sessions().map_err(|e| error!(logger, "Error reaping game sessions: {e}")).is_ok();
+        Ok(FixSanityErrorsResponse { return_value })
+    }
+}
+
+/// Creates a new boxed `HealthProtocolServer` instance.
+///
+/// This function is typically used to register the health protocol
+/// with the server's protocol dispatcher.
+pub fn new_protocol<T: 'static>(storage: Arc<Storage>) -> Box<dyn Protocol<T>> {
+    Box::new(HealthProtocolServer::new(HealthProtocolServerImpl { storage }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::health_service::health_protocol::HealthProtocolMethod;
+    use crate::test_util;
+
+    #[test]
+    fn moderator_required() {
+        let prot = new_protocol::<()>(Arc::new(Storage::in_memory().unwrap()));
+        // pings answer probes
+        assert_eq!(test_util::call(&*prot, None, HealthProtocolMethod::PingDatabase as u32, &[]).unwrap(), b"\x01");
+        for method_id in [HealthProtocolMethod::RunSanityCheck as u32, HealthProtocolMethod::FixSanityErrors as u32] {
+            assert!(matches!(test_util::call(&*prot, None, method_id, &[]), Err(Error::AccessDenied)));
+            // 1000 isn't a moderator
+            assert!(matches!(test_util::call(&*prot, Some(1000), method_id, &[]), Err(Error::AccessDenied)));
+        }
+    }
+}
@@ -1,18 +1,148 @@
-use std::time::Duration;
-use std::time::SystemTime;
+//! Implements the `LadderHelperProtocol` for the seasonal ladder.
+//!
+//! The seasons are defined in `data/ladder.json` with their start and end dates in UTC. The placement points of a
+//! player in a season are computed from the stats history of their `SvM` match results: each points rule names a stat
+//! and how many points it's worth when it grows by one during the season. A season reset is just the next season
+//! starting, the stats themselves are never cleared. History older than `stats_history.retention_days` is thinned
+//! out to the last value of each stat, so only seasons that started before then lose their exact points.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
 
 use quazal::prudp::ClientRegistry;
+use quazal::rmc::types::DateTime;
 use quazal::rmc::Protocol;
 use quazal::Context;
+use serde::Deserialize;
+use slog::Logger;
 
+use crate::data_file::DataFile;
 use crate::login_required;
+use crate::overlord_challenge::unix_now;
+use crate::protocols::ladder_helper_service::ladder_helper_protocol::AreLaddersAvailableInCountryRequest;
+use crate::protocols::ladder_helper_service::ladder_helper_protocol::AreLaddersAvailableInCountryResponse;
+use crate::protocols::ladder_helper_service::ladder_helper_protocol::CheckLadderIsRunningRequest;
+use crate::protocols::ladder_helper_service::ladder_helper_protocol::CheckLadderIsRunningResponse;
+use crate::protocols::ladder_helper_service::ladder_helper_protocol::ClearLadderLeaderboardRequest;
+use crate::protocols::ladder_helper_service::ladder_helper_protocol::ClearLadderLeaderboardResponse;
 use crate::protocols::ladder_helper_service::ladder_helper_protocol::GetUnixUtcRequest;
 use crate::protocols::ladder_helper_service::ladder_helper_protocol::GetUnixUtcResponse;
 use crate::protocols::ladder_helper_service::ladder_helper_protocol::LadderHelperProtocolServer;
 use crate::protocols::ladder_helper_service::ladder_helper_protocol::LadderHelperProtocolServerTrait;
+use crate::storage::Storage;
+
+/// Where the ladder configuration is read from.
+pub const LADDER_PATH: &str = "data/ladder.json";
+
+#[derive(Debug, Deserialize)]
+pub struct Season {
+    pub id: u32,
+    pub name: String,
+    /// "YYYY-MM-DD HH:MM:SS" in UTC.
+    pub start: String,
+    /// "YYYY-MM-DD HH:MM:SS" in UTC, the first second after the season.
+    pub end: String,
+}
+
+impl Season {
+    /// Returns the start and end of the season, `None` if they are invalid.
+    pub fn times(&self) -> Option<(DateTime, DateTime)> {
+        Some((self.start.parse().ok()?, self.end.parse().ok()?))
+    }
+}
+
+/// Points for a stat of the `SvM` match results.
+#[derive(Debug, Deserialize)]
+pub struct PointsRule {
+    pub board_id: u32,
+    pub stat_id: u32,
+    /// Points per one the stat grew during the season, negative for stats like losses.
+    pub points: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct LadderConfig {
+    /// Whether players are offered the ladder at all.
+    pub available: bool,
+    pub seasons: Vec<Season>,
+    pub points: Vec<PointsRule>,
+}
+
+impl Default for LadderConfig {
+    fn default() -> Self {
+        Self {
+            available: true,
+            seasons: Vec::new(),
+            points: Vec::new(),
+        }
+    }
+}
+
+impl LadderConfig {
+    /// Loads the configuration, `None` if the file is missing or invalid.
+    pub fn load(logger: &Logger, path: &Path) -> Option<Self> {
+        let file = std::fs::File::open(path).ok()?;
+        serde_json::from_reader(file)
+            .map_err(|e| error!(logger, "Invalid ladder configuration {}: {e}", path.display()))
+            .ok()
+    }
+
+    /// Returns the season running at a time.
+    pub fn active_season(&self, now: DateTime) -> Option<&Season> {
+        self.seasons.iter().find(|s| s.times().is_some_and(|(start, end)| start <= now && now < end))
+    }
+}
+
+/// The placement of a player in a season.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Placement {
+    pub user_id: u32,
+    pub points: i64,
+    /// Players with the same points share a rank.
+    pub rank: u32,
+}
+
+/// Computes the placements of the players who played during a season, best first.
+pub async fn standings(storage: &Storage, config: &LadderConfig, season: &Season) -> eyre::Result<Vec<Placement>> {
+    let (start, end) = season.times().ok_or_else(|| eyre::eyre!("invalid dates of season {}", season.id))?;
+    let (start, end) = (start.to_string(), end.to_string());
+    let mut points = HashMap::<u32, i64>::new();
+    for rule in &config.points {
+        for (user_id, gain) in storage.list_stat_gains_async(rule.board_id, rule.stat_id, &start, &end).await? {
+            *points.entry(user_id).or_default() += gain.saturating_mul(rule.points);
+        }
+    }
+    let mut points = points.into_iter().collect::<Vec<_>>();
+    points.sort_unstable_by_key(|(user_id, points)| (std::cmp::Reverse(*points), *user_id));
+
+    let mut placements = Vec::<Placement>::with_capacity(points.len());
+    for (position, (user_id, points)) in (1..).zip(points) {
+        let rank = match placements.last() {
+            Some(previous) if previous.points == points => previous.rank,
+            _ => position,
+        };
+        placements.push(Placement { user_id, points, rank });
+    }
+    Ok(placements)
+}
 
 /// Implementation of the `LadderHelperProtocolServerTrait` for handling ladder-related requests.
-struct LadderHelperProtocolServerImpl;
+struct LadderHelperProtocolServerImpl {
+    config: DataFile<LadderConfig>,
+}
+
+impl LadderHelperProtocolServerImpl {
+    /// Returns the configuration, `None` if the file is missing or invalid.
+    fn config(&self, logger: &Logger) -> Option<Arc<LadderConfig>> {
+        self.config
+            .load()
+            .map_err(|e| error!(logger, "Invalid ladder configuration {}: {e}", self.config.path().display()))
+            .ok()
+            .flatten()
+    }
+}
 
 impl<T> LadderHelperProtocolServerTrait<T> for LadderHelperProtocolServerImpl {
     /// Handles the `GetUnixUtc` request, returning the current Unix timestamp.
@@ -29,11 +159,71 @@ impl<T> LadderHelperProtocolServerTrait<T> for LadderHelperProtocolServerImpl {
     ) -> Result<GetUnixUtcResponse, quazal::rmc::Error> {
         login_required(&*ci)?;
 
-        #[allow(clippy::cast_possible_truncation)]
         Ok(GetUnixUtcResponse {
-            time: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).as_ref().map(Duration::as_secs).unwrap_or_default() as u32,
+            time: u32::try_from(unix_now()).unwrap_or(u32::MAX),
+        })
+    }
+
+    /// Handles the `AreLaddersAvailableInCountry` request, the ladder is offered if it's enabled and has seasons.
+    ///
+    /// This function requires the client to be logged in.
+    fn are_ladders_available_in_country(
+        &self,
+        logger: &slog::Logger,
+        _ctx: &Context,
+        ci: &mut quazal::ClientInfo<T>,
+        _request: AreLaddersAvailableInCountryRequest,
+        _client_registry: &ClientRegistry<T>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<AreLaddersAvailableInCountryResponse, quazal::rmc::Error> {
+        login_required(&*ci)?;
+        Ok(AreLaddersAvailableInCountryResponse {
+            allowed: self.config(logger).is_some_and(|c| c.available && !c.seasons.is_empty()),
         })
     }
+
+    /// Handles the `CheckLadderIsRunning` request, checking whether a season is running now that overlaps the time
+    /// range of the client's ladder.
+    ///
+    /// This function requires the client to be logged in.
+    fn check_ladder_is_running(
+        &self,
+        logger: &slog::Logger,
+        _ctx: &Context,
+        ci: &mut quazal::ClientInfo<T>,
+        request: CheckLadderIsRunningRequest,
+        _client_registry: &ClientRegistry<T>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<CheckLadderIsRunningResponse, quazal::rmc::Error> {
+        login_required(&*ci)?;
+        let from = DateTime::from_unix_timestamp(request.start_time.into());
+        let to = DateTime::from_unix_timestamp(request.end_time.into());
+        let running = self.config(logger).is_some_and(|c| {
+            c.available
+                && c.active_season(DateTime::from_unix_timestamp(unix_now()))
+                    .and_then(Season::times)
+                    .is_some_and(|(start, end)| start < to && from < end)
+        });
+        Ok(CheckLadderIsRunningResponse { running })
+    }
+
+    /// Handles the `ClearLadderLeaderboard` request, which is refused: seasons reset on their dates and the stats
+    /// history they are computed from is kept.
+    ///
+    /// This function requires the client to be logged in.
+    fn clear_ladder_leaderboard(
+        &self,
+        logger: &slog::Logger,
+        _ctx: &Context,
+        ci: &mut quazal::ClientInfo<T>,
+        request: ClearLadderLeaderboardRequest,
+        _client_registry: &ClientRegistry<T>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<ClearLadderLeaderboardResponse, quazal::rmc::Error> {
+        let user_id = login_required(&*ci)?;
+        warn!(logger, "User {user_id} tried to clear the ladder leaderboard of stat set {}", request.stat_set);
+        Ok(ClearLadderLeaderboardResponse { success: false })
+    }
 }
 
 /// Creates a new boxed `LadderHelperProtocolServer` instance.
@@ -41,5 +231,92 @@ impl<T> LadderHelperProtocolServerTrait<T> for LadderHelperProtocolServerImpl {
 /// This function is typically used to register the ladder helper protocol
 /// with the server's protocol dispatcher.
 pub fn new_protocol<T: 'static>() -> Box<dyn Protocol<T>> {
-    Box::new(LadderHelperProtocolServer::new(LadderHelperProtocolServerImpl))
+    Box::new(LadderHelperProtocolServer::new(LadderHelperProtocolServerImpl {
+        config: DataFile::new(LADDER_PATH),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use quazal::rmc::basic::FromStream;
+    use quazal::rmc::basic::ToStream;
+
+    use super::*;
+    use crate::protocols::ladder_helper_service::ladder_helper_protocol::LadderHelperProtocolMethod;
+    use crate::test_util;
+
+    fn config(seasons: &str) -> LadderConfig {
+        serde_json::from_str(&format!(
+            r#"{{"seasons": {seasons}, "points": [
+                {{"board_id": 10, "stat_id": 1, "points": 25}},
+                {{"board_id": 10, "stat_id": 2, "points": -10}}
+            ]}}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn standings_from_history() {
+        let storage = Storage::in_memory().unwrap();
+        // wins and losses on two maps
+        storage.store_player_stats(1000, 10, 5, &[(1, 2), (2, 1)]).unwrap();
+        storage.store_player_stats(1000, 10, 6, &[(1, 1)]).unwrap();
+        storage.store_player_stats(1001, 10, 5, &[(1, 4), (2, 5)]).unwrap();
+        storage.store_player_stats(1002, 10, 5, &[(1, 1), (2, 0)]).unwrap();
+        // a later result replaces the earlier one
+        storage.store_player_stats(1002, 10, 5, &[(1, 2), (2, 0)]).unwrap();
+
+        let config = config(
+            r#"[
+                {"id": 1, "name": "Past", "start": "2000-01-01 00:00:00", "end": "2001-01-01 00:00:00"},
+                {"id": 2, "name": "Current", "start": "2001-01-01 00:00:00", "end": "9999-01-01 00:00:00"}
+            ]"#,
+        );
+        let placements = test_util::block_on(standings(&storage, &config, &config.seasons[1])).unwrap();
+        assert_eq!(
+            placements.iter().map(|p| (p.user_id, p.points, p.rank)).collect::<Vec<_>>(),
+            [(1000, 65, 1), (1001, 50, 2), (1002, 50, 2)]
+        );
+        // nothing was played during the past season
+        assert_eq!(test_util::block_on(standings(&storage, &config, &config.seasons[0])).unwrap(), []);
+    }
+
+    #[test]
+    fn pruned_history() {
+        let storage = Storage::in_memory().unwrap();
+        storage.store_player_stats(1000, 10, 5, &[(1, 1)]).unwrap();
+        storage.store_player_stats(1000, 10, 5, &[(1, 3)]).unwrap();
+        storage.store_player_stats(1001, 10, 5, &[(1, 2)]).unwrap();
+        let config = config(r#"[{"id": 1, "name": "Future", "start": "9000-01-01 00:00:00", "end": "9999-01-01 00:00:00"}]"#);
+        let before = test_util::block_on(standings(&storage, &config, &config.seasons[0])).unwrap();
+
+        assert_eq!(storage.prune_stats_history("2000-01-01 00:00:00").unwrap(), 0);
+        // only the last value of each stat is kept
+        assert_eq!(storage.prune_stats_history("9000-01-01 00:00:00").unwrap(), 1);
+        assert_eq!(storage.prune_stats_history("9000-01-01 00:00:00").unwrap(), 0);
+        assert_eq!(test_util::block_on(standings(&storage, &config, &config.seasons[0])).unwrap(), before);
+    }
+
+    #[test]
+    fn ladder_running() {
+        let path = std::env::temp_dir().join(format!("ladder_{}.json", std::process::id()));
+        let prot = LadderHelperProtocolServer::new(LadderHelperProtocolServerImpl { config: DataFile::new(&path) });
+        let check = |start_time: u32, end_time: u32| {
+            let request = CheckLadderIsRunningRequest { start_time, end_time };
+            let resp = test_util::call(&prot, Some(1000), LadderHelperProtocolMethod::CheckLadderIsRunning as u32, &request.to_bytes()).unwrap();
+            CheckLadderIsRunningResponse::from_bytes(&resp).unwrap().running
+        };
+
+        // no configuration
+        assert!(!check(0, u32::MAX));
+        std::fs::write(
+            &path,
+            r#"{"seasons": [{"id": 1, "name": "Current", "start": "2001-01-01 00:00:00", "end": "9999-01-01 00:00:00"}]}"#,
+        )
+        .unwrap();
+        assert!(check(0, u32::MAX));
+        // the client's ladder ended before the season
+        assert!(!check(0, 1000));
+        std::fs::remove_file(path).unwrap();
+    }
 }
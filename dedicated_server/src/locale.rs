@@ -1,5 +1,7 @@
 //! Implements the `LocalizationProtocolServer` for handling locale-related requests.
 
+use std::sync::Arc;
+
 use quazal::prudp::ClientRegistry;
 use quazal::rmc::Error;
 use quazal::rmc::Protocol;
@@ -12,14 +14,17 @@ use crate::protocols::localization_service::localization_protocol::LocalizationP
 use crate::protocols::localization_service::localization_protocol::LocalizationProtocolServerTrait;
 use crate::protocols::localization_service::localization_protocol::SetLocaleCodeRequest;
 use crate::protocols::localization_service::localization_protocol::SetLocaleCodeResponse;
+use crate::storage::Storage;
 
 /// Implementation of the `LocalizationProtocolServerTrait` for handling localization requests.
-struct LocalizationProtocolServerImpl;
+struct LocalizationProtocolServerImpl {
+    storage: Arc<Storage>,
+}
 
 impl<T> LocalizationProtocolServerTrait<T> for LocalizationProtocolServerImpl {
     /// Handles the `SetLocaleCode` request, setting the client's locale code.
     ///
-    /// This function requires the client to be logged in.
+    /// This function requires the client to be logged in. The locale is kept with the user's session and profile.
     fn set_locale_code(
         &self,
         logger: &Logger,
@@ -30,9 +35,10 @@ impl<T> LocalizationProtocolServerTrait<T> for LocalizationProtocolServerImpl {
         _socket: &std::net::UdpSocket,
     ) -> Result<SetLocaleCodeResponse, Error> {
         // Ensure the client is logged in before setting the locale.
-        login_required(&*ci)?;
+        let user_id = login_required(&*ci)?;
         // Log the locale code being set.
         debug!(logger, "setting locale to {}", request.local_code);
+        rmc_err!(self.storage.update_session_locale(user_id, &request.local_code), logger, "error saving locale")?;
         Ok(SetLocaleCodeResponse)
     }
 }
@@ -41,6 +47,6 @@ impl<T> LocalizationProtocolServerTrait<T> for LocalizationProtocolServerImpl {
 ///
 /// This function is typically used to register the localization protocol
 /// with the server's protocol dispatcher.
-pub fn new_protocol<T: 'static>() -> Box<dyn Protocol<T>> {
-    Box::new(LocalizationProtocolServer::new(LocalizationProtocolServerImpl))
+pub fn new_protocol<T: 'static>(storage: Arc<Storage>) -> Box<dyn Protocol<T>> {
+    Box::new(LocalizationProtocolServer::new(LocalizationProtocolServerImpl { storage }))
 }
@@ -13,9 +13,11 @@ use std::net::SocketAddr;
 use std::net::UdpSocket;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
 use std::sync::Arc;
 
 use quazal::prudp::packet::QPacket;
+use quazal::rmc::types::DateTime;
 use quazal::ClientInfo;
 use quazal::Context;
 use sc_bl_protocols as protocols;
@@ -44,37 +46,74 @@ fn login_required<T>(ci: &ClientInfo<T>) -> quazal::rmc::Result<u32> {
     ci.user_id.ok_or(quazal::rmc::Error::AccessDenied)
 }
 
+/// Checks if a client is logged in as a moderator and returns their user ID.
+///
+/// Returns an `AccessDenied` error if the client is not logged in or not flagged as moderator in the database.
+fn moderator_required<T>(logger: &Logger, storage: &Storage, ci: &ClientInfo<T>) -> quazal::rmc::Result<u32> {
+    let user_id = login_required(ci)?;
+    if rmc_err!(storage.is_moderator(user_id), logger, "Error checking moderator")? {
+        Ok(user_id)
+    } else {
+        warn!(logger, "User {user_id} isn't a moderator");
+        Err(quazal::rmc::Error::AccessDenied)
+    }
+}
+
+mod acc_mgmt;
 mod api;
 mod challenge;
 mod clan;
 mod config;
+mod data_file;
 mod game_session;
 mod game_session_ex;
+mod health;
 mod ladder;
 mod locale;
+mod matchmaking;
+mod monitoring;
 mod nat_traversal;
+mod offline_notifications;
 mod overlord_challenge;
 mod overlord_core;
 mod overlord_news;
 mod player_stats;
 mod privileges;
+mod relay;
 mod secure;
 mod simple_http;
 mod storage;
+mod strings;
+#[cfg(test)]
+mod test_util;
 mod ticket;
 mod tracking;
 mod tracking_ext;
 mod ubi_acc_mgmt;
 mod uplay_win;
 mod user_storage;
+mod user_storage_admin;
 
 use crate::config::Config;
+use crate::config::MatchmakingConfig;
+use crate::config::UserStorageConfig;
+use crate::relay::Relay;
 
 /// Starts a Quazal server (either secure or authentication).
 ///
 /// This function sets up the necessary protocols and handlers for the server
 /// and then enters the server loop.
-fn start_server(logger: &slog::Logger, ctx: &Context, storage: &Arc<Storage>, is_secure: bool) -> io::Result<()> {
+#[allow(clippy::too_many_arguments)]
+fn start_server(
+    logger: &slog::Logger,
+    ctx: &Context,
+    storage: &Arc<Storage>,
+    matchmaking: MatchmakingConfig,
+    user_storage: UserStorageConfig,
+    is_secure: bool,
+    kick_receiver: Option<Receiver<u32>>,
+    relay: Option<&Arc<Relay>>,
+) -> io::Result<()> {
     use quazal::prudp::packet::StreamHandlerRegistry;
     use quazal::prudp::packet::StreamType;
     use quazal::prudp::packet::VPort;
@@ -82,26 +121,42 @@ fn start_server(logger: &slog::Logger, ctx: &Context, storage: &Arc<Storage>, is
     use quazal::rmc::RVSecHandler;
 
     let mut handler = RVSecHandler::<()>::new(logger.clone());
+    // relayed players reach each other even if NAT traversal fails between them
+    let matchmaking = if relay.is_some() {
+        MatchmakingConfig {
+            unreachable_after: 0,
+            ..matchmaking
+        }
+    } else {
+        matchmaking
+    };
 
     if is_secure {
-        handler.register_protocol(challenge::new_protocol());
+        handler.register_protocol(acc_mgmt::new_protocol(Arc::clone(storage)));
+        handler.register_protocol(challenge::new_protocol(Arc::clone(storage)));
         handler.register_protocol(clan::new_protocol());
-        handler.register_protocol(game_session_ex::new_protocol(Arc::clone(storage)));
-        handler.register_protocol(game_session::new_protocol(Arc::clone(storage)));
+        handler.register_protocol(game_session_ex::new_protocol(Arc::clone(storage), matchmaking));
+        handler.register_protocol(game_session::new_protocol(Arc::clone(storage), relay.cloned()));
+        handler.register_protocol(health::new_protocol(Arc::clone(storage)));
         handler.register_protocol(ladder::new_protocol());
-        handler.register_protocol(locale::new_protocol());
-        handler.register_protocol(nat_traversal::new_protocol());
-        handler.register_protocol(overlord_challenge::new_protocol());
+        handler.register_protocol(locale::new_protocol(Arc::clone(storage)));
+        handler.register_protocol(monitoring::new_protocol(Arc::clone(storage)));
+        handler.register_protocol(nat_traversal::new_protocol(Arc::clone(storage), matchmaking.unreachable_for));
+        handler.register_protocol(offline_notifications::new_protocol(Arc::clone(storage)));
+        handler.register_protocol(overlord_challenge::new_protocol(Arc::clone(storage)));
         handler.register_protocol(overlord_core::new_protocol());
-        handler.register_protocol(overlord_news::new_protocol());
-        handler.register_protocol(player_stats::new_protocol());
-        handler.register_protocol(privileges::new_protocol());
-        handler.register_protocol(secure::new_protocol());
-        handler.register_protocol(tracking_ext::new_protocol());
-        handler.register_protocol(tracking::new_protocol());
+        handler.register_protocol(overlord_news::new_protocol(Arc::clone(storage)));
+        handler.register_protocol(player_stats::new_protocol(Arc::clone(storage)));
+        handler.register_protocol(privileges::new_protocol(Arc::clone(storage)));
+        handler.register_protocol(secure::new_protocol(Arc::clone(storage), relay.cloned()));
+        handler.register_protocol(tracking_ext::new_protocol(Arc::clone(storage)));
+        handler.register_protocol(tracking::new_protocol(Arc::clone(storage)));
         handler.register_protocol(ubi_acc_mgmt::new_protocol(Arc::clone(storage)));
-        handler.register_protocol(uplay_win::new_protocol());
-        handler.register_protocol(user_storage::new_protocol());
+        handler.register_protocol(uplay_win::new_protocol(Arc::clone(storage)));
+        handler.register_protocol(user_storage::new_protocol(Arc::clone(storage), user_storage));
+        if user_storage.admin_protocol {
+            handler.register_protocol(user_storage_admin::new_protocol(Arc::clone(storage)));
+        }
     } else {
         handler.register_protocol(ticket::new_protocol(Arc::clone(storage)));
     }
@@ -119,6 +174,9 @@ fn start_server(logger: &slog::Logger, ctx: &Context, storage: &Arc<Storage>, is
     server.expired_client_handler = Some(|ci: ClientInfo| {
         if let Some(user_id) = ci.user_id {
             info!(logger, "Cleaning old session of user {user_id}");
+            if let Some(relay) = relay {
+                relay.release(user_id);
+            }
             if let Err(e) = storage.delete_user_session(user_id) {
                 error!(logger, "session clean error: {e}");
             }
@@ -127,11 +185,15 @@ fn start_server(logger: &slog::Logger, ctx: &Context, storage: &Arc<Storage>, is
     server.disconnect_handler = Some(|ci: ClientInfo| {
         if let Some(user_id) = ci.user_id {
             info!(logger, "Cleaning closed session of user {user_id}");
+            if let Some(relay) = relay {
+                relay.release(user_id);
+            }
             if let Err(e) = storage.delete_user_session(user_id) {
                 error!(logger, "session clean error: {e}");
             }
         }
     });
+    server.kick_receiver = kick_receiver;
     if is_secure {
         server.user_handler = Some(handle_user_packet);
     }
@@ -227,6 +289,81 @@ struct Args {
     launcher: bool,
 }
 
+/// Spawns a thread running a cleanup every `interval` seconds, starting right away.
+///
+/// `cleanup` returns how many `what` it removed.
+fn spawn_cleanup<F>(logger: &Logger, name: &'static str, what: &'static str, interval: u64, cleanup: F) -> std::thread::JoinHandle<()>
+where
+    F: Fn() -> color_eyre::Result<u64> + Send + 'static,
+{
+    let logger = logger.new(o!("service" => name));
+    std::thread::Builder::new()
+        .name(String::from(name))
+        .spawn(move || loop {
+            match cleanup() {
+                Ok(0) => {}
+                Ok(n) => info!(logger, "Removed {n} {what}"),
+                Err(e) => error!(logger, "{name} error: {e}"),
+            }
+            std::thread::sleep(std::time::Duration::from_secs(interval));
+        })
+        .unwrap()
+}
+
+/// Spawns the threads of the enabled periodic cleanups.
+fn spawn_cleanups(logger: &Logger, storage: &Arc<Storage>, config: &Config) -> Vec<std::thread::JoinHandle<()>> {
+    let mut cleanups = vec![];
+    if config.game_sessions.reaper_interval > 0 {
+        let storage = Arc::clone(storage);
+        cleanups.push(spawn_cleanup(
+            logger,
+            "session_reaper",
+            "stale game sessions",
+            config.game_sessions.reaper_interval,
+            move || storage.reap_game_sessions(),
+        ));
+    }
+
+    if config.tracking.retention_days > 0 || config.tracking.max_tags > 0 {
+        let storage = Arc::clone(storage);
+        let tracking = config.tracking;
+        cleanups.push(spawn_cleanup(logger, "tracking_cleanup", "old tracking tags", tracking.cleanup_interval, move || {
+            storage.prune_tracking_tags(tracking.retention_days, tracking.max_tags)
+        }));
+    }
+
+    if config.user_storage.upload_timeout > 0 {
+        let storage = Arc::clone(storage);
+        let timeout = config.user_storage.upload_timeout;
+        cleanups.push(spawn_cleanup(
+            logger,
+            "upload_cleanup",
+            "abandoned content uploads",
+            config.user_storage.cleanup_interval,
+            move || {
+                let before = DateTime::from_unix_timestamp(overlord_challenge::unix_now().saturating_sub(timeout));
+                storage.prune_content_uploads(&before.to_string())
+            },
+        ));
+    }
+
+    if config.stats_history.retention_days > 0 {
+        let storage = Arc::clone(storage);
+        let retention = u64::from(config.stats_history.retention_days) * 24 * 60 * 60;
+        cleanups.push(spawn_cleanup(
+            logger,
+            "stats_history_cleanup",
+            "old stats history entries",
+            config.stats_history.cleanup_interval,
+            move || {
+                let before = DateTime::from_unix_timestamp(overlord_challenge::unix_now().saturating_sub(retention));
+                storage.prune_stats_history(&before.to_string())
+            },
+        ));
+    }
+    cleanups
+}
+
 fn main() -> color_eyre::Result<()> {
     color_eyre::install()?;
     let args = argh::from_env::<Args>();
@@ -257,23 +394,37 @@ fn main() -> color_eyre::Result<()> {
 
     warn!(logger, "Clearing stale sessions");
     storage.invalidate_sessions()?;
-
-    let mut threads = vec![];
+    storage.update_privileges(&config.privileges)?;
+
+    let matchmaking = config.matchmaking;
+    let user_storage = config.user_storage;
+    let relay = config
+        .relay
+        .enabled
+        .then(|| Relay::new(logger.new(o!("service" => "relay")), config.relay, Arc::clone(&storage)));
+    let mut threads = spawn_cleanups(&logger, &storage, &config);
+    // one per secure server, used by the api to kick banned users
+    let mut kick_senders = vec![];
     for (name, svc) in config.quazal.into_services()? {
         let logger = logger.new(o!("service" => name.clone()));
         info!(logger, "Loaded service {:#?}", svc);
         let storage = Arc::clone(&storage);
+        let relay = relay.clone();
         let handle = match svc {
             quazal::Service::Authentication(ctx) => std::thread::Builder::new().name(name).spawn(move || {
-                if let Err(e) = start_server(&logger, &ctx, &storage, false) {
+                if let Err(e) = start_server(&logger, &ctx, &storage, matchmaking, user_storage, false, None, None) {
                     crit!(logger, "Error running authentication server: {e:?}");
                 }
             }),
-            quazal::Service::Secure(ctx) => std::thread::Builder::new().name(name).spawn(move || {
-                if let Err(e) = start_server(&logger, &ctx, &storage, true) {
-                    crit!(logger, "Error running secure server: {e:?}");
-                }
-            }),
+            quazal::Service::Secure(ctx) => {
+                let (kick_sender, kick_receiver) = std::sync::mpsc::channel();
+                kick_senders.push(kick_sender);
+                std::thread::Builder::new().name(name).spawn(move || {
+                    if let Err(e) = start_server(&logger, &ctx, &storage, matchmaking, user_storage, true, Some(kick_receiver), relay.as_ref()) {
+                        crit!(logger, "Error running secure server: {e:?}");
+                    }
+                })
+            }
             quazal::Service::Config(cfg) => std::thread::Builder::new().name(name).spawn(move || {
                 if cfg.listen.port() != 80 {
                     warn!(
@@ -287,7 +438,7 @@ fn main() -> color_eyre::Result<()> {
                 }
             }),
             quazal::Service::Content(srv) => std::thread::Builder::new().name(name).spawn(move || {
-                if let Err(e) = simple_http::serve_many(&logger, srv.listen, &srv.files) {
+                if let Err(e) = simple_http::serve_many(&logger, srv.listen, &srv.files, &storage, user_storage.max_content_size as usize) {
                     crit!(logger, "Error running content server: {e:?}");
                 }
             }),
@@ -300,11 +451,15 @@ fn main() -> color_eyre::Result<()> {
             .name(String::from("api"))
             .spawn(move || {
                 let logger = logger.new(o!("service" => "api"));
-                if let Err(e) =
-                    tokio::runtime::Runtime::new()
-                        .unwrap()
-                        .block_on(api::start_server(logger.clone(), storage, config.api_server, Arc::new(config.debug), args.launcher))
-                {
+                if let Err(e) = tokio::runtime::Runtime::new().unwrap().block_on(api::start_server(
+                    logger.clone(),
+                    storage,
+                    config.api_server,
+                    Arc::new(config.debug),
+                    args.launcher,
+                    kick_senders,
+                    config.matchmaking,
+                )) {
                     crit!(logger, "Error running api server: {e:?}");
                 }
             })
@@ -0,0 +1,207 @@
+//! Ranks game session search results for matchmaking.
+//!
+//! Sessions with a player the searching player repeatedly failed to reach with NAT traversal are dropped. Every other
+//! candidate gets a score between 0 and the sum of the configured weights, made up of:
+//! - free slots: sessions that need fewer players to be full rank higher, full sessions are dropped
+//! - age: recently created sessions rank higher, as old ones are more likely to be stale
+//! - region: hosts whose registered station urls are in the same network as the searching player rank higher
+//! - skill: sessions whose average rating, a configured player stat, is close to the searching player's rating rank higher
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::net::IpAddr;
+
+use quazal::rmc::types::StationURL;
+
+use crate::config::MatchmakingConfig;
+use crate::game_session::attribute_ids;
+use crate::storage::GameSession;
+
+/// Information about the player searching for a session.
+pub struct Searcher {
+    /// Address the player connects from.
+    pub address: IpAddr,
+    /// Skill rating of the player, if known.
+    pub skill: Option<f64>,
+    /// Players the player can't reach.
+    pub unreachable: HashSet<u32>,
+}
+
+/// Sorts `sessions` by their matchmaking score, best first, and applies the configured result cap.
+///
+/// `skills` maps user ids to skill ratings and is only used if `skill_weight` is set.
+pub fn rank(config: &MatchmakingConfig, searcher: &Searcher, sessions: Vec<GameSession>, skills: &HashMap<u32, f64>) -> Vec<GameSession> {
+    let mut scored: Vec<_> = sessions
+        .into_iter()
+        .filter_map(|session| score(config, searcher, &session, skills).map(|score| (score, session)))
+        .collect();
+    // stable sort, keeps the database order for equal scores
+    scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    let sessions = scored.into_iter().map(|(_, session)| session);
+    if config.max_results == 0 {
+        sessions.collect()
+    } else {
+        sessions.take(config.max_results).collect()
+    }
+}
+
+/// Calculates the score of a session. Returns `None` if the session is full or has an unreachable player.
+fn score(config: &MatchmakingConfig, searcher: &Searcher, session: &GameSession, skills: &HashMap<u32, f64>) -> Option<f64> {
+    if session.participants.iter().any(|p| searcher.unreachable.contains(&p.user_id)) {
+        return None;
+    }
+
+    let slots = session.attributes.iter().find(|(id, _)| *id == attribute_ids::SLOTS).map(|(_, value)| *value);
+    let free_slots = match slots {
+        Some(slots) => {
+            let taken = u32::try_from(session.participants.len()).unwrap_or(u32::MAX);
+            let free = slots.checked_sub(taken).filter(|free| *free > 0)?;
+            // 1.0 if only one slot is left
+            1.0 - f64::from(free - 1) / f64::from(slots)
+        }
+        // unknown capacity, neither prefer nor penalize it
+        None => 0.5,
+    };
+
+    #[allow(clippy::cast_precision_loss)]
+    let age = if config.max_age == 0 {
+        0.0
+    } else {
+        1.0 - (session.age_secs.max(0) as f64 / config.max_age as f64).min(1.0)
+    };
+
+    let region = session
+        .participants
+        .iter()
+        .filter(|p| p.user_id == session.creator_id)
+        .flat_map(|p| p.station_urls.iter())
+        .filter_map(|url| url.parse::<StationURL>().ok())
+        .filter_map(|url| url.address.parse::<IpAddr>().ok())
+        .map(|host| proximity(searcher.address, host))
+        .fold(0.0, f64::max);
+
+    let skill = if config.skill_weight <= 0.0 || config.skill_range <= 0.0 {
+        0.0
+    } else {
+        let ratings: Vec<f64> = session.participants.iter().filter_map(|p| skills.get(&p.user_id).copied()).collect();
+        match searcher.skill {
+            #[allow(clippy::cast_precision_loss)]
+            Some(own) if !ratings.is_empty() => {
+                let average = ratings.iter().sum::<f64>() / ratings.len() as f64;
+                1.0 - ((own - average).abs() / config.skill_range).min(1.0)
+            }
+            _ => 0.5,
+        }
+    };
+
+    Some(config.free_slots_weight * free_slots + config.age_weight * age + config.region_weight * region + config.skill_weight * skill)
+}
+
+/// Rough closeness of two addresses, from 1.0 (same address) to 0.0 (unrelated networks).
+fn proximity(a: IpAddr, b: IpAddr) -> f64 {
+    match (a, b) {
+        (IpAddr::V4(a), IpAddr::V4(b)) => {
+            let common = a.octets().iter().zip(b.octets()).take_while(|(a, b)| **a == *b).count();
+            match common {
+                4 => 1.0,
+                3 => 0.75,
+                2 => 0.5,
+                1 => 0.25,
+                _ => 0.0,
+            }
+        }
+        (a, b) if a == b => 1.0,
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Participant;
+
+    fn session(id: u32, participants: u32, slots: u32, age_secs: i64, host_address: &str) -> GameSession {
+        GameSession {
+            session_type: 1,
+            session_id: id,
+            creator_id: 1,
+            age_secs,
+            attributes: vec![(attribute_ids::SLOTS, slots)],
+            participants: (1..=participants)
+                .map(|user_id| Participant {
+                    user_id,
+                    name: format!("player{user_id}"),
+                    station_urls: vec![format!("prudp:/address={host_address};port=3074;type=3")],
+                })
+                .collect(),
+        }
+    }
+
+    fn searcher() -> Searcher {
+        Searcher {
+            address: "10.0.0.5".parse().unwrap(),
+            skill: None,
+            unreachable: HashSet::new(),
+        }
+    }
+
+    #[test]
+    fn drops_full_sessions() {
+        let ranked = rank(&MatchmakingConfig::default(), &searcher(), vec![session(1, 2, 2, 0, "10.0.0.1")], &HashMap::new());
+        assert!(ranked.is_empty());
+    }
+
+    #[test]
+    fn prefers_nearby_hosts() {
+        let config = MatchmakingConfig {
+            free_slots_weight: 0.0,
+            age_weight: 0.0,
+            ..Default::default()
+        };
+        let sessions = vec![session(1, 1, 2, 0, "192.168.1.1"), session(2, 1, 2, 0, "10.0.0.1")];
+        let ranked = rank(&config, &searcher(), sessions, &HashMap::new());
+        assert_eq!(ranked.iter().map(|s| s.session_id).collect::<Vec<_>>(), [2, 1]);
+    }
+
+    #[test]
+    fn prefers_fuller_and_newer_sessions() {
+        let config = MatchmakingConfig {
+            region_weight: 0.0,
+            ..Default::default()
+        };
+        let sessions = vec![session(1, 1, 4, 0, "10.0.0.1"), session(2, 3, 4, 0, "10.0.0.1"), session(3, 3, 4, 900, "10.0.0.1")];
+        let ranked = rank(&config, &searcher(), sessions, &HashMap::new());
+        assert_eq!(ranked.iter().map(|s| s.session_id).collect::<Vec<_>>(), [2, 3, 1]);
+    }
+
+    #[test]
+    fn drops_unreachable_players() {
+        let searcher = Searcher {
+            unreachable: HashSet::from([2]),
+            ..searcher()
+        };
+        let sessions = vec![session(1, 2, 4, 0, "10.0.0.1"), session(2, 1, 4, 0, "10.0.0.1")];
+        let ranked = rank(&MatchmakingConfig::default(), &searcher, sessions, &HashMap::new());
+        assert_eq!(ranked.iter().map(|s| s.session_id).collect::<Vec<_>>(), [2]);
+    }
+
+    #[test]
+    fn caps_results() {
+        let config = MatchmakingConfig {
+            max_results: 1,
+            ..Default::default()
+        };
+        let sessions = vec![session(1, 1, 2, 0, "10.0.0.1"), session(2, 1, 2, 0, "10.0.0.1")];
+        assert_eq!(rank(&config, &searcher(), sessions, &HashMap::new()).len(), 1);
+    }
+
+    #[test]
+    fn skill_ratings_from_stats() {
+        let storage = crate::storage::Storage::in_memory().unwrap();
+        storage.store_player_stats(1000, 3, 1, &[(7, 200), (8, 5)]).unwrap();
+        storage.store_player_stats(1000, 3, 2, &[(7, 300)]).unwrap();
+        storage.store_player_stats(1001, 3, 1, &[(8, 10)]).unwrap();
+        let ratings = storage.find_skill_ratings(3, 7, &[1000, 1001]).unwrap();
+        assert_eq!(ratings, HashMap::from([(1000, 500.0)]));
+    }
+}
@@ -0,0 +1,100 @@
+//! Implements the `MonitoringProtocol` for liveness checks.
+//!
+//! Like the `HealthProtocol`, it's registered on the secure server and answers without login. There is no cluster,
+//! `GetClusterMembers` reports the state of the server instead, as `key=value` strings: its uptime in seconds, whether
+//! the database is reachable, the connected clients and, with a reachable database, the running game sessions and the
+//! users online.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use quazal::prudp::ClientRegistry;
+use quazal::rmc::Error;
+use quazal::rmc::Protocol;
+use quazal::ClientInfo;
+use quazal::Context;
+use slog::Logger;
+
+use crate::protocols::health_service::monitoring_protocol::GetClusterMembersRequest;
+use crate::protocols::health_service::monitoring_protocol::GetClusterMembersResponse;
+use crate::protocols::health_service::monitoring_protocol::MonitoringProtocolServer;
+use crate::protocols::health_service::monitoring_protocol::MonitoringProtocolServerTrait;
+use crate::protocols::health_service::monitoring_protocol::PingDaemonRequest;
+use crate::protocols::health_service::monitoring_protocol::PingDaemonResponse;
+use crate::storage::Storage;
+
+/// Implementation of the `MonitoringProtocolServerTrait`.
+struct MonitoringProtocolServerImpl {
+    storage: Arc<Storage>,
+    started: Instant,
+}
+
+impl<CI> MonitoringProtocolServerTrait<CI> for MonitoringProtocolServerImpl {
+    /// Handles the `PingDaemon` request, the server answering is all that's checked.
+    fn ping_daemon(
+        &self,
+        _logger: &Logger,
+        _ctx: &Context,
+        _ci: &mut ClientInfo<CI>,
+        _request: PingDaemonRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<PingDaemonResponse, Error> {
+        Ok(PingDaemonResponse { return_value: true })
+    }
+
+    /// Handles the `GetClusterMembers` request, returning the state of the server.
+    fn get_cluster_members(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        _ci: &mut ClientInfo<CI>,
+        _request: GetClusterMembersRequest,
+        client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetClusterMembersResponse, Error> {
+        let sessions = self.storage.count_sessions().map_err(|e| error!(logger, "Error counting sessions: {e}")).ok();
+        let mut str_values = vec![
+            format!("uptime={}", self.started.elapsed().as_secs()),
+            format!("database={}", if sessions.is_some() { "ok" } else { "unreachable" }),
+            format!("clients={}", client_registry.client_count()),
+        ];
+        if let Some((game_sessions, online_users)) = sessions {
+            str_values.push(format!("game_sessions={game_sessions}"));
+            str_values.push(format!("online_users={online_users}"));
+        }
+        Ok(GetClusterMembersResponse { str_values })
+    }
+}
+
+/// Creates a new boxed `MonitoringProtocolServer` instance, whose uptime starts now.
+///
+/// This function is typically used to register the monitoring protocol
+/// with the server's protocol dispatcher.
+pub fn new_protocol<T: 'static>(storage: Arc<Storage>) -> Box<dyn Protocol<T>> {
+    Box::new(MonitoringProtocolServer::new(MonitoringProtocolServerImpl { storage, started: Instant::now() }))
+}
+
+#[cfg(test)]
+mod tests {
+    use quazal::rmc::basic::FromStream;
+
+    use super::*;
+    use crate::protocols::health_service::monitoring_protocol::MonitoringProtocolMethod;
+    use crate::test_util;
+
+    #[test]
+    fn cluster_members() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        // besides the sample session of the migrations
+        storage.create_game_session(1000, 1, &[]).unwrap();
+        storage.create_user_session(1000, b"key").unwrap();
+        let prot = new_protocol::<()>(storage);
+        // anonymous like a probe
+        let resp = test_util::call(&*prot, None, MonitoringProtocolMethod::GetClusterMembers as u32, &[]).unwrap();
+        assert_eq!(
+            GetClusterMembersResponse::from_bytes(&resp).unwrap().str_values,
+            ["uptime=0", "database=ok", "clients=0", "game_sessions=2", "online_users=1"]
+        );
+    }
+}
@@ -1,5 +1,11 @@
 //! Implements the `NatTraversalProtocolServer` for handling NAT traversal requests,
 //! such as initiating probes to other clients.
+//!
+//! Probes requested between players are counted per pair, and the results the game reports are tracked per pair as
+//! well. Matchmaking uses the failure streaks of the pairs to avoid matching players that can't reach each other, until
+//! the last failure of a pair is older than the configured window.
+
+use std::sync::Arc;
 
 use quazal::prudp::packet::PacketType;
 use quazal::prudp::packet::QPacket;
@@ -7,6 +13,7 @@ use quazal::prudp::packet::StreamType;
 use quazal::prudp::packet::VPort;
 use quazal::prudp::ClientRegistry;
 use quazal::rmc::basic::ToStream;
+use quazal::rmc::types::StationURL;
 use quazal::rmc::Error;
 use quazal::rmc::Protocol;
 use quazal::rmc::Request;
@@ -16,41 +23,49 @@ use slog::Logger;
 
 use crate::login_required;
 use crate::protocols::nat_traversal::nat_traversal_protocol::InitiateProbeRequest;
+use crate::protocols::nat_traversal::nat_traversal_protocol::InitiateProbeResponse;
 use crate::protocols::nat_traversal::nat_traversal_protocol::NatTraversalProtocolMethod;
 use crate::protocols::nat_traversal::nat_traversal_protocol::NatTraversalProtocolServer;
 use crate::protocols::nat_traversal::nat_traversal_protocol::NatTraversalProtocolServerTrait;
+use crate::protocols::nat_traversal::nat_traversal_protocol::ReportNatPropertiesRequest;
+use crate::protocols::nat_traversal::nat_traversal_protocol::ReportNatPropertiesResponse;
+use crate::protocols::nat_traversal::nat_traversal_protocol::ReportNatTraversalResultRequest;
+use crate::protocols::nat_traversal::nat_traversal_protocol::ReportNatTraversalResultResponse;
 use crate::protocols::nat_traversal::nat_traversal_protocol::RequestProbeInitiationExtRequest;
 use crate::protocols::nat_traversal::nat_traversal_protocol::RequestProbeInitiationExtResponse;
+use crate::protocols::nat_traversal::nat_traversal_protocol::RequestProbeInitiationRequest;
+use crate::protocols::nat_traversal::nat_traversal_protocol::RequestProbeInitiationResponse;
 use crate::protocols::nat_traversal::nat_traversal_protocol::NAT_TRAVERSAL_PROTOCOL_ID;
+use crate::secure::is_public_url;
+use crate::secure::merge_station_urls;
+use crate::storage::Storage;
 
 /// Implementation of the `NatTraversalProtocolServerTrait` for NAT traversal operations.
-struct NatTraversalProtocolServerImpl;
+struct NatTraversalProtocolServerImpl {
+    storage: Arc<Storage>,
+    /// Seconds after which the failures of a pair are forgotten.
+    unreachable_for: u64,
+}
 
-impl<T> NatTraversalProtocolServerTrait<T> for NatTraversalProtocolServerImpl {
-    /// Handles the `RequestProbeInitiationExt` request.
+impl NatTraversalProtocolServerImpl {
+    /// Sends `InitiateProbe` to the clients behind `targets`, asking them to probe `station`.
     ///
-    /// This method is responsible for initiating NAT probes to other clients.
-    fn request_probe_initiation_ext(
-        &self,
+    /// Returns the users the probes were sent to.
+    fn initiate_probes<T>(
         logger: &Logger,
         ctx: &Context,
-        ci: &mut ClientInfo<T>,
-        request: RequestProbeInitiationExtRequest,
+        targets: &[StationURL],
+        station: &StationURL,
         client_registry: &ClientRegistry<T>,
         socket: &std::net::UdpSocket,
-    ) -> Result<RequestProbeInitiationExtResponse, Error> {
-        // Ensure the client is logged in.
-        let _user_id = login_required(&*ci)?;
-        info!(logger, "Probe initiation requested: {request:?}");
-
-        // Iterate over each target URL provided in the request.
-        for url in request.url_target_list.iter() {
+    ) -> Vec<u32> {
+        let mut peers = Vec::with_capacity(targets.len());
+        for url in targets {
             // Extract the connection ID (RVCID) from the URL parameters.
             let Some(conn_id) = url.params.get("RVCID") else {
                 warn!(logger, "{url} doesn't include RVCID");
                 continue;
             };
-            // Parse the connection ID into a u32.
             let Ok(conn_id) = conn_id.parse() else {
                 warn!(logger, "{url} doesn't include valid RVCID");
                 continue;
@@ -62,33 +77,24 @@ impl<T> NatTraversalProtocolServerTrait<T> for NatTraversalProtocolServerImpl {
                 continue;
             };
 
-            // Construct the payload for the InitiateProbe request.
             let payload = Request {
                 protocol_id: NAT_TRAVERSAL_PROTOCOL_ID,
                 call_id: rand::random(), // Generate a random call ID for the probe.
                 method_id: NatTraversalProtocolMethod::InitiateProbe as u32,
                 parameters: InitiateProbeRequest {
-                    url_station_to_probe: request.url_station_to_probe.clone(),
+                    url_station_to_probe: station.clone(),
                 }
                 .to_bytes(),
             }
             .to_bytes();
 
-            // The commented-out section below shows an example of a hardcoded payload.
-            // This is kept for reference but is not actively used.
-            // let payload = Request {
-            //     protocol_id: 14,
-            //     call_id: rand::random(),
-            //     method_id: 1,
-            //     parameters: b"\x0c#\x1d\x00[\x1b\x00\x0015\x19\x00\xbd\xb3\x98\x02\x01\x00\x00\x01\x00\x00\x00".to_vec(),
-            // }
-            // .to_bytes();
-
-            // Get the address of the target client.
-            let addr = { *target.borrow().address() };
-            info!(logger, "Sending probe to {url} ({addr})\n{payload:x?}");
-
-            // Create a QPacket for sending the probe.
+            // the requesting client is borrowed while its request is handled
+            let Ok(mut target) = target.try_borrow_mut() else {
+                warn!(logger, "Not sending a probe to the requesting client");
+                continue;
+            };
+            let addr = *target.address();
+            info!(logger, "Sending probe to {url} ({addr})");
             let qpacket = QPacket {
                 source: VPort {
                     port: 1,
@@ -102,17 +108,230 @@ impl<T> NatTraversalProtocolServerTrait<T> for NatTraversalProtocolServerImpl {
                 payload,
                 ..Default::default()
             };
-            // Send the QPacket to the target client.
-            quazal::prudp::send_request(logger, ctx, &addr, socket, qpacket, &mut *target.borrow_mut()).unwrap();
+            if let Err(e) = quazal::prudp::send_request(logger, ctx, &addr, socket, qpacket, &mut *target) {
+                error!(logger, "Error sending probe to {addr}: {e}");
+                continue;
+            }
+            if let Some(peer_id) = target.user_id {
+                peers.push(peer_id);
+            }
         }
+        peers
+    }
+
+    /// Returns the public station URL the requesting client registered, or one with the observed address if it didn't
+    /// register any.
+    fn public_station<T>(&self, logger: &Logger, ci: &ClientInfo<T>, user_id: u32) -> StationURL {
+        let urls = self.storage.list_station_urls(user_id).unwrap_or_else(|e| {
+            error!(logger, "Error listing station URLs: {e}");
+            Vec::new()
+        });
+        urls.iter()
+            .filter_map(|url| url.parse::<StationURL>().ok())
+            .find(is_public_url)
+            .unwrap_or_else(|| merge_station_urls(*ci.address(), ci.connection_id.map(u32::from), &[], "15").swap_remove(0))
+    }
+
+    fn record_probes(&self, logger: &Logger, user_id: u32, peers: &[u32]) {
+        if let Err(e) = self.storage.record_nat_probes(user_id, peers) {
+            error!(logger, "Error recording NAT probes: {e}");
+        }
+    }
+}
+
+impl<T> NatTraversalProtocolServerTrait<T> for NatTraversalProtocolServerImpl {
+    /// Handles the `RequestProbeInitiation` request.
+    ///
+    /// The targets are asked to probe the registered public station of the requesting client.
+    fn request_probe_initiation(
+        &self,
+        logger: &Logger,
+        ctx: &Context,
+        ci: &mut ClientInfo<T>,
+        request: RequestProbeInitiationRequest,
+        client_registry: &ClientRegistry<T>,
+        socket: &std::net::UdpSocket,
+    ) -> Result<RequestProbeInitiationResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        info!(logger, "Probe initiation requested: {request:?}");
+        let station = self.public_station(logger, ci, user_id);
+        let peers = Self::initiate_probes(logger, ctx, &request.url_target_list, &station, client_registry, socket);
+        self.record_probes(logger, user_id, &peers);
+        Ok(RequestProbeInitiationResponse)
+    }
+
+    /// Handles the `InitiateProbe` request.
+    ///
+    /// The server sends this request to clients. A client sending it to the server gets an answer, but the server
+    /// doesn't probe stations.
+    fn initiate_probe(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<T>,
+        request: InitiateProbeRequest,
+        _client_registry: &ClientRegistry<T>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<InitiateProbeResponse, Error> {
+        let _user_id = login_required(&*ci)?;
+        info!(logger, "Ignoring probe of {} requested by a client", request.url_station_to_probe);
+        Ok(InitiateProbeResponse)
+    }
+
+    /// Handles the `RequestProbeInitiationExt` request.
+    ///
+    /// The targets are asked to probe the given station.
+    fn request_probe_initiation_ext(
+        &self,
+        logger: &Logger,
+        ctx: &Context,
+        ci: &mut ClientInfo<T>,
+        request: RequestProbeInitiationExtRequest,
+        client_registry: &ClientRegistry<T>,
+        socket: &std::net::UdpSocket,
+    ) -> Result<RequestProbeInitiationExtResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        info!(logger, "Probe initiation requested: {request:?}");
+        let peers = Self::initiate_probes(logger, ctx, &request.url_target_list, &request.url_station_to_probe, client_registry, socket);
+        self.record_probes(logger, user_id, &peers);
         Ok(RequestProbeInitiationExtResponse)
     }
+
+    /// Handles the `ReportNatTraversalResult` request, recording whether the client reached the peer with the given
+    /// connection id. Reports about the client's own connection are ignored.
+    ///
+    /// This function requires the client to be logged in.
+    fn report_nat_traversal_result(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<T>,
+        request: ReportNatTraversalResultRequest,
+        client_registry: &ClientRegistry<T>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<ReportNatTraversalResultResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        if ci.connection_id.map(u32::from) == Some(request.cid) {
+            warn!(logger, "Ignoring NAT traversal result for the own connection");
+            return Ok(ReportNatTraversalResultResponse);
+        }
+        let Some(peer_id) = client_registry.client_by_connection_id(request.cid.into()).and_then(|peer| peer.try_borrow().ok()?.user_id) else {
+            warn!(logger, "NAT traversal result for unknown connection {}", request.cid);
+            return Ok(ReportNatTraversalResultResponse);
+        };
+        info!(
+            logger,
+            "NAT traversal from user {user_id} to user {peer_id} {}",
+            if request.result { "succeeded" } else { "failed" }
+        );
+        rmc_err!(
+            self.storage.record_nat_result(user_id, peer_id, request.result, self.unreachable_for),
+            logger,
+            "error recording NAT traversal result"
+        )?;
+        Ok(ReportNatTraversalResultResponse)
+    }
+
+    /// Handles the `ReportNatProperties` request, storing the NAT behavior the client detected.
+    ///
+    /// This function requires the client to be logged in.
+    fn report_nat_properties(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<T>,
+        request: ReportNatPropertiesRequest,
+        _client_registry: &ClientRegistry<T>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<ReportNatPropertiesResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        rmc_err!(
+            self.storage.set_nat_properties(user_id, request.natmapping, request.natfiltering, request.rtt),
+            logger,
+            "error storing NAT properties"
+        )?;
+        Ok(ReportNatPropertiesResponse)
+    }
 }
 
 /// Creates a new boxed `NatTraversalProtocolServer` instance.
 ///
 /// This function is typically used to register the NAT traversal protocol
 /// with the server's protocol dispatcher.
-pub fn new_protocol<T: 'static>() -> Box<dyn Protocol<T>> {
-    Box::new(NatTraversalProtocolServer::new(NatTraversalProtocolServerImpl))
+pub fn new_protocol<T: 'static>(storage: Arc<Storage>, unreachable_for: u64) -> Box<dyn Protocol<T>> {
+    Box::new(NatTraversalProtocolServer::new(NatTraversalProtocolServerImpl { storage, unreachable_for }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::test_util;
+
+    #[test]
+    fn results_and_properties() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let prot = new_protocol::<()>(Arc::clone(&storage), 3600);
+        let properties = ReportNatPropertiesRequest {
+            natmapping: 1,
+            natfiltering: 2,
+            rtt: 40,
+        };
+        test_util::call(&*prot, Some(1000), NatTraversalProtocolMethod::ReportNatProperties as u32, &properties.to_bytes()).unwrap();
+        // the peer isn't connected
+        let result = ReportNatTraversalResultRequest { cid: 7, result: false };
+        test_util::call(&*prot, Some(1000), NatTraversalProtocolMethod::ReportNatTraversalResult as u32, &result.to_bytes()).unwrap();
+        // a client can't report about itself
+        let mut ci = ClientInfo::new("127.0.0.1:2".parse().unwrap());
+        ci.user_id = Some(1000);
+        ci.connection_id = Some(7.into());
+        test_util::call_with(&*prot, &mut ci, NatTraversalProtocolMethod::ReportNatTraversalResult as u32, &result.to_bytes()).unwrap();
+
+        storage.record_nat_probes(1000, &[1001, 1002]).unwrap();
+        storage.record_nat_result(1001, 1000, false, 3600).unwrap();
+        storage.record_nat_result(1000, 1001, false, 3600).unwrap();
+        storage.record_nat_result(1000, 1002, false, 3600).unwrap();
+        assert_eq!(storage.find_unreachable_peers(1000, 2, 3600).unwrap(), HashSet::from([1001]));
+        assert_eq!(storage.find_unreachable_peers(1001, 2, 3600).unwrap(), HashSet::from([1000]));
+
+        storage.record_nat_result(1000, 1001, true, 3600).unwrap();
+        assert!(storage.find_unreachable_peers(1000, 2, 3600).unwrap().is_empty());
+
+        let stats = test_util::block_on(storage.nat_stats_async(2, 3600)).unwrap();
+        assert_eq!(
+            (stats.pairs, stats.probes, stats.successes, stats.failures, stats.unreachable, stats.reported_properties),
+            (2, 2, 1, 3, 0, 1)
+        );
+    }
+
+    #[test]
+    fn public_station() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let prot = NatTraversalProtocolServerImpl {
+            storage: Arc::clone(&storage),
+            unreachable_for: 3600,
+        };
+        let logger = Logger::root(slog::Discard, slog::o!());
+        let mut ci = ClientInfo::<()>::new("192.0.2.1:3074".parse().unwrap());
+        ci.connection_id = Some(7.into());
+
+        // without registered URLs, the observed address is used
+        let station = prot.public_station(&logger, &ci, 1001);
+        assert_eq!((station.address.as_str(), station.port), ("192.0.2.1", 3074));
+
+        storage
+            .register_urls(
+                1001,
+                vec![
+                    "prudp:/address=10.0.0.2;port=3074;sid=15;type=1".to_string(),
+                    "prudp:/address=192.0.2.1;port=3074;sid=15;type=3;natm=1;natf=2;RVCID=7".to_string(),
+                ],
+            )
+            .unwrap();
+        let station = prot.public_station(&logger, &ci, 1001);
+        // the registered URL keeps the NAT parameters
+        assert_eq!(station.params.get("natf").map(String::as_str), Some("2"));
+        assert_eq!(station.address, "192.0.2.1");
+    }
 }
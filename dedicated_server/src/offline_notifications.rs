@@ -0,0 +1,209 @@
+//! Implements the `OfflineGameNotificationsProtocol`, which delivers the stored notifications of a player.
+//!
+//! The game polls for notifications after logging in. Game invites are stored for receivers that are offline when
+//! they're sent, admin messages are sent with the admin API. Friend requests and clan invites have their kinds, but
+//! the server has no friend or clan system creating them yet. Polls return the notifications until the client acknowledged
+//! a response containing them, so a lost response doesn't lose notifications.
+
+use std::sync::Arc;
+
+use quazal::prudp::ClientRegistry;
+use quazal::rmc::types::Data;
+use quazal::rmc::types::QList;
+use quazal::rmc::Error;
+use quazal::rmc::Protocol;
+use quazal::ClientInfo;
+use quazal::Context;
+use slog::Logger;
+
+use crate::login_required;
+use crate::protocols::offline_game_notifications_service::offline_game_notifications_protocol::OfflineGameNotificationsProtocolServer;
+use crate::protocols::offline_game_notifications_service::offline_game_notifications_protocol::OfflineGameNotificationsProtocolServerTrait;
+use crate::protocols::offline_game_notifications_service::offline_game_notifications_protocol::PollAnyOfflineNotificationsRequest;
+use crate::protocols::offline_game_notifications_service::offline_game_notifications_protocol::PollAnyOfflineNotificationsResponse;
+use crate::protocols::offline_game_notifications_service::offline_game_notifications_protocol::PollNotificationsRequest;
+use crate::protocols::offline_game_notifications_service::offline_game_notifications_protocol::PollNotificationsResponse;
+use crate::protocols::offline_game_notifications_service::offline_game_notifications_protocol::PollSpecificOfflineNotificationsRequest;
+use crate::protocols::offline_game_notifications_service::offline_game_notifications_protocol::PollSpecificOfflineNotificationsResponse;
+use crate::protocols::offline_game_notifications_service::types::TimedNotification;
+use crate::protocols::protocol_foundation::types::NotificationEvent;
+use crate::storage::Notification;
+use crate::storage::NotificationKind;
+use crate::storage::Storage;
+
+/// Notifications returned per poll, the game polls again while some remain.
+const PAGE_SIZE: u32 = 20;
+
+/// Converts a stored notification to the event the game gets. Like Quazal's notification types, the type is the major
+/// type times 1000.
+fn event(notification: &Notification) -> NotificationEvent {
+    NotificationEvent {
+        pid_source: notification.sender_id.unwrap_or_default(),
+        ui_type: notification.kind as u32 * 1000,
+        ui_param_1: notification.param1,
+        ui_param_2: notification.param2,
+        str_param: notification.text.clone(),
+        ui_param_3: 0,
+    }
+}
+
+fn timed(notification: &Notification) -> TimedNotification {
+    TimedNotification {
+        data: Data,
+        timestamp: notification.created_at.parse().unwrap_or_default(),
+        notification: event(notification),
+    }
+}
+
+/// Implementation of the `OfflineGameNotificationsProtocolServerTrait`.
+struct OfflineGameNotificationsProtocolServerImpl {
+    storage: Arc<Storage>,
+}
+
+impl OfflineGameNotificationsProtocolServerImpl {
+    /// Returns the next unread notifications and marks them read once the client acknowledged the response.
+    fn take<CI>(&self, logger: &Logger, ci: &mut ClientInfo<CI>, kinds: Option<&[NotificationKind]>) -> Result<(Vec<Notification>, u32), Error> {
+        let user_id = login_required(&*ci)?;
+        let (notifications, remaining) = rmc_err!(self.storage.unread_notifications(user_id, kinds, PAGE_SIZE), logger, "error reading notifications")?;
+        if !notifications.is_empty() {
+            info!(logger, "Delivering {} notifications to user {user_id}, {remaining} remaining", notifications.len());
+            let ids = notifications.iter().map(|notification| notification.id).collect::<Vec<_>>();
+            let storage = Arc::clone(&self.storage);
+            let logger = logger.clone();
+            ci.on_response_ack(move || {
+                if let Err(e) = storage.mark_notifications_read(user_id, &ids) {
+                    error!(logger, "Error marking notifications read: {e}");
+                }
+            });
+        }
+        Ok((notifications, remaining))
+    }
+}
+
+impl<CI> OfflineGameNotificationsProtocolServerTrait<CI> for OfflineGameNotificationsProtocolServerImpl {
+    /// Handles the `PollNotifications` request, returning the unread notifications of the player.
+    ///
+    /// This function requires the client to be logged in.
+    fn poll_notifications(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        _request: PollNotificationsRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<PollNotificationsResponse, Error> {
+        let (notifications, remaining) = self.take(logger, ci, None)?;
+        Ok(PollNotificationsResponse {
+            list_notifications: notifications.iter().map(event).collect::<QList<_>>(),
+            nb_remaining_notifs: remaining,
+        })
+    }
+
+    /// Handles the `PollSpecificOfflineNotifications` request, returning the unread notifications of the requested
+    /// major types with the time they were sent.
+    ///
+    /// This function requires the client to be logged in.
+    fn poll_specific_offline_notifications(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: PollSpecificOfflineNotificationsRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<PollSpecificOfflineNotificationsResponse, Error> {
+        let kinds = request.majortype.iter().filter_map(|major| NotificationKind::try_from(*major).ok()).collect::<Vec<_>>();
+        let (notifications, remaining) = self.take(logger, ci, Some(&kinds))?;
+        Ok(PollSpecificOfflineNotificationsResponse {
+            list_timed_notification: notifications.iter().map(timed).collect::<QList<_>>(),
+            ret: remaining,
+        })
+    }
+
+    /// Handles the `PollAnyOfflineNotifications` request, returning the unread notifications of the player with the
+    /// time they were sent.
+    ///
+    /// This function requires the client to be logged in.
+    fn poll_any_offline_notifications(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        _request: PollAnyOfflineNotificationsRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<PollAnyOfflineNotificationsResponse, Error> {
+        let (notifications, remaining) = self.take(logger, ci, None)?;
+        Ok(PollAnyOfflineNotificationsResponse {
+            list_timed_notification: notifications.iter().map(timed).collect::<QList<_>>(),
+            nb_remaining_notifs: remaining,
+        })
+    }
+}
+
+/// Creates a new boxed `OfflineGameNotificationsProtocolServer` instance.
+///
+/// This function is typically used to register the offline game notifications protocol
+/// with the server's protocol dispatcher.
+pub fn new_protocol<T: 'static>(storage: Arc<Storage>) -> Box<dyn Protocol<T>> {
+    Box::new(OfflineGameNotificationsProtocolServer::new(OfflineGameNotificationsProtocolServerImpl { storage }))
+}
+
+#[cfg(test)]
+mod tests {
+    use quazal::rmc::basic::FromStream;
+    use quazal::rmc::basic::ToStream;
+    use quazal::rmc::types::DateTime;
+
+    use super::*;
+    use crate::protocols::offline_game_notifications_service::offline_game_notifications_protocol::OfflineGameNotificationsProtocolMethod;
+    use crate::test_util;
+
+    #[test]
+    fn delivery() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let prot = new_protocol::<()>(Arc::clone(&storage));
+        // 1001 is offline, 1002 gets the invite right away
+        storage.create_user_session(1002, b"key").unwrap();
+        let session_id = storage.create_game_session(1000, 1, &[]).unwrap();
+        storage.add_session_invites(1000, 1, session_id, &[1001, 1002], "join me").unwrap();
+        assert!(storage.unread_notifications(1002, None, PAGE_SIZE).unwrap().0.is_empty());
+        test_util::block_on(storage.add_notification_async(1001, None, NotificationKind::AdminMessage, (0, 0), "maintenance")).unwrap();
+
+        let request = PollSpecificOfflineNotificationsRequest {
+            majortype: QList(vec![NotificationKind::AdminMessage as u32, 99]),
+        };
+        let resp = test_util::call(
+            &*prot,
+            Some(1001),
+            OfflineGameNotificationsProtocolMethod::PollSpecificOfflineNotifications as u32,
+            &request.to_bytes(),
+        )
+        .unwrap();
+        let resp = PollSpecificOfflineNotificationsResponse::from_bytes(&resp).unwrap();
+        assert_eq!(resp.ret, 0);
+        assert_eq!(resp.list_timed_notification.len(), 1);
+        assert_eq!(resp.list_timed_notification[0].notification.ui_type, 4000);
+        assert_eq!(resp.list_timed_notification[0].notification.str_param, "maintenance");
+        assert!(resp.list_timed_notification[0].timestamp > DateTime::new(2020, 1, 1, 0, 0, 0));
+
+        // without an ack the notifications are delivered again
+        let resp = test_util::call(&*prot, Some(1001), OfflineGameNotificationsProtocolMethod::PollNotifications as u32, &[]).unwrap();
+        let resp = PollNotificationsResponse::from_bytes(&resp).unwrap();
+        let events = resp
+            .list_notifications
+            .iter()
+            .map(|n| (n.pid_source, n.ui_type, n.ui_param_1, n.ui_param_2, n.str_param.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(events, [(1000, 1000, 1, session_id, "join me"), (0, 4000, 0, 0, "maintenance")]);
+
+        // acknowledged notifications aren't delivered again
+        let (notifications, _) = storage.unread_notifications(1001, None, PAGE_SIZE).unwrap();
+        storage.mark_notifications_read(1001, &[notifications[0].id]).unwrap();
+        let resp = test_util::call(&*prot, Some(1001), OfflineGameNotificationsProtocolMethod::PollAnyOfflineNotifications as u32, &[]).unwrap();
+        let resp = PollAnyOfflineNotificationsResponse::from_bytes(&resp).unwrap();
+        assert_eq!(resp.list_timed_notification.len(), 1);
+        assert_eq!(resp.list_timed_notification[0].notification.str_param, "maintenance");
+    }
+}
@@ -1,4 +1,15 @@
+//! Implements the `OverlordChallengeProtocol`, which sends the online challenges.
+//!
+//! The challenges are defined in `data/challenges.json`. Permanent challenges are always active and community
+//! challenges between fixed dates, while daily and weekly challenges rotate through their pools. Completions are
+//! recorded per player and per occurrence of a challenge. `{{key}}` placeholders in the challenges are replaced with the
+//! strings for the player's locale.
+//!
+//! Only `get_challenges` is known, the requests of the other methods are logged until their payloads are figured out.
+
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
 
 use quazal::prudp::ClientRegistry;
 use quazal::rmc::basic::FromStream;
@@ -8,30 +19,42 @@ use quazal::rmc::types::Variant;
 use quazal::rmc::Protocol;
 use quazal::Context;
 use serde::Deserialize;
+use slog::Logger;
 
+use crate::data_file::DataFile;
 use crate::login_required;
+use crate::storage::Storage;
+use crate::strings::Strings;
+
+/// Where the challenge catalogue is read from.
+pub const CATALOGUE_PATH: &str = "data/challenges.json";
+
+const DAY: u64 = 24 * 60 * 60;
+/// Days from a saturday to the unix epoch, a thursday. Weekly challenges start on saturdays like the official ones did.
+const WEEK_OFFSET: u64 = 5;
 
 #[derive(Debug, ToStream, FromStream)]
 struct GetChallengesRequest {
     class: String,
 }
 
-#[derive(Debug, ToStream, FromStream, Deserialize)]
+#[derive(Debug, ToStream, FromStream)]
 struct Challenge {
-    unk1: u32,
+    id: u32,
     unk2: String,
     some_xml: String,
     unk4: u32,
     unk5: u32,
     unk6: u32,
     unk7: u32,
-    unk8: bool,
-    unk9: DateTime,
-    unk10: DateTime,
+    is_complete: bool,
+    start_time: DateTime,
+    end_time: DateTime,
     unk11: String,
     unk12: String,
     unk13: String,
     unk14: HashMap<String, Variant>,
+    /// `p` is the number of steps to complete the challenge, `s` is always 1.
     unk15: HashMap<String, Variant>,
     unk16: HashMap<String, Variant>,
     unk17: u32,
@@ -44,8 +67,286 @@ struct GetChallengesResponse {
     challenges: Vec<Challenge>,
 }
 
+/// When a challenge is active.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Schedule {
+    Permanent,
+    /// Rotates with the other daily challenges.
+    Daily,
+    /// Rotates with the other weekly challenges.
+    Weekly,
+    /// Active between two "YYYY-MM-DD HH:MM:SS" timestamps in UTC.
+    Community {
+        start: String,
+        end: String,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChallengeDefinition {
+    pub id: u32,
+    pub schedule: Schedule,
+    /// The challenge as the game expects it.
+    pub xml: String,
+}
+
+impl ChallengeDefinition {
+    /// Returns the number of steps to complete the challenge, the count of its `StepReward`.
+    pub fn target(&self) -> u32 {
+        const PREFIX: &str = "<StepReward Count=\"";
+        self.xml
+            .split_once(PREFIX)
+            .and_then(|(_, rest)| rest.split_once('"'))
+            .and_then(|(count, _)| count.parse().ok())
+            .unwrap_or(1)
+    }
+}
+
+/// The challenges offered to players.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ChallengeCatalogue {
+    /// Number of daily challenges active at once.
+    pub daily_count: usize,
+    /// Number of weekly challenges active at once.
+    pub weekly_count: usize,
+    /// Hour of the day in UTC when daily and weekly challenges rotate.
+    pub reset_hour: u64,
+    pub challenges: Vec<ChallengeDefinition>,
+}
+
+impl Default for ChallengeCatalogue {
+    fn default() -> Self {
+        Self {
+            daily_count: 1,
+            weekly_count: 1,
+            reset_hour: 9,
+            challenges: Vec::new(),
+        }
+    }
+}
+
+/// A challenge that's currently active.
+pub struct ActiveChallenge<'a> {
+    pub definition: &'a ChallengeDefinition,
+    pub start_time: DateTime,
+    pub end_time: DateTime,
+}
+
+impl ActiveChallenge<'_> {
+    /// Identifies the occurrence of the challenge, so that progress resets when it rotates in again.
+    pub fn period(&self) -> String {
+        self.start_time.to_string()
+    }
+}
+
+/// Picks `count` challenges of a pool for a period, moving on by `count` challenges every period.
+fn rotate<'a>(pool: &[&'a ChallengeDefinition], count: usize, period: u64) -> Vec<&'a ChallengeDefinition> {
+    if pool.is_empty() {
+        return Vec::new();
+    }
+    let period = usize::try_from(period).unwrap_or_default();
+    let start = period.wrapping_mul(count) % pool.len();
+    pool.iter().cycle().skip(start).take(count.min(pool.len())).copied().collect()
+}
+
+impl ChallengeCatalogue {
+    /// Loads the catalogue, `None` if the file is missing or invalid.
+    pub fn load(logger: &Logger, path: &Path) -> Option<Self> {
+        let file = std::fs::File::open(path).ok()?;
+        serde_json::from_reader(file)
+            .map_err(|e| error!(logger, "Invalid challenge catalogue {}: {e}", path.display()))
+            .ok()
+    }
+
+    /// Returns the challenges active at a time, given in seconds since the unix epoch.
+    pub fn active(&self, now: u64) -> Vec<ActiveChallenge<'_>> {
+        let reset = self.reset_hour * 60 * 60;
+        let day = now.saturating_sub(reset) / DAY;
+        let week = (day + WEEK_OFFSET) / 7;
+        let day_start = DateTime::from_unix_timestamp(day * DAY + reset);
+        let day_end = DateTime::from_unix_timestamp((day + 1) * DAY + reset);
+        let week_start = DateTime::from_unix_timestamp((week * 7).saturating_sub(WEEK_OFFSET) * DAY + reset);
+        let week_end = DateTime::from_unix_timestamp(((week + 1) * 7 - WEEK_OFFSET) * DAY + reset);
+        let now = DateTime::from_unix_timestamp(now);
+
+        let pool = |schedule: fn(&Schedule) -> bool| self.challenges.iter().filter(|c| schedule(&c.schedule)).collect::<Vec<_>>();
+        let mut active = Vec::new();
+        for definition in &self.challenges {
+            match &definition.schedule {
+                Schedule::Permanent => active.push(ActiveChallenge {
+                    definition,
+                    start_time: DateTime(0),
+                    end_time: DateTime(u64::MAX),
+                }),
+                Schedule::Community { start, end } => {
+                    let (Ok(start_time), Ok(end_time)) = (start.parse::<DateTime>(), end.parse::<DateTime>()) else {
+                        continue;
+                    };
+                    if start_time <= now && now < end_time {
+                        active.push(ActiveChallenge { definition, start_time, end_time });
+                    }
+                }
+                Schedule::Daily | Schedule::Weekly => {}
+            }
+        }
+        for definition in rotate(&pool(|s| matches!(s, Schedule::Daily)), self.daily_count, day) {
+            active.push(ActiveChallenge {
+                definition,
+                start_time: day_start,
+                end_time: day_end,
+            });
+        }
+        for definition in rotate(&pool(|s| matches!(s, Schedule::Weekly)), self.weekly_count, week) {
+            active.push(ActiveChallenge {
+                definition,
+                start_time: week_start,
+                end_time: week_end,
+            });
+        }
+        active
+    }
+}
+
+/// Returns the current time in seconds since the unix epoch.
+pub fn unix_now() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// An active challenge of the catalogue, localized for a player.
+pub struct PlayerChallenge {
+    pub id: u32,
+    pub xml: String,
+    pub target: u32,
+    pub is_complete: bool,
+    pub start_time: DateTime,
+    pub end_time: DateTime,
+}
+
+/// Returns the challenges active at a time and whether the user completed them, `None` without a catalogue.
+///
+/// Both the `OverlordChallengeProtocol` and the `ChallengeHelperProtocol` send these.
+pub fn player_challenges(
+    logger: &Logger,
+    storage: &Storage,
+    strings: &Strings,
+    catalogue_file: &DataFile<ChallengeCatalogue>,
+    user_id: u32,
+    now: u64,
+) -> Result<Option<Vec<PlayerChallenge>>, quazal::rmc::Error> {
+    let catalogue = match catalogue_file.load() {
+        Ok(Some(catalogue)) => catalogue,
+        Ok(None) => return Ok(None),
+        Err(e) => {
+            error!(logger, "Invalid challenge catalogue {}: {e}", catalogue_file.path().display());
+            return Ok(None);
+        }
+    };
+    let completed = rmc_err!(storage.list_completed_challenges(user_id), logger, "error listing completed challenges")?;
+    let locale = rmc_err!(storage.find_user_locale(user_id), logger, "error finding locale")?;
+    Ok(Some(
+        catalogue
+            .active(now)
+            .into_iter()
+            .map(|c| {
+                let period = c.period();
+                PlayerChallenge {
+                    id: c.definition.id,
+                    xml: strings.localize(locale.as_deref(), &c.definition.xml),
+                    target: c.definition.target(),
+                    is_complete: completed.iter().any(|(id, p)| *id == c.definition.id && *p == period),
+                    start_time: c.start_time,
+                    end_time: c.end_time,
+                }
+            })
+            .collect(),
+    ))
+}
+
 #[allow(clippy::module_name_repetitions)]
-pub struct OverlordChallengeProtocol;
+pub struct OverlordChallengeProtocol {
+    storage: Arc<Storage>,
+    catalogue: DataFile<ChallengeCatalogue>,
+    strings: Strings,
+}
+
+impl OverlordChallengeProtocol {
+    /// Returns the active challenges and whether the user completed them.
+    fn challenges(&self, logger: &Logger, user_id: u32, now: u64) -> Result<Vec<Challenge>, quazal::rmc::Error> {
+        let Some(challenges) = player_challenges(logger, &self.storage, &self.strings, &self.catalogue, user_id, now)? else {
+            return Ok(vec![gone_dark_reminder()]);
+        };
+        Ok(challenges
+            .into_iter()
+            .map(|c| Challenge {
+                id: c.id,
+                unk2: String::from("{}"),
+                some_xml: c.xml,
+                unk4: 0,
+                unk5: 0,
+                unk6: 0,
+                unk7: 1,
+                is_complete: c.is_complete,
+                start_time: c.start_time,
+                end_time: c.end_time,
+                unk11: String::from("{}"),
+                unk12: String::from("{}"),
+                unk13: String::from("{}"),
+                unk14: HashMap::default(),
+                unk15: HashMap::from([(String::from("s"), Variant::I64(1)), (String::from("p"), Variant::I64(c.target.into()))]),
+                unk16: HashMap::default(),
+                unk17: 2,
+                unk18: DateTime::new(9999, 12, 31, 23, 59, 59),
+                unk19: HashMap::default(),
+            })
+            .collect())
+    }
+}
+
+/// The challenge sent without a catalogue.
+fn gone_dark_reminder() -> Challenge {
+    Challenge {
+        id: 16_0200,
+        unk2: String::from("{}"),
+        some_xml: String::from(
+            "<Challenge Name=\"LocID_SNN_ReminderGoneDark_20\" Desc=\"LocID_SNDES_ReminderGoneDark_20\" Guid=\"160200\" ShortDesc=\"LocID_SNSD_ReminderGoneDark_20\" \
+Category=\"OnlineChallengeGoneDarkHeader\">\
+<GoneDark id=\"160200\" PosX=\"262\" PosY=\"397\" Resource=\"GD_Grim_004\" title=\"LocID_C_INT_20_Title\" loc=\"LocID_C_INT_20_Loc_0\" desc=\"LocID_C_INT_20_Desc_1\" />\
+<Definition>\
+<GameEvent>\
+<Event>\
+<GoneDarkUI>\
+<ID Op=\"Equal\" Value=\"160200\" />\
+</GoneDarkUI>\
+</Event>\
+</GameEvent>\
+</Definition>\
+<StepReward Count=\"123\">\
+<UnlockChallenge>\
+<ID val=\"160201\" />\
+</UnlockChallenge>\
+</StepReward>\
+</Challenge>",
+        ),
+        unk4: 0,
+        unk5: 0,
+        unk6: 0,
+        unk7: 1,
+        is_complete: false,
+        start_time: DateTime(0),
+        end_time: DateTime(0xFFFF_FFFF_FFFF_FFFF),
+        unk11: String::from("{}"),
+        unk12: String::from("{}"),
+        unk13: String::from("{}"),
+        unk14: HashMap::default(),
+        unk15: HashMap::from([(String::from("s"), Variant::I64(1)), (String::from("p"), Variant::I64(123))]),
+        unk16: HashMap::default(),
+        unk17: 2,
+        unk18: DateTime(0xFFFF_FFFF_FFFF_FFFF),
+        unk19: HashMap::default(),
+    }
+}
 
 impl<T> Protocol<T> for OverlordChallengeProtocol {
     fn id(&self) -> u16 {
@@ -69,59 +370,20 @@ impl<T> Protocol<T> for OverlordChallengeProtocol {
         _client_registry: &ClientRegistry<T>,
         _socket: &std::net::UdpSocket,
     ) -> std::result::Result<Vec<u8>, quazal::rmc::Error> {
-        login_required(&*ci)?;
+        let user_id = login_required(&*ci)?;
         match request.method_id {
             1 => {
                 let _request: GetChallengesRequest = FromStream::from_bytes(&request.parameters)?;
-
-                let challenges = std::fs::File::open("data/challenges.json")
-                    .map(serde_json::from_reader)
-                    .ok()
-                    .and_then(Result::ok)
-                    .unwrap_or(vec![Challenge {
-                    unk1: 16_0200,
-                    unk2: String::from("{}"),
-                    some_xml: String::from(
-                        "<Challenge Name=\"LocID_SNN_ReminderGoneDark_20\" Desc=\"LocID_SNDES_ReminderGoneDark_20\" Guid=\"160200\" ShortDesc=\"LocID_SNSD_ReminderGoneDark_20\" \
-Category=\"OnlineChallengeGoneDarkHeader\">\
-<GoneDark id=\"160200\" PosX=\"262\" PosY=\"397\" Resource=\"GD_Grim_004\" title=\"LocID_C_INT_20_Title\" loc=\"LocID_C_INT_20_Loc_0\" desc=\"LocID_C_INT_20_Desc_1\" />\
-<Definition>\
-<GameEvent>\
-<Event>\
-<GoneDarkUI>\
-<ID Op=\"Equal\" Value=\"160200\" />\
-</GoneDarkUI>\
-</Event>\
-</GameEvent>\
-</Definition>\
-<StepReward Count=\"123\">\
-<UnlockChallenge>\
-<ID val=\"160201\" />\
-</UnlockChallenge>\
-</StepReward>\
-</Challenge>",
-                    ),
-                    unk4: 0,
-                    unk5: 0,
-                    unk6: 0,
-                    unk7: 1,
-                    unk8: false,
-                    unk9: DateTime(0),
-                    unk10: DateTime(0xFFFF_FFFF_FFFF_FFFF),
-                    unk11: String::from("{}"),
-                    unk12: String::from("{}"),
-                    unk13: String::from("{}"),
-                    unk14: HashMap::default(),
-                    unk15: HashMap::from([(String::from("s"), Variant::I64(1)), (String::from("p"), Variant::I64(123))]),
-                    unk16: HashMap::default(),
-                    unk17: 2,
-                    unk18: DateTime(0xFFFF_FFFF_FFFF_FFFF),
-                    unk19: HashMap::default(),
-                }]);
+                let challenges = self.challenges(logger, user_id, unix_now())?;
                 Ok(GetChallengesResponse { challenges }.to_bytes())
             }
             2..=6 => {
-                error!(logger, "not implemented yet");
+                error!(
+                    logger,
+                    "not implemented yet";
+                    "method_id" => request.method_id,
+                    "parameters" => format!("{:02x?}", request.parameters)
+                );
                 Err(quazal::rmc::Error::UnknownMethod)
             }
             _ => Err(quazal::rmc::Error::UnknownMethod),
@@ -137,8 +399,12 @@ Category=\"OnlineChallengeGoneDarkHeader\">\
     }
 }
 
-pub fn new_protocol<T: 'static>() -> Box<dyn Protocol<T>> {
-    Box::new(OverlordChallengeProtocol)
+pub fn new_protocol<T: 'static>(storage: Arc<Storage>) -> Box<dyn Protocol<T>> {
+    Box::new(OverlordChallengeProtocol {
+        storage,
+        catalogue: DataFile::new(CATALOGUE_PATH),
+        strings: Strings::default(),
+    })
 }
 
 #[cfg(test)]
@@ -146,6 +412,7 @@ mod tests {
     use quazal::rmc::basic::FromStream;
 
     use super::*;
+    use crate::test_util;
 
     #[allow(clippy::too_many_lines)]
     #[test]
@@ -279,4 +546,90 @@ mod tests {
         let parsed: GetChallengesResponse = FromStream::from_bytes(data).unwrap();
         println!("{parsed:#?}");
     }
+
+    fn catalogue(json: &str) -> ChallengeCatalogue {
+        serde_json::from_str(json).unwrap()
+    }
+
+    const ROTATION: &str = r#"{"challenges": [
+        {"id": 1, "schedule": "permanent", "xml": "<StepReward Count=\"5\">"},
+        {"id": 2, "schedule": "daily", "xml": ""},
+        {"id": 3, "schedule": "daily", "xml": ""},
+        {"id": 4, "schedule": "daily", "xml": ""},
+        {"id": 5, "schedule": "weekly", "xml": ""},
+        {"id": 6, "schedule": "weekly", "xml": ""},
+        {"id": 7, "schedule": {"community": {"start": "2026-10-01 00:00:00", "end": "2026-11-01 00:00:00"}}, "xml": ""}
+    ]}"#;
+
+    /// Returns the seconds since the unix epoch of a UTC date and time.
+    fn timestamp(year: u64, month: u64, day: u64, hour: u64) -> u64 {
+        (0..u64::MAX / DAY)
+            .map(|d| d * DAY)
+            .find(|&secs| DateTime::from_unix_timestamp(secs) >= DateTime::new(year, month, day, 0, 0, 0))
+            .unwrap()
+            + hour * 60 * 60
+    }
+
+    #[test]
+    fn rotation() {
+        let catalogue = catalogue(ROTATION);
+        assert_eq!(catalogue.challenges[0].target(), 5);
+        assert_eq!(catalogue.challenges[1].target(), 1);
+
+        // monday 2026-10-19, before the daily reset at 9:00
+        let active = catalogue.active(timestamp(2026, 10, 19, 8));
+        let ids = active.iter().map(|c| c.definition.id).collect::<Vec<_>>();
+        assert_eq!(ids.len(), 4);
+        assert_eq!(ids[0], 1);
+        assert_eq!(ids[1], 7);
+        let daily = &active[2];
+        assert_eq!(daily.period(), "2026-10-18 09:00:00");
+        assert_eq!(daily.end_time.to_string(), "2026-10-19 09:00:00");
+        let weekly = &active[3];
+        assert_eq!(weekly.period(), "2026-10-17 09:00:00");
+        assert_eq!(weekly.end_time.to_string(), "2026-10-24 09:00:00");
+
+        // the daily challenge rotates after the reset, the weekly one doesn't
+        let after_reset = catalogue.active(timestamp(2026, 10, 19, 10));
+        assert_eq!(after_reset[2].period(), "2026-10-19 09:00:00");
+        assert_ne!(after_reset[2].definition.id, daily.definition.id);
+        assert_eq!(after_reset[3].definition.id, weekly.definition.id);
+        let next_week = catalogue.active(timestamp(2026, 10, 24, 10));
+        assert_eq!(next_week[3].period(), "2026-10-24 09:00:00");
+        assert_ne!(next_week[3].definition.id, weekly.definition.id);
+
+        // the community challenge is over
+        let ids = catalogue.active(timestamp(2026, 11, 1, 0)).iter().map(|c| c.definition.id).collect::<Vec<_>>();
+        assert!(!ids.contains(&7));
+    }
+
+    #[test]
+    fn completion() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let path = std::env::temp_dir().join(format!("challenges_{}.json", std::process::id()));
+        std::fs::write(&path, ROTATION).unwrap();
+        let prot = OverlordChallengeProtocol {
+            storage: Arc::clone(&storage),
+            catalogue: DataFile::new(&path),
+            strings: Strings::default(),
+        };
+        let logger = Logger::root(slog::Discard, slog::o!());
+        let now = timestamp(2026, 10, 19, 10);
+        let catalogue = catalogue(ROTATION);
+        let daily = catalogue.active(now).into_iter().find(|c| matches!(c.definition.schedule, Schedule::Daily)).unwrap();
+
+        let is_complete = |id| prot.challenges(&logger, 1000, now).unwrap().into_iter().find(|c| c.id == id).unwrap().is_complete;
+        assert!(!is_complete(daily.definition.id));
+        // completed in a previous occurrence
+        assert!(test_util::block_on(storage.update_challenge_progress_async(1000, daily.definition.id, "2026-10-16 09:00:00", 1, 1)).unwrap());
+        assert!(!is_complete(daily.definition.id));
+        assert!(test_util::block_on(storage.update_challenge_progress_async(1000, daily.definition.id, &daily.period(), 1, 1)).unwrap());
+        assert!(is_complete(daily.definition.id));
+
+        assert!(!test_util::block_on(storage.update_challenge_progress_async(1000, 1, &DateTime(0).to_string(), 4, 5)).unwrap());
+        assert!(!is_complete(1));
+        let progress = test_util::block_on(storage.list_challenge_progress_async(1000)).unwrap();
+        assert_eq!(progress.len(), 3);
+        std::fs::remove_file(path).unwrap();
+    }
 }
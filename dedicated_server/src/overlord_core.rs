@@ -1,17 +1,110 @@
 //! Implements the `OverlordCoreProtocol` for handling core server functionalities
 //! and configuration requests.
+//!
+//! The client configuration is read from `data/overlord_core.json`, which is reloaded when it changes. Each secure
+//! server can override values with `overlord_core.<name>` entries in its `settings`, e.g.
+//! `"overlord_core.COMMUNITYEVENT_DOUBLEXP" = "1"`.
 
 use quazal::prudp::ClientRegistry;
 use quazal::rmc::basic::ToStream;
 use quazal::rmc::types::Variant;
 use quazal::rmc::Protocol;
 use quazal::Context;
+use serde::Deserialize;
+use slog::Logger;
 
+use crate::data_file::DataFile;
 use crate::login_required;
 
+/// The configuration the server was shipped with, used if the data file is missing or invalid.
+const DEFAULT_CONFIG: &str = include_str!("../../data/overlord_core.json");
+/// Prefix of the settings overriding configuration values.
+const OVERRIDE_PREFIX: &str = "overlord_core.";
+
+#[derive(Debug, Clone, Deserialize)]
+struct ConfigEntry {
+    name: String,
+    value: ConfigValue,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum ConfigValue {
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl ConfigValue {
+    /// Parses an override, as the same type as the value it replaces if there's one.
+    fn parse(value: &str, like: Option<&ConfigValue>) -> Option<Self> {
+        match like {
+            Some(Self::Int(_)) => value.parse().ok().map(Self::Int),
+            Some(Self::Float(_)) => value.parse().ok().map(Self::Float),
+            Some(Self::String(_)) => Some(Self::String(value.to_owned())),
+            None => Some(
+                value
+                    .parse()
+                    .map(Self::Int)
+                    .or_else(|_| value.parse().map(Self::Float))
+                    .unwrap_or_else(|_| Self::String(value.to_owned())),
+            ),
+        }
+    }
+}
+
+impl From<ConfigValue> for Variant {
+    fn from(value: ConfigValue) -> Self {
+        match value {
+            ConfigValue::Int(v) => Variant::I64(v),
+            ConfigValue::Float(v) => Variant::F64(v),
+            ConfigValue::String(v) => Variant::String(v),
+        }
+    }
+}
+
 #[allow(clippy::module_name_repetitions)]
 /// Implements the `Protocol` trait for the Overlord Core protocol.
-pub struct OverlordCoreProtocol;
+pub struct OverlordCoreProtocol {
+    file: DataFile<Vec<ConfigEntry>>,
+}
+
+impl OverlordCoreProtocol {
+    /// Returns the configuration from the data file, or the default one if the file is missing or invalid.
+    fn entries(&self, logger: &Logger) -> Vec<ConfigEntry> {
+        match self.file.load() {
+            Ok(Some(entries)) => entries.to_vec(),
+            Ok(None) => serde_json::from_str(DEFAULT_CONFIG).expect("invalid default config"),
+            Err(e) => {
+                error!(logger, "Invalid config file {}: {e}", self.file.path().display());
+                serde_json::from_str(DEFAULT_CONFIG).expect("invalid default config")
+            }
+        }
+    }
+
+    /// Returns the configuration with the overrides of a server applied. New values are added in alphabetical order.
+    fn config(&self, logger: &Logger, ctx: &Context) -> Vec<(String, Variant)> {
+        let mut entries = self.entries(logger);
+        let mut overrides = ctx
+            .settings
+            .iter()
+            .filter_map(|(key, value)| Some((key.strip_prefix(OVERRIDE_PREFIX)?, value)))
+            .collect::<Vec<_>>();
+        overrides.sort_unstable();
+        for (name, value) in overrides {
+            let existing = entries.iter_mut().find(|e| e.name == name);
+            let Some(value) = ConfigValue::parse(value, existing.as_ref().map(|e| &e.value)) else {
+                warn!(logger, "Ignoring invalid override of {name}: {value}");
+                continue;
+            };
+            match existing {
+                Some(entry) => entry.value = value,
+                None => entries.push(ConfigEntry { name: name.to_owned(), value }),
+            }
+        }
+        entries.into_iter().map(|e| (e.name, e.value.into())).collect()
+    }
+}
 
 impl<T> Protocol<T> for OverlordCoreProtocol {
     /// Returns the unique ID of this protocol.
@@ -34,83 +127,20 @@ impl<T> Protocol<T> for OverlordCoreProtocol {
     /// This function dispatches requests based on their method ID.
     fn handle(
         &self,
-        _logger: &slog::Logger,
-        _ctx: &Context,
+        logger: &slog::Logger,
+        ctx: &Context,
         ci: &mut quazal::ClientInfo<T>,
         request: &quazal::rmc::Request,
         _client_registry: &ClientRegistry<T>,
         _socket: &std::net::UdpSocket,
     ) -> std::result::Result<Vec<u8>, quazal::rmc::Error> {
-        #[allow(clippy::enum_glob_use)]
-        use Variant::*;
-
         // Ensure the client is logged in before processing the request.
         login_required(&*ci)?;
         // Only method ID 1 (fetch_config) is supported by this protocol.
         if request.method_id != 1 {
             return Err(quazal::rmc::Error::UnknownMethod);
         }
-
-        // Hardcoded configuration values returned by the server.
-        let cfg: Vec<(std::string::String, Variant)> = vec![
-            ("VER_SERVER_STAGE".to_owned(), I64(10)),
-            ("2593515025".to_owned(), I64(1)),
-            ("2626349757".to_owned(), I64(1)),
-            ("NC_CONNECTION_TICKET_TIMEOUT".to_owned(), F64(10.0)),
-            ("NC_CONNECTION_HANDSHAKING_TIMEOUT".to_owned(), F64(5.0)),
-            ("314486871".to_owned(), I64(1)),
-            ("3759634546".to_owned(), I64(1)),
-            ("NC_MAIN_PORT_RANGE".to_owned(), I64(32)),
-            ("2449304206".to_owned(), I64(1)),
-            ("OVERLORD_VERSION".to_owned(), String("0.8.0.0".into())),
-            ("2685611256".to_owned(), I64(1)),
-            ("3376331533".to_owned(), I64(1)),
-            ("VER_SERVER_CODE".to_owned(), I64(3007)),
-            ("FILESERVICE_UNLOCKS_UPLOAD_ENABLED".to_owned(), I64(1)),
-            ("2739184075".to_owned(), I64(1)),
-            ("2942435614".to_owned(), I64(1)),
-            ("SN_FRIENDCHALLENGES_MAX_READ_INTERVAL".to_owned(), F64(900.0)),
-            ("NC_CONNECTION_JOIN_TIMEOUT".to_owned(), F64(15.0)),
-            ("1027449109".to_owned(), I64(1)),
-            ("4175756708".to_owned(), I64(1)),
-            ("NC_CONNECTION_INACTIVITY_THRESHOLD".to_owned(), F64(8.0)),
-            ("FILESERVICE_ADMIN_RDVID".to_owned(), I64(1119)),
-            ("SN_WEEKLYCHALLENGES_ENABLE".to_owned(), I64(1)),
-            ("1597953054".to_owned(), I64(1)),
-            ("2156388390".to_owned(), I64(1)),
-            ("3785106560".to_owned(), I64(1)),
-            ("STATS_WRITE_INTERVAL".to_owned(), F64(1.0)),
-            ("3835530207".to_owned(), I64(1)),
-            ("1492891464".to_owned(), I64(1)),
-            ("SN_DAILYCHALLENGES_ENABLE".to_owned(), I64(1)),
-            ("2505766166".to_owned(), I64(1)),
-            ("11866509".to_owned(), I64(1)),
-            ("SN_FRIENDCHALLENGES_ENABLE".to_owned(), I64(1)),
-            ("FILESERVICE_UNLOCKS_UPLOAD_INTERVAL".to_owned(), F64(3600.0)),
-            ("2524360986".to_owned(), I64(1)),
-            ("721797971".to_owned(), I64(1)),
-            ("1525666223".to_owned(), I64(1)),
-            ("COMMUNITYEVENT_DOUBLECASH".to_owned(), I64(0)),
-            ("UPLAY_MAX_RANK_LIMITED_MODE".to_owned(), I64(5)),
-            ("SN_GONEDARKCHALLENGES_ENABLE".to_owned(), I64(1)),
-            ("_OSDK_VERSION".to_owned(), String("1.4.16.32918".into())),
-            ("NC_MAIN_PORT".to_owned(), I64(13000)),
-            ("COMMUNITYEVENT_DOUBLEXP".to_owned(), I64(0)),
-            ("3804368594".to_owned(), I64(1)),
-            ("NC_CONNECTION_CLOSING_TIMEOUT".to_owned(), F64(2.0)),
-            ("NC_CONNECTION_ESTABLISHED_TIMEOUT".to_owned(), F64(10.0)),
-        ];
-
-                /*
-        // Alternative implementation using a HashMap for configuration values.
-        // This could be used for more dynamic configuration loading.
-         let cfg: std::collections::HashMap<std::string::String, Variant> = [
-            ...
-        ].iter()
-        .cloned()
-        .collect();
-        */
-        Ok(cfg.to_bytes())
+        Ok(self.config(logger, ctx).to_bytes())
     }
 
     /// Returns the name of the method corresponding to the given `method_id`.
@@ -130,47 +160,49 @@ impl<T> Protocol<T> for OverlordCoreProtocol {
 /// This function is typically used to register the core protocol
 /// with the server's protocol dispatcher.
 pub fn new_protocol<T: 'static>() -> Box<dyn Protocol<T>> {
-    Box::new(OverlordCoreProtocol)
+    Box::new(OverlordCoreProtocol {
+        file: DataFile::new("data/overlord_core.json"),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    /// Tests the `handle` method for method ID 1 (fetch_config).
-    ///
-    /// Verifies that the returned configuration matches the expected binary data.
-    fn test_method1() {
+    /// Calls `fetch_config` with the configuration file and the settings of the server.
+    fn fetch_config(path: &str, settings: &[(&str, &str)]) -> Vec<u8> {
         let logger = slog::Logger::root(slog::Discard, slog::o!());
-        let ctx = quazal::Context::default();
+        let ctx = quazal::Context {
+            settings: settings.iter().map(|(k, v)| ((*k).to_owned(), (*v).to_owned())).collect(),
+            ..Default::default()
+        };
         let mut ci = quazal::ClientInfo::<()>::new("127.0.0.1:2".parse().unwrap());
         ci.user_id = Some(1);
-        let prot = OverlordCoreProtocol;
+        let prot = OverlordCoreProtocol { file: DataFile::new(path) };
         let request = quazal::rmc::Request {
             protocol_id: Protocol::<()>::id(&prot),
             call_id: 1,
             method_id: 1,
             parameters: vec![],
         };
-        let resp = prot
-            .handle(
-                &logger,
-                &ctx,
-                &mut ci,
-                &request,
-                &ClientRegistry::default(),
-                &std::net::UdpSocket::bind("127.0.0.1:12345").unwrap(),
-            )
-            .unwrap();
-
-        let expected = include_bytes!("../../testdata/overlord_core_config.bin");
+        prot.handle(
+            &logger,
+            &ctx,
+            &mut ci,
+            &request,
+            &ClientRegistry::default(),
+            &std::net::UdpSocket::bind("127.0.0.1:0").unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn assert_bytes(expected: &[u8], resp: &[u8]) {
         assert_eq!(expected.len(), resp.len());
         assert_eq!(
-            expected.as_slice(),
-            resp.as_slice(),
+            expected,
+            resp,
             "{}",
-            diff::slice(expected.as_ref(), resp.as_slice())
+            diff::slice(expected, resp)
                 .into_iter()
                 .map(|diff| match diff {
                     diff::Result::Left(l) => format!("-{l:02x}"),
@@ -181,4 +213,47 @@ mod tests {
                 .join("\n")
         );
     }
+
+    const EXPECTED: &[u8] = include_bytes!("../../testdata/overlord_core_config.bin");
+
+    #[test]
+    /// Tests the `handle` method for method ID 1 (`fetch_config`).
+    ///
+    /// Verifies that the returned configuration matches the expected binary data.
+    fn test_method1() {
+        let resp = fetch_config(concat!(env!("CARGO_MANIFEST_DIR"), "/../data/overlord_core.json"), &[]);
+        assert_bytes(EXPECTED, &resp);
+    }
+
+    #[test]
+    fn missing_file() {
+        let resp = fetch_config("missing/overlord_core.json", &[]);
+        assert_bytes(EXPECTED, &resp);
+    }
+
+    #[test]
+    fn overrides() {
+        let settings = [
+            ("overlord_core.NC_MAIN_PORT", "14000"),
+            ("overlord_core.NC_CONNECTION_JOIN_TIMEOUT", "20"),
+            ("overlord_core.NEW_VALUE", "1.5"),
+            // not an integer like the value it overrides
+            ("overlord_core.VER_SERVER_CODE", "abc"),
+            ("storage_host", "127.0.0.1:8000"),
+        ];
+        let resp = fetch_config("missing/overlord_core.json", &settings);
+        let mut expected = EXPECTED.to_vec();
+        expected[0] += 1;
+        // NC_MAIN_PORT
+        let port = b"\x0d\x00NC_MAIN_PORT\x00\x01";
+        let offset = expected.windows(port.len()).position(|w| w == port).unwrap() + port.len();
+        expected[offset..offset + 8].copy_from_slice(&14000_i64.to_le_bytes());
+        // NC_CONNECTION_JOIN_TIMEOUT
+        let timeout = b"\x1b\x00NC_CONNECTION_JOIN_TIMEOUT\x00\x02";
+        let offset = expected.windows(timeout.len()).position(|w| w == timeout).unwrap() + timeout.len();
+        expected[offset..offset + 8].copy_from_slice(&20_f64.to_le_bytes());
+        expected.extend_from_slice(b"\x0a\x00NEW_VALUE\x00\x02");
+        expected.extend_from_slice(&1.5_f64.to_le_bytes());
+        assert_bytes(&expected, &resp);
+    }
 }
@@ -1,4 +1,12 @@
 //! Implements the `OverlordNewsProtocol` for handling news-related requests.
+//!
+//! The news are read from `data/news.json`, which is reloaded whenever it changes. Players get the published,
+//! unexpired items for their locale, or a localized welcome item without a news file. The admin api publishes and
+//! retracts items by rewriting the file.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
 
 use quazal::prudp::ClientRegistry;
 use quazal::rmc::basic::ToStream;
@@ -6,28 +14,204 @@ use quazal::rmc::types::DateTime;
 use quazal::rmc::Protocol;
 use quazal::Context;
 use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use slog::Logger;
 
+use crate::data_file::DataFile;
 use crate::login_required;
+use crate::storage::Storage;
+use crate::strings::Strings;
 
 /// Represents a single news item.
-#[derive(Debug, ToStream, FromStream, Default, Deserialize)]
+#[derive(Debug, ToStream, FromStream, Default)]
 struct NewsItem {
-    maybe_id: u32, // Likely a unique identifier for the news item.
-    unk2: u32,     // Unknown purpose.
-    unk3: u32,     // Unknown purpose.
-    unk4: u32,     // Unknown purpose.
-    unk5: String,  // Unknown purpose, possibly a category or source.
-    unk6: DateTime,// Unknown purpose, possibly creation or start date.
-    unk7: DateTime,// Unknown purpose, possibly an end date or last modified date.
+    id: u32,
+    unk2: u32, // Unknown purpose.
+    unk3: u32, // Unknown purpose.
+    unk4: u32, // Unknown purpose.
+    source: String,
+    publish_time: DateTime,
+    unk7: DateTime,            // Unknown purpose, possibly an end date or last modified date.
     expiration_time: DateTime, // The time when this news item expires.
-    title: String,       // The title of the news item.
-    link: String,        // A URL associated with the news item.
-    description: String, // The main content or description of the news item.
+    title: String,             // The title of the news item.
+    link: String,              // A URL associated with the news item.
+    description: String,       // The main content or description of the news item.
+}
+
+/// A news item as stored in the news file.
+///
+/// The aliases read news files written for older versions, which stored the fields of `NewsItem` as they are.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NewsEntry {
+    #[serde(alias = "maybe_id")]
+    pub id: u32,
+    /// A locale like `en-US` or only a language like `en`, empty for all locales.
+    pub locale: String,
+    pub title: String,
+    pub link: String,
+    pub description: String,
+    #[serde(alias = "unk5")]
+    pub source: String,
+    /// Empty to publish right away.
+    #[serde(alias = "unk6", deserialize_with = "deserialize_time")]
+    pub publish_at: String,
+    /// Empty to never expire.
+    #[serde(alias = "expiration_time", deserialize_with = "deserialize_time")]
+    pub expires_at: String,
+    pub unk2: u32,
+    pub unk3: u32,
+    pub unk4: u32,
+}
+
+/// Reads a time written as "YYYY-MM-DD HH:MM:SS", or as the raw `DateTime` of older news files with 0 for none.
+fn deserialize_time<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Time {
+        Text(String),
+        Raw(u64),
+    }
+    Ok(match Time::deserialize(deserializer)? {
+        Time::Text(text) => text,
+        Time::Raw(0) => String::new(),
+        Time::Raw(raw) => DateTime(raw).to_string(),
+    })
+}
+
+impl Default for NewsEntry {
+    fn default() -> Self {
+        // the unknown values are the ones from news sent by the original server
+        Self {
+            id: 0,
+            locale: String::new(),
+            title: String::new(),
+            link: String::new(),
+            description: String::new(),
+            source: String::from("Quazal Rendez-Vous"),
+            publish_at: String::new(),
+            expires_at: String::new(),
+            unk2: 9,
+            unk3: 2,
+            unk4: 2,
+        }
+    }
+}
+
+impl NewsEntry {
+    /// Checks if the item is published and not expired yet.
+    fn is_active(&self, now: DateTime) -> bool {
+        let published = self.publish_at.is_empty() || self.publish_at.parse::<DateTime>().is_ok_and(|t| t <= now);
+        let expired = !self.expires_at.is_empty() && !self.expires_at.parse::<DateTime>().is_ok_and(|t| t > now);
+        published && !expired
+    }
+
+    /// Checks if the item is meant for a locale, either exactly or by its language.
+    fn is_for_locale(&self, locale: Option<&str>) -> bool {
+        if self.locale.is_empty() {
+            return true;
+        }
+        locale
+            .is_some_and(|locale| locale.eq_ignore_ascii_case(&self.locale) || locale.split(['-', '_']).next().is_some_and(|language| language.eq_ignore_ascii_case(&self.locale)))
+    }
+
+    fn to_item(&self) -> NewsItem {
+        NewsItem {
+            id: self.id,
+            unk2: self.unk2,
+            unk3: self.unk3,
+            unk4: self.unk4,
+            source: self.source.clone(),
+            publish_time: self.publish_at.parse().unwrap_or_default(),
+            unk7: DateTime::default(),
+            expiration_time: self.expires_at.parse().unwrap_or_default(),
+            title: self.title.clone(),
+            link: self.link.clone(),
+            description: self.description.clone(),
+        }
+    }
+}
+
+/// The news file, reloaded when it's modified.
+pub struct NewsFeed {
+    file: DataFile<Vec<NewsEntry>>,
+    /// Serializes the changes of the file.
+    changes: Mutex<()>,
+}
+
+impl NewsFeed {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            file: DataFile::new(path),
+            changes: Mutex::new(()),
+        }
+    }
+
+    fn read(&self) -> eyre::Result<Vec<NewsEntry>> {
+        match std::fs::File::open(self.file.path()) {
+            Ok(file) => Ok(serde_json::from_reader(std::io::BufReader::new(file))?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Returns all news items, `None` if the file is missing or invalid.
+    pub fn entries(&self, logger: &Logger) -> Option<Vec<NewsEntry>> {
+        match self.file.load() {
+            Ok(entries) => entries.map(|entries| entries.to_vec()),
+            Err(e) => {
+                error!(logger, "Invalid news file {}: {e}", self.file.path().display());
+                None
+            }
+        }
+    }
+
+    /// Adds a news item, or replaces the one with the same id. An id of 0 assigns a new one.
+    ///
+    /// Returns the id of the item.
+    pub fn publish(&self, mut entry: NewsEntry) -> eyre::Result<u32> {
+        let _changes = self.changes.lock().unwrap();
+        let mut entries = self.read()?;
+        if entry.id == 0 {
+            entry.id = entries.iter().map(|e| e.id).max().unwrap_or(0) + 1;
+        }
+        let id = entry.id;
+        match entries.iter_mut().find(|e| e.id == id) {
+            Some(existing) => *existing = entry,
+            None => entries.push(entry),
+        }
+        self.write(&entries)?;
+        Ok(id)
+    }
+
+    /// Removes a news item. Returns whether it existed.
+    pub fn retract(&self, id: u32) -> eyre::Result<bool> {
+        let _changes = self.changes.lock().unwrap();
+        let mut entries = self.read()?;
+        let count = entries.len();
+        entries.retain(|e| e.id != id);
+        if entries.len() == count {
+            return Ok(false);
+        }
+        self.write(&entries)?;
+        Ok(true)
+    }
+
+    fn write(&self, entries: &[NewsEntry]) -> eyre::Result<()> {
+        let data = serde_json::to_string_pretty(entries)?;
+        std::fs::write(self.file.path(), data)?;
+        Ok(())
+    }
 }
 
 #[allow(clippy::module_name_repetitions)]
 /// Implements the `Protocol` trait for handling Overlord News requests.
-pub struct OverlordNewsProtocol;
+pub struct OverlordNewsProtocol {
+    storage: Arc<Storage>,
+    feed: NewsFeed,
+    strings: Strings,
+}
 
 impl<T> Protocol<T> for OverlordNewsProtocol {
     /// Returns the unique ID of this protocol.
@@ -57,28 +241,39 @@ impl<T> Protocol<T> for OverlordNewsProtocol {
         _client_registry: &ClientRegistry<T>,
         _socket: &std::net::UdpSocket,
     ) -> std::result::Result<Vec<u8>, quazal::rmc::Error> {
-        login_required(&*ci)?;
+        let user_id = login_required(&*ci)?;
         match request.method_id {
             1 => {
-                let news: Vec<NewsItem> = std::fs::File::open("data/news.json")
-                    .ok()
-                    .map(serde_json::from_reader)
-                    .and_then(Result::ok)
-                    .unwrap_or(vec![NewsItem {
-                        maybe_id: 19_5389,
+                let locale = rmc_err!(self.storage.find_user_locale(user_id), logger, "error finding locale")?;
+                let news: Vec<NewsItem> = if let Some(entries) = self.feed.entries(logger) {
+                    let now = DateTime::now();
+                    entries
+                        .iter()
+                        .filter(|e| e.is_active(now) && e.is_for_locale(locale.as_deref()))
+                        .map(NewsEntry::to_item)
+                        .collect()
+                } else {
+                    vec![NewsItem {
+                        id: 19_5389,
                         unk2: 9,
                         unk3: 2,
                         unk4: 2,
-                        title: String::from("WELCOME BACK!"),
-                        description: String::from("5th Echelon is here!"),
+                        title: self.strings.get(locale.as_deref(), "news.welcome.title", &[]),
+                        description: self.strings.get(locale.as_deref(), "news.welcome.description", &[]),
                         link: String::from("https://github.com/unixoide/5th-echelon"),
-                        unk5: String::from("Quazal Rendez-Vous"),
+                        source: String::from("Quazal Rendez-Vous"),
                         ..Default::default()
-                    }]);
+                    }]
+                };
                 Ok(news.to_bytes())
             }
             2 => {
-                error!(logger, "not implemented yet");
+                error!(
+                    logger,
+                    "not implemented yet";
+                    "method_id" => request.method_id,
+                    "parameters" => format!("{:02x?}", request.parameters)
+                );
                 Err(quazal::rmc::Error::UnknownMethod)
             }
             _ => Err(quazal::rmc::Error::UnknownMethod),
@@ -101,8 +296,12 @@ impl<T> Protocol<T> for OverlordNewsProtocol {
 ///
 /// This function is typically used to register the news protocol
 /// with the server's protocol dispatcher.
-pub fn new_protocol<T: 'static>() -> Box<dyn Protocol<T>> {
-    Box::new(OverlordNewsProtocol)
+pub fn new_protocol<T: 'static>(storage: Arc<Storage>) -> Box<dyn Protocol<T>> {
+    Box::new(OverlordNewsProtocol {
+        storage,
+        feed: NewsFeed::new("data/news.json"),
+        strings: Strings::default(),
+    })
 }
 
 #[cfg(test)]
@@ -110,6 +309,7 @@ mod tests {
     use quazal::rmc::basic::FromStream;
 
     use super::*;
+    use crate::test_util;
     #[test]
     /// Tests the parsing of a sample news item byte array.
     fn parse_sample() {
@@ -172,4 +372,68 @@ mod tests {
         let parsed: Vec<NewsItem> = FromStream::from_bytes(data).unwrap();
         println!("{parsed:#?}");
     }
+
+    /// Requests the news as user 1000 (`Foo`) and returns their ids.
+    fn news_ids(prot: &OverlordNewsProtocol) -> Vec<u32> {
+        let resp = test_util::call(prot, Some(1000), 1, &[]).unwrap();
+        let news: Vec<NewsItem> = FromStream::from_bytes(&resp).unwrap();
+        news.into_iter().map(|n| n.id).collect()
+    }
+
+    #[test]
+    fn old_news_file() {
+        let entries: Vec<NewsEntry> = serde_json::from_str(&format!(
+            r#"[{{
+                "maybe_id": 195389, "unk2": 9, "unk3": 1, "unk4": 2, "title": "Hello", "description": "5th Echelon is here!",
+                "link": "https://github.com/unixoide/5th-echelon", "unk5": "Quazal Rendez-Vous", "unk6": 0, "unk7": 0,
+                "expiration_time": {}
+            }}]"#,
+            DateTime::new(2099, 1, 1, 0, 0, 0).0
+        ))
+        .unwrap();
+        let entry = &entries[0];
+        assert_eq!((entry.id, entry.unk3, entry.source.as_str()), (195_389, 1, "Quazal Rendez-Vous"));
+        assert_eq!(entry.publish_at, "");
+        assert_eq!(entry.expires_at, "2099-01-01 00:00:00");
+        assert!(entry.is_active(DateTime::now()));
+    }
+
+    #[test]
+    fn publish_and_retract() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        storage.create_user_session(1000, b"key").unwrap();
+        storage.update_session_locale(1000, "de-DE").unwrap();
+        let path = std::env::temp_dir().join(format!("news_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let prot = OverlordNewsProtocol {
+            storage,
+            feed: NewsFeed::new(&path),
+            strings: Strings::default(),
+        };
+        // without a file the welcome item is sent
+        assert_eq!(news_ids(&prot), [19_5389]);
+
+        let publish = |locale: &str, publish_at: &str, expires_at: &str| {
+            prot.feed
+                .publish(NewsEntry {
+                    locale: locale.into(),
+                    publish_at: publish_at.into(),
+                    expires_at: expires_at.into(),
+                    ..NewsEntry::default()
+                })
+                .unwrap()
+        };
+        assert_eq!(publish("", "", ""), 1);
+        assert_eq!(publish("de", "2000-01-01 00:00:00", "9999-01-01 00:00:00"), 2);
+        assert_eq!(publish("fr-FR", "", ""), 3);
+        assert_eq!(publish("", "", "2000-01-01 00:00:00"), 4);
+        assert_eq!(publish("", "9999-01-01 00:00:00", ""), 5);
+        assert_eq!(news_ids(&prot), [1, 2]);
+
+        assert!(prot.feed.retract(1).unwrap());
+        assert!(!prot.feed.retract(1).unwrap());
+        assert_eq!(news_ids(&prot), [2]);
+        assert_eq!(prot.feed.entries(&Logger::root(slog::Discard, slog::o!())).unwrap().len(), 4);
+        std::fs::remove_file(path).unwrap();
+    }
 }
@@ -1,7 +1,13 @@
 //! Implements the `PlayerStatsProtocolServer` for handling player statistics requests.
+//!
+//! The integer stats players write are stored, so that matchmaking can rate the skill of players and that they can be
+//! compared with the stats of their friends.
+
+use std::sync::Arc;
 
 use quazal::prudp::ClientRegistry;
 use quazal::rmc::types::PropertyVariant;
+use quazal::rmc::types::Variant;
 use quazal::rmc::Error;
 use quazal::rmc::Protocol;
 use quazal::ClientInfo;
@@ -17,9 +23,12 @@ use crate::protocols::player_stats_service::player_stats_protocol::ReadStatsByPl
 use crate::protocols::player_stats_service::player_stats_protocol::ReadStatsByPlayersResponse;
 use crate::protocols::player_stats_service::player_stats_protocol::WriteStatsRequest;
 use crate::protocols::player_stats_service::player_stats_protocol::WriteStatsResponse;
+use crate::storage::Storage;
 
 /// Implementation of the `PlayerStatsProtocolServerTrait` for managing player statistics.
-struct PlayerStatsProtocolServerImpl;
+struct PlayerStatsProtocolServerImpl {
+    storage: Arc<Storage>,
+}
 
 impl<T> PlayerStatsProtocolServerTrait<T> for PlayerStatsProtocolServerImpl {
     /// Handles the `ReadStatsByPlayers` request, returning hardcoded player statistics.
@@ -127,20 +136,40 @@ impl<T> PlayerStatsProtocolServerTrait<T> for PlayerStatsProtocolServerImpl {
         })
     }
 
-    /// Handles the `WriteStats` request.
+    /// Handles the `WriteStats` request, storing the integer stats of the user in each of the given contexts.
     ///
     /// This function requires the client to be logged in.
     fn write_stats(
         &self,
-        _logger: &Logger,
+        logger: &Logger,
         _ctx: &Context,
         ci: &mut ClientInfo<T>,
-        _request: WriteStatsRequest,
+        request: WriteStatsRequest,
         _client_registry: &ClientRegistry<T>,
         _socket: &std::net::UdpSocket,
     ) -> Result<WriteStatsResponse, Error> {
-        // Ensure the client is logged in.
-        login_required(&*ci)?;
+        let user_id = login_required(&*ci)?;
+        for update in request.player_stat_updates.0 {
+            let stats = update
+                .stats
+                .iter()
+                .filter_map(|stat| match stat.value {
+                    Variant::I64(value) => Some((stat.id, value)),
+                    Variant::U64(value) => Some((stat.id, i64::try_from(value).unwrap_or(i64::MAX))),
+                    _ => {
+                        debug!(logger, "Ignoring stat {} of board {}: {:?}", stat.id, update.board_id, stat.value);
+                        None
+                    }
+                })
+                .collect::<Vec<_>>();
+            for context_id in update.context_ids.iter() {
+                rmc_err!(
+                    self.storage.store_player_stats(user_id, update.board_id, *context_id, &stats),
+                    logger,
+                    "error storing stats"
+                )?;
+            }
+        }
         Ok(WriteStatsResponse)
     }
 }
@@ -149,6 +178,6 @@ impl<T> PlayerStatsProtocolServerTrait<T> for PlayerStatsProtocolServerImpl {
 ///
 /// This function is typically used to register the player stats protocol
 /// with the server's protocol dispatcher.
-pub fn new_protocol<T: 'static>() -> Box<dyn Protocol<T>> {
-    Box::new(PlayerStatsProtocolServer::new(PlayerStatsProtocolServerImpl))
+pub fn new_protocol<T: 'static>(storage: Arc<Storage>) -> Box<dyn Protocol<T>> {
+    Box::new(PlayerStatsProtocolServer::new(PlayerStatsProtocolServerImpl { storage }))
 }
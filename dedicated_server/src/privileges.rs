@@ -1,65 +1,196 @@
 //! Implements the `PrivilegesProtocolServer` for handling user privilege requests.
+//!
+//! Every user has the default privileges from the config, except for those revoked with the admin api,
+//! plus the privileges granted to them.
 
-use std::collections::HashMap;
+use std::sync::Arc;
 
 use quazal::prudp::ClientRegistry;
+use quazal::rmc::types::QList;
 use quazal::rmc::Error;
 use quazal::rmc::Protocol;
 use quazal::ClientInfo;
 use quazal::Context;
 use sc_bl_protocols::privileges_service::types::Privilege;
+use sc_bl_protocols::privileges_service::types::PrivilegeEx;
 use slog::Logger;
 
 use crate::login_required;
+use crate::protocols::privileges_service::privileges_protocol::GetPrivilegeRemainDurationRequest;
+use crate::protocols::privileges_service::privileges_protocol::GetPrivilegeRemainDurationResponse;
+use crate::protocols::privileges_service::privileges_protocol::GetPrivilegesExRequest;
+use crate::protocols::privileges_service::privileges_protocol::GetPrivilegesExResponse;
 use crate::protocols::privileges_service::privileges_protocol::GetPrivilegesRequest;
 use crate::protocols::privileges_service::privileges_protocol::GetPrivilegesResponse;
 use crate::protocols::privileges_service::privileges_protocol::PrivilegesProtocolServer;
 use crate::protocols::privileges_service::privileges_protocol::PrivilegesProtocolServerTrait;
+use crate::storage::Storage;
+use crate::storage::UserPrivilege;
+
+/// Duration reported for privileges that never expire.
+const UNLIMITED: i32 = -1;
 
 /// Implementation of the `PrivilegesProtocolServerTrait` for managing user privileges.
-struct PrivilegesProtocolServerImpl;
+struct PrivilegesProtocolServerImpl {
+    storage: Arc<Storage>,
+}
+
+impl PrivilegesProtocolServerImpl {
+    /// Returns the privileges the user currently has.
+    fn privileges(&self, logger: &Logger, user_id: u32) -> Result<Vec<UserPrivilege>, Error> {
+        let privileges = rmc_err!(self.storage.list_user_privileges(user_id), logger, "error listing privileges")?;
+        Ok(privileges.into_iter().filter(UserPrivilege::is_granted).collect())
+    }
+}
+
+/// Returns the seconds until a privilege expires.
+fn duration(privilege: &UserPrivilege) -> i32 {
+    privilege.remaining_secs.map_or(UNLIMITED, |secs| i32::try_from(secs.max(0)).unwrap_or(i32::MAX))
+}
 
 impl<T> PrivilegesProtocolServerTrait<T> for PrivilegesProtocolServerImpl {
-    /// Handles the `GetPrivileges` request, returning a map of user privileges.
-    ///
-    /// This function requires the client to be logged in. Currently, it returns a hardcoded
-    /// "PlayOnline" privilege. The commented-out section shows how additional privileges
-    /// could be dynamically generated.
+    /// Handles the `GetPrivileges` request, returning a map of the user's privileges.
     fn get_privileges(
         &self,
-        _logger: &Logger,
+        logger: &Logger,
         _ctx: &Context,
         ci: &mut ClientInfo<T>,
         _request: GetPrivilegesRequest,
         _client_registry: &ClientRegistry<T>,
         _socket: &std::net::UdpSocket,
     ) -> Result<GetPrivilegesResponse, Error> {
-        // Ensure the client is logged in before retrieving privileges.
-        login_required(&*ci)?;
-        let privileges = HashMap::from([(
-            1,
-            Privilege {
-                id: 1,
-                description: "PlayOnline".into(),
-            },
-        )]);
-        // for id in 2000..2045 {
-        //     privileges.insert(
-        //         id,
-        //         Privilege {
-        //             id,
-        //             description: format!("Priv {id}"),
-        //         },
-        //     );
-        // }
+        let user_id = login_required(&*ci)?;
+        let privileges = self
+            .privileges(logger, user_id)?
+            .into_iter()
+            .map(|p| {
+                (
+                    p.id,
+                    Privilege {
+                        id: p.id,
+                        description: p.description,
+                    },
+                )
+            })
+            .collect();
         Ok(GetPrivilegesResponse { privileges })
     }
+
+    /// Handles the `GetPrivilegeRemainDuration` request.
+    ///
+    /// Returns the seconds until the privilege expires, -1 if it never does and 0 if the user doesn't have it.
+    fn get_privilege_remain_duration(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<T>,
+        request: GetPrivilegeRemainDurationRequest,
+        _client_registry: &ClientRegistry<T>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetPrivilegeRemainDurationResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let seconds = self.privileges(logger, user_id)?.iter().find(|p| p.id == request.privilege_id).map_or(0, duration);
+        Ok(GetPrivilegeRemainDurationResponse { seconds })
+    }
+
+    /// Handles the `GetPrivilegesEx` request, returning the user's privileges with their remaining duration.
+    fn get_privileges_ex(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<T>,
+        _request: GetPrivilegesExRequest,
+        _client_registry: &ClientRegistry<T>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetPrivilegesExResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let privileges_ex = self
+            .privileges(logger, user_id)?
+            .into_iter()
+            .map(|p| PrivilegeEx {
+                id: p.id,
+                duration: duration(&p),
+                description: p.description,
+            })
+            .collect::<QList<_>>();
+        Ok(GetPrivilegesExResponse { privileges_ex })
+    }
 }
 
 /// Creates a new boxed `PrivilegesProtocolServer` instance.
 ///
 /// This function is typically used to register the privileges protocol
 /// with the server's protocol dispatcher.
-pub fn new_protocol<T: 'static>() -> Box<dyn Protocol<T>> {
-    Box::new(PrivilegesProtocolServer::new(PrivilegesProtocolServerImpl))
+pub fn new_protocol<T: 'static>(storage: Arc<Storage>) -> Box<dyn Protocol<T>> {
+    Box::new(PrivilegesProtocolServer::new(PrivilegesProtocolServerImpl { storage }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::privileges_service::privileges_protocol::PrivilegesProtocolMethod;
+    use crate::test_util;
+
+    fn set_privilege(storage: &Storage, privilege_id: u32, granted: bool, expires_at: Option<&str>) {
+        test_util::block_on(storage.set_user_privilege_async(1000, privilege_id, "Tester", granted, expires_at)).unwrap();
+    }
+
+    /// `GetPrivileges` with locale `en-US`
+    const GET_PRIVILEGES: &[u8] = b"\x06\x00en-US\x00";
+
+    #[test]
+    fn default_privileges() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let prot = new_protocol::<()>(Arc::clone(&storage));
+        let resp = test_util::call(&*prot, Some(1000), PrivilegesProtocolMethod::GetPrivileges as u32, GET_PRIVILEGES).unwrap();
+        assert_eq!(resp, b"\x01\x00\x00\x00\x01\x00\x00\x00\x01\x00\x00\x00\x0b\x00PlayOnline\x00");
+    }
+
+    #[test]
+    fn revoked_privilege() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let prot = new_protocol::<()>(Arc::clone(&storage));
+        set_privilege(&storage, 1, false, None);
+        let resp = test_util::call(&*prot, Some(1000), PrivilegesProtocolMethod::GetPrivileges as u32, GET_PRIVILEGES).unwrap();
+        assert_eq!(resp, b"\x00\x00\x00\x00");
+
+        // expired revocations are ignored
+        set_privilege(&storage, 1, false, Some("2000-01-01 00:00:00"));
+        let resp = test_util::call(&*prot, Some(1000), PrivilegesProtocolMethod::GetPrivileges as u32, GET_PRIVILEGES).unwrap();
+        assert_eq!(resp, b"\x01\x00\x00\x00\x01\x00\x00\x00\x01\x00\x00\x00\x0b\x00PlayOnline\x00");
+    }
+
+    #[test]
+    fn granted_privilege() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let prot = new_protocol::<()>(Arc::clone(&storage));
+        set_privilege(&storage, 1, false, None);
+        set_privilege(&storage, 2000, true, None);
+        let resp = test_util::call(&*prot, Some(1000), PrivilegesProtocolMethod::GetPrivilegesEx as u32, GET_PRIVILEGES).unwrap();
+        assert_eq!(resp, b"\x01\x00\x00\x00\xd0\x07\x00\x00\x07\x00Tester\x00\xff\xff\xff\xff");
+
+        // privilege 2000
+        let resp = test_util::call(&*prot, Some(1000), PrivilegesProtocolMethod::GetPrivilegeRemainDuration as u32, b"\xd0\x07\x00\x00").unwrap();
+        assert_eq!(resp, b"\xff\xff\xff\xff");
+        // privilege 1
+        let resp = test_util::call(&*prot, Some(1000), PrivilegesProtocolMethod::GetPrivilegeRemainDuration as u32, b"\x01\x00\x00\x00").unwrap();
+        assert_eq!(resp, b"\x00\x00\x00\x00");
+    }
+
+    #[test]
+    fn temporary_privilege() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let prot = new_protocol::<()>(Arc::clone(&storage));
+        set_privilege(&storage, 1, true, Some("9999-12-31 23:59:59"));
+        let resp = test_util::call(&*prot, Some(1000), PrivilegesProtocolMethod::GetPrivilegeRemainDuration as u32, b"\x01\x00\x00\x00").unwrap();
+        // saturated, the expiration is further away than an i32 of seconds
+        assert_eq!(resp, b"\xff\xff\xff\x7f");
+    }
+
+    #[test]
+    fn requires_login() {
+        let prot = new_protocol::<()>(Arc::new(Storage::in_memory().unwrap()));
+        let resp = test_util::call(&*prot, None, PrivilegesProtocolMethod::GetPrivileges as u32, GET_PRIVILEGES);
+        assert!(matches!(resp, Err(Error::AccessDenied)));
+    }
 }
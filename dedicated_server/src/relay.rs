@@ -0,0 +1,319 @@
+//! A UDP relay for players that can't reach each other directly, like players behind symmetric NATs or CGNATs.
+//!
+//! Each registered player gets its own relay port, handed out as an additional station URL. Peers send their P2P traffic
+//! for the player to its relay port, the relay forwards it to the player from the sender's relay port. Replies take the
+//! same way back, so both players only talk to the relay. Only traffic between relayed players in the same game session
+//! is forwarded, and only up to the configured bandwidth per player. A relay port is released when the player disconnects or after a while
+//! without traffic and registrations.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::net::UdpSocket;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use quazal::rmc::types::StationURL;
+use slog::Logger;
+
+use crate::config::RelayConfig;
+use crate::storage::Storage;
+
+/// How often the forwarding threads check for timeouts.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How long the relay remembers whether two players are in the same game session.
+const SESSION_CHECK_TTL: Duration = Duration::from_secs(5);
+
+/// Traffic state of an allocation.
+struct Activity {
+    last_active: Instant,
+    /// Bytes the player can still send, refilled with the configured rate.
+    budget: f64,
+    refilled: Instant,
+}
+
+/// The relay port of a player.
+struct Allocation {
+    user_id: u32,
+    socket: UdpSocket,
+    port: u16,
+    /// Where the player receives relayed traffic.
+    address: Mutex<SocketAddr>,
+    activity: Mutex<Activity>,
+    closed: AtomicBool,
+}
+
+impl Allocation {
+    fn touch(&self) {
+        self.activity.lock().unwrap().last_active = Instant::now();
+    }
+
+    fn idle(&self) -> Duration {
+        self.activity.lock().unwrap().last_active.elapsed()
+    }
+
+    /// Takes `len` bytes from the bandwidth budget of the player. Returns false if the budget is exhausted.
+    fn consume(&self, len: usize, max_bytes_per_sec: u32) -> bool {
+        let mut activity = self.activity.lock().unwrap();
+        let now = Instant::now();
+        activity.last_active = now;
+        if max_bytes_per_sec == 0 {
+            return true;
+        }
+        let rate = f64::from(max_bytes_per_sec);
+        activity.budget = (activity.budget + now.duration_since(activity.refilled).as_secs_f64() * rate).min(rate);
+        activity.refilled = now;
+        #[allow(clippy::cast_precision_loss)]
+        let len = len as f64;
+        if activity.budget < len {
+            return false;
+        }
+        activity.budget -= len;
+        true
+    }
+}
+
+/// The relay, shared by the secure servers.
+pub struct Relay {
+    logger: Logger,
+    config: RelayConfig,
+    storage: Arc<Storage>,
+    /// Allocations by user id.
+    allocations: Mutex<HashMap<u32, Arc<Allocation>>>,
+    /// Whether pairs of players, the lower id first, share a game session, and when it was checked.
+    sessions: Mutex<HashMap<(u32, u32), (bool, Instant)>>,
+}
+
+impl Relay {
+    /// Creates a relay without allocations.
+    pub fn new(logger: Logger, config: RelayConfig, storage: Arc<Storage>) -> Arc<Self> {
+        Arc::new(Self {
+            logger,
+            config,
+            storage,
+            allocations: Mutex::default(),
+            sessions: Mutex::default(),
+        })
+    }
+
+    /// Returns the relay port of a player reachable at `address`, allocating one if it has none.
+    ///
+    /// Registering again keeps the port, updates the address and resets the idle timeout.
+    pub fn allocate(self: &Arc<Self>, user_id: u32, address: SocketAddr) -> io::Result<u16> {
+        let mut allocations = self.allocations.lock().unwrap();
+        if let Some(allocation) = allocations.get(&user_id) {
+            *allocation.address.lock().unwrap() = address;
+            allocation.touch();
+            return Ok(allocation.port);
+        }
+        if allocations.len() >= usize::from(self.config.max_allocations) {
+            return Err(io::Error::other("no free relay port"));
+        }
+
+        let socket = if self.config.first_port == 0 {
+            UdpSocket::bind((self.config.listen, 0))?
+        } else {
+            // ports of released allocations are reused
+            let last_port = self.config.first_port.saturating_add(self.config.max_allocations - 1);
+            (self.config.first_port..=last_port)
+                .filter(|port| !allocations.values().any(|a| a.port == *port))
+                .find_map(|port| UdpSocket::bind((self.config.listen, port)).ok())
+                .ok_or_else(|| io::Error::other("no free relay port"))?
+        };
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        let port = socket.local_addr()?.port();
+        let now = Instant::now();
+        let allocation = Arc::new(Allocation {
+            user_id,
+            socket,
+            port,
+            address: Mutex::new(address),
+            activity: Mutex::new(Activity {
+                last_active: now,
+                budget: f64::from(self.config.max_bytes_per_sec),
+                refilled: now,
+            }),
+            closed: AtomicBool::new(false),
+        });
+        allocations.insert(user_id, Arc::clone(&allocation));
+        drop(allocations);
+
+        info!(self.logger, "Relaying user {user_id} ({address}) on port {port}");
+        let relay = Arc::clone(self);
+        std::thread::Builder::new().name(format!("relay_{port}")).spawn(move || relay.forward(&allocation))?;
+        Ok(port)
+    }
+
+    /// Releases the relay port of a player.
+    pub fn release(&self, user_id: u32) {
+        if let Some(allocation) = self.allocations.lock().unwrap().remove(&user_id) {
+            info!(self.logger, "Released relay port {} of user {user_id}", allocation.port);
+            allocation.closed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the relay station URL of a player with the parameters of its public URL, allocating a relay port if
+    /// necessary.
+    pub fn station_url(self: &Arc<Self>, user_id: u32, public: &StationURL) -> io::Result<StationURL> {
+        let port = self.allocate(user_id, public.address.parse().map(|ip| SocketAddr::new(ip, public.port)).map_err(io::Error::other)?)?;
+        let mut url = public.clone();
+        url.address = self.config.public_address.to_string();
+        url.port = port;
+        // the relay is a public station without NAT
+        for (key, value) in [("type", "2"), ("natm", "0"), ("natf", "0")] {
+            url.params.insert(key.to_string(), value.to_string());
+        }
+        Ok(url)
+    }
+
+    /// Checks if a URL points to a relay port, like the relay URL a client registers again.
+    pub fn is_relay_url(&self, url: &StationURL) -> bool {
+        url.address.parse::<IpAddr>().is_ok_and(|ip| ip == self.config.public_address) && self.allocations.lock().unwrap().values().any(|a| a.port == url.port)
+    }
+
+    /// Forwards the traffic sent to the relay port of a player until it's released or times out.
+    fn forward(&self, allocation: &Allocation) {
+        let timeout = Duration::from_secs(self.config.idle_timeout);
+        let mut buf = vec![0u8; 64 * 1024];
+        while !allocation.closed.load(Ordering::Relaxed) {
+            match allocation.socket.recv_from(&mut buf) {
+                Ok((len, source)) => self.relay_packet(allocation, source, &buf[..len]),
+                Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {}
+                // e.g. ICMP port unreachable of an earlier packet
+                Err(e) => debug!(self.logger, "Error receiving on relay port {}: {e}", allocation.port),
+            }
+            if allocation.idle() > timeout {
+                let mut allocations = self.allocations.lock().unwrap();
+                if allocations.get(&allocation.user_id).is_some_and(|a| std::ptr::eq(Arc::as_ptr(a), allocation)) {
+                    allocations.remove(&allocation.user_id);
+                    info!(self.logger, "Relay port {} of user {} timed out", allocation.port, allocation.user_id);
+                }
+                break;
+            }
+        }
+    }
+
+    /// Checks if two players are in the same game session, asking the storage at most every `SESSION_CHECK_TTL`.
+    fn share_session(&self, user_id: u32, peer_id: u32) -> bool {
+        let pair = (user_id.min(peer_id), user_id.max(peer_id));
+        if let Some((shared, checked)) = self.sessions.lock().unwrap().get(&pair) {
+            if checked.elapsed() < SESSION_CHECK_TTL {
+                return *shared;
+            }
+        }
+        let shared = self.storage.share_game_session(user_id, peer_id).unwrap_or_else(|e| {
+            error!(self.logger, "Error checking game sessions: {e}");
+            false
+        });
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, (_, checked)| checked.elapsed() < SESSION_CHECK_TTL);
+        sessions.insert(pair, (shared, Instant::now()));
+        shared
+    }
+
+    /// Forwards a packet sent to the relay port of `target` from the relay port of the sender.
+    fn relay_packet(&self, target: &Allocation, source: SocketAddr, data: &[u8]) {
+        let sender = {
+            let allocations = self.allocations.lock().unwrap();
+            allocations.values().find(|a| *a.address.lock().unwrap() == source).cloned()
+        };
+        // not an open relay, the sender has to be relayed as well
+        let Some(sender) = sender else {
+            debug!(self.logger, "Dropping packet from unknown address {source} to relay port {}", target.port);
+            return;
+        };
+        if sender.user_id == target.user_id {
+            return;
+        }
+        if !self.share_session(sender.user_id, target.user_id) {
+            debug!(self.logger, "Dropping packet of user {} to user {}, no shared game session", sender.user_id, target.user_id);
+            return;
+        }
+        if !sender.consume(data.len(), self.config.max_bytes_per_sec) {
+            debug!(self.logger, "Dropping packet of user {}, bandwidth exceeded", sender.user_id);
+            return;
+        }
+        target.touch();
+        let destination = *target.address.lock().unwrap();
+        if let Err(e) = sender.socket.send_to(data, destination) {
+            warn!(self.logger, "Error relaying packet to {destination}: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    fn url(address: SocketAddr) -> StationURL {
+        format!("prudp:/address={};port={};sid=15;type=3;RVCID=1", address.ip(), address.port()).parse().unwrap()
+    }
+
+    #[test]
+    fn loopback() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        // 1000 hosts the seeded game session
+        storage.add_participants(1, 1, vec![1001], vec![]).unwrap();
+        let relay = Relay::new(
+            Logger::root(slog::Discard, slog::o!()),
+            RelayConfig {
+                enabled: true,
+                listen: IpAddr::V4(Ipv4Addr::LOCALHOST),
+                public_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+                first_port: 0,
+                max_allocations: 3,
+                max_bytes_per_sec: 1000,
+                idle_timeout: 1,
+            },
+            storage,
+        );
+        let a = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let b = UdpSocket::bind("127.0.0.1:0").unwrap();
+        for socket in [&a, &b] {
+            socket.set_read_timeout(Some(Duration::from_millis(300))).unwrap();
+        }
+        let relay_a = relay.station_url(1000, &url(a.local_addr().unwrap())).unwrap();
+        let relay_b = relay.station_url(1001, &url(b.local_addr().unwrap())).unwrap();
+        assert_eq!(relay_a.params["type"], "2");
+        assert_eq!(relay_a.params["RVCID"], "1");
+        assert!(relay.is_relay_url(&relay_b));
+        let c = UdpSocket::bind("127.0.0.1:0").unwrap();
+        relay.allocate(1002, c.local_addr().unwrap()).unwrap();
+        // registering again keeps the port, a fourth player doesn't fit
+        assert_eq!(relay.allocate(1000, a.local_addr().unwrap()).unwrap(), relay_a.port);
+        assert!(relay.allocate(1003, "127.0.0.1:9".parse().unwrap()).is_err());
+
+        let mut buf = [0u8; 1024];
+        a.send_to(b"hello", ("127.0.0.1", relay_b.port)).unwrap();
+        let (len, from) = b.recv_from(&mut buf).unwrap();
+        assert_eq!((&buf[..len], from.port()), (&b"hello"[..], relay_a.port));
+        b.send_to(b"hi", ("127.0.0.1", relay_a.port)).unwrap();
+        let (len, from) = a.recv_from(&mut buf).unwrap();
+        assert_eq!((&buf[..len], from.port()), (&b"hi"[..], relay_b.port));
+
+        // senders that aren't relayed or in the same game session are dropped
+        let d = UdpSocket::bind("127.0.0.1:0").unwrap();
+        d.send_to(b"spam", ("127.0.0.1", relay_b.port)).unwrap();
+        c.send_to(b"spam", ("127.0.0.1", relay_b.port)).unwrap();
+        assert!(b.recv_from(&mut buf).is_err());
+
+        // the second packet exceeds the bandwidth
+        a.send_to(&[1; 600], ("127.0.0.1", relay_b.port)).unwrap();
+        a.send_to(&[2; 600], ("127.0.0.1", relay_b.port)).unwrap();
+        assert_eq!(b.recv_from(&mut buf).unwrap().0, 600);
+        assert_eq!(buf[0], 1);
+        assert!(b.recv_from(&mut buf).is_err());
+
+        // idle ports are released
+        std::thread::sleep(Duration::from_secs(2));
+        assert!(!relay.is_relay_url(&relay_a));
+        assert!(!relay.is_relay_url(&relay_b));
+    }
+}
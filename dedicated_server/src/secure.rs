@@ -1,5 +1,18 @@
+//! Implements the `SecureConnectionProtocol`, with which clients register the station URLs peers connect to.
+//!
+//! Clients only know their local addresses, which are private ones behind a NAT or in a VPN. The URLs they register are
+//! merged with the address and port the server observes: the first URL of the merged list is the public one, with the
+//! observed address, followed by the URLs of the client. The `type` parameter flags the public URL and whether the
+//! client is behind a NAT, `natm` and `natf` describe the NAT's mapping and filtering behavior. With the relay enabled,
+//! the relay URL of the client comes last.
+
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
 use quazal::prudp::ClientRegistry;
 use quazal::rmc::types::QResult;
+use quazal::rmc::types::StationURL;
 use quazal::rmc::Protocol;
 
 use crate::login_required;
@@ -9,9 +22,131 @@ use crate::protocols::secure_connection_service::secure_connection_protocol::Reg
 use crate::protocols::secure_connection_service::secure_connection_protocol::RegisterResponse;
 use crate::protocols::secure_connection_service::secure_connection_protocol::SecureConnectionProtocolServer;
 use crate::protocols::secure_connection_service::secure_connection_protocol::SecureConnectionProtocolServerTrait;
+use crate::relay::Relay;
+use crate::storage::Storage;
+
+/// Flag of the `type` parameter for stations behind a NAT.
+const URL_BEHIND_NAT: u32 = 1;
+/// Flag of the `type` parameter for the public URL of a station.
+const URL_PUBLIC: u32 = 2;
+
+/// NAT mapping or filtering behavior, as in the `natm` and `natf` parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum NatBehavior {
+    /// Not known, or no NAT at all.
+    Unknown = 0,
+    /// The same public port is used for every peer.
+    EndpointIndependent = 1,
+    /// The public port depends on the peer.
+    EndpointDependent = 2,
+}
+
+fn url_type(url: &StationURL) -> u32 {
+    url.params.get("type").and_then(|t| t.parse().ok()).unwrap_or_default()
+}
+
+/// Whether a merged station URL is the public one of its client.
+pub(crate) fn is_public_url(url: &StationURL) -> bool {
+    url_type(url) & URL_PUBLIC != 0
+}
+
+fn is_observed(url: &StationURL, observed: SocketAddr) -> bool {
+    url.port == observed.port() && url.address.parse::<IpAddr>().is_ok_and(|ip| ip == observed.ip())
+}
+
+/// Merges the station URLs a client reports with the address the server observes.
+///
+/// The first URL of the result is the public one and replaces a reported URL with the observed address, like the
+/// public URL a client got back and registers again. The client is behind a NAT unless it reports the observed address
+/// itself, without it being flagged as behind a NAT. Without a reported URL, the public URL gets the stream ID `sid`.
+pub(crate) fn merge_station_urls(observed: SocketAddr, connection_id: Option<u32>, urls: &[StationURL], sid: &str) -> Vec<StationURL> {
+    let local = urls.iter().filter(|url| !is_observed(url, observed)).collect::<Vec<_>>();
+    let direct = urls.iter().any(|url| is_observed(url, observed) && url_type(url) & URL_BEHIND_NAT == 0);
+    let behind_nat = !direct;
+
+    let mut public = local.first().copied().or(urls.first()).cloned().unwrap_or_else(|| StationURL {
+        scheme: "prudp".to_string(),
+        params: [("sid".to_string(), sid.to_string())].into(),
+        ..Default::default()
+    });
+    // the mapping can be guessed from a preserved port, the filtering can't be observed from a single connection
+    let mapping = match local.first() {
+        Some(url) if behind_nat && url.port == observed.port() => NatBehavior::EndpointIndependent,
+        Some(_) if behind_nat => NatBehavior::EndpointDependent,
+        _ => NatBehavior::Unknown,
+    };
+    public.params.entry("natm".to_string()).or_insert_with(|| (mapping as u8).to_string());
+    public.params.entry("natf".to_string()).or_insert_with(|| (NatBehavior::Unknown as u8).to_string());
+    if let Some(connection_id) = connection_id {
+        public.params.entry("RVCID".to_string()).or_insert_with(|| connection_id.to_string());
+    }
+
+    let nat_flag = if behind_nat { URL_BEHIND_NAT } else { 0 };
+    let mut merged = Vec::with_capacity(local.len() + 1);
+    for url in local {
+        let mut url = url.clone();
+        for key in ["natm", "natf", "RVCID"] {
+            if let Some(value) = public.params.get(key) {
+                url.params.entry(key.to_string()).or_insert_with(|| value.clone());
+            }
+        }
+        url.params.insert("type".to_string(), ((url_type(&url) & !URL_PUBLIC) | nat_flag).to_string());
+        merged.push(url);
+    }
+
+    public.address = observed.ip().to_string();
+    public.port = observed.port();
+    public.params.insert("type".to_string(), (url_type(&public) | nat_flag | URL_PUBLIC).to_string());
+    merged.insert(0, public);
+    merged
+}
+
+/// Merges the URLs a logged in client reports like `merge_station_urls` and adds its relay URL if the relay is enabled.
+pub(crate) fn client_station_urls<T>(
+    logger: &slog::Logger,
+    ci: &quazal::ClientInfo<T>,
+    user_id: u32,
+    urls: &[StationURL],
+    sid: &str,
+    relay: Option<&Arc<Relay>>,
+) -> Vec<StationURL> {
+    // a registered relay URL is replaced by the current one
+    let urls = urls.iter().filter(|url| !relay.is_some_and(|relay| relay.is_relay_url(url))).cloned().collect::<Vec<_>>();
+    let mut merged = merge_station_urls(*ci.address(), ci.connection_id.map(u32::from), &urls, sid);
+    if let Some(relay) = relay {
+        match relay.station_url(user_id, &merged[0]) {
+            Ok(url) => merged.push(url),
+            Err(e) => warn!(logger, "Not relaying user {user_id}: {e}"),
+        }
+    }
+    merged
+}
 
 /// Implementation of the `SecureConnectionProtocolServerTrait` for handling secure connection requests.
-struct SecureConnectionProtocolServerImpl;
+struct SecureConnectionProtocolServerImpl {
+    storage: Arc<Storage>,
+    relay: Option<Arc<Relay>>,
+}
+
+impl SecureConnectionProtocolServerImpl {
+    /// Merges and stores the URLs of a client, returning its public URL.
+    fn register_station<T>(&self, logger: &slog::Logger, ci: &quazal::ClientInfo<T>, urls: &[StationURL], sid: &str) -> Result<StationURL, quazal::rmc::Error> {
+        let user_id = login_required(ci)?;
+        let merged = client_station_urls(logger, ci, user_id, urls, sid, self.relay.as_ref());
+        info!(
+            logger,
+            "Registered station URLs of user {user_id}: {}",
+            merged.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+        );
+        rmc_err!(
+            self.storage.register_urls(user_id, merged.iter().map(ToString::to_string).collect()),
+            logger,
+            "error storing station urls"
+        )?;
+        Ok(merged.into_iter().next().unwrap_or_default())
+    }
+}
 
 impl<T> SecureConnectionProtocolServerTrait<T> for SecureConnectionProtocolServerImpl {
     /// Handles the `Register` request, establishing a secure connection.
@@ -27,14 +162,12 @@ impl<T> SecureConnectionProtocolServerTrait<T> for SecureConnectionProtocolServe
         _client_registry: &ClientRegistry<T>,
         _socket: &std::net::UdpSocket,
     ) -> Result<RegisterResponse, quazal::rmc::Error> {
-        let _user_id = login_required(&*ci)?;
         info!(logger, "Client registers with {:?}", request);
+        let url_public = self.register_station(logger, ci, &request.vec_my_urls, "14")?;
         Ok(RegisterResponse {
             return_value: QResult::Ok,
             pid_connection_id: ci.connection_id.unwrap().into(), // should be set at this point
-            url_public: format!("prudp:/address={};port={};sid={};type=2", ci.address().ip(), ci.address().port(), 14)
-                .parse()
-                .map_err(|_| quazal::rmc::Error::InternalError)?,
+            url_public,
         })
     }
 
@@ -51,14 +184,12 @@ impl<T> SecureConnectionProtocolServerTrait<T> for SecureConnectionProtocolServe
         _client_registry: &ClientRegistry<T>,
         _socket: &std::net::UdpSocket,
     ) -> Result<RegisterExResponse, quazal::rmc::Error> {
-        let _user_id = login_required(&*ci)?;
         info!(logger, "Client registers with {:?}", request);
+        let url_public = self.register_station(logger, ci, &request.vec_my_urls, "15")?;
         Ok(RegisterExResponse {
             return_value: QResult::Ok,
             pid_connection_id: ci.connection_id.unwrap().into(), // should be set at this point
-            url_public: format!("prudp:/address={};port={};sid={};type=3", ci.address().ip(), ci.address().port(), 15)
-                .parse()
-                .map_err(|_| quazal::rmc::Error::InternalError)?,
+            url_public,
         })
     }
 }
@@ -67,6 +198,56 @@ impl<T> SecureConnectionProtocolServerTrait<T> for SecureConnectionProtocolServe
 ///
 /// This function is typically used to register the secure connection protocol
 /// with the server's protocol dispatcher.
-pub fn new_protocol<T: 'static>() -> Box<dyn Protocol<T>> {
-    Box::new(SecureConnectionProtocolServer::new(SecureConnectionProtocolServerImpl))
+pub fn new_protocol<T: 'static>(storage: Arc<Storage>, relay: Option<Arc<Relay>>) -> Box<dyn Protocol<T>> {
+    Box::new(SecureConnectionProtocolServer::new(SecureConnectionProtocolServerImpl { storage, relay }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge(observed: &str, urls: &[&str]) -> Vec<String> {
+        let urls = urls.iter().map(|url| url.parse().unwrap()).collect::<Vec<StationURL>>();
+        merge_station_urls(observed.parse().unwrap(), Some(1234), &urls, "15")
+            .into_iter()
+            .map(|url| {
+                let mut params = url.params.into_iter().collect::<Vec<_>>();
+                params.sort();
+                let params = params.into_iter().map(|(k, v)| format!("{k}={v}")).collect::<Vec<_>>();
+                format!("{}:{};{}", url.address, url.port, params.join(";"))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn behind_nat() {
+        assert_eq!(
+            merge("203.0.113.7:3074", &["prudp:/address=192.168.1.20;port=3074;sid=15;type=2"]),
+            [
+                "203.0.113.7:3074;RVCID=1234;natf=0;natm=1;sid=15;type=3",
+                "192.168.1.20:3074;RVCID=1234;natf=0;natm=1;sid=15;type=1",
+            ]
+        );
+        // a rewritten port and the public url registered again
+        assert_eq!(
+            merge(
+                "203.0.113.7:61000",
+                &["prudp:/address=26.1.2.3;port=3074;sid=15;type=0", "prudp:/address=203.0.113.7;port=61000;sid=15;type=3"]
+            ),
+            [
+                "203.0.113.7:61000;RVCID=1234;natf=0;natm=2;sid=15;type=3",
+                "26.1.2.3:3074;RVCID=1234;natf=0;natm=2;sid=15;type=1",
+            ]
+        );
+        // without a reported URL, nothing tells that the client isn't behind a NAT
+        assert_eq!(merge("203.0.113.7:3074", &[]), ["203.0.113.7:3074;RVCID=1234;natf=0;natm=0;sid=15;type=3"]);
+    }
+
+    #[test]
+    fn public_station() {
+        assert_eq!(
+            merge("198.51.100.1:3074", &["prudp:/address=198.51.100.1;port=3074;sid=15;type=0;natm=1;natf=1"]),
+            ["198.51.100.1:3074;RVCID=1234;natf=1;natm=1;sid=15;type=2"]
+        );
+    }
 }
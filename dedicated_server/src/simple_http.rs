@@ -3,10 +3,26 @@ use std::io::BufRead;
 use std::io::Write;
 use std::net::SocketAddr;
 use std::net::TcpListener;
+use std::net::TcpStream;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use slog::debug;
 use slog::error;
+use slog::warn;
+
+use crate::storage::Storage;
+
+/// How long a client may take to send its request or receive the response.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Accepts the next connection, with timeouts so a stalled client can't block the server.
+fn accept(listener: &TcpListener) -> std::io::Result<TcpStream> {
+    let (stream, _addr) = listener.accept()?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    Ok(stream)
+}
 
 /// Serves a single file over HTTP.
 ///
@@ -21,7 +37,13 @@ pub fn serve(logger: &slog::Logger, addr: SocketAddr, content: &str) -> std::io:
     );
     let resp = resp.as_bytes();
     loop {
-        let (mut stream, _addr) = listener.accept()?;
+        let mut stream = match accept(&listener) {
+            Ok(stream) => stream,
+            Err(e) => {
+                error!(logger, "simple_http: accept error: {:?}", e);
+                continue;
+            }
+        };
         let mut rdr = std::io::BufReader::new(stream.try_clone()?);
         let mut path = String::new();
         if let Err(e) = rdr.read_line(&mut path) {
@@ -35,15 +57,29 @@ pub fn serve(logger: &slog::Logger, addr: SocketAddr, content: &str) -> std::io:
     }
 }
 
+/// Path prefix for downloading user contents, followed by the content id.
+pub const USER_CONTENT_PATH: &str = "/usercontent/";
+/// Path prefix for uploading user contents, followed by the pending upload id.
+pub const USER_CONTENT_UPLOAD_PATH: &str = "/usercontent/upload/";
+
 /// Serves multiple files over HTTP.
 ///
 /// This function binds to the given address and serves files from the provided
 /// `files` map. The keys of the map are the request paths, and the values are
 /// the paths to the files on disk.
-pub fn serve_many(logger: &slog::Logger, addr: SocketAddr, files: &HashMap<String, PathBuf>) -> std::io::Result<()> {
+///
+/// Additionally, user contents are uploaded with `PUT` requests to [`USER_CONTENT_UPLOAD_PATH`]
+/// and downloaded from [`USER_CONTENT_PATH`]. Upload bodies larger than `max_body_size` are rejected.
+pub fn serve_many(logger: &slog::Logger, addr: SocketAddr, files: &HashMap<String, PathBuf>, storage: &Storage, max_body_size: usize) -> std::io::Result<()> {
     let listener = TcpListener::bind(addr)?;
     loop {
-        let (mut stream, _addr) = listener.accept()?;
+        let mut stream = match accept(&listener) {
+            Ok(stream) => stream,
+            Err(e) => {
+                error!(logger, "simple_http: accept error: {:?}", e);
+                continue;
+            }
+        };
         let mut rdr = std::io::BufReader::new(stream.try_clone()?);
         let mut path = String::new();
         if let Err(e) = rdr.read_line(&mut path) {
@@ -51,15 +87,7 @@ pub fn serve_many(logger: &slog::Logger, addr: SocketAddr, files: &HashMap<Strin
             continue;
         }
         debug!(logger, "Request: {}", path);
-        let prefix = "GET ";
         let suffix = " HTTP/1.1\r\n";
-        if !path.starts_with(prefix) {
-            debug!(logger, "Status 405");
-            if let Err(e) = stream.write_all(b"HTTP/1.0 405 Method Not Allowed\r\n\r\n") {
-                error!(logger, "simple_http: write error: {:?}", e);
-            }
-            continue;
-        }
         if !path.ends_with(suffix) {
             debug!(logger, "Status 400");
             if let Err(e) = stream.write_all(b"HTTP/1.0 400 Bad Request\r\n\r\n") {
@@ -67,24 +95,115 @@ pub fn serve_many(logger: &slog::Logger, addr: SocketAddr, files: &HashMap<Strin
             }
             continue;
         }
-        let path = &path[prefix.len()..(path.len() - suffix.len())];
-        if let Some(path) = files.get(path) {
-            let data = std::fs::read(path)?;
+        let (method, path) = path[..(path.len() - suffix.len())].split_once(' ').unwrap_or_default();
+        let resp = match method {
+            "GET" => get(logger, path, files, storage),
+            "PUT" => match read_body(&mut rdr, max_body_size) {
+                Ok(body) => put(logger, path, &body, storage),
+                Err(e) if e.kind() == std::io::ErrorKind::FileTooLarge => {
+                    warn!(logger, "simple_http: {}", e);
+                    b"HTTP/1.0 413 Payload Too Large\r\n\r\n".to_vec()
+                }
+                Err(e) => {
+                    error!(logger, "simple_http: read error: {:?}", e);
+                    b"HTTP/1.0 400 Bad Request\r\n\r\n".to_vec()
+                }
+            },
+            _ => b"HTTP/1.0 405 Method Not Allowed\r\n\r\n".to_vec(),
+        };
+        debug!(logger, "Status {}", String::from_utf8_lossy(&resp[9..12]));
+        if let Err(e) = stream.write_all(&resp) {
+            error!(logger, "simple_http: write error: {:?}", e);
+        }
+    }
+}
 
-            let resp = format!("HTTP/1.0 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\n\r\n", data.len(),);
-            debug!(logger, "Status 200");
-            let mut resp = resp.into_bytes();
-            resp.extend(data);
-            if let Err(e) = stream.write_all(&resp) {
-                error!(logger, "simple_http: write error: {:?}", e);
+/// Reads the headers of a request and returns the body announced by `Content-Length`, which may not exceed `max_size`.
+fn read_body<R: BufRead>(rdr: &mut R, max_size: usize) -> std::io::Result<Vec<u8>> {
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        if rdr.read_line(&mut header)? == 0 || header == "\r\n" {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid content length"))?;
             }
-            continue;
         }
+    }
+    if length > max_size {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::FileTooLarge,
+            format!("body of {length} bytes exceeds {max_size} bytes"),
+        ));
+    }
+    let mut body = vec![0; length];
+    rdr.read_exact(&mut body)?;
+    Ok(body)
+}
 
-        if let Err(e) = stream.write_all(b"HTTP/1.0 404 Not Found\r\n\r\n") {
-            error!(logger, "simple_http: write error: {:?}", e);
-            continue;
+fn ok(data: &[u8]) -> Vec<u8> {
+    let mut resp = format!("HTTP/1.0 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\n\r\n", data.len()).into_bytes();
+    resp.extend(data);
+    resp
+}
+
+fn get(logger: &slog::Logger, path: &str, files: &HashMap<String, PathBuf>, storage: &Storage) -> Vec<u8> {
+    if let Some(path) = files.get(path) {
+        return match std::fs::read(path) {
+            Ok(data) => ok(&data),
+            Err(e) => {
+                error!(logger, "simple_http: error reading {}: {:?}", path.display(), e);
+                b"HTTP/1.0 500 Internal Server Error\r\n\r\n".to_vec()
+            }
+        };
+    }
+
+    if let Some(content_id) = path.strip_prefix(USER_CONTENT_PATH).and_then(|id| id.parse().ok()) {
+        match storage.find_content_data(content_id) {
+            Ok(Some(data)) => return ok(&data),
+            Ok(None) => {}
+            Err(e) => {
+                error!(logger, "simple_http: error loading content {}: {:?}", content_id, e);
+                return b"HTTP/1.0 500 Internal Server Error\r\n\r\n".to_vec();
+            }
         }
-        debug!(logger, "Status 404");
+    }
+
+    b"HTTP/1.0 404 Not Found\r\n\r\n".to_vec()
+}
+
+fn put(logger: &slog::Logger, path: &str, body: &[u8], storage: &Storage) -> Vec<u8> {
+    let Some(pending_id) = path.strip_prefix(USER_CONTENT_UPLOAD_PATH).and_then(|id| id.parse().ok()) else {
+        return b"HTTP/1.0 404 Not Found\r\n\r\n".to_vec();
+    };
+    match storage.store_content_upload(pending_id, body) {
+        Ok(true) => b"HTTP/1.0 200 OK\r\nContent-Length: 0\r\n\r\n".to_vec(),
+        Ok(false) => {
+            warn!(logger, "simple_http: rejected upload {} of {} bytes", pending_id, body.len());
+            b"HTTP/1.0 403 Forbidden\r\n\r\n".to_vec()
+        }
+        Err(e) => {
+            error!(logger, "simple_http: error storing upload {}: {:?}", pending_id, e);
+            b"HTTP/1.0 500 Internal Server Error\r\n\r\n".to_vec()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn body_size() {
+        let request = b"Content-Length: 4\r\nHost: localhost\r\n\r\nbody";
+        assert_eq!(read_body(&mut &request[..], 4).unwrap(), b"body");
+        // rejected before reading the body
+        let request = b"Content-Length: 18446744073709551615\r\n\r\n";
+        assert_eq!(read_body(&mut &request[..], 4).unwrap_err().kind(), std::io::ErrorKind::FileTooLarge);
     }
 }
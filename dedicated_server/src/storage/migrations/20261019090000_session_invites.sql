@@ -0,0 +1,7 @@
+ALTER TABLE invites ADD COLUMN session_type INTEGER;
+ALTER TABLE invites ADD COLUMN session_id INTEGER REFERENCES game_sessions(id) ON DELETE CASCADE;
+ALTER TABLE invites ADD COLUMN message TEXT NOT NULL DEFAULT '';
+-- 0 = pending, 1 = accepted, 2 = declined, 3 = cancelled
+ALTER TABLE invites ADD COLUMN status INTEGER NOT NULL DEFAULT 0 CHECK (status IN (0,1,2,3));
+-- set once the overlay picked up the invite via `Misc.Event`
+ALTER TABLE invites ADD COLUMN delivered INTEGER NOT NULL DEFAULT 0 CHECK (delivered IN (0,1));
@@ -0,0 +1,12 @@
+CREATE TABLE game_session_attributes (
+  game_id INTEGER NOT NULL REFERENCES game_sessions(id) ON DELETE CASCADE,
+  attr_id INTEGER NOT NULL,
+  value INTEGER NOT NULL,
+  PRIMARY KEY (game_id, attr_id)
+);
+
+CREATE INDEX game_session_attributes_lookup ON game_session_attributes (attr_id, value);
+
+-- attributes used to be stored as "id => value;id => value" strings. Sessions are invalidated on
+-- startup anyway, so there is nothing worth converting.
+ALTER TABLE game_sessions DROP COLUMN attributes;
@@ -0,0 +1,13 @@
+-- latest value of each stat players wrote with PlayerStatsProtocol WriteStats
+CREATE TABLE player_stats (
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  board_id INTEGER NOT NULL,
+  -- usually the map
+  context_id INTEGER NOT NULL,
+  stat_id INTEGER NOT NULL,
+  value INTEGER NOT NULL,
+  updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (user_id, board_id, context_id, stat_id)
+);
+
+CREATE INDEX player_stats_board ON player_stats (board_id, stat_id);
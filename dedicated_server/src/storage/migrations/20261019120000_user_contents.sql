@@ -0,0 +1,32 @@
+CREATE TABLE user_contents (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  owner_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  type_id INTEGER NOT NULL,
+  data BLOB NOT NULL DEFAULT x'',
+  size INTEGER NOT NULL DEFAULT 0,
+  -- upper case hex md5 of data
+  hash TEXT NOT NULL DEFAULT '',
+  -- 0 until the first upload has been finished, unfinished contents are hidden from other players
+  finalized INTEGER NOT NULL DEFAULT 0,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX user_contents_owner ON user_contents (owner_id, type_id);
+
+-- properties set by the game, values are quazal encoded variants
+CREATE TABLE user_content_properties (
+  content_id INTEGER NOT NULL REFERENCES user_contents(id) ON DELETE CASCADE,
+  prop_id INTEGER NOT NULL,
+  value BLOB NOT NULL,
+  PRIMARY KEY (content_id, prop_id)
+);
+
+-- uploads announced by SaveContentAndGetUploadInfo, the id is random as it doubles as upload token
+CREATE TABLE user_content_uploads (
+  id INTEGER PRIMARY KEY,
+  content_id INTEGER NOT NULL REFERENCES user_contents(id) ON DELETE CASCADE,
+  size INTEGER NOT NULL,
+  data BLOB,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,45 @@
+ALTER TABLE user_contents ADD COLUMN play_count INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE user_content_likes (
+  content_id INTEGER NOT NULL REFERENCES user_contents(id) ON DELETE CASCADE,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  PRIMARY KEY (content_id, user_id)
+);
+
+CREATE TABLE user_content_favourites (
+  content_id INTEGER NOT NULL REFERENCES user_contents(id) ON DELETE CASCADE,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  PRIMARY KEY (content_id, user_id)
+);
+
+CREATE INDEX user_content_favourites_user ON user_content_favourites (user_id);
+
+-- every player can put every tag on a content once
+CREATE TABLE user_content_tags (
+  content_id INTEGER NOT NULL REFERENCES user_contents(id) ON DELETE CASCADE,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  tag_id INTEGER NOT NULL,
+  PRIMARY KEY (content_id, user_id, tag_id)
+);
+
+CREATE TABLE user_content_stats (
+  content_id INTEGER NOT NULL REFERENCES user_contents(id) ON DELETE CASCADE,
+  stat_id INTEGER NOT NULL,
+  value INTEGER NOT NULL DEFAULT 0,
+  PRIMARY KEY (content_id, stat_id)
+);
+
+-- moderation queue, reports stay pending until a moderator handled them
+CREATE TABLE user_content_reports (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  content_id INTEGER NOT NULL REFERENCES user_contents(id) ON DELETE CASCADE,
+  reporter_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  reason TEXT NOT NULL,
+  handled INTEGER NOT NULL DEFAULT 0,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX user_content_reports_pending ON user_content_reports (handled, content_id);
+
+-- a player reports a content at most once, reporting it again replaces the reason
+CREATE UNIQUE INDEX user_content_reports_reporter ON user_content_reports (content_id, reporter_id);
@@ -0,0 +1,18 @@
+-- moderators may use the UserStorageAdmin protocol from within the game
+ALTER TABLE users ADD COLUMN is_moderator INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE user_contents ADD COLUMN verified INTEGER NOT NULL DEFAULT 0;
+-- banned contents are only visible to their owner and moderators
+ALTER TABLE user_contents ADD COLUMN banned INTEGER NOT NULL DEFAULT 0;
+
+-- players banned from uploading contents
+CREATE TABLE user_content_bans (
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  -- 0 bans from all content types
+  type_id INTEGER NOT NULL DEFAULT 0,
+  reason TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  -- NULL never expires
+  expires_at TEXT,
+  PRIMARY KEY (user_id, type_id)
+);
@@ -0,0 +1,14 @@
+ALTER TABLE users ADD COLUMN email TEXT NOT NULL DEFAULT '';
+-- free text set by the player with AccountManagement.UpdateStatus
+ALTER TABLE users ADD COLUMN status TEXT NOT NULL DEFAULT '';
+
+-- custom data set with AccountManagement.UpdateCustomData, stored as class name and serialized data of the `Any`
+ALTER TABLE users ADD COLUMN public_data_type TEXT;
+ALTER TABLE users ADD COLUMN public_data BLOB;
+ALTER TABLE users ADD COLUMN private_data_type TEXT;
+ALTER TABLE users ADD COLUMN private_data BLOB;
+
+-- connection stats of the game sessions, updated when the secure connection is established or dropped
+ALTER TABLE users ADD COLUMN current_login TEXT;
+ALTER TABLE users ADD COLUMN previous_login TEXT;
+ALTER TABLE users ADD COLUMN last_logout TEXT;
@@ -0,0 +1,20 @@
+-- address the user last logged in from, used for ip bans
+ALTER TABLE users ADD COLUMN last_ip TEXT;
+
+-- bans survive deleting the account, so the ubi id and address stay blocked
+CREATE TABLE user_bans (
+  id INTEGER PRIMARY KEY,
+  user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+  ubi_id TEXT,
+  -- NULL if the ban doesn't cover the address
+  ip TEXT,
+  reason TEXT NOT NULL DEFAULT '',
+  banned_by TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  -- NULL never expires
+  expires_at TEXT
+);
+
+CREATE INDEX user_bans_user_id ON user_bans (user_id);
+CREATE INDEX user_bans_ubi_id ON user_bans (ubi_id);
+CREATE INDEX user_bans_ip ON user_bans (ip);
@@ -0,0 +1,21 @@
+-- known privileges, the defaults are updated from the config on startup
+CREATE TABLE privileges (
+  id INTEGER PRIMARY KEY,
+  description TEXT NOT NULL,
+  -- granted to every user unless revoked
+  is_default INTEGER NOT NULL DEFAULT 0
+);
+
+INSERT INTO privileges (id, description, is_default) VALUES (1, 'PlayOnline', 1);
+
+-- per user exceptions from the defaults
+CREATE TABLE user_privileges (
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  privilege_id INTEGER NOT NULL REFERENCES privileges(id) ON DELETE CASCADE,
+  -- 1 grants the privilege, 0 revokes it
+  granted INTEGER NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  -- NULL never expires
+  expires_at TEXT,
+  PRIMARY KEY (user_id, privilege_id)
+);
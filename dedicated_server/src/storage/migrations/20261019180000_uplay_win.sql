@@ -0,0 +1,20 @@
+-- values are copied from the catalogue, so balances don't change when it's edited
+CREATE TABLE uplay_actions_completed (
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  action_code TEXT NOT NULL,
+  platform_code TEXT NOT NULL,
+  -- units earned
+  value INTEGER NOT NULL,
+  completed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (user_id, action_code, platform_code)
+);
+
+CREATE TABLE uplay_rewards_purchased (
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  reward_code TEXT NOT NULL,
+  platform_code TEXT NOT NULL,
+  -- units spent
+  value INTEGER NOT NULL,
+  purchased_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (user_id, reward_code, platform_code)
+);
@@ -0,0 +1,15 @@
+-- telemetry sent by the game with TrackingProtocol3 SendTags
+CREATE TABLE tracking_tags (
+  id INTEGER PRIMARY KEY,
+  user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+  tracking_id INTEGER NOT NULL,
+  tag TEXT NOT NULL,
+  -- as sent by the game
+  attributes TEXT NOT NULL,
+  delta_time INTEGER NOT NULL,
+  received_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX tracking_tags_tag ON tracking_tags (tag, received_at);
+CREATE INDEX tracking_tags_user_id ON tracking_tags (user_id, received_at);
+CREATE INDEX tracking_tags_received_at ON tracking_tags (received_at);
@@ -0,0 +1,2 @@
+-- locale the game set with LocalizationProtocol SetLocaleCode, e.g. en-US
+ALTER TABLE user_sessions ADD COLUMN locale TEXT;
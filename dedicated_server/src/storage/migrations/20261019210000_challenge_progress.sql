@@ -0,0 +1,11 @@
+-- progress of players in OverlordChallenge challenges, per occurrence of rotating challenges
+CREATE TABLE challenge_progress (
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  challenge_id INTEGER NOT NULL,
+  -- start of the daily or weekly period the progress counts for
+  period TEXT NOT NULL,
+  progress INTEGER NOT NULL,
+  completed_at TEXT,
+  updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (user_id, challenge_id, period)
+);
@@ -0,0 +1,2 @@
+-- locale the user's game last ran with, used for messages sent before the game sets the locale of the session
+ALTER TABLE users ADD COLUMN locale TEXT;
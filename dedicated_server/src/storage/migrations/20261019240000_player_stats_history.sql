@@ -0,0 +1,11 @@
+-- every stat value players wrote, so that seasons can be computed from the changes during their dates
+CREATE TABLE player_stats_history (
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  board_id INTEGER NOT NULL,
+  context_id INTEGER NOT NULL,
+  stat_id INTEGER NOT NULL,
+  value INTEGER NOT NULL,
+  recorded_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX player_stats_history_stat ON player_stats_history (board_id, stat_id, recorded_at);
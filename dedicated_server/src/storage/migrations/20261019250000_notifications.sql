@@ -0,0 +1,15 @@
+-- notifications for players, kept until the game polls them
+CREATE TABLE notifications (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  sender_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+  kind INTEGER NOT NULL,
+  param1 INTEGER NOT NULL DEFAULT 0,
+  param2 INTEGER NOT NULL DEFAULT 0,
+  text TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  -- when a poll returned it to the game
+  read_at TEXT
+);
+
+CREATE INDEX notifications_unread ON notifications (user_id, read_at);
@@ -0,0 +1,24 @@
+-- NAT traversal between pairs of players, user_a is the lower id of the pair
+CREATE TABLE nat_traversal_results (
+  user_a INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  user_b INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  probes INTEGER NOT NULL DEFAULT 0,
+  successes INTEGER NOT NULL DEFAULT 0,
+  failures INTEGER NOT NULL DEFAULT 0,
+  -- failures since the last success
+  failure_streak INTEGER NOT NULL DEFAULT 0,
+  updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (user_a, user_b),
+  CHECK (user_a < user_b)
+);
+
+CREATE INDEX nat_traversal_results_user_b ON nat_traversal_results (user_b);
+
+-- NAT behavior the game reports for a player
+CREATE TABLE nat_properties (
+  user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+  mapping INTEGER NOT NULL,
+  filtering INTEGER NOT NULL,
+  rtt INTEGER NOT NULL,
+  updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
 use argon2::password_hash::rand_core::OsRng;
 use argon2::password_hash::SaltString;
 use argon2::Argon2;
@@ -6,11 +9,15 @@ use argon2::PasswordHasher;
 use argon2::PasswordVerifier;
 use eyre::eyre;
 use slog::Logger;
+use sqlx::sqlite::SqliteConnectOptions;
 use sqlx::sqlite::SqlitePool;
+use sqlx::sqlite::SqlitePoolOptions;
 use sqlx::Execute;
 use sqlx::Executor;
 use sqlx::Statement;
 
+use crate::config::PrivilegeConfig;
+
 type Result<T> = eyre::Result<T>;
 
 fn run<F>(future: F) -> Result<F::Output>
@@ -23,6 +30,9 @@ where
 pub struct Storage {
     logger: Logger,
     pool: SqlitePool,
+    /// Keeps an in-memory database alive, it's gone once its last connection is closed.
+    #[cfg(test)]
+    memory: Option<sqlx::SqliteConnection>,
 }
 
 pub enum LoginError {
@@ -32,14 +42,34 @@ pub enum LoginError {
 
 impl Storage {
     pub fn init(logger: Logger) -> Result<Self> {
+        Self::open(logger, SqlitePoolOptions::new(), "sqlite://5th-echelon.db?mode=rwc".parse()?)
+    }
+
+    /// Opens a fresh in-memory database for tests.
+    #[cfg(test)]
+    pub fn in_memory() -> Result<Self> {
+        let connect_options: SqliteConnectOptions = "sqlite::memory:".parse()?;
+        // the pool loses its connection whenever a runtime is dropped before the connection was returned
+        let memory = run(sqlx::ConnectOptions::connect(&connect_options))??;
+        let mut storage = Self::open(Logger::root(slog::Discard, slog::o!()), SqlitePoolOptions::new().max_connections(1), connect_options)?;
+        storage.memory = Some(memory);
+        Ok(storage)
+    }
+
+    fn open(logger: Logger, options: SqlitePoolOptions, connect_options: SqliteConnectOptions) -> Result<Self> {
         let pool = run(async {
-            let pool = SqlitePool::connect("sqlite://5th-echelon.db?mode=rwc").await?;
+            let pool = options.connect_with(connect_options).await?;
             // enable foreign key checks
             sqlx::query("PRAGMA foreign_keys=ON").execute(&pool).await?;
             sqlx::migrate!("src/storage/migrations").run(&pool).await?;
             Ok::<_, eyre::Error>(pool)
         })??;
-        Ok(Self { logger, pool })
+        Ok(Self {
+            logger,
+            pool,
+            #[cfg(test)]
+            memory: None,
+        })
     }
 
     pub async fn login_user_async(&self, username: &str, password: &str) -> Result<std::result::Result<u32, LoginError>> {
@@ -187,6 +217,519 @@ impl Storage {
         Ok(uid)
     }
 
+    pub fn find_account(&self, user_id: u32) -> Result<Option<Account>> {
+        Ok(run(sqlx::query_as(
+            r"
+            SELECT id, username, email, status, created_at, current_login, previous_login, last_logout
+            FROM users
+            WHERE id = ?
+            ",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool))??)
+    }
+
+    /// Replaces the password of a user with a hash of `password`.
+    pub fn change_password(&self, user_id: u32, password: &str) -> Result<()> {
+        let salt = SaltString::try_from_rng(&mut OsRng).unwrap();
+        let password_hash = Argon2::default()
+            .hash_password(password.as_bytes(), salt.as_salt())
+            .map_err(|_| eyre!("password hashing failed"))?
+            .to_string();
+        run(
+            sqlx::query("UPDATE users SET password = NULL, password_hash = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+                .bind(password_hash)
+                .bind(user_id)
+                .execute(&self.pool),
+        )??;
+        Ok(())
+    }
+
+    /// Renames a user. Returns `false` if the name is already taken by another user.
+    pub fn rename_user(&self, user_id: u32, username: &str) -> Result<bool> {
+        run(async {
+            let mut tx = self.pool.begin().await?;
+            let taken: Option<(u32,)> = sqlx::query_as("SELECT id FROM users WHERE username = ? AND id != ?")
+                .bind(username)
+                .bind(user_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+            if taken.is_some() {
+                return Ok(false);
+            }
+            sqlx::query("UPDATE users SET username = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+                .bind(username)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            Ok::<_, eyre::Error>(true)
+        })?
+    }
+
+    pub fn update_user_email(&self, user_id: u32, email: &str) -> Result<()> {
+        run(sqlx::query("UPDATE users SET email = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(email)
+            .bind(user_id)
+            .execute(&self.pool))??;
+        Ok(())
+    }
+
+    pub fn update_user_status(&self, user_id: u32, status: &str) -> Result<()> {
+        run(sqlx::query("UPDATE users SET status = ? WHERE id = ?").bind(status).bind(user_id).execute(&self.pool))??;
+        Ok(())
+    }
+
+    /// Returns the public custom data of a user as `(class name, serialized data)`, if any was set.
+    pub fn find_public_data(&self, user_id: u32) -> Result<Option<(String, Vec<u8>)>> {
+        let data: Option<(Option<String>, Option<Vec<u8>>)> = run(sqlx::query_as("SELECT public_data_type, public_data FROM users WHERE id = ?")
+            .bind(user_id)
+            .fetch_optional(&self.pool))??;
+        Ok(data.and_then(|(type_name, data)| type_name.zip(data)))
+    }
+
+    /// Returns the private custom data of a user as `(class name, serialized data)`, if any was set.
+    pub fn find_private_data(&self, user_id: u32) -> Result<Option<(String, Vec<u8>)>> {
+        let data: Option<(Option<String>, Option<Vec<u8>>)> = run(sqlx::query_as("SELECT private_data_type, private_data FROM users WHERE id = ?")
+            .bind(user_id)
+            .fetch_optional(&self.pool))??;
+        Ok(data.and_then(|(type_name, data)| type_name.zip(data)))
+    }
+
+    /// Stores the custom data of a user, each as `(class name, serialized data)`.
+    pub fn update_custom_data(&self, user_id: u32, public_data: (&str, &[u8]), private_data: (&str, &[u8])) -> Result<()> {
+        run(sqlx::query(
+            r"
+            UPDATE users
+            SET public_data_type = ?, public_data = ?, private_data_type = ?, private_data = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?
+            ",
+        )
+        .bind(public_data.0)
+        .bind(public_data.1)
+        .bind(private_data.0)
+        .bind(private_data.1)
+        .bind(user_id)
+        .execute(&self.pool))??;
+        Ok(())
+    }
+
+    /// Finds players whose name matches a SQL `LIKE` pattern, ordered by name.
+    pub fn find_users_by_name_like(&self, pattern: &str, offset: u32, limit: u32) -> Result<Vec<(u32, String)>> {
+        Ok(run(sqlx::query_as(
+            r"
+            SELECT id, username
+            FROM users
+            WHERE ubi_id IS NOT NULL AND username LIKE ?
+            ORDER BY username
+            LIMIT ? OFFSET ?
+            ",
+        )
+        .bind(pattern)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool))??)
+    }
+
+    /// Remembers the address a user logged in from, so that a later ban can cover it.
+    pub fn update_user_ip(&self, user_id: u32, ip: &str) -> Result<()> {
+        run(self.update_user_ip_async(user_id, ip))?
+    }
+
+    pub async fn update_user_ip_async(&self, user_id: u32, ip: &str) -> Result<()> {
+        sqlx::query("UPDATE users SET last_ip = ? WHERE id = ?").bind(ip).bind(user_id).execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Bans a user and their ubi id, and their last known address if `ban_ip` is set. Returns the id of the ban.
+    ///
+    /// `expires_at` is a timestamp in sqlite's format, `None` bans permanently.
+    pub async fn ban_user_async(&self, user_id: u32, reason: &str, banned_by: &str, expires_at: Option<&str>, ban_ip: bool) -> Result<i64> {
+        warn!(self.logger, "user {user_id} banned by {banned_by}: {reason}");
+        let result = sqlx::query(
+            r"
+            INSERT INTO user_bans (user_id, ubi_id, ip, reason, banned_by, expires_at)
+            SELECT id, ubi_id, CASE WHEN ? THEN last_ip END, ?, ?, ?
+            FROM users
+            WHERE id = ?
+            ",
+        )
+        .bind(ban_ip)
+        .bind(reason)
+        .bind(banned_by)
+        .bind(expires_at)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(eyre!("user {user_id} not found"));
+        }
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Lifts a ban. Returns whether it existed.
+    pub async fn unban_async(&self, ban_id: i64) -> Result<bool> {
+        let deleted = sqlx::query("DELETE FROM user_bans WHERE id = ?").bind(ban_id).execute(&self.pool).await?.rows_affected();
+        Ok(deleted > 0)
+    }
+
+    /// Returns the active ban covering a user, a ubi id or an address, permanent bans first.
+    ///
+    /// A user is also covered by bans of their ubi id, which outlive deleted accounts.
+    pub fn find_active_ban(&self, user_id: Option<u32>, ubi_id: Option<&str>, ip: Option<&str>) -> Result<Option<Ban>> {
+        run(self.find_active_ban_async(user_id, ubi_id, ip))?
+    }
+
+    pub async fn find_active_ban_async(&self, user_id: Option<u32>, ubi_id: Option<&str>, ip: Option<&str>) -> Result<Option<Ban>> {
+        Ok(sqlx::query_as(
+            r"
+            SELECT b.id, u.username, b.ubi_id, b.ip, b.reason, b.banned_by, b.created_at, b.expires_at
+            FROM user_bans AS b
+            LEFT JOIN users AS u ON u.id = b.user_id
+            WHERE (b.user_id = ? OR b.ubi_id = (SELECT ubi_id FROM users WHERE id = ?) OR b.ubi_id = ? OR b.ip = ?)
+                AND (b.expires_at IS NULL OR b.expires_at > CURRENT_TIMESTAMP)
+            ORDER BY b.expires_at IS NOT NULL, b.expires_at DESC
+            LIMIT 1
+            ",
+        )
+        .bind(user_id)
+        .bind(user_id)
+        .bind(ubi_id)
+        .bind(ip)
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// Lists all active bans, newest first.
+    pub async fn list_bans_async(&self) -> Result<Vec<Ban>> {
+        Ok(sqlx::query_as(
+            r"
+            SELECT b.id, u.username, b.ubi_id, b.ip, b.reason, b.banned_by, b.created_at, b.expires_at
+            FROM user_bans AS b
+            LEFT JOIN users AS u ON u.id = b.user_id
+            WHERE b.expires_at IS NULL OR b.expires_at > CURRENT_TIMESTAMP
+            ORDER BY b.created_at DESC, b.id DESC
+            ",
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Updates the known privileges from the config.
+    ///
+    /// Privileges missing from the config are kept for existing grants, but are no longer granted by default.
+    pub fn update_privileges(&self, privileges: &[PrivilegeConfig]) -> Result<()> {
+        run(async {
+            let mut tx = self.pool.begin().await?;
+            sqlx::query("UPDATE privileges SET is_default = 0").execute(&mut *tx).await?;
+            for privilege in privileges {
+                sqlx::query(
+                    r"
+                    INSERT INTO privileges (id, description, is_default) VALUES (?, ?, ?)
+                    ON CONFLICT (id) DO UPDATE SET description = excluded.description, is_default = excluded.is_default
+                    ",
+                )
+                .bind(privilege.id)
+                .bind(&privilege.description)
+                .bind(privilege.default)
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit().await?;
+            Ok::<_, eyre::Error>(())
+        })?
+    }
+
+    /// Lists all known privileges with the grants and revocations of a user, ordered by id.
+    pub fn list_user_privileges(&self, user_id: u32) -> Result<Vec<UserPrivilege>> {
+        run(self.list_user_privileges_async(user_id))?
+    }
+
+    pub async fn list_user_privileges_async(&self, user_id: u32) -> Result<Vec<UserPrivilege>> {
+        Ok(sqlx::query_as(
+            r"
+            SELECT p.id, p.description, p.is_default, up.granted, up.expires_at,
+                CAST(strftime('%s', up.expires_at) - strftime('%s', 'now') AS INTEGER) AS remaining_secs
+            FROM privileges AS p
+            LEFT JOIN user_privileges AS up ON up.privilege_id = p.id AND up.user_id = ?
+                AND (up.expires_at IS NULL OR up.expires_at > CURRENT_TIMESTAMP)
+            ORDER BY p.id
+            ",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Grants or revokes a privilege of a user, replacing an earlier grant or revocation.
+    ///
+    /// Unknown privileges are added with `description`, but aren't granted by default.
+    /// `expires_at` is a timestamp in sqlite's format, `None` never expires.
+    pub async fn set_user_privilege_async(&self, user_id: u32, privilege_id: u32, description: &str, granted: bool, expires_at: Option<&str>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT OR IGNORE INTO privileges (id, description) VALUES (?, ?)")
+            .bind(privilege_id)
+            .bind(description)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("INSERT OR REPLACE INTO user_privileges (user_id, privilege_id, granted, expires_at) VALUES (?, ?, ?, ?)")
+            .bind(user_id)
+            .bind(privilege_id)
+            .bind(granted)
+            .bind(expires_at)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Removes the grant or revocation of a privilege, so the user gets the default again. Returns whether there was one.
+    pub async fn reset_user_privilege_async(&self, user_id: u32, privilege_id: u32) -> Result<bool> {
+        let deleted = sqlx::query("DELETE FROM user_privileges WHERE user_id = ? AND privilege_id = ?")
+            .bind(user_id)
+            .bind(privilege_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(deleted > 0)
+    }
+
+    /// Returns the codes of the Uplay actions a user completed on a platform.
+    pub fn list_uplay_actions_completed(&self, user_id: u32, platform_code: &str) -> Result<Vec<String>> {
+        Ok(run(
+            sqlx::query_as("SELECT action_code FROM uplay_actions_completed WHERE user_id = ? AND platform_code = ? ORDER BY completed_at")
+                .bind(user_id)
+                .bind(platform_code)
+                .fetch_all(&self.pool),
+        )??
+        .into_iter()
+        .map(|r: (String,)| r.0)
+        .collect())
+    }
+
+    /// Marks a Uplay action as completed, earning its value in units. Returns whether it wasn't completed before.
+    pub fn complete_uplay_action(&self, user_id: u32, action_code: &str, platform_code: &str, value: i32) -> Result<bool> {
+        let inserted = run(
+            sqlx::query("INSERT OR IGNORE INTO uplay_actions_completed (user_id, action_code, platform_code, value) VALUES (?, ?, ?, ?)")
+                .bind(user_id)
+                .bind(action_code)
+                .bind(platform_code)
+                .bind(value)
+                .execute(&self.pool),
+        )??
+        .rows_affected();
+        Ok(inserted > 0)
+    }
+
+    /// Returns the codes of the Uplay rewards a user purchased on a platform.
+    pub fn list_uplay_rewards_purchased(&self, user_id: u32, platform_code: &str) -> Result<Vec<String>> {
+        Ok(run(
+            sqlx::query_as("SELECT reward_code FROM uplay_rewards_purchased WHERE user_id = ? AND platform_code = ? ORDER BY purchased_at")
+                .bind(user_id)
+                .bind(platform_code)
+                .fetch_all(&self.pool),
+        )??
+        .into_iter()
+        .map(|r: (String,)| r.0)
+        .collect())
+    }
+
+    /// Returns the units a user earned with Uplay actions and didn't spend on rewards yet.
+    pub fn uplay_balance(&self, user_id: u32) -> Result<i32> {
+        run(Self::uplay_balance_async(&self.pool, user_id))?
+    }
+
+    async fn uplay_balance_async<'e, E>(executor: E, user_id: u32) -> Result<i32>
+    where
+        E: Executor<'e, Database = sqlx::Sqlite>,
+    {
+        let (balance,): (i32,) = sqlx::query_as(
+            r"
+            SELECT
+                (SELECT COALESCE(SUM(value), 0) FROM uplay_actions_completed WHERE user_id = ?)
+                - (SELECT COALESCE(SUM(value), 0) FROM uplay_rewards_purchased WHERE user_id = ?)
+            ",
+        )
+        .bind(user_id)
+        .bind(user_id)
+        .fetch_one(executor)
+        .await?;
+        Ok(balance)
+    }
+
+    /// Purchases a Uplay reward with the units of a user.
+    ///
+    /// Returns the remaining balance, or `None` if the user can't afford the reward. Purchasing an owned reward again is free.
+    pub fn purchase_uplay_reward(&self, user_id: u32, reward_code: &str, platform_code: &str, value: i32) -> Result<Option<i32>> {
+        run(async {
+            let mut tx = self.pool.begin().await?;
+            let balance = Self::uplay_balance_async(&mut *tx, user_id).await?;
+            let (owned,): (bool,) = sqlx::query_as("SELECT EXISTS (SELECT 1 FROM uplay_rewards_purchased WHERE user_id = ? AND reward_code = ? AND platform_code = ?)")
+                .bind(user_id)
+                .bind(reward_code)
+                .bind(platform_code)
+                .fetch_one(&mut *tx)
+                .await?;
+            if owned {
+                return Ok(Some(balance));
+            }
+            if balance < value {
+                return Ok(None);
+            }
+            sqlx::query("INSERT INTO uplay_rewards_purchased (user_id, reward_code, platform_code, value) VALUES (?, ?, ?, ?)")
+                .bind(user_id)
+                .bind(reward_code)
+                .bind(platform_code)
+                .bind(value)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            Ok::<_, eyre::Error>(Some(balance - value))
+        })?
+    }
+
+    /// Returns the `(challenge id, period)` pairs of the challenges a user completed.
+    pub fn list_completed_challenges(&self, user_id: u32) -> Result<Vec<(u32, String)>> {
+        Ok(run(sqlx::query_as(
+            "SELECT challenge_id, period FROM challenge_progress WHERE user_id = ? AND completed_at IS NOT NULL",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool))??)
+    }
+
+    /// Lists the progress of a user in all challenges, latest first.
+    pub async fn list_challenge_progress_async(&self, user_id: u32) -> Result<Vec<ChallengeProgress>> {
+        Ok(sqlx::query_as(
+            r"
+            SELECT challenge_id, period, progress, completed_at, updated_at
+            FROM challenge_progress
+            WHERE user_id = ?
+            ORDER BY updated_at DESC, challenge_id
+            ",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Sets the progress of a user in a period of a challenge, which is completed once the progress reaches the target.
+    ///
+    /// Returns whether the challenge is completed.
+    pub async fn update_challenge_progress_async(&self, user_id: u32, challenge_id: u32, period: &str, progress: u32, target: u32) -> Result<bool> {
+        let completed = progress >= target;
+        sqlx::query(
+            r"
+            INSERT INTO challenge_progress (user_id, challenge_id, period, progress, completed_at)
+            VALUES (?, ?, ?, ?, CASE WHEN ? THEN CURRENT_TIMESTAMP END)
+            ON CONFLICT (user_id, challenge_id, period) DO UPDATE SET
+                progress = excluded.progress,
+                completed_at = CASE WHEN ? THEN COALESCE(completed_at, CURRENT_TIMESTAMP) END,
+                updated_at = CURRENT_TIMESTAMP
+            ",
+        )
+        .bind(user_id)
+        .bind(challenge_id)
+        .bind(period)
+        .bind(progress)
+        .bind(completed)
+        .bind(completed)
+        .execute(&self.pool)
+        .await?;
+        Ok(completed)
+    }
+
+    /// Stores the tracking tags a user sent, as `(tracking id, tag, attributes, delta time)`.
+    pub fn store_tracking_tags(&self, user_id: u32, tags: &[(u32, &str, &str, u32)]) -> Result<()> {
+        run(async {
+            let mut tx = self.pool.begin().await?;
+            for (tracking_id, tag, attributes, delta_time) in tags {
+                sqlx::query("INSERT INTO tracking_tags (user_id, tracking_id, tag, attributes, delta_time) VALUES (?, ?, ?, ?, ?)")
+                    .bind(user_id)
+                    .bind(tracking_id)
+                    .bind(tag)
+                    .bind(attributes)
+                    .bind(delta_time)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            tx.commit().await?;
+            Ok::<_, eyre::Error>(())
+        })?
+    }
+
+    /// Deletes tracking tags older than `retention_days` and all but the newest `max_tags`. 0 disables either limit.
+    ///
+    /// Returns the number of deleted tags.
+    pub fn prune_tracking_tags(&self, retention_days: u32, max_tags: u32) -> Result<u64> {
+        run(async {
+            let mut deleted = 0;
+            if retention_days > 0 {
+                deleted += sqlx::query("DELETE FROM tracking_tags WHERE received_at < datetime('now', ?)")
+                    .bind(format!("-{retention_days} days"))
+                    .execute(&self.pool)
+                    .await?
+                    .rows_affected();
+            }
+            if max_tags > 0 {
+                deleted += sqlx::query("DELETE FROM tracking_tags WHERE id <= (SELECT id FROM tracking_tags ORDER BY id DESC LIMIT 1 OFFSET ?)")
+                    .bind(max_tags)
+                    .execute(&self.pool)
+                    .await?
+                    .rows_affected();
+            }
+            Ok::<_, eyre::Error>(deleted)
+        })?
+    }
+
+    fn push_tracking_filter<'a>(builder: &mut sqlx::QueryBuilder<'a, sqlx::Sqlite>, filter: &TrackingFilter<'a>) {
+        builder.push(" FROM tracking_tags AS t LEFT JOIN users AS u ON u.id = t.user_id WHERE 1 = 1");
+        if let Some(tag) = filter.tag {
+            builder.push(" AND t.tag = ").push_bind(tag);
+        }
+        if let Some(user_id) = filter.user_id {
+            builder.push(" AND t.user_id = ").push_bind(user_id);
+        }
+        if let Some(since) = filter.since {
+            builder.push(" AND t.received_at >= ").push_bind(since);
+        }
+        if let Some(until) = filter.until {
+            builder.push(" AND t.received_at < ").push_bind(until);
+        }
+    }
+
+    /// Counts the tracking tags matching the filter, grouped by the requested columns.
+    ///
+    /// Ordered by time, then tag, then user.
+    pub async fn aggregate_tracking_tags_async(&self, filter: &TrackingFilter<'_>, by_tag: bool, by_user: bool, interval: TrackingInterval) -> Result<Vec<TrackingCount>> {
+        let mut builder = sqlx::QueryBuilder::new("SELECT ");
+        builder.push(if by_tag { "t.tag" } else { "NULL AS tag" });
+        // the user id is only selected to group by it
+        builder.push(if by_user {
+            ", t.user_id, u.username, u.ubi_id"
+        } else {
+            ", NULL AS user_id, NULL AS username, NULL AS ubi_id"
+        });
+        builder.push(match interval {
+            TrackingInterval::None => ", NULL",
+            TrackingInterval::Hour => ", strftime('%Y-%m-%d %H:00:00', t.received_at)",
+            TrackingInterval::Day => ", strftime('%Y-%m-%d 00:00:00', t.received_at)",
+        });
+        builder.push(" AS time, COUNT(*) AS count");
+        Self::push_tracking_filter(&mut builder, filter);
+        builder.push(" GROUP BY 1, 2, 5 ORDER BY 5, 1, 2");
+        let query = builder.build_query_as::<TrackingCount>();
+        debug!(self.logger, "SQL: {}", query.sql());
+        Ok(query.fetch_all(&self.pool).await?)
+    }
+
+    /// Lists the newest tracking tags matching the filter.
+    pub async fn list_tracking_tags_async(&self, filter: &TrackingFilter<'_>, limit: u32) -> Result<Vec<StoredTrackingTag>> {
+        let mut builder = sqlx::QueryBuilder::new("SELECT t.id, u.username, u.ubi_id, t.tag, t.attributes, t.delta_time, t.received_at");
+        Self::push_tracking_filter(&mut builder, filter);
+        builder.push(" ORDER BY t.id DESC LIMIT ").push_bind(limit);
+        Ok(builder.build_query_as().fetch_all(&self.pool).await?)
+    }
+
     pub fn create_user_session(&self, user_id: u32, key: &[u8]) -> Result<()> {
         use std::fmt::Write;
         let mut s = String::new();
@@ -200,19 +743,64 @@ impl Storage {
                 .execute(&self.pool)
                 .await?;
 
-            sqlx::query("UPDATE users SET is_online=1 WHERE id=?").bind(user_id).execute(&self.pool).await
+            sqlx::query("UPDATE users SET is_online=1, previous_login=current_login, current_login=CURRENT_TIMESTAMP WHERE id=?")
+                .bind(user_id)
+                .execute(&self.pool)
+                .await
         })??;
 
         Ok(())
     }
 
-    pub fn delete_user_session(&self, user_id: u32) -> Result<()> {
+    /// Checks if a player is logged in.
+    pub fn has_user_session(&self, user_id: u32) -> Result<bool> {
+        let (exists,): (bool,) = run(sqlx::query_as("SELECT EXISTS (SELECT 1 FROM user_sessions WHERE user_id = ?)")
+            .bind(user_id)
+            .fetch_one(&self.pool))??;
+        Ok(exists)
+    }
+
+    /// Remembers the locale the game of a user runs with, for the session and as the default for the next ones.
+    pub fn update_session_locale(&self, user_id: u32, locale: &str) -> Result<()> {
         run(async {
-            sqlx::query("DELETE FROM station_urls WHERE user_id = ?").bind(user_id).execute(&self.pool).await?;
-            sqlx::query("UPDATE game_sessions SET destroyed_at=CURRENT_TIMESTAMP WHERE creator_id = ?")
+            let mut tx = self.pool.begin().await?;
+            sqlx::query("UPDATE user_sessions SET locale = ? WHERE user_id = ?")
+                .bind(locale)
                 .bind(user_id)
-                .execute(&self.pool)
+                .execute(&mut *tx)
                 .await?;
+            sqlx::query("UPDATE users SET locale = ? WHERE id = ?").bind(locale).bind(user_id).execute(&mut *tx).await?;
+            tx.commit().await?;
+            Ok::<_, eyre::Error>(())
+        })?
+    }
+
+    /// Returns the locale of the latest session of a user, or the one they used last. `None` if the game never set one.
+    pub fn find_user_locale(&self, user_id: u32) -> Result<Option<String>> {
+        run(self.find_user_locale_async(user_id))?
+    }
+
+    pub async fn find_user_locale_async(&self, user_id: u32) -> Result<Option<String>> {
+        let locale: Option<(Option<String>,)> = sqlx::query_as(
+            r"
+            SELECT COALESCE(
+                (SELECT locale FROM user_sessions WHERE user_id = users.id AND locale IS NOT NULL ORDER BY created_at DESC LIMIT 1),
+                locale
+            )
+            FROM users
+            WHERE id = ?
+            ",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(locale.and_then(|r| r.0))
+    }
+
+    pub fn delete_user_session(&self, user_id: u32) -> Result<()> {
+        run(async {
+            sqlx::query("DELETE FROM station_urls WHERE user_id = ?").bind(user_id).execute(&self.pool).await?;
+            self.leave_all_game_sessions_async(user_id).await?;
             sqlx::query("DELETE FROM user_sessions WHERE user_id = ?")
                 .bind(user_id)
                 .execute(&self.pool)
@@ -221,7 +809,12 @@ impl Storage {
                 // sqlx::query("UPDATE users SET is_online=0 WHERE id=?")
                 //     .bind(user_id)
                 //     .execute(&self.pool)
-                .await
+                .await?;
+            sqlx::query("UPDATE users SET last_logout=CURRENT_TIMESTAMP WHERE id=?")
+                .bind(user_id)
+                .execute(&self.pool)
+                .await?;
+            Ok::<_, eyre::Error>(())
         })??;
 
         Ok(())
@@ -240,67 +833,87 @@ impl Storage {
         Ok(())
     }
 
-    pub fn create_game_session(&self, user_id: u32, type_id: u32, attributes: String) -> Result<u32> {
-        let id = run(sqlx::query("INSERT INTO game_sessions (type_id, creator_id, attributes) VALUES (?, ?, ?)")
+    pub fn create_game_session(&self, user_id: u32, type_id: u32, attributes: &[(u32, u32)]) -> Result<u32> {
+        run(self.create_game_session_async(user_id, type_id, attributes))?
+    }
+
+    pub async fn create_game_session_async(&self, user_id: u32, type_id: u32, attributes: &[(u32, u32)]) -> Result<u32> {
+        let mut tx = self.pool.begin().await?;
+        let id = sqlx::query("INSERT INTO game_sessions (type_id, creator_id) VALUES (?, ?)")
             .bind(type_id)
             .bind(user_id)
-            .bind(attributes)
-            .execute(&self.pool))??
-        .last_insert_rowid();
-
+            .execute(&mut *tx)
+            .await?
+            .last_insert_rowid();
         #[allow(clippy::cast_possible_truncation)]
         #[allow(clippy::cast_sign_loss)]
-        Ok(id as u32)
+        let id = id as u32;
+
+        Self::set_attributes(&mut tx, id, attributes).await?;
+        tx.commit().await?;
+
+        Ok(id)
     }
 
-    pub fn update_game_session(&self, type_id: u32, game_id: u32, attributes: String) -> Result<()> {
-        let _id = run(sqlx::query("UPDATE game_sessions SET attributes = ? WHERE id = ? AND type_id = ?")
-            .bind(attributes)
-            .bind(game_id)
-            .bind(type_id)
-            .execute(&self.pool))??;
+    pub fn update_game_session(&self, type_id: u32, game_id: u32, attributes: &[(u32, u32)]) -> Result<()> {
+        run(async {
+            let mut tx = self.pool.begin().await?;
+            let exists: Option<(u32,)> = sqlx::query_as("SELECT id FROM game_sessions WHERE id = ? AND type_id = ?")
+                .bind(game_id)
+                .bind(type_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+            if exists.is_none() {
+                warn!(self.logger, "Game session {game_id} of type {type_id} not found");
+                return Ok(());
+            }
+            Self::set_attributes(&mut tx, game_id, attributes).await?;
+            tx.commit().await?;
+            Ok::<_, eyre::Error>(())
+        })?
+    }
 
+    /// Inserts or overwrites the given attributes of a game session.
+    async fn set_attributes(tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>, game_id: u32, attributes: &[(u32, u32)]) -> sqlx::Result<()> {
+        if attributes.is_empty() {
+            return Ok(());
+        }
+        let mut builder = sqlx::QueryBuilder::new("INSERT OR REPLACE INTO game_session_attributes (game_id, attr_id, value) ");
+        builder.push_values(attributes, |mut b, (attr_id, value)| {
+            b.push_bind(game_id).push_bind(*attr_id).push_bind(*value);
+        });
+        builder.build().execute(&mut **tx).await?;
         Ok(())
     }
 
-    pub fn search_sessions(&self, type_id: u32, exclude_user: Option<u32>) -> Result<Vec<GameSession>> {
-        let mut sessions: Vec<GameSession> = if let Some(uid) = exclude_user {
-            run(sqlx::query_as(
-                "SELECT type_id as session_type, id as session_id, creator_id, attributes FROM game_sessions WHERE type_id = ? AND creator_id != ? AND destroyed_at IS NULL",
-            )
-            .bind(type_id)
-            .bind(uid)
-            .fetch_all(&self.pool))??
-        } else {
-            run(
-                sqlx::query_as("SELECT type_id as session_type, id as session_id FROM game_sessions WHERE type_id = ? AND destroyed_at IS NULL")
-                    .bind(type_id)
-                    .fetch_all(&self.pool),
-            )??
-        };
-
-        for session in &mut sessions {
-            session.participants = run(
-                sqlx::query_as("SELECT user_id, username as name FROM participants p, users u WHERE u.id = user_id AND game_id = ?")
-                    .bind(session.session_id)
-                    .fetch_all(&self.pool),
-            )??;
-
-            for participant in &mut session.participants {
-                // Is this needed? Games seems to try to connect to itself
-                if matches!(exclude_user, Some(pid) if pid == participant.user_id) {
-                    continue;
+    /// Searches for sessions of the given type whose attributes match all of `filters`.
+    pub fn search_sessions(&self, type_id: u32, exclude_user: Option<u32>, filters: &[(u32, u32)]) -> Result<Vec<GameSession>> {
+        let mut builder = sqlx::QueryBuilder::new("SELECT type_id as session_type, id as session_id, creator_id, CAST(strftime('%s', 'now') - strftime('%s', created_at) AS INTEGER) as age_secs FROM game_sessions WHERE destroyed_at IS NULL AND type_id = ");
+        builder.push_bind(type_id);
+        if let Some(uid) = exclude_user {
+            builder.push(" AND creator_id != ").push_bind(uid);
+        }
+        for (attr_id, value) in filters {
+            builder
+                .push(" AND id IN (SELECT game_id FROM game_session_attributes WHERE attr_id = ")
+                .push_bind(*attr_id)
+                .push(" AND value = ")
+                .push_bind(*value)
+                .push(")");
+        }
+        let query = builder.build_query_as::<GameSession>();
+        debug!(self.logger, "SQL: {}", query.sql());
+        run(async {
+            let mut sessions: Vec<GameSession> = query.fetch_all(&self.pool).await?;
+            self.load_session_details_async(&mut sessions).await?;
+            // Is this needed? Games seems to try to connect to itself
+            for participant in sessions.iter_mut().flat_map(|s| &mut s.participants) {
+                if exclude_user == Some(participant.user_id) {
+                    participant.station_urls.clear();
                 }
-                participant.station_urls = run(sqlx::query_as("SELECT url FROM station_urls WHERE user_id = ?")
-                    .bind(participant.user_id)
-                    .fetch_all(&self.pool))??
-                .into_iter()
-                .map(|r: (String,)| r.0)
-                .collect();
             }
-        }
-
-        Ok(sessions)
+            Ok::<_, eyre::Error>(sessions)
+        })?
     }
 
     pub fn add_participants(&self, _type_id: u32, session_id: u32, private_participants: Vec<u32>, public_participants: Vec<u32>) -> Result<()> {
@@ -328,6 +941,7 @@ impl Storage {
         for p in participants {
             stmt.query().bind(session_id).bind(p).execute(&self.pool).await?;
         }
+        self.cleanup_game_session_async(session_id).await?;
         Ok(())
     }
 
@@ -346,50 +960,606 @@ impl Storage {
         .rows_affected())
     }
 
-    pub fn register_urls(&self, user_id: u32, urls: Vec<String>) -> Result<()> {
-        if urls.is_empty() {
-            warn!(self.logger, "Empty url list");
-            return Ok(());
-        }
-
-        let mut builder = sqlx::QueryBuilder::new("INSERT OR REPLACE INTO station_urls (user_id, url) ");
-
-        builder.push_values(urls.into_iter().map(|url| (user_id, url)), |mut b, (user_id, url)| {
-            b.push_bind(user_id).push_bind(url);
-        });
-        let query = builder.build();
-        debug!(self.logger, "SQL: {}", query.sql());
-        run(query.execute(&self.pool))??;
-        Ok(())
+    /// Checks if two players are in the same active game session, as host or participant.
+    pub fn share_game_session(&self, user_id: u32, peer_id: u32) -> Result<bool> {
+        let (shared,): (bool,) = run(sqlx::query_as(
+            "SELECT EXISTS (
+                SELECT 1 FROM game_sessions g WHERE destroyed_at IS NULL
+                    AND (creator_id = ? OR EXISTS (SELECT 1 FROM participants WHERE game_id = g.id AND user_id = ?))
+                    AND (creator_id = ? OR EXISTS (SELECT 1 FROM participants WHERE game_id = g.id AND user_id = ?))
+            )",
+        )
+        .bind(user_id)
+        .bind(user_id)
+        .bind(peer_id)
+        .bind(peer_id)
+        .fetch_one(&self.pool))??;
+        Ok(shared)
     }
 
-    pub async fn list_users_async(&self) -> Result<Vec<User>> {
-        Ok(sqlx::query_as("SELECT id, username, ubi_id, is_online FROM users WHERE ubi_id IS NOT NULL")
-            .fetch_all(&self.pool)
-            .await?)
+    /// Removes a user from a game session.
+    ///
+    /// If the host left, the longest-joined remaining participant becomes the new host. Sessions without
+    /// any participants are destroyed.
+    pub fn leave_game_session(&self, session_id: u32, user_id: u32) -> Result<()> {
+        run(self.leave_game_session_async(session_id, user_id))?
     }
 
-    pub async fn add_invite_async(&self, sender_id: u32, receiver_id: u32) -> Result<i64> {
-        info!(self.logger, "sending invite from {sender_id} to {receiver_id}");
-        Ok(sqlx::query("INSERT INTO invites (sender, receiver) VALUES (?, ?)")
-            .bind(sender_id)
-            .bind(receiver_id)
+    pub async fn leave_game_session_async(&self, session_id: u32, user_id: u32) -> Result<()> {
+        sqlx::query("DELETE FROM participants WHERE game_id = ? AND user_id = ?")
+            .bind(session_id)
+            .bind(user_id)
             .execute(&self.pool)
-            .await?
-            .last_insert_rowid())
+            .await?;
+        self.cleanup_game_session_async(session_id).await?;
+        Ok(())
     }
 
-    pub async fn take_invite_async(&self, user_id: u32) -> Result<Option<Invite>> {
-        let row: Option<Invite> = sqlx::query_as("SELECT rowid as id, sender, receiver FROM invites WHERE receiver = ?")
-            .bind(user_id)
-            .fetch_optional(&self.pool)
-            .await?;
-        if let Some(invite) = row {
-            sqlx::query("DELETE FROM invites WHERE rowid = ?").bind(invite.id).execute(&self.pool).await?;
-            Ok(Some(invite))
+    /// Removes a user from all game sessions they participate in or host.
+    pub async fn leave_all_game_sessions_async(&self, user_id: u32) -> Result<()> {
+        let session_ids: Vec<(u32,)> = sqlx::query_as(
+            r"
+            SELECT id
+            FROM game_sessions
+            WHERE destroyed_at IS NULL AND (
+                creator_id = ?
+                OR id IN (SELECT game_id FROM participants WHERE user_id = ?)
+            )
+            ",
+        )
+        .bind(user_id)
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        for (session_id,) in session_ids {
+            info!(self.logger, "Removing user {user_id} from game session {session_id}");
+            self.leave_game_session_async(session_id, user_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Migrates the host of a session whose creator left and destroys it if nobody is left.
+    ///
+    /// Returns whether the session was destroyed.
+    async fn cleanup_game_session_async(&self, session_id: u32) -> Result<bool> {
+        let next_host: Option<(u32,)> = sqlx::query_as("SELECT user_id FROM participants WHERE game_id = ? ORDER BY rowid LIMIT 1")
+            .bind(session_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        if let Some((next_host,)) = next_host {
+            let migrated = sqlx::query(
+                r"
+                UPDATE game_sessions
+                SET creator_id = ?
+                WHERE id = ? AND destroyed_at IS NULL
+                    AND creator_id NOT IN (SELECT user_id FROM participants WHERE game_id = ?)
+                ",
+            )
+            .bind(next_host)
+            .bind(session_id)
+            .bind(session_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+            if migrated > 0 {
+                info!(self.logger, "Game session {session_id} migrated to host {next_host}");
+            }
+            return Ok(false);
+        }
+
+        let destroyed = sqlx::query("UPDATE game_sessions SET destroyed_at=CURRENT_TIMESTAMP WHERE id = ? AND destroyed_at IS NULL")
+            .bind(session_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        if destroyed > 0 {
+            info!(self.logger, "Destroyed empty game session {session_id}");
+        }
+        Ok(destroyed > 0)
+    }
+
+    /// Removes participants without an active user session from all game sessions and destroys
+    /// sessions that end up empty.
+    ///
+    /// Returns the number of destroyed sessions.
+    pub fn reap_game_sessions(&self) -> Result<u64> {
+        run(async {
+            let removed = sqlx::query(
+                r"
+                DELETE FROM participants
+                WHERE user_id NOT IN (SELECT user_id FROM user_sessions)
+                    AND game_id IN (SELECT id FROM game_sessions WHERE destroyed_at IS NULL)
+                ",
+            )
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+            if removed > 0 {
+                info!(self.logger, "Removed {removed} offline participants from game sessions");
+            }
+
+            // give freshly created sessions some time to get their participants added
+            let session_ids: Vec<(u32,)> = sqlx::query_as("SELECT id FROM game_sessions WHERE destroyed_at IS NULL AND created_at < datetime('now', '-1 minute')")
+                .fetch_all(&self.pool)
+                .await?;
+            let mut destroyed = 0;
+            for (session_id,) in session_ids {
+                if self.cleanup_game_session_async(session_id).await? {
+                    destroyed += 1;
+                }
+            }
+            Ok::<_, eyre::Error>(destroyed)
+        })?
+    }
+
+    /// Moves the creation of a game session into the past, so the reaper doesn't skip it.
+    #[cfg(test)]
+    pub fn age_game_session(&self, session_id: u32, secs: u32) -> Result<()> {
+        run(sqlx::query("UPDATE game_sessions SET created_at = datetime('now', '-' || ? || ' seconds') WHERE id = ?")
+            .bind(secs)
+            .bind(session_id)
+            .execute(&self.pool))??;
+        Ok(())
+    }
+
+    /// Checks that the database answers queries.
+    pub fn ping(&self) -> Result<()> {
+        run(sqlx::query("SELECT 1").execute(&self.pool))??;
+        Ok(())
+    }
+
+    /// Runs the quick integrity check of `SQLite`. Returns whether the database is consistent.
+    pub fn check_integrity(&self) -> Result<bool> {
+        let (result,): (String,) = run(sqlx::query_as("PRAGMA quick_check").fetch_one(&self.pool))??;
+        Ok(result == "ok")
+    }
+
+    /// Returns the number of running game sessions and of users online.
+    pub fn count_sessions(&self) -> Result<(u32, u32)> {
+        Ok(run(sqlx::query_as(
+            "SELECT (SELECT COUNT(*) FROM game_sessions WHERE destroyed_at IS NULL), (SELECT COUNT(*) FROM user_sessions)",
+        )
+        .fetch_one(&self.pool))??)
+    }
+
+    /// Returns the skill ratings of the given users, their value of a stat summed over the contexts of its stat board.
+    /// Users that didn't write the stat are missing from the result.
+    pub fn find_skill_ratings(&self, board_id: u32, stat_id: u32, user_ids: &[u32]) -> Result<HashMap<u32, f64>> {
+        if user_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let mut builder = sqlx::QueryBuilder::new("SELECT user_id, CAST(SUM(value) AS REAL) FROM player_stats WHERE board_id = ");
+        builder.push_bind(board_id).push(" AND stat_id = ").push_bind(stat_id).push(" AND user_id IN (");
+        let mut separated = builder.separated(", ");
+        for user_id in user_ids {
+            separated.push_bind(*user_id);
+        }
+        separated.push_unseparated(") GROUP BY user_id");
+        let ratings: Vec<(u32, f64)> = run(builder.build_query_as().fetch_all(&self.pool))??;
+        Ok(ratings.into_iter().collect())
+    }
+
+    /// Inserts or overwrites stats of a user in a context of a stat board and adds them to the stats history.
+    pub fn store_player_stats(&self, user_id: u32, board_id: u32, context_id: u32, stats: &[(u32, i64)]) -> Result<()> {
+        if stats.is_empty() {
+            return Ok(());
+        }
+        run(async {
+            let mut tx = self.pool.begin().await?;
+            for table in ["INSERT OR REPLACE INTO player_stats", "INSERT INTO player_stats_history"] {
+                let mut builder = sqlx::QueryBuilder::new(table);
+                builder.push(" (user_id, board_id, context_id, stat_id, value) ");
+                builder.push_values(stats, |mut b, (stat_id, value)| {
+                    b.push_bind(user_id).push_bind(board_id).push_bind(context_id).push_bind(*stat_id).push_bind(*value);
+                });
+                builder.build().execute(&mut *tx).await?;
+            }
+            tx.commit().await?;
+            Ok::<_, eyre::Error>(())
+        })?
+    }
+
+    /// Returns how much a stat grew for each user between two times, summed over the contexts of the stat board.
+    ///
+    /// The times are SQL timestamps like `2026-10-01 00:00:00`. The growth is computed from the stats history: the last
+    /// value written before `end` minus the last value written before `start`. Users who didn't write the stat before
+    /// `end` are left out.
+    pub async fn list_stat_gains_async(&self, board_id: u32, stat_id: u32, start: &str, end: &str) -> Result<Vec<(u32, i64)>> {
+        // SQLite takes the bare value column from the row with the maximum rowid, the last one written
+        let last_values = "SELECT user_id, context_id, value, MAX(rowid) FROM player_stats_history \
+                           WHERE board_id = ? AND stat_id = ? AND recorded_at < ? GROUP BY user_id, context_id";
+        Ok(sqlx::query_as(&format!(
+            "SELECT e.user_id, SUM(e.value - COALESCE(s.value, 0)) FROM ({last_values}) AS e \
+             LEFT JOIN ({last_values}) AS s ON s.user_id = e.user_id AND s.context_id = e.context_id \
+             GROUP BY e.user_id ORDER BY e.user_id"
+        ))
+        .bind(board_id)
+        .bind(stat_id)
+        .bind(end)
+        .bind(board_id)
+        .bind(stat_id)
+        .bind(start)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Deletes the stats history written before `before`, except the last value of every stat before then.
+    ///
+    /// `before` is an SQL timestamp like `2026-10-01 00:00:00`. The kept values are the starting points of seasons
+    /// that start later, so their gains stay exact.
+    pub fn prune_stats_history(&self, before: &str) -> Result<u64> {
+        Ok(run(sqlx::query(
+            "DELETE FROM player_stats_history WHERE recorded_at < ? AND rowid NOT IN \
+             (SELECT MAX(rowid) FROM player_stats_history WHERE recorded_at < ? GROUP BY user_id, board_id, context_id, stat_id)",
+        )
+        .bind(before)
+        .bind(before)
+        .execute(&self.pool))??
+        .rows_affected())
+    }
+
+    /// Returns a stat of the given users in all contexts of a stat board.
+    pub fn list_player_stats(&self, user_ids: &[u32], board_id: u32, stat_id: u32) -> Result<Vec<PlayerStat>> {
+        if user_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut builder = sqlx::QueryBuilder::new("SELECT user_id, context_id, value FROM player_stats WHERE board_id = ");
+        builder.push_bind(board_id).push(" AND stat_id = ").push_bind(stat_id).push(" AND user_id IN (");
+        let mut separated = builder.separated(", ");
+        for user_id in user_ids {
+            separated.push_bind(*user_id);
+        }
+        separated.push_unseparated(") ORDER BY context_id, user_id");
+        Ok(run(builder.build_query_as().fetch_all(&self.pool))??)
+    }
+
+    pub fn register_urls(&self, user_id: u32, urls: Vec<String>) -> Result<()> {
+        if urls.is_empty() {
+            warn!(self.logger, "Empty url list");
+            return Ok(());
+        }
+
+        run(async {
+            // the latest registration replaces the urls, so outdated addresses aren't handed to peers
+            let mut tx = self.pool.begin().await?;
+            sqlx::query("DELETE FROM station_urls WHERE user_id = ?").bind(user_id).execute(&mut *tx).await?;
+
+            let mut builder = sqlx::QueryBuilder::new("INSERT OR REPLACE INTO station_urls (user_id, url) ");
+            builder.push_values(urls.into_iter().map(|url| (user_id, url)), |mut b, (user_id, url)| {
+                b.push_bind(user_id).push_bind(url);
+            });
+            let query = builder.build();
+            debug!(self.logger, "SQL: {}", query.sql());
+            query.execute(&mut *tx).await?;
+            tx.commit().await?;
+            Ok::<_, eyre::Error>(())
+        })??;
+        Ok(())
+    }
+
+    pub async fn list_users_async(&self) -> Result<Vec<User>> {
+        Ok(sqlx::query_as("SELECT id, username, ubi_id, is_online FROM users WHERE ubi_id IS NOT NULL")
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    pub async fn add_invite_async(&self, sender_id: u32, receiver_id: u32) -> Result<i64> {
+        info!(self.logger, "sending invite from {sender_id} to {receiver_id}");
+        // attach the session the sender is currently in, so the game can list the invite as well
+        let session: Option<(u32, u32)> = sqlx::query_as(
+            r"
+            SELECT g.type_id, g.id
+            FROM game_sessions AS g, participants AS p
+            WHERE p.game_id = g.id AND p.user_id = ? AND g.destroyed_at IS NULL
+            ORDER BY g.id DESC
+            LIMIT 1
+            ",
+        )
+        .bind(sender_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        let mut tx = self.pool.begin().await?;
+        let id = sqlx::query("INSERT INTO invites (sender, receiver, session_type, session_id) VALUES (?, ?, ?, ?)")
+            .bind(sender_id)
+            .bind(receiver_id)
+            .bind(session.map(|s| s.0))
+            .bind(session.map(|s| s.1))
+            .execute(&mut *tx)
+            .await?
+            .last_insert_rowid();
+        offline_invite_notifications(sender_id, session.unwrap_or_default(), &[receiver_id], "")
+            .build()
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    pub async fn take_invite_async(&self, user_id: u32) -> Result<Option<Invite>> {
+        let row: Option<Invite> = sqlx::query_as(
+            r"
+            SELECT rowid as id, sender, receiver, session_type, session_id, message, CAST(created AS TEXT) as created
+            FROM invites
+            WHERE receiver = ? AND status = 0 AND delivered = 0
+            ORDER BY rowid
+            LIMIT 1
+            ",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(invite) = row else {
+            return Ok(None);
+        };
+        if invite.session_id.is_some() {
+            // keep session invites around, the game still needs to accept or decline them
+            sqlx::query("UPDATE invites SET delivered = 1 WHERE rowid = ?").bind(invite.id).execute(&self.pool).await?;
         } else {
-            Ok(None)
+            sqlx::query("DELETE FROM invites WHERE rowid = ?").bind(invite.id).execute(&self.pool).await?;
+        }
+        Ok(Some(invite))
+    }
+
+    /// Stores a notification for a user, delivered when the game polls for notifications.
+    pub async fn add_notification_async(&self, user_id: u32, sender_id: Option<u32>, kind: NotificationKind, params: (u32, u32), text: &str) -> Result<i64> {
+        Ok(
+            sqlx::query("INSERT INTO notifications (user_id, sender_id, kind, param1, param2, text) VALUES (?, ?, ?, ?, ?, ?)")
+                .bind(user_id)
+                .bind(sender_id)
+                .bind(kind)
+                .bind(params.0)
+                .bind(params.1)
+                .bind(text)
+                .execute(&self.pool)
+                .await?
+                .last_insert_rowid(),
+        )
+    }
+
+    /// Returns up to `limit` unread notifications of a user, oldest first. `kinds` restricts the notifications to some
+    /// kinds.
+    ///
+    /// Also returns how many unread notifications are left after these.
+    pub fn unread_notifications(&self, user_id: u32, kinds: Option<&[NotificationKind]>, limit: u32) -> Result<(Vec<Notification>, u32)> {
+        if kinds.is_some_and(<[_]>::is_empty) {
+            return Ok((Vec::new(), 0));
+        }
+        let filter = |builder: &mut sqlx::QueryBuilder<'_, sqlx::Sqlite>| {
+            builder.push(" WHERE read_at IS NULL AND user_id = ").push_bind(user_id);
+            if let Some(kinds) = kinds {
+                builder.push(" AND kind IN (");
+                let mut separated = builder.separated(", ");
+                for kind in kinds {
+                    separated.push_bind(*kind);
+                }
+                separated.push_unseparated(")");
+            }
+        };
+        run(async {
+            let mut tx = self.pool.begin().await?;
+            let mut builder = sqlx::QueryBuilder::new("SELECT id, sender_id, kind, param1, param2, text, created_at, read_at FROM notifications");
+            filter(&mut builder);
+            builder.push(" ORDER BY id LIMIT ").push_bind(limit);
+            let notifications: Vec<Notification> = builder.build_query_as().fetch_all(&mut *tx).await?;
+
+            let mut builder = sqlx::QueryBuilder::new("SELECT COUNT(*) FROM notifications");
+            filter(&mut builder);
+            let (unread,): (u32,) = builder.build_query_as().fetch_one(&mut *tx).await?;
+            tx.commit().await?;
+            #[allow(clippy::cast_possible_truncation)]
+            let remaining = unread.saturating_sub(notifications.len() as u32);
+            Ok::<_, eyre::Error>((notifications, remaining))
+        })?
+    }
+
+    /// Marks notifications of a user read.
+    pub fn mark_notifications_read(&self, user_id: u32, notification_ids: &[i64]) -> Result<()> {
+        if notification_ids.is_empty() {
+            return Ok(());
+        }
+        let mut builder = sqlx::QueryBuilder::new("UPDATE notifications SET read_at = CURRENT_TIMESTAMP WHERE read_at IS NULL AND user_id = ");
+        builder.push_bind(user_id).push(" AND id IN (");
+        let mut separated = builder.separated(", ");
+        for id in notification_ids {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(")");
+        run(builder.build().execute(&self.pool))??;
+        Ok(())
+    }
+
+    /// Returns the latest notifications of a user, read or not.
+    pub async fn list_notifications_async(&self, user_id: u32, limit: u32) -> Result<Vec<Notification>> {
+        Ok(
+            sqlx::query_as("SELECT id, sender_id, kind, param1, param2, text, created_at, read_at FROM notifications WHERE user_id = ? ORDER BY id DESC LIMIT ?")
+                .bind(user_id)
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await?,
+        )
+    }
+
+    /// Counts a NAT probe between a player and each of `peer_ids`.
+    pub fn record_nat_probes(&self, user_id: u32, peer_ids: &[u32]) -> Result<()> {
+        let pairs = peer_ids
+            .iter()
+            .filter(|peer_id| **peer_id != user_id)
+            .map(|peer_id| nat_pair(user_id, *peer_id))
+            .collect::<Vec<_>>();
+        if pairs.is_empty() {
+            return Ok(());
+        }
+        let mut builder = sqlx::QueryBuilder::new("INSERT INTO nat_traversal_results (user_a, user_b, probes) ");
+        builder.push_values(pairs, |mut b, (user_a, user_b)| {
+            b.push_bind(user_a).push_bind(user_b).push_bind(1);
+        });
+        builder.push(" ON CONFLICT (user_a, user_b) DO UPDATE SET probes = probes + 1, updated_at = CURRENT_TIMESTAMP");
+        run(builder.build().execute(&self.pool))??;
+        Ok(())
+    }
+
+    /// Records whether a player reached a peer. A success resets the failure streak of the pair, a failure more than
+    /// `window` seconds after the last result starts a new one.
+    pub fn record_nat_result(&self, user_id: u32, peer_id: u32, success: bool, window: u64) -> Result<()> {
+        if user_id == peer_id {
+            return Ok(());
+        }
+        let (user_a, user_b) = nat_pair(user_id, peer_id);
+        run(sqlx::query(
+            "INSERT INTO nat_traversal_results (user_a, user_b, successes, failures, failure_streak) VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT (user_a, user_b) DO UPDATE SET
+                successes = successes + excluded.successes,
+                failures = failures + excluded.failures,
+                failure_streak = CASE
+                    WHEN excluded.successes > 0 THEN 0
+                    WHEN updated_at < datetime('now', ?) THEN 1
+                    ELSE failure_streak + 1
+                END,
+                updated_at = CURRENT_TIMESTAMP",
+        )
+        .bind(user_a)
+        .bind(user_b)
+        .bind(u32::from(success))
+        .bind(u32::from(!success))
+        .bind(u32::from(!success))
+        .bind(nat_window(window))
+        .execute(&self.pool))??;
+        Ok(())
+    }
+
+    /// Stores the NAT mapping and filtering behavior a player reported.
+    pub fn set_nat_properties(&self, user_id: u32, mapping: u32, filtering: u32, rtt: u32) -> Result<()> {
+        run(sqlx::query(
+            "INSERT INTO nat_properties (user_id, mapping, filtering, rtt) VALUES (?, ?, ?, ?)
+            ON CONFLICT (user_id) DO UPDATE SET mapping = excluded.mapping, filtering = excluded.filtering, rtt = excluded.rtt, updated_at = CURRENT_TIMESTAMP",
+        )
+        .bind(user_id)
+        .bind(mapping)
+        .bind(filtering)
+        .bind(rtt)
+        .execute(&self.pool))??;
+        Ok(())
+    }
+
+    /// Returns the peers a player failed to reach at least `min_failures` times in a row, the last time within the last
+    /// `window` seconds.
+    pub fn find_unreachable_peers(&self, user_id: u32, min_failures: u32, window: u64) -> Result<HashSet<u32>> {
+        let peers: Vec<(u32,)> = run(sqlx::query_as(
+            "SELECT CASE WHEN user_a = ? THEN user_b ELSE user_a END FROM nat_traversal_results
+            WHERE (user_a = ? OR user_b = ?) AND failure_streak >= ? AND updated_at >= datetime('now', ?)",
+        )
+        .bind(user_id)
+        .bind(user_id)
+        .bind(user_id)
+        .bind(min_failures.max(1))
+        .bind(nat_window(window))
+        .fetch_all(&self.pool))??;
+        Ok(peers.into_iter().map(|(peer_id,)| peer_id).collect())
+    }
+
+    /// Sums up the NAT traversal results of all pairs. Pairs failing at least `min_failures` times in a row, the last time
+    /// within the last `window` seconds, count as unreachable.
+    pub async fn nat_stats_async(&self, min_failures: u32, window: u64) -> Result<NatStats> {
+        Ok(sqlx::query_as(
+            "SELECT COUNT(*) AS pairs, COALESCE(SUM(probes), 0) AS probes, COALESCE(SUM(successes), 0) AS successes,
+                COALESCE(SUM(failures), 0) AS failures,
+                COALESCE(SUM(failure_streak >= ? AND updated_at >= datetime('now', ?)), 0) AS unreachable,
+                (SELECT COUNT(*) FROM nat_properties) AS reported_properties
+            FROM nat_traversal_results",
+        )
+        .bind(min_failures.max(1))
+        .bind(nat_window(window))
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    /// Returns the NAT traversal results of a player's pairs, or of all pairs, most failing first. Failure streaks older
+    /// than `window` seconds are forgotten.
+    pub async fn list_nat_results_async(&self, user_id: Option<u32>, limit: u32, window: u64) -> Result<Vec<NatResult>> {
+        let mut builder = sqlx::QueryBuilder::new("SELECT user_a, user_b, probes, successes, failures, CASE WHEN updated_at >= datetime('now', ");
+        builder.push_bind(nat_window(window));
+        builder.push(") THEN failure_streak ELSE 0 END AS failure_streak, updated_at FROM nat_traversal_results");
+        if let Some(user_id) = user_id {
+            builder.push(" WHERE user_a = ").push_bind(user_id).push(" OR user_b = ").push_bind(user_id);
+        }
+        builder.push(" ORDER BY failure_streak DESC, updated_at DESC LIMIT ").push_bind(limit);
+        Ok(builder.build_query_as().fetch_all(&self.pool).await?)
+    }
+
+    pub fn add_session_invites(&self, sender_id: u32, type_id: u32, session_id: u32, receivers: &[u32], message: &str) -> Result<()> {
+        if receivers.is_empty() {
+            warn!(self.logger, "Empty recipient list");
+            return Ok(());
         }
+        info!(self.logger, "sending invites for session {session_id} from {sender_id} to {receivers:?}");
+
+        let mut builder = sqlx::QueryBuilder::new("INSERT INTO invites (sender, receiver, session_type, session_id, message) ");
+        builder.push_values(receivers, |mut b, receiver_id| {
+            b.push_bind(sender_id)
+                .push_bind(*receiver_id)
+                .push_bind(type_id)
+                .push_bind(session_id)
+                .push_bind(message.to_owned());
+        });
+        run(async {
+            let mut tx = self.pool.begin().await?;
+            let query = builder.build();
+            debug!(self.logger, "SQL: {}", query.sql());
+            query.execute(&mut *tx).await?;
+            offline_invite_notifications(sender_id, (type_id, session_id), receivers, message)
+                .build()
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            Ok::<_, eyre::Error>(())
+        })?
+    }
+
+    pub fn list_invites_received(&self, receiver_id: u32, type_id: u32) -> Result<Vec<Invite>> {
+        Ok(run(sqlx::query_as(
+            r"
+            SELECT rowid as id, sender, receiver, session_type, session_id, message, CAST(created AS TEXT) as created
+            FROM invites
+            WHERE receiver = ? AND session_type = ? AND status = 0
+                AND session_id IN (SELECT id FROM game_sessions WHERE destroyed_at IS NULL)
+            ORDER BY rowid
+            ",
+        )
+        .bind(receiver_id)
+        .bind(type_id)
+        .fetch_all(&self.pool))??)
+    }
+
+    pub fn list_invites_sent(&self, sender_id: u32, type_id: u32) -> Result<Vec<Invite>> {
+        Ok(run(sqlx::query_as(
+            r"
+            SELECT rowid as id, sender, receiver, session_type, session_id, message, CAST(created AS TEXT) as created
+            FROM invites
+            WHERE sender = ? AND session_type = ? AND status = 0
+                AND session_id IN (SELECT id FROM game_sessions WHERE destroyed_at IS NULL)
+            ORDER BY rowid
+            ",
+        )
+        .bind(sender_id)
+        .bind(type_id)
+        .fetch_all(&self.pool))??)
+    }
+
+    /// Moves the pending invite(s) matching the given sender, receiver and session to `status`.
+    pub fn update_invite_status(&self, sender_id: u32, receiver_id: u32, type_id: u32, session_id: u32, status: InviteStatus) -> Result<u64> {
+        Ok(run(
+            sqlx::query("UPDATE invites SET status = ? WHERE sender = ? AND receiver = ? AND session_type = ? AND session_id = ? AND status = ?")
+                .bind(status)
+                .bind(sender_id)
+                .bind(receiver_id)
+                .bind(type_id)
+                .bind(session_id)
+                .bind(InviteStatus::Pending)
+                .execute(&self.pool),
+        )??
+        .rows_affected())
     }
 
     pub fn search_sessions_with_participants(&self, type_id: u32, participant_ids: &[u32]) -> Result<Vec<GameSession>> {
@@ -403,7 +1573,7 @@ impl Storage {
                     g.type_id as session_type,
                     g.id as session_id,
                     g.creator_id,
-                    g.attributes
+                    CAST(strftime('%s', 'now') - strftime('%s', g.created_at) AS INTEGER) as age_secs
                 FROM game_sessions AS g
                 WHERE type_id = ? AND destroyed_at IS NULL AND g.id IN (
                     SELECT game_id
@@ -420,37 +1590,7 @@ impl Storage {
         info!(self.logger, "Searching sessions with participants: {}", query.sql());
 
         let mut sessions: Vec<GameSession> = query.fetch_all(&self.pool).await?;
-
-        for session in &mut sessions {
-            session.participants = sqlx::query_as(
-                r"
-                SELECT
-                    user_id,
-                    username as name
-                FROM participants p, users u
-                WHERE u.id = user_id AND game_id = ?
-                ",
-            )
-            .bind(session.session_id)
-            .fetch_all(&self.pool)
-            .await?;
-
-            for participant in &mut session.participants {
-                participant.station_urls = sqlx::query_as(
-                    r"
-                    SELECT url
-                    FROM station_urls
-                    WHERE user_id = ?
-                    ",
-                )
-                .bind(participant.user_id)
-                .fetch_all(&self.pool)
-                .await?
-                .into_iter()
-                .map(|r: (String,)| r.0)
-                .collect();
-            }
-        }
+        self.load_session_details_async(&mut sessions).await?;
         Ok(sessions)
     }
 
@@ -459,6 +1599,11 @@ impl Storage {
         Ok(())
     }
 
+    /// Returns the station URLs a player registered.
+    pub fn list_station_urls(&self, user_id: u32) -> Result<Vec<String>> {
+        run(self.list_urls(user_id))?
+    }
+
     pub async fn list_urls(&self, user_id: u32) -> Result<Vec<String>> {
         Ok(sqlx::query_as("SELECT url FROM station_urls WHERE user_id = ?")
             .bind(user_id)
@@ -476,45 +1621,119 @@ impl Storage {
             g.type_id as session_type,
             g.id as session_id,
             g.creator_id,
-            g.attributes
+            CAST(strftime('%s', 'now') - strftime('%s', g.created_at) AS INTEGER) as age_secs
         FROM game_sessions AS g
         WHERE destroyed_at IS NULL
         ",
         )
         .fetch_all(&self.pool)
         .await?;
+        self.load_session_details_async(&mut sessions).await?;
+        Ok(sessions)
+    }
 
-        for session in &mut sessions {
-            session.participants = sqlx::query_as(
-                r"
-                SELECT
-                    user_id,
-                    username as name
-                FROM participants p, users u
-                WHERE u.id = user_id AND game_id = ?
-                ",
-            )
-            .bind(session.session_id)
-            .fetch_all(&self.pool)
-            .await?;
+    /// Loads the attributes and participants (including their station urls) of sessions.
+    async fn load_session_details_async(&self, sessions: &mut [GameSession]) -> Result<()> {
+        if sessions.is_empty() {
+            return Ok(());
+        }
+        let mut builder = sqlx::QueryBuilder::new("SELECT game_id, attr_id, value FROM game_session_attributes WHERE game_id IN (");
+        let mut ids = builder.separated(", ");
+        for session in &*sessions {
+            ids.push_bind(session.session_id);
+        }
+        builder.push(") ORDER BY attr_id");
+        let attributes: Vec<(u32, u32, u32)> = builder.build_query_as().fetch_all(&self.pool).await?;
 
-            for participant in &mut session.participants {
-                participant.station_urls = sqlx::query_as(
-                    r"
-                    SELECT url
-                    FROM station_urls
-                    WHERE user_id = ?
-                    ",
-                )
-                .bind(participant.user_id)
-                .fetch_all(&self.pool)
-                .await?
-                .into_iter()
-                .map(|r: (String,)| r.0)
-                .collect();
+        let mut builder = sqlx::QueryBuilder::new(
+            r"
+            SELECT p.game_id, p.user_id, u.username, s.url
+            FROM participants p
+            JOIN users u ON u.id = p.user_id
+            LEFT JOIN station_urls s ON s.user_id = p.user_id
+            WHERE p.game_id IN (",
+        );
+        let mut ids = builder.separated(", ");
+        for session in &*sessions {
+            ids.push_bind(session.session_id);
+        }
+        builder.push(") ORDER BY p.rowid, s.rowid");
+        let participants: Vec<(u32, u32, String, Option<String>)> = builder.build_query_as().fetch_all(&self.pool).await?;
+
+        let mut by_id: HashMap<u32, &mut GameSession> = sessions.iter_mut().map(|s| (s.session_id, s)).collect();
+        for (game_id, attr_id, value) in attributes {
+            if let Some(session) = by_id.get_mut(&game_id) {
+                session.attributes.push((attr_id, value));
             }
         }
-        Ok(sessions)
+        // rows of the same participant are adjacent because of the ordering by participant rowid
+        for (game_id, user_id, name, url) in participants {
+            let Some(session) = by_id.get_mut(&game_id) else {
+                continue;
+            };
+            let participants = &mut session.participants;
+            if participants.last().is_none_or(|p| p.user_id != user_id) {
+                participants.push(Participant {
+                    user_id,
+                    name,
+                    station_urls: Vec::new(),
+                });
+            }
+            if let (Some(participant), Some(url)) = (participants.last_mut(), url) {
+                participant.station_urls.push(url);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn find_game_session(&self, type_id: u32, session_id: u32) -> Result<Option<GameSession>> {
+        run(self.find_game_session_async(type_id, session_id))?
+    }
+
+    pub async fn find_game_session_async(&self, type_id: u32, session_id: u32) -> Result<Option<GameSession>> {
+        let session: Option<GameSession> = sqlx::query_as(
+            r"
+            SELECT
+                type_id as session_type,
+                id as session_id,
+                creator_id,
+                CAST(strftime('%s', 'now') - strftime('%s', created_at) AS INTEGER) as age_secs
+            FROM game_sessions
+            WHERE type_id = ? AND id = ? AND destroyed_at IS NULL
+            ",
+        )
+        .bind(type_id)
+        .bind(session_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some(mut session) = session else {
+            return Ok(None);
+        };
+        self.load_session_details_async(std::slice::from_mut(&mut session)).await?;
+        Ok(Some(session))
+    }
+
+    /// Makes `new_host_id` the creator of the session, if it is one of its participants.
+    ///
+    /// Returns whether the host was changed.
+    pub fn migrate_game_session_host(&self, type_id: u32, session_id: u32, new_host_id: u32) -> Result<bool> {
+        let changed = run(sqlx::query(
+            r"
+            UPDATE game_sessions
+            SET creator_id = ?
+            WHERE type_id = ? AND id = ? AND destroyed_at IS NULL
+                AND ? IN (SELECT user_id FROM participants WHERE game_id = ?)
+            ",
+        )
+        .bind(new_host_id)
+        .bind(type_id)
+        .bind(session_id)
+        .bind(new_host_id)
+        .bind(session_id)
+        .execute(&self.pool))??
+        .rows_affected();
+        Ok(changed > 0)
     }
 
     pub async fn delete_game_session_by_id_async(&self, session_id: u32) -> Result<()> {
@@ -524,22 +1743,671 @@ impl Storage {
             .await?;
         Ok(())
     }
-}
 
-#[derive(Debug, sqlx::FromRow)]
-pub struct User {
-    pub id: u32,
-    pub username: String,
-    pub ubi_id: String,
-    pub is_online: bool,
-}
+    /// Returns the number of contents of the given type owned by the user, including unfinished ones.
+    pub fn count_contents(&self, owner_id: u32, type_id: u32) -> Result<u32> {
+        let (count,): (u32,) = run(sqlx::query_as("SELECT COUNT(*) FROM user_contents WHERE owner_id = ? AND type_id = ?")
+            .bind(owner_id)
+            .bind(type_id)
+            .fetch_one(&self.pool))??;
+        Ok(count)
+    }
 
-#[derive(Debug, sqlx::FromRow)]
-pub struct GameSession {
-    pub session_type: u32,
-    pub session_id: u32,
-    pub creator_id: u32,
-    pub attributes: String,
+    /// Returns a content including its properties.
+    ///
+    /// Unfinished and banned contents are only visible to their owner, or to everyone if `viewer_id` is `None`.
+    pub fn find_content(&self, content_id: i64, viewer_id: Option<u32>) -> Result<Option<Content>> {
+        run(async {
+            let content: Option<Content> = sqlx::query_as(
+                r"
+                SELECT id, owner_id, type_id, size, hash, verified, banned, created_at, updated_at
+                FROM user_contents
+                WHERE id = ? AND (? IS NULL OR (finalized = 1 AND banned = 0) OR owner_id = ?)
+                ",
+            )
+            .bind(content_id)
+            .bind(viewer_id)
+            .bind(viewer_id)
+            .fetch_optional(&self.pool)
+            .await?;
+            let Some(mut content) = content else {
+                return Ok(None);
+            };
+            content.properties = self.list_content_properties_async(content.id).await?;
+            Ok::<_, eyre::Error>(Some(content))
+        })?
+    }
+
+    /// Returns the data of a finalized content that isn't banned.
+    pub fn find_content_data(&self, content_id: i64) -> Result<Option<Vec<u8>>> {
+        Ok(run(sqlx::query_as("SELECT data FROM user_contents WHERE id = ? AND finalized = 1 AND banned = 0")
+            .bind(content_id)
+            .fetch_optional(&self.pool))??
+        .map(|r: (Vec<u8>,)| r.0))
+    }
+
+    /// Searches finalized contents of a type that aren't banned, optionally restricted to the given owners.
+    ///
+    /// Returns the requested page in the given order and the total number of matches.
+    pub fn search_contents(&self, type_id: u32, owners: Option<&[u32]>, order: ContentOrder, offset: u32, limit: u32) -> Result<(Vec<Content>, u32)> {
+        let push_filters = |builder: &mut sqlx::QueryBuilder<'_, sqlx::Sqlite>| {
+            builder.push(" FROM user_contents WHERE finalized = 1 AND banned = 0 AND type_id = ").push_bind(type_id);
+            if let Some(owners) = owners {
+                builder.push(" AND owner_id IN (");
+                let mut separated = builder.separated(", ");
+                for owner_id in owners {
+                    separated.push_bind(*owner_id);
+                }
+                // `IN ()` is valid in sqlite and matches nothing
+                separated.push_unseparated(")");
+            }
+        };
+
+        let mut builder = sqlx::QueryBuilder::new("SELECT COUNT(*)");
+        push_filters(&mut builder);
+        let (total,): (u32,) = run(builder.build_query_as().fetch_one(&self.pool))??;
+
+        let mut builder = sqlx::QueryBuilder::new("SELECT id, owner_id, type_id, size, hash, verified, banned, created_at, updated_at");
+        push_filters(&mut builder);
+        builder.push(match order {
+            ContentOrder::Newest => " ORDER BY updated_at DESC",
+            ContentOrder::Likes => " ORDER BY (SELECT COUNT(*) FROM user_content_likes AS l WHERE l.content_id = user_contents.id) DESC",
+            ContentOrder::Plays => " ORDER BY play_count DESC",
+        });
+        builder.push(", id DESC LIMIT ").push_bind(limit).push(" OFFSET ").push_bind(offset);
+        let query = builder.build_query_as::<Content>();
+        debug!(self.logger, "SQL: {}", query.sql());
+        let mut contents: Vec<Content> = run(query.fetch_all(&self.pool))??;
+        for content in &mut contents {
+            content.properties = run(self.list_content_properties_async(content.id))??;
+        }
+        Ok((contents, total))
+    }
+
+    /// Lists all contents of a type owned by the user, including unfinished ones.
+    pub fn list_own_contents(&self, owner_id: u32, type_id: u32) -> Result<Vec<Content>> {
+        run(async {
+            let mut contents: Vec<Content> = sqlx::query_as(
+                r"
+                SELECT id, owner_id, type_id, size, hash, verified, banned, created_at, updated_at
+                FROM user_contents
+                WHERE owner_id = ? AND type_id = ?
+                ORDER BY id
+                ",
+            )
+            .bind(owner_id)
+            .bind(type_id)
+            .fetch_all(&self.pool)
+            .await?;
+            for content in &mut contents {
+                content.properties = self.list_content_properties_async(content.id).await?;
+            }
+            Ok::<_, eyre::Error>(contents)
+        })?
+    }
+
+    async fn list_content_properties_async(&self, content_id: i64) -> Result<Vec<(u32, Vec<u8>)>> {
+        Ok(sqlx::query_as("SELECT prop_id, value FROM user_content_properties WHERE content_id = ? ORDER BY prop_id")
+            .bind(content_id)
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    /// Creates a new content (`content_id` is `None`) or updates an existing one owned by `owner_id`.
+    ///
+    /// `properties` are inserted or overwritten. If `data` is given the content is finalized with it.
+    /// Returns the id of the content or `None` if the content to update doesn't belong to the user.
+    pub fn save_content(&self, owner_id: u32, type_id: u32, content_id: Option<i64>, properties: &[(u32, Vec<u8>)], data: Option<&[u8]>) -> Result<Option<i64>> {
+        run(async {
+            let mut tx = self.pool.begin().await?;
+            let content_id = if let Some(content_id) = content_id {
+                let exists: Option<(i64,)> = sqlx::query_as("SELECT id FROM user_contents WHERE id = ? AND owner_id = ? AND type_id = ?")
+                    .bind(content_id)
+                    .bind(owner_id)
+                    .bind(type_id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+                if exists.is_none() {
+                    warn!(self.logger, "Content {content_id} of type {type_id} not found for user {owner_id}");
+                    return Ok(None);
+                }
+                sqlx::query("UPDATE user_contents SET updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+                    .bind(content_id)
+                    .execute(&mut *tx)
+                    .await?;
+                content_id
+            } else {
+                sqlx::query("INSERT INTO user_contents (owner_id, type_id) VALUES (?, ?)")
+                    .bind(owner_id)
+                    .bind(type_id)
+                    .execute(&mut *tx)
+                    .await?
+                    .last_insert_rowid()
+            };
+
+            if !properties.is_empty() {
+                let mut builder = sqlx::QueryBuilder::new("INSERT OR REPLACE INTO user_content_properties (content_id, prop_id, value) ");
+                builder.push_values(properties, |mut b, (prop_id, value)| {
+                    b.push_bind(content_id).push_bind(*prop_id).push_bind(value.as_slice());
+                });
+                builder.build().execute(&mut *tx).await?;
+            }
+            if let Some(data) = data {
+                Self::set_content_data(&mut tx, content_id, data).await?;
+            }
+            tx.commit().await?;
+            Ok::<_, eyre::Error>(Some(content_id))
+        })?
+    }
+
+    /// Replaces the data of a content and finalizes it.
+    async fn set_content_data(tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>, content_id: i64, data: &[u8]) -> sqlx::Result<()> {
+        use md5::Digest;
+
+        let hash = format!("{:X}", md5::Md5::digest(data));
+        sqlx::query("UPDATE user_contents SET data = ?, size = ?, hash = ?, finalized = 1, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(data)
+            .bind(i64::try_from(data.len()).unwrap_or(i64::MAX))
+            .bind(hash)
+            .bind(content_id)
+            .execute(&mut **tx)
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes a content owned by the user. Returns whether it existed.
+    pub fn delete_content(&self, owner_id: u32, content_id: i64) -> Result<bool> {
+        let deleted = run(sqlx::query("DELETE FROM user_contents WHERE id = ? AND owner_id = ?")
+            .bind(content_id)
+            .bind(owner_id)
+            .execute(&self.pool))??
+        .rows_affected();
+        Ok(deleted > 0)
+    }
+
+    /// Announces an upload of `size` bytes for a content and returns its pending id.
+    pub fn create_content_upload(&self, content_id: i64, size: u32) -> Result<i64> {
+        // the pending id is part of the upload url, so it must not be guessable
+        let pending_id = rand::random::<i64>() & i64::MAX;
+        run(sqlx::query("INSERT INTO user_content_uploads (id, content_id, size) VALUES (?, ?, ?)")
+            .bind(pending_id)
+            .bind(content_id)
+            .bind(size)
+            .execute(&self.pool))??;
+        Ok(pending_id)
+    }
+
+    /// Stores the uploaded data of a pending upload.
+    ///
+    /// Returns `false` if the upload is unknown or `data` is bigger than announced.
+    pub fn store_content_upload(&self, pending_id: i64, data: &[u8]) -> Result<bool> {
+        let stored = run(sqlx::query("UPDATE user_content_uploads SET data = ? WHERE id = ? AND size >= ?")
+            .bind(data)
+            .bind(pending_id)
+            .bind(i64::try_from(data.len()).unwrap_or(i64::MAX))
+            .execute(&self.pool))??
+        .rows_affected();
+        Ok(stored > 0)
+    }
+
+    /// Finishes a pending upload of a content owned by `owner_id`, or of any content if `owner_id` is `None`.
+    ///
+    /// On success the uploaded data replaces the data of the content. Otherwise the upload is discarded,
+    /// together with the content if it never got finalized.
+    /// Returns the `(type id, content id)` of the content or `None` if the upload is unknown.
+    pub fn finish_content_upload(&self, owner_id: Option<u32>, pending_id: i64, success: bool) -> Result<Option<(u32, i64)>> {
+        run(async {
+            let mut tx = self.pool.begin().await?;
+            let upload: Option<(u32, i64, bool, Option<Vec<u8>>)> = sqlx::query_as(
+                r"
+                SELECT c.type_id, c.id, c.finalized, u.data
+                FROM user_content_uploads AS u, user_contents AS c
+                WHERE u.content_id = c.id AND u.id = ? AND (? IS NULL OR c.owner_id = ?)
+                ",
+            )
+            .bind(pending_id)
+            .bind(owner_id)
+            .bind(owner_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+            let Some((type_id, content_id, finalized, data)) = upload else {
+                return Ok(None);
+            };
+
+            sqlx::query("DELETE FROM user_content_uploads WHERE id = ?").bind(pending_id).execute(&mut *tx).await?;
+            match data {
+                Some(data) if success => Self::set_content_data(&mut tx, content_id, &data).await?,
+                _ => {
+                    warn!(self.logger, "Upload {pending_id} for content {content_id} failed or has no data");
+                    if !finalized {
+                        sqlx::query("DELETE FROM user_contents WHERE id = ?").bind(content_id).execute(&mut *tx).await?;
+                    }
+                }
+            }
+            tx.commit().await?;
+            Ok::<_, eyre::Error>(Some((type_id, content_id)))
+        })?
+    }
+
+    /// Discards the pending uploads created before `before`, an SQL timestamp like `2026-10-01 00:00:00`.
+    ///
+    /// Like failed uploads, contents that never got finalized are deleted with their last upload.
+    /// Returns the number of discarded uploads.
+    pub fn prune_content_uploads(&self, before: &str) -> Result<u64> {
+        run(async {
+            let mut tx = self.pool.begin().await?;
+            let (uploads,): (u64,) = sqlx::query_as("SELECT COUNT(*) FROM user_content_uploads WHERE created_at < ?")
+                .bind(before)
+                .fetch_one(&mut *tx)
+                .await?;
+            sqlx::query(
+                r"
+                DELETE FROM user_contents
+                WHERE finalized = 0
+                  AND id IN (SELECT content_id FROM user_content_uploads WHERE created_at < ?)
+                  AND id NOT IN (SELECT content_id FROM user_content_uploads WHERE created_at >= ?)
+                ",
+            )
+            .bind(before)
+            .bind(before)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query("DELETE FROM user_content_uploads WHERE created_at < ?").bind(before).execute(&mut *tx).await?;
+            tx.commit().await?;
+            Ok::<_, eyre::Error>(uploads)
+        })?
+    }
+
+    /// Likes or unlikes a content for the user.
+    pub fn set_content_liked(&self, user_id: u32, content_id: i64, liked: bool) -> Result<()> {
+        let query = if liked {
+            "INSERT OR IGNORE INTO user_content_likes (content_id, user_id) VALUES (?, ?)"
+        } else {
+            "DELETE FROM user_content_likes WHERE content_id = ? AND user_id = ?"
+        };
+        run(sqlx::query(query).bind(content_id).bind(user_id).execute(&self.pool))??;
+        Ok(())
+    }
+
+    pub fn is_content_liked(&self, user_id: u32, content_id: i64) -> Result<bool> {
+        let liked: Option<(i64,)> = run(sqlx::query_as("SELECT content_id FROM user_content_likes WHERE content_id = ? AND user_id = ?")
+            .bind(content_id)
+            .bind(user_id)
+            .fetch_optional(&self.pool))??;
+        Ok(liked.is_some())
+    }
+
+    /// Adds a content to or removes it from the favourites of the user.
+    pub fn set_content_favourite(&self, user_id: u32, content_id: i64, favourite: bool) -> Result<()> {
+        let query = if favourite {
+            "INSERT OR IGNORE INTO user_content_favourites (content_id, user_id) VALUES (?, ?)"
+        } else {
+            "DELETE FROM user_content_favourites WHERE content_id = ? AND user_id = ?"
+        };
+        run(sqlx::query(query).bind(content_id).bind(user_id).execute(&self.pool))??;
+        Ok(())
+    }
+
+    /// Lists the finalized and not banned favourite contents of the user with one of the given types.
+    pub fn list_favourite_contents(&self, user_id: u32, type_ids: &[u32]) -> Result<Vec<Content>> {
+        if type_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut builder = sqlx::QueryBuilder::new(
+            r"
+            SELECT c.id, c.owner_id, c.type_id, c.size, c.hash, c.verified, c.banned, c.created_at, c.updated_at
+            FROM user_contents AS c, user_content_favourites AS f
+            WHERE f.content_id = c.id AND c.finalized = 1 AND c.banned = 0 AND f.user_id = ",
+        );
+        builder.push_bind(user_id).push(" AND c.type_id IN (");
+        let mut separated = builder.separated(", ");
+        for type_id in type_ids {
+            separated.push_bind(*type_id);
+        }
+        separated.push_unseparated(") ORDER BY f.rowid");
+        let query = builder.build_query_as::<Content>();
+        debug!(self.logger, "SQL: {}", query.sql());
+        let mut contents: Vec<Content> = run(query.fetch_all(&self.pool))??;
+        for content in &mut contents {
+            content.properties = run(self.list_content_properties_async(content.id))??;
+        }
+        Ok(contents)
+    }
+
+    /// Puts a content into the moderation queue. Reporting it again replaces the reason and puts the report back in the
+    /// queue.
+    pub fn report_content(&self, reporter_id: u32, content_id: i64, reason: &str) -> Result<()> {
+        info!(self.logger, "user {reporter_id} reported content {content_id}: {reason}");
+        run(sqlx::query(
+            "INSERT INTO user_content_reports (content_id, reporter_id, reason) VALUES (?, ?, ?)
+            ON CONFLICT (content_id, reporter_id) DO UPDATE SET reason = excluded.reason, handled = 0",
+        )
+        .bind(content_id)
+        .bind(reporter_id)
+        .bind(reason)
+        .execute(&self.pool))??;
+        Ok(())
+    }
+
+    pub fn increment_content_play_count(&self, content_id: i64) -> Result<()> {
+        run(sqlx::query("UPDATE user_contents SET play_count = play_count + 1 WHERE id = ?")
+            .bind(content_id)
+            .execute(&self.pool))??;
+        Ok(())
+    }
+
+    /// Adds `increment` to a custom stat of a content.
+    pub fn update_content_stat(&self, content_id: i64, stat_id: u16, increment: i64) -> Result<()> {
+        run(sqlx::query(
+            r"
+            INSERT INTO user_content_stats (content_id, stat_id, value) VALUES (?, ?, ?)
+            ON CONFLICT (content_id, stat_id) DO UPDATE SET value = value + excluded.value
+            ",
+        )
+        .bind(content_id)
+        .bind(stat_id)
+        .bind(increment)
+        .execute(&self.pool))??;
+        Ok(())
+    }
+
+    /// Adds the tags of the user to a content. Tags the user already set are ignored.
+    pub fn tag_content(&self, user_id: u32, content_id: i64, tag_ids: &[u32]) -> Result<()> {
+        if tag_ids.is_empty() {
+            return Ok(());
+        }
+        let mut builder = sqlx::QueryBuilder::new("INSERT OR IGNORE INTO user_content_tags (content_id, user_id, tag_id) ");
+        builder.push_values(tag_ids, |mut b, tag_id| {
+            b.push_bind(content_id).push_bind(user_id).push_bind(*tag_id);
+        });
+        run(builder.build().execute(&self.pool))??;
+        Ok(())
+    }
+
+    pub fn is_moderator(&self, user_id: u32) -> Result<bool> {
+        let moderator: Option<(bool,)> = run(sqlx::query_as("SELECT is_moderator FROM users WHERE id = ?").bind(user_id).fetch_optional(&self.pool))??;
+        Ok(moderator.is_some_and(|m| m.0))
+    }
+
+    /// Flags a user as moderator, outside of tests that's up to the server admin.
+    #[cfg(test)]
+    pub fn set_moderator(&self, user_id: u32) -> Result<()> {
+        run(sqlx::query("UPDATE users SET is_moderator = 1 WHERE id = ?").bind(user_id).execute(&self.pool))??;
+        Ok(())
+    }
+
+    /// Lists finalized contents that haven't been verified or banned yet and have at least `threshold` pending reports,
+    /// most reported first. A `type_id` of `None` includes all types.
+    ///
+    /// Returns the requested page and the total number of matches.
+    pub fn list_contents_to_moderate(&self, type_id: Option<u32>, threshold: u32, offset: u32, limit: u32) -> Result<(Vec<Content>, u32)> {
+        run(self.list_contents_to_moderate_async(type_id, threshold, offset, limit))?
+    }
+
+    pub async fn list_contents_to_moderate_async(&self, type_id: Option<u32>, threshold: u32, offset: u32, limit: u32) -> Result<(Vec<Content>, u32)> {
+        let push_filters = |builder: &mut sqlx::QueryBuilder<'_, sqlx::Sqlite>| {
+            builder.push(
+                r"
+                FROM user_contents AS c
+                WHERE c.finalized = 1 AND c.verified = 0 AND c.banned = 0
+                    AND (SELECT COUNT(*) FROM user_content_reports AS r WHERE r.content_id = c.id AND r.handled = 0) >= ",
+            );
+            builder.push_bind(threshold.max(1));
+            if let Some(type_id) = type_id {
+                builder.push(" AND c.type_id = ").push_bind(type_id);
+            }
+        };
+
+        let mut builder = sqlx::QueryBuilder::new("SELECT COUNT(*)");
+        push_filters(&mut builder);
+        let (total,): (u32,) = builder.build_query_as().fetch_one(&self.pool).await?;
+
+        let mut builder = sqlx::QueryBuilder::new("SELECT c.id, c.owner_id, c.type_id, c.size, c.hash, c.verified, c.banned, c.created_at, c.updated_at");
+        push_filters(&mut builder);
+        builder
+            .push(" ORDER BY (SELECT COUNT(*) FROM user_content_reports AS r WHERE r.content_id = c.id AND r.handled = 0) DESC, c.id LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+        let query = builder.build_query_as::<Content>();
+        debug!(self.logger, "SQL: {}", query.sql());
+        let mut contents: Vec<Content> = query.fetch_all(&self.pool).await?;
+        for content in &mut contents {
+            content.properties = self.list_content_properties_async(content.id).await?;
+        }
+        Ok((contents, total))
+    }
+
+    /// Lists all finalized contents of a type, including banned ones.
+    ///
+    /// Returns the requested page, newest first, and the total number of matches.
+    pub fn browse_contents(&self, type_id: u32, offset: u32, limit: u32) -> Result<(Vec<Content>, u32)> {
+        run(async {
+            let (total,): (u32,) = sqlx::query_as("SELECT COUNT(*) FROM user_contents WHERE finalized = 1 AND type_id = ?")
+                .bind(type_id)
+                .fetch_one(&self.pool)
+                .await?;
+            let mut contents: Vec<Content> = sqlx::query_as(
+                r"
+                SELECT id, owner_id, type_id, size, hash, verified, banned, created_at, updated_at
+                FROM user_contents
+                WHERE finalized = 1 AND type_id = ?
+                ORDER BY updated_at DESC, id DESC
+                LIMIT ? OFFSET ?
+                ",
+            )
+            .bind(type_id)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.pool)
+            .await?;
+            for content in &mut contents {
+                content.properties = self.list_content_properties_async(content.id).await?;
+            }
+            Ok::<_, eyre::Error>((contents, total))
+        })?
+    }
+
+    /// Lists the pending reports of a content, oldest first.
+    pub async fn list_content_reports_async(&self, content_id: i64) -> Result<Vec<ContentReport>> {
+        Ok(sqlx::query_as(
+            r"
+            SELECT r.reporter_id, u.username as reporter_name, r.reason, r.created_at
+            FROM user_content_reports AS r, users AS u
+            WHERE u.id = r.reporter_id AND r.content_id = ? AND r.handled = 0
+            ORDER BY r.id
+            ",
+        )
+        .bind(content_id)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Marks a content as verified or banned and closes its pending reports.
+    ///
+    /// Returns whether the content exists.
+    pub fn moderate_content(&self, content_id: i64, banned: bool) -> Result<bool> {
+        run(self.moderate_content_async(content_id, banned))?
+    }
+
+    pub async fn moderate_content_async(&self, content_id: i64, banned: bool) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let query = if banned {
+            "UPDATE user_contents SET banned = 1 WHERE id = ?"
+        } else {
+            "UPDATE user_contents SET verified = 1 WHERE id = ?"
+        };
+        let updated = sqlx::query(query).bind(content_id).execute(&mut *tx).await?.rows_affected();
+        sqlx::query("UPDATE user_content_reports SET handled = 1 WHERE content_id = ?")
+            .bind(content_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(updated > 0)
+    }
+
+    /// Deletes a content regardless of its owner. Returns whether it existed.
+    pub fn delete_content_by_id(&self, content_id: i64) -> Result<bool> {
+        run(self.delete_content_by_id_async(content_id))?
+    }
+
+    pub async fn delete_content_by_id_async(&self, content_id: i64) -> Result<bool> {
+        let deleted = sqlx::query("DELETE FROM user_contents WHERE id = ?")
+            .bind(content_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(deleted > 0)
+    }
+
+    /// Bans a user from uploading contents of a type, or of all types if `type_id` is 0.
+    ///
+    /// `expires_at` is a timestamp in sqlite's format, `None` bans permanently. If `ban_contents` is set,
+    /// the existing contents of the user affected by the ban are banned as well.
+    pub fn ban_content_user(&self, user_id: u32, type_id: u32, reason: &str, expires_at: Option<&str>, ban_contents: bool) -> Result<()> {
+        run(self.ban_content_user_async(user_id, type_id, reason, expires_at, ban_contents))?
+    }
+
+    pub async fn ban_content_user_async(&self, user_id: u32, type_id: u32, reason: &str, expires_at: Option<&str>, ban_contents: bool) -> Result<()> {
+        warn!(self.logger, "banning user {user_id} from content type {type_id}: {reason}");
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT OR REPLACE INTO user_content_bans (user_id, type_id, reason, expires_at) VALUES (?, ?, ?, ?)")
+            .bind(user_id)
+            .bind(type_id)
+            .bind(reason)
+            .bind(expires_at)
+            .execute(&mut *tx)
+            .await?;
+        if ban_contents {
+            sqlx::query("UPDATE user_contents SET banned = 1 WHERE owner_id = ? AND (? = 0 OR type_id = ?)")
+                .bind(user_id)
+                .bind(type_id)
+                .bind(type_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Lifts the ban of a user for a type, or the ban for all types if `type_id` is 0. Returns whether there was one.
+    pub fn unban_content_user(&self, user_id: u32, type_id: u32) -> Result<bool> {
+        run(self.unban_content_user_async(user_id, type_id))?
+    }
+
+    pub async fn unban_content_user_async(&self, user_id: u32, type_id: u32) -> Result<bool> {
+        let deleted = sqlx::query("DELETE FROM user_content_bans WHERE user_id = ? AND type_id = ?")
+            .bind(user_id)
+            .bind(type_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(deleted > 0)
+    }
+
+    /// Returns the active ban of a user for a content type, including bans for all types.
+    pub fn find_content_ban(&self, user_id: u32, type_id: u32) -> Result<Option<ContentBan>> {
+        Ok(run(sqlx::query_as(
+            r"
+            SELECT b.user_id, u.username, u.ubi_id, b.type_id, b.reason, b.created_at, b.expires_at
+            FROM user_content_bans AS b, users AS u
+            WHERE u.id = b.user_id AND b.user_id = ? AND b.type_id IN (0, ?)
+                AND (b.expires_at IS NULL OR b.expires_at > CURRENT_TIMESTAMP)
+            ORDER BY b.type_id DESC
+            LIMIT 1
+            ",
+        )
+        .bind(user_id)
+        .bind(type_id)
+        .fetch_optional(&self.pool))??)
+    }
+
+    /// Lists active bans for a content type, including bans for all types. A `type_id` of 0 lists all bans.
+    ///
+    /// Returns the requested page, newest first, and the total number of matches.
+    pub fn list_content_bans(&self, type_id: u32, offset: u32, limit: u32) -> Result<(Vec<ContentBan>, u32)> {
+        run(self.list_content_bans_async(type_id, offset, limit))?
+    }
+
+    pub async fn list_content_bans_async(&self, type_id: u32, offset: u32, limit: u32) -> Result<(Vec<ContentBan>, u32)> {
+        let (total,): (u32,) = sqlx::query_as(
+            r"
+            SELECT COUNT(*)
+            FROM user_content_bans
+            WHERE (? = 0 OR type_id IN (0, ?)) AND (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)
+            ",
+        )
+        .bind(type_id)
+        .bind(type_id)
+        .fetch_one(&self.pool)
+        .await?;
+        let bans = sqlx::query_as(
+            r"
+            SELECT b.user_id, u.username, u.ubi_id, b.type_id, b.reason, b.created_at, b.expires_at
+            FROM user_content_bans AS b, users AS u
+            WHERE u.id = b.user_id AND (? = 0 OR b.type_id IN (0, ?))
+                AND (b.expires_at IS NULL OR b.expires_at > CURRENT_TIMESTAMP)
+            ORDER BY b.created_at DESC, b.user_id
+            LIMIT ? OFFSET ?
+            ",
+        )
+        .bind(type_id)
+        .bind(type_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok((bans, total))
+    }
+
+    /// Returns the tags of a content with the number of players that set them, most used first.
+    pub fn list_content_tags(&self, content_id: i64) -> Result<Vec<(u32, u32)>> {
+        Ok(run(sqlx::query_as(
+            r"
+            SELECT tag_id, COUNT(*) as count
+            FROM user_content_tags
+            WHERE content_id = ?
+            GROUP BY tag_id
+            ORDER BY count DESC, tag_id
+            ",
+        )
+        .bind(content_id)
+        .fetch_all(&self.pool))??)
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct User {
+    pub id: u32,
+    pub username: String,
+    pub ubi_id: String,
+    pub is_online: bool,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct Account {
+    pub id: u32,
+    pub username: String,
+    pub email: String,
+    pub status: String,
+    pub created_at: String,
+    /// start of the current or last game session
+    pub current_login: Option<String>,
+    /// start of the game session before that
+    pub previous_login: Option<String>,
+    pub last_logout: Option<String>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct GameSession {
+    pub session_type: u32,
+    pub session_id: u32,
+    pub creator_id: u32,
+    /// seconds since the session was created
+    pub age_secs: i64,
+    /// `(attribute id, value)` pairs, ordered by id
+    #[sqlx(skip)]
+    pub attributes: Vec<(u32, u32)>,
     #[sqlx(skip)]
     pub participants: Vec<Participant>,
 }
@@ -552,9 +2420,255 @@ pub struct Participant {
     pub station_urls: Vec<String>,
 }
 
+/// Sort order for content searches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentOrder {
+    /// Most recently updated first.
+    Newest,
+    /// Most liked first.
+    Likes,
+    /// Most played first.
+    Plays,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct Content {
+    pub id: i64,
+    pub owner_id: u32,
+    pub type_id: u32,
+    pub size: i64,
+    pub hash: String,
+    pub verified: bool,
+    pub banned: bool,
+    pub created_at: String,
+    pub updated_at: String,
+    /// `(property id, quazal encoded variant)` pairs, ordered by id
+    #[sqlx(skip)]
+    pub properties: Vec<(u32, Vec<u8>)>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct ContentReport {
+    pub reporter_id: u32,
+    pub reporter_name: String,
+    pub reason: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct ContentBan {
+    pub user_id: u32,
+    pub username: String,
+    pub ubi_id: String,
+    /// 0 for bans from all content types
+    pub type_id: u32,
+    pub reason: String,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct UserPrivilege {
+    pub id: u32,
+    pub description: String,
+    pub is_default: bool,
+    /// `Some` if the privilege was granted to or revoked from the user
+    pub granted: Option<bool>,
+    /// `None` if the grant or revocation never expires
+    pub expires_at: Option<String>,
+    pub remaining_secs: Option<i64>,
+}
+
+impl UserPrivilege {
+    /// Whether the user has the privilege.
+    #[must_use]
+    pub fn is_granted(&self) -> bool {
+        self.granted.unwrap_or(self.is_default)
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct Ban {
+    pub id: i64,
+    /// `None` once the banned account is deleted
+    pub username: Option<String>,
+    pub ubi_id: Option<String>,
+    /// `None` if the ban doesn't cover an address
+    pub ip: Option<String>,
+    pub reason: String,
+    pub banned_by: String,
+    pub created_at: String,
+    /// `None` for permanent bans
+    pub expires_at: Option<String>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct PlayerStat {
+    pub user_id: u32,
+    pub context_id: u32,
+    pub value: i64,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct ChallengeProgress {
+    pub challenge_id: u32,
+    pub period: String,
+    pub progress: u32,
+    /// `None` until the target is reached
+    pub completed_at: Option<String>,
+    pub updated_at: String,
+}
+
+/// Restricts the tracking tags to aggregate or list. `None` matches everything.
+#[derive(Debug, Default)]
+pub struct TrackingFilter<'a> {
+    pub tag: Option<&'a str>,
+    pub user_id: Option<u32>,
+    /// Timestamps in sqlite's format, `since` is inclusive and `until` exclusive
+    pub since: Option<&'a str>,
+    pub until: Option<&'a str>,
+}
+
+/// Time interval to group tracking tags by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackingInterval {
+    None,
+    Hour,
+    Day,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct TrackingCount {
+    /// `None` unless grouped by tag
+    pub tag: Option<String>,
+    /// `None` unless grouped by user, or if the user was deleted
+    pub username: Option<String>,
+    pub ubi_id: Option<String>,
+    /// start of the interval, `None` unless grouped by time
+    pub time: Option<String>,
+    pub count: i64,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct StoredTrackingTag {
+    pub id: i64,
+    /// `None` once the user is deleted
+    pub username: Option<String>,
+    pub ubi_id: Option<String>,
+    pub tag: String,
+    pub attributes: String,
+    pub delta_time: u32,
+    pub received_at: String,
+}
+
+/// Builds the query storing game invite notifications for the receivers that are offline, online players see the
+/// invites in the game.
+fn offline_invite_notifications<'a>(sender_id: u32, session: (u32, u32), receivers: &'a [u32], message: &'a str) -> sqlx::QueryBuilder<'a, sqlx::Sqlite> {
+    let mut builder = sqlx::QueryBuilder::new("INSERT INTO notifications (user_id, sender_id, kind, param1, param2, text) SELECT id, ");
+    let mut separated = builder.separated(", ");
+    separated
+        .push_bind(sender_id)
+        .push_bind(NotificationKind::GameInvite)
+        .push_bind(session.0)
+        .push_bind(session.1)
+        .push_bind(message);
+    builder.push(" FROM users WHERE NOT EXISTS (SELECT 1 FROM user_sessions WHERE user_id = users.id) AND id IN (");
+    let mut separated = builder.separated(", ");
+    for receiver_id in receivers {
+        separated.push_bind(*receiver_id);
+    }
+    separated.push_unseparated(")");
+    builder
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[repr(i32)]
+pub enum InviteStatus {
+    Pending = 0,
+    Accepted = 1,
+    Declined = 2,
+    Cancelled = 3,
+}
+
+/// What a notification is about. The game gets it as the major type of the notification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[repr(i32)]
+pub enum NotificationKind {
+    /// An invite to a game session, with its type and id as parameters.
+    GameInvite = 1,
+    FriendRequest = 2,
+    ClanInvite = 3,
+    AdminMessage = 4,
+}
+
+impl TryFrom<u32> for NotificationKind {
+    type Error = u32;
+
+    fn try_from(value: u32) -> std::result::Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Self::GameInvite),
+            2 => Ok(Self::FriendRequest),
+            3 => Ok(Self::ClanInvite),
+            4 => Ok(Self::AdminMessage),
+            _ => Err(value),
+        }
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct Notification {
+    pub id: i64,
+    pub sender_id: Option<u32>,
+    pub kind: NotificationKind,
+    pub param1: u32,
+    pub param2: u32,
+    pub text: String,
+    pub created_at: String,
+    pub read_at: Option<String>,
+}
+
+/// Orders a pair of players like the `nat_traversal_results` table.
+fn nat_pair(user_id: u32, peer_id: u32) -> (u32, u32) {
+    (user_id.min(peer_id), user_id.max(peer_id))
+}
+
+/// Modifier for `datetime('now', ?)`, going back `window` seconds.
+fn nat_window(window: u64) -> String {
+    format!("-{window} seconds")
+}
+
+/// NAT traversal results between two players, `user_a` being the lower id.
+#[derive(Debug, sqlx::FromRow)]
+pub struct NatResult {
+    pub user_a: u32,
+    pub user_b: u32,
+    pub probes: u32,
+    pub successes: u32,
+    pub failures: u32,
+    /// Failures since the last success, 0 once they are too old.
+    pub failure_streak: u32,
+    pub updated_at: String,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct NatStats {
+    pub pairs: u32,
+    pub probes: u32,
+    pub successes: u32,
+    pub failures: u32,
+    /// Pairs failing too often to be matched.
+    pub unreachable: u32,
+    /// Players that reported their NAT properties.
+    pub reported_properties: u32,
+}
+
 #[derive(Debug, sqlx::FromRow)]
 pub struct Invite {
     pub id: i64,
     pub sender: u32,
     pub receiver: u32,
+    pub session_type: Option<u32>,
+    pub session_id: Option<u32>,
+    pub message: String,
+    pub created: Option<String>,
 }
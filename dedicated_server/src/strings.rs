@@ -0,0 +1,139 @@
+//! Localized texts of the messages the server generates.
+//!
+//! The string tables are read from `data/strings/<locale>.json`, each mapping keys to texts with `{name}` placeholders.
+//! A string missing for a locale like `de-AT` is looked up for its language `de` and then in English. The English
+//! strings the server was shipped with are the last resort. Tables are reloaded when their file is modified.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::LazyLock;
+use std::sync::Mutex;
+
+use crate::data_file::DataFile;
+use crate::storage::Ban;
+
+type Table = HashMap<String, String>;
+
+/// The English strings the server was shipped with.
+static DEFAULT_STRINGS: LazyLock<Table> = LazyLock::new(|| serde_json::from_str(include_str!("../../data/strings/en.json")).unwrap_or_default());
+const FALLBACK_LOCALE: &str = "en";
+
+/// The string tables in a directory.
+pub struct Strings {
+    dir: PathBuf,
+    /// The table files per locale.
+    tables: Mutex<HashMap<String, DataFile<Table>>>,
+}
+
+impl Default for Strings {
+    fn default() -> Self {
+        Self::new("data/strings")
+    }
+}
+
+impl Strings {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            tables: Mutex::default(),
+        }
+    }
+
+    /// Returns the table of a locale, `None` if it's missing or invalid.
+    fn table(&self, locale: &str) -> Option<Arc<Table>> {
+        // the locale comes from the client
+        if locale.is_empty() || !locale.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return None;
+        }
+        let mut tables = self.tables.lock().unwrap();
+        let file = tables.entry(locale.to_owned()).or_insert_with(|| DataFile::new(self.dir.join(format!("{locale}.json"))));
+        let table = file.load().ok().flatten();
+        // only the files of existing tables are kept
+        if table.is_none() {
+            tables.remove(locale);
+        }
+        table
+    }
+
+    /// Returns the text of `key` for a locale with the placeholders replaced by `args`, or the key itself if there's
+    /// no such string.
+    pub fn get(&self, locale: Option<&str>, key: &str, args: &[(&str, &str)]) -> String {
+        let locale = locale.unwrap_or(FALLBACK_LOCALE).replace('_', "-");
+        let language = locale.split('-').next().unwrap_or_default();
+        let text = [locale.as_str(), language, FALLBACK_LOCALE]
+            .into_iter()
+            .find_map(|l| self.table(l)?.get(key).cloned())
+            .or_else(|| DEFAULT_STRINGS.get(key).cloned())
+            .unwrap_or_else(|| key.to_owned());
+        args.iter().fold(text, |text, (name, value)| text.replace(&format!("{{{name}}}"), value))
+    }
+
+    /// Returns the message telling a user why they're banned.
+    pub fn ban_message(&self, locale: Option<&str>, ban: &Ban) -> String {
+        match &ban.expires_at {
+            Some(expires_at) => self.get(locale, "ban.temporary", &[("expires_at", expires_at), ("reason", &ban.reason)]),
+            None => self.get(locale, "ban.permanent", &[("reason", &ban.reason)]),
+        }
+    }
+
+    /// Replaces the `{{key}}` placeholders of an XML text with the strings for a locale, escaped for XML.
+    pub fn localize(&self, locale: Option<&str>, text: &str) -> String {
+        let mut localized = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some((before, after)) = rest.split_once("{{") {
+            let Some((key, after)) = after.split_once("}}") else {
+                break;
+            };
+            localized.push_str(before);
+            for c in self.get(locale, key, &[]).chars() {
+                match c {
+                    '&' => localized.push_str("&amp;"),
+                    '<' => localized.push_str("&lt;"),
+                    '>' => localized.push_str("&gt;"),
+                    '"' => localized.push_str("&quot;"),
+                    '\'' => localized.push_str("&apos;"),
+                    c => localized.push(c),
+                }
+            }
+            rest = after;
+        }
+        localized.push_str(rest);
+        localized
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fallbacks() {
+        let dir = std::env::temp_dir().join(format!("strings_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("de.json"), r#"{"greeting": "Hallo {name}", "farewell": "Tschüss", "quote": "\"Sam\" & <Co>"}"#).unwrap();
+        std::fs::write(dir.join("de-AT.json"), r#"{"greeting": "Servus {name}"}"#).unwrap();
+        let strings = Strings::new(&dir);
+
+        assert_eq!(strings.get(Some("de-AT"), "greeting", &[("name", "Sam")]), "Servus Sam");
+        assert_eq!(strings.get(Some("de_AT"), "farewell", &[]), "Tschüss");
+        assert_eq!(strings.get(Some("de-DE"), "greeting", &[("name", "Sam")]), "Hallo Sam");
+        // the shipped english strings
+        assert_eq!(strings.get(Some("fr-FR"), "ban.permanent", &[("reason", "cheating")]), "Banned: cheating");
+        assert_eq!(strings.get(None, "unknown", &[]), "unknown");
+        assert_eq!(strings.get(Some("../de"), "farewell", &[]), "farewell");
+
+        assert_eq!(
+            strings.localize(Some("de"), "<a b=\"{{farewell}}\" c=\"{{greeting}}\" />{{"),
+            "<a b=\"Tschüss\" c=\"Hallo {name}\" />{{"
+        );
+        assert_eq!(strings.localize(Some("de"), "<a b=\"{{quote}}\" />"), "<a b=\"&quot;Sam&quot; &amp; &lt;Co&gt;\" />");
+
+        // reloaded when modified
+        std::fs::write(dir.join("de-AT.json"), r#"{"greeting": "Griaß di {name}"}"#).unwrap();
+        assert_eq!(strings.get(Some("de-AT"), "greeting", &[("name", "Sam")]), "Griaß di Sam");
+        std::fs::remove_file(dir.join("de-AT.json")).unwrap();
+        assert_eq!(strings.get(Some("de-AT"), "greeting", &[("name", "Sam")]), "Hallo Sam");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
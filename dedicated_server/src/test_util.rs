@@ -0,0 +1,50 @@
+//! Helpers shared by the protocol tests.
+
+use std::future::Future;
+use std::net::UdpSocket;
+
+use quazal::prudp::ClientRegistry;
+use quazal::rmc::Protocol;
+use quazal::rmc::Request;
+use quazal::ClientInfo;
+use quazal::Context;
+use slog::Logger;
+
+/// Sends a request with the given parameters to `prot`, logged in as `user_id` if given.
+pub(crate) fn call(prot: &dyn Protocol<()>, user_id: Option<u32>, method_id: u32, parameters: &[u8]) -> quazal::rmc::Result<Vec<u8>> {
+    call_in(prot, &Context::default(), user_id, method_id, parameters)
+}
+
+/// Like [`call`], but with the settings of `ctx`.
+pub(crate) fn call_in(prot: &dyn Protocol<()>, ctx: &Context, user_id: Option<u32>, method_id: u32, parameters: &[u8]) -> quazal::rmc::Result<Vec<u8>> {
+    let mut ci = ClientInfo::<()>::new("127.0.0.1:2".parse().unwrap());
+    ci.user_id = user_id;
+    handle(prot, ctx, &mut ci, method_id, parameters)
+}
+
+/// Like [`call`], but on behalf of an existing client.
+pub(crate) fn call_with(prot: &dyn Protocol<()>, ci: &mut ClientInfo<()>, method_id: u32, parameters: &[u8]) -> quazal::rmc::Result<Vec<u8>> {
+    handle(prot, &Context::default(), ci, method_id, parameters)
+}
+
+fn handle(prot: &dyn Protocol<()>, ctx: &Context, ci: &mut ClientInfo<()>, method_id: u32, parameters: &[u8]) -> quazal::rmc::Result<Vec<u8>> {
+    let request = Request {
+        protocol_id: prot.id(),
+        call_id: 1,
+        method_id,
+        parameters: parameters.to_vec(),
+    };
+    prot.handle(
+        &Logger::root(slog::Discard, slog::o!()),
+        ctx,
+        ci,
+        &request,
+        &ClientRegistry::default(),
+        &UdpSocket::bind("127.0.0.1:0").unwrap(),
+    )
+}
+
+/// Runs an async function, like the storage functions only the admin api calls.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap().block_on(future)
+}
@@ -4,6 +4,7 @@ use quazal::kerberos::KerberosTicket;
 use quazal::kerberos::KerberosTicketInternal;
 use quazal::kerberos::SESSION_KEY_SIZE;
 use quazal::prudp::ClientRegistry;
+use quazal::rmc::result::RendezVousError;
 use quazal::rmc::types::QResult;
 use quazal::rmc::types::StationURL;
 use quazal::rmc::Protocol;
@@ -14,11 +15,13 @@ pub use crate::protocols::authentication_foundation::ticket_granting_protocol::*
 pub use crate::protocols::authentication_foundation::types::*;
 use crate::protocols::ubi_authentication::types::UbiAuthenticationLoginCustomData;
 use crate::storage::Storage;
+use crate::strings::Strings;
 use crate::SERVER_PID;
 
 /// Implementation of the `TicketGrantingProtocolServerTrait` for handling ticket-granting requests.
 struct TicketGrantingProtocolServerImpl {
     storage: Arc<Storage>,
+    strings: Strings,
 }
 
 impl TicketGrantingProtocolServerImpl {
@@ -76,6 +79,44 @@ impl TicketGrantingProtocolServerImpl {
             })
             .map(Result::ok)
     }
+
+    /// Checks for a ban covering the user or the address they connect from.
+    ///
+    /// Returns the result and message to deny the login with, otherwise remembers the address for future bans.
+    fn check_ban<T>(&self, logger: &slog::Logger, ci: &quazal::ClientInfo<T>, user_id: u32) -> quazal::rmc::Result<Option<(QResult, String)>> {
+        let ip = ci.address().ip().to_string();
+        let ban = self.storage.find_active_ban(Some(user_id), None, Some(&ip)).map_err(|e| {
+            error!(logger, "Error finding user ban: {e}");
+            quazal::rmc::Error::InternalError
+        })?;
+        let Some(ban) = ban else {
+            if let Err(e) = self.storage.update_user_ip(user_id, &ip) {
+                error!(logger, "Error saving user address: {e}");
+            }
+            return Ok(None);
+        };
+        warn!(logger, "login of banned user {} from {} denied (ban {})", user_id, ip, ban.id);
+        let error = if ban.expires_at.is_some() {
+            RendezVousError::AccountTemporarilyDisabled
+        } else {
+            RendezVousError::AccountDisabled
+        };
+        let locale = self.storage.find_user_locale(user_id).unwrap_or_else(|e| {
+            error!(logger, "Error finding user locale: {e}");
+            None
+        });
+        let message = self.strings.ban_message(locale.as_deref(), &ban);
+        Ok(Some((QResult::Error(quazal::rmc::result::Error::RendezVous(error)), message)))
+    }
+}
+
+/// Connection data for denied logins.
+fn no_connection_data() -> RVConnectionData {
+    RVConnectionData {
+        url_regular_protocols: StationURL::default(),
+        lst_special_protocols: vec![],
+        url_special_protocols: StationURL::default(),
+    }
 }
 
 /// Creates the `RVConnectionData` for a client.
@@ -126,6 +167,15 @@ impl<T> TicketGrantingProtocolServerTrait<T> for TicketGrantingProtocolServerImp
             warn!(logger, "user {} has no plaintext password", request.str_user_name);
             None
         });
+        if let Some((return_value, str_return_msg)) = self.check_ban(logger, ci, user_id)? {
+            return Ok(LoginResponse {
+                return_value,
+                pid_principal: 0,
+                pbuf_response: vec![],
+                p_connection_data: no_connection_data(),
+                str_return_msg,
+            });
+        }
         ci.user_id = Some(user_id);
         let session_key = self.get_session_key(logger, user_id);
         let ticket = KerberosTicket {
@@ -176,6 +226,15 @@ impl<T> TicketGrantingProtocolServerTrait<T> for TicketGrantingProtocolServerImp
             warn!(logger, "login failed for {}", ubi_username);
             return Err(quazal::rmc::Error::AccessDenied);
         };
+        if let Some((return_value, str_return_msg)) = self.check_ban(logger, ci, user_id)? {
+            return Ok(LoginExResponse {
+                return_value,
+                pid_principal: 0,
+                pbuf_response: vec![],
+                p_connection_data: no_connection_data(),
+                str_return_msg,
+            });
+        }
         info!(logger, "login successful for {}", ubi_username);
 
         ci.user_id = Some(user_id);
@@ -235,5 +294,100 @@ impl<T> TicketGrantingProtocolServerTrait<T> for TicketGrantingProtocolServerImp
 /// This function is typically used to register the ticket-granting protocol
 /// with the server's protocol dispatcher.
 pub fn new_protocol<T: 'static>(storage: Arc<Storage>) -> Box<dyn Protocol<T>> {
-    Box::new(TicketGrantingProtocolServer::new(TicketGrantingProtocolServerImpl { storage }))
+    Box::new(TicketGrantingProtocolServer::new(TicketGrantingProtocolServerImpl {
+        storage,
+        strings: Strings::default(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use quazal::rmc::result::Error;
+
+    use super::*;
+    use crate::test_util;
+
+    /// Logs in `username` from `address` and returns the response and the user id set on the client.
+    fn login(storage: &Arc<Storage>, username: &str, address: &str) -> (LoginResponse, Option<u32>) {
+        let prot = TicketGrantingProtocolServerImpl {
+            storage: Arc::clone(storage),
+            strings: Strings::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../data/strings")),
+        };
+        let mut ci = quazal::ClientInfo::<()>::new(address.parse().unwrap());
+        let resp = TicketGrantingProtocolServerTrait::login(
+            &prot,
+            &slog::Logger::root(slog::Discard, slog::o!()),
+            &Context::default(),
+            &mut ci,
+            LoginRequest { str_user_name: username.into() },
+            &ClientRegistry::default(),
+            &std::net::UdpSocket::bind("127.0.0.1:0").unwrap(),
+        )
+        .unwrap();
+        (resp, ci.user_id)
+    }
+
+    fn ban(storage: &Arc<Storage>, user_id: u32, expires_at: Option<&str>, ban_ip: bool) {
+        test_util::block_on(storage.ban_user_async(user_id, "cheating", "admin", expires_at, ban_ip)).unwrap();
+    }
+
+    #[test]
+    fn banned_login() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let (resp, user_id) = login(&storage, "Foo", "127.0.0.1:2");
+        assert!(matches!(resp.return_value, QResult::Ok));
+        assert_eq!(user_id, Some(1000));
+
+        ban(&storage, 1000, None, false);
+        let (resp, user_id) = login(&storage, "Foo", "127.0.0.1:2");
+        assert!(matches!(resp.return_value, QResult::Error(Error::RendezVous(RendezVousError::AccountDisabled))));
+        assert_eq!(resp.str_return_msg, "Banned: cheating");
+        assert_eq!(resp.pid_principal, 0);
+        assert!(resp.pbuf_response.is_empty());
+        assert_eq!(user_id, None);
+    }
+
+    #[test]
+    fn localized_ban() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        login(&storage, "Foo", "127.0.0.1:2");
+        storage.update_session_locale(1000, "de-DE").unwrap();
+        // the locale is kept with the profile after logging out
+        storage.delete_user_session(1000).unwrap();
+        ban(&storage, 1000, None, false);
+        let (resp, _) = login(&storage, "Foo", "127.0.0.1:2");
+        assert_eq!(resp.str_return_msg, "Gesperrt: cheating");
+    }
+
+    #[test]
+    fn temporary_ban() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        ban(&storage, 1000, Some("9999-12-31 23:59:59"), false);
+        let (resp, _) = login(&storage, "Foo", "127.0.0.1:2");
+        assert!(matches!(resp.return_value, QResult::Error(Error::RendezVous(RendezVousError::AccountTemporarilyDisabled))));
+    }
+
+    #[test]
+    fn expired_ban() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        ban(&storage, 1000, Some("2000-01-01 00:00:00"), false);
+        let (resp, user_id) = login(&storage, "Foo", "127.0.0.1:2");
+        assert!(matches!(resp.return_value, QResult::Ok));
+        assert_eq!(user_id, Some(1000));
+    }
+
+    #[test]
+    fn ip_ban() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        // remembers the address of the user
+        login(&storage, "Foo", "127.0.0.2:2");
+        ban(&storage, 1000, None, true);
+
+        let (resp, user_id) = login(&storage, "sam_the_fisher", "127.0.0.2:3");
+        assert!(matches!(resp.return_value, QResult::Error(Error::RendezVous(RendezVousError::AccountDisabled))));
+        assert_eq!(user_id, None);
+
+        let (resp, _) = login(&storage, "sam_the_fisher", "127.0.0.3:3");
+        assert!(matches!(resp.return_value, QResult::Ok));
+    }
 }
@@ -1,4 +1,8 @@
 //! Implements the `TrackingProtocol3Server` for handling tracking-related requests.
+//!
+//! Received tags are stored in the database and can be queried with the admin api.
+
+use std::sync::Arc;
 
 use quazal::prudp::ClientRegistry;
 use quazal::rmc::Error;
@@ -14,9 +18,12 @@ use crate::protocols::tracking_service::tracking_protocol_3::SendTagsRequest;
 use crate::protocols::tracking_service::tracking_protocol_3::SendTagsResponse;
 use crate::protocols::tracking_service::tracking_protocol_3::TrackingProtocol3Server;
 use crate::protocols::tracking_service::tracking_protocol_3::TrackingProtocol3ServerTrait;
+use crate::storage::Storage;
 
 /// Implementation of the `TrackingProtocol3ServerTrait` for handling tracking requests.
-struct TrackingProtocol3ServerImpl;
+struct TrackingProtocol3ServerImpl {
+    storage: Arc<Storage>,
+}
 
 impl<T> TrackingProtocol3ServerTrait<T> for TrackingProtocol3ServerImpl {
     /// Handles the `GetConfiguration` request, returning a list of tracking tags.
@@ -114,20 +121,29 @@ impl<T> TrackingProtocol3ServerTrait<T> for TrackingProtocol3ServerImpl {
         })
     }
 
-    /// Handles the `SendTags` request, processing incoming tracking tags.
+    /// Handles the `SendTags` request, storing the incoming tracking tags.
     ///
-    /// This function requires the client to be logged in.
+    /// This function requires the client to be logged in. Tags that can't be stored are
+    /// dropped, the game doesn't need to know about it.
     fn send_tags(
         &self,
-        _logger: &Logger,
+        logger: &Logger,
         _ctx: &Context,
         ci: &mut ClientInfo<T>,
-        _request: SendTagsRequest,
+        request: SendTagsRequest,
         _client_registry: &ClientRegistry<T>,
         _socket: &std::net::UdpSocket,
     ) -> Result<SendTagsResponse, Error> {
         // Ensure the client is logged in before processing the tags.
-        login_required(&*ci)?;
+        let user_id = login_required(&*ci)?;
+        let tags = request
+            .tag_data
+            .iter()
+            .map(|t| (t.tracking_id, t.tag.as_str(), t.attributes.as_str(), t.delta_time))
+            .collect::<Vec<_>>();
+        if let Err(e) = self.storage.store_tracking_tags(user_id, &tags) {
+            error!(logger, "error storing {} tracking tags", request.tag_data.len(); "error" => %e);
+        }
         Ok(SendTagsResponse)
     }
 }
@@ -136,6 +152,41 @@ impl<T> TrackingProtocol3ServerTrait<T> for TrackingProtocol3ServerImpl {
 ///
 /// This function is typically used to register the tracking protocol
 /// with the server's protocol dispatcher.
-pub fn new_protocol<T: 'static>() -> Box<dyn Protocol<T>> {
-    Box::new(TrackingProtocol3Server::new(TrackingProtocol3ServerImpl))
+pub fn new_protocol<T: 'static>(storage: Arc<Storage>) -> Box<dyn Protocol<T>> {
+    Box::new(TrackingProtocol3Server::new(TrackingProtocol3ServerImpl { storage }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::tracking_service::tracking_protocol_3::TrackingProtocol3Method;
+    use crate::storage::TrackingFilter;
+    use crate::storage::TrackingInterval;
+    use crate::test_util;
+
+    #[test]
+    fn send_tags() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let prot = new_protocol::<()>(Arc::clone(&storage));
+        // two tags with tracking id 7, attributes `a=1`, delta time 5 and no new user id
+        let mut parameters = b"\x02\x00\x00\x00".to_vec();
+        parameters.extend_from_slice(b"\x07\x00\x00\x00\x0c\x00PLAYER_KILL\x00\x04\x00a=1\x00\x05\x00\x00\x00\x01\x00\x00");
+        parameters.extend_from_slice(b"\x07\x00\x00\x00\x10\x00ADVROUND_FINISH\x00\x04\x00a=1\x00\x05\x00\x00\x00\x01\x00\x00");
+        test_util::call(&*prot, Some(1000), TrackingProtocol3Method::SendTags as u32, &parameters).unwrap();
+
+        let filter = TrackingFilter {
+            tag: Some("PLAYER_KILL"),
+            ..TrackingFilter::default()
+        };
+        let tags = test_util::block_on(storage.list_tracking_tags_async(&filter, 10)).unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].username.as_deref(), Some("Foo"));
+        assert_eq!(tags[0].attributes, "a=1");
+        assert_eq!(tags[0].delta_time, 5);
+
+        let counts = test_util::block_on(storage.aggregate_tracking_tags_async(&TrackingFilter::default(), false, true, TrackingInterval::Day)).unwrap();
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts[0].username.as_deref(), Some("Foo"));
+        assert_eq!(counts[0].count, 2);
+    }
 }
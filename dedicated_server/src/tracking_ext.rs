@@ -1,10 +1,20 @@
+//! Implements the `TrackingExtensionProtocolServer`, which tells the game which tracking tags to send.
+//!
+//! The user groups are read from `data/tracking_groups.json` on each request. A user belongs to the first group
+//! whose rule matches them, users without a group are in group 0 and send no tags. Like the configuration of the
+//! `TrackingProtocol3`, the groups are only used with the `tracking` feature.
+
+use std::sync::Arc;
+
 use quazal::prudp::ClientRegistry;
 use quazal::rmc::Error;
 use quazal::rmc::Protocol;
 use quazal::ClientInfo;
 use quazal::Context;
+use serde::Deserialize;
 use slog::Logger;
 
+use crate::data_file::DataFile;
 use crate::login_required;
 use crate::protocols::trackingextension::tracking_extension_protocol::GetTrackingUserGroupRequest;
 use crate::protocols::trackingextension::tracking_extension_protocol::GetTrackingUserGroupResponse;
@@ -12,63 +22,122 @@ use crate::protocols::trackingextension::tracking_extension_protocol::GetTrackin
 use crate::protocols::trackingextension::tracking_extension_protocol::GetTrackingUserGroupTagsResponse;
 use crate::protocols::trackingextension::tracking_extension_protocol::TrackingExtensionProtocolServer;
 use crate::protocols::trackingextension::tracking_extension_protocol::TrackingExtensionProtocolServerTrait;
+use crate::storage::Storage;
+
+/// Group of users without tracking.
+const NO_GROUP: u32 = 0;
+
+#[derive(Debug, Default, Deserialize)]
+struct TrackingGroups {
+    #[serde(default)]
+    groups: Vec<GroupDefinition>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GroupDefinition {
+    id: u32,
+    rule: Rule,
+    /// Tags the users of the group send.
+    tags: Vec<String>,
+}
+
+/// Decides which users belong to a group.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Rule {
+    All,
+    /// Users that have the privilege with this id.
+    Privilege(u32),
+    /// This percentage of users, always the same ones for a group.
+    Percentage(u32),
+}
+
+/// Returns the bucket of a user in a percentage rollout, from 0 to 99.
+///
+/// Each group gets a different bucket, so that small rollouts don't always hit the same users.
+fn rollout_bucket(user_id: u32, group_id: u32) -> u32 {
+    // Knuth's multiplicative hash, stable across restarts
+    user_id.wrapping_add(group_id.wrapping_mul(0x9E37_79B9)).wrapping_mul(2_654_435_761) % 100
+}
 
 /// Implementation of the `TrackingExtensionProtocolServerTrait` for handling extended tracking requests.
-struct TrackingExtensionProtocolServerImpl;
+struct TrackingExtensionProtocolServerImpl {
+    storage: Arc<Storage>,
+    groups: DataFile<TrackingGroups>,
+}
+
+impl TrackingExtensionProtocolServerImpl {
+    /// Loads the user groups, there are none without the `tracking` feature or if the file is missing or invalid.
+    fn groups(&self, logger: &Logger) -> Arc<TrackingGroups> {
+        if cfg!(not(feature = "tracking")) {
+            return Arc::default();
+        }
+        match self.groups.load() {
+            Ok(groups) => groups.unwrap_or_default(),
+            Err(e) => {
+                error!(logger, "Invalid tracking groups {}: {e}", self.groups.path().display());
+                Arc::default()
+            }
+        }
+    }
+
+    /// Checks if a user belongs to a group.
+    fn is_member(&self, logger: &Logger, user_id: u32, group: &GroupDefinition) -> Result<bool, Error> {
+        Ok(match group.rule {
+            Rule::All => true,
+            Rule::Privilege(privilege_id) => rmc_err!(self.storage.list_user_privileges(user_id), logger, "error listing privileges")?
+                .iter()
+                .any(|p| p.id == privilege_id && p.is_granted()),
+            Rule::Percentage(percentage) => rollout_bucket(user_id, group.id) < percentage,
+        })
+    }
+}
 
 impl<T> TrackingExtensionProtocolServerTrait<T> for TrackingExtensionProtocolServerImpl {
-    /// Handles the `GetTrackingUserGroup` request, returning a hardcoded user group.
+    /// Handles the `GetTrackingUserGroup` request, returning the group of the user.
     ///
     /// This function requires the client to be logged in.
     fn get_tracking_user_group(
         &self,
-        _logger: &Logger,
+        logger: &Logger,
         _ctx: &Context,
         ci: &mut ClientInfo<T>,
         _request: GetTrackingUserGroupRequest,
         _client_registry: &ClientRegistry<T>,
         _socket: &std::net::UdpSocket,
     ) -> Result<GetTrackingUserGroupResponse, Error> {
-        login_required(&*ci)?;
-        Ok(GetTrackingUserGroupResponse { usergroup: 0 })
+        let user_id = login_required(&*ci)?;
+        for group in &self.groups(logger).groups {
+            if self.is_member(logger, user_id, group)? {
+                return Ok(GetTrackingUserGroupResponse { usergroup: group.id });
+            }
+        }
+        Ok(GetTrackingUserGroupResponse { usergroup: NO_GROUP })
     }
 
-    /// Handles the `GetTrackingUserGroupTags` request, returning a list of tracking tags for a user group.
+    /// Handles the `GetTrackingUserGroupTags` request, returning the tracking tags of a user group.
     ///
-    /// This function requires the client to be logged in. The list of tags returned
-    /// depends on whether the "tracking" feature is enabled during compilation.
-    /// If the "tracking" feature is not enabled, an empty list of tags is returned.
+    /// This function requires the client to be logged in. Unknown groups have no tags.
     fn get_tracking_user_group_tags(
         &self,
-        _logger: &Logger,
+        logger: &Logger,
         _ctx: &Context,
         ci: &mut ClientInfo<T>,
-        _request: GetTrackingUserGroupTagsRequest,
+        request: GetTrackingUserGroupTagsRequest,
         _client_registry: &ClientRegistry<T>,
         _socket: &std::net::UdpSocket,
     ) -> Result<GetTrackingUserGroupTagsResponse, Error> {
         login_required(&*ci)?;
+        let groups = self.groups(logger);
+        let tags = groups
+            .groups
+            .iter()
+            .find(|g| g.id == request.usergroup && request.usergroup != NO_GROUP)
+            .map(|g| g.tags.as_slice())
+            .unwrap_or_default();
         Ok(GetTrackingUserGroupTagsResponse {
-            #[cfg(not(feature = "tracking"))]
-            tags: Vec::new(),
-            #[cfg(feature = "tracking")]
-            tags: vec![
-                "GAME_START\0".to_string(),
-                "ADVCLIENT_STOP\0".to_string(),
-                "LEVEL_START\0".to_string(),
-                "LEVEL_STOP\0".to_string(),
-                "TX_SPEND\0".to_string(),
-                "LOBBY_ENTER\0".to_string(),
-                "LOBBY_EXITHOST\0".to_string(),
-                "LOBBY_EXITCLIENT\0".to_string(),
-                "AWARD_UNLOCK\0".to_string(),
-                "GAME_LOC\0".to_string(),
-                "PC_SPECS\0".to_string(),
-                "UPLAY_PASS\0".to_string(),
-                "MENU_PASS\0".to_string(),
-                "UPLAY_ACCOUNT\0".to_string(),
-                "UPLAY_ACCOUNT_MENU\0".to_string(),
-            ],
+            // keeps the additional terminator the tags have always been sent with
+            tags: tags.iter().map(|tag| format!("{tag}\0")).collect(),
         })
     }
 }
@@ -77,6 +146,92 @@ impl<T> TrackingExtensionProtocolServerTrait<T> for TrackingExtensionProtocolSer
 ///
 /// This function is typically used to register the tracking extension protocol
 /// with the server's protocol dispatcher.
-pub fn new_protocol<T: 'static>() -> Box<dyn Protocol<T>> {
-    Box::new(TrackingExtensionProtocolServer::new(TrackingExtensionProtocolServerImpl))
+pub fn new_protocol<T: 'static>(storage: Arc<Storage>) -> Box<dyn Protocol<T>> {
+    Box::new(TrackingExtensionProtocolServer::new(TrackingExtensionProtocolServerImpl {
+        storage,
+        groups: DataFile::new("data/tracking_groups.json"),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::trackingextension::tracking_extension_protocol::TrackingExtensionProtocolMethod;
+    use crate::test_util;
+
+    /// Sends a request as user 1000 (`Foo`) with the given groups.
+    fn call(storage: &Arc<Storage>, groups: &str, method: TrackingExtensionProtocolMethod, parameters: &[u8]) -> Vec<u8> {
+        let groups_path = std::env::temp_dir().join(format!("tracking_groups_{}_{:?}.json", std::process::id(), std::thread::current().id()));
+        std::fs::write(&groups_path, groups).unwrap();
+        let prot = TrackingExtensionProtocolServer::new(TrackingExtensionProtocolServerImpl {
+            storage: Arc::clone(storage),
+            groups: DataFile::new(&groups_path),
+        });
+        let resp = test_util::call(&prot, Some(1000), method as u32, parameters);
+        std::fs::remove_file(groups_path).unwrap();
+        resp.unwrap()
+    }
+
+    /// `GetTrackingUserGroup` for pid 1000
+    const GET_GROUP: &[u8] = b"\xe8\x03\x00\x00";
+
+    #[cfg(feature = "tracking")]
+    #[test]
+    fn group_by_privilege() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let groups = r#"{"groups": [
+            {"id": 2, "rule": {"privilege": 2000}, "tags": ["PLAYER_KILL"]},
+            {"id": 3, "rule": "all", "tags": ["GAME_START", "GAME_STOP"]}
+        ]}"#;
+        let resp = call(&storage, groups, TrackingExtensionProtocolMethod::GetTrackingUserGroup, GET_GROUP);
+        assert_eq!(resp, b"\x03\x00\x00\x00");
+        let resp = call(&storage, groups, TrackingExtensionProtocolMethod::GetTrackingUserGroupTags, b"\x03\x00\x00\x00");
+        assert_eq!(resp, b"\x02\x00\x00\x00\x0c\x00GAME_START\x00\x00\x0b\x00GAME_STOP\x00\x00");
+
+        test_util::block_on(storage.set_user_privilege_async(1000, 2000, "Tracking", true, None)).unwrap();
+        let resp = call(&storage, groups, TrackingExtensionProtocolMethod::GetTrackingUserGroup, GET_GROUP);
+        assert_eq!(resp, b"\x02\x00\x00\x00");
+    }
+
+    #[cfg(feature = "tracking")]
+    #[test]
+    fn group_by_percentage() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let resp = call(
+            &storage,
+            r#"{"groups": [{"id": 1, "rule": {"percentage": 0}, "tags": ["GAME_START"]}]}"#,
+            TrackingExtensionProtocolMethod::GetTrackingUserGroup,
+            GET_GROUP,
+        );
+        assert_eq!(resp, b"\x00\x00\x00\x00");
+        let resp = call(
+            &storage,
+            r#"{"groups": [{"id": 1, "rule": {"percentage": 100}, "tags": ["GAME_START"]}]}"#,
+            TrackingExtensionProtocolMethod::GetTrackingUserGroup,
+            GET_GROUP,
+        );
+        assert_eq!(resp, b"\x01\x00\x00\x00");
+
+        let in_rollout = (0..1000).filter(|user_id| rollout_bucket(*user_id, 1) < 10).count();
+        assert!((50..150).contains(&in_rollout), "{in_rollout} users in a 10% rollout");
+    }
+
+    #[test]
+    fn missing_groups() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let resp = call(&storage, "", TrackingExtensionProtocolMethod::GetTrackingUserGroup, GET_GROUP);
+        assert_eq!(resp, b"\x00\x00\x00\x00");
+        // without the feature nobody is tracked
+        if cfg!(not(feature = "tracking")) {
+            let resp = call(
+                &storage,
+                r#"{"groups": [{"id": 3, "rule": "all", "tags": ["GAME_START"]}]}"#,
+                TrackingExtensionProtocolMethod::GetTrackingUserGroup,
+                GET_GROUP,
+            );
+            assert_eq!(resp, b"\x00\x00\x00\x00");
+        }
+        let resp = call(&storage, "", TrackingExtensionProtocolMethod::GetTrackingUserGroupTags, b"\x00\x00\x00\x00");
+        assert_eq!(resp, b"\x00\x00\x00\x00");
+    }
 }
@@ -1,71 +1,449 @@
+//! Implements the `UplayWinProtocolServer` for Uplay actions and rewards.
+//!
+//! The catalogue of actions and rewards is read from `data/uplay_win.json`, which is reloaded when it changes.
+//! Completing an action earns its value in units, which can be spent on rewards. The codes have to match the ones the
+//! game knows.
+//!
+//! The shipped catalogue is empty, as the codes of the game aren't known yet. Its format is:
+//!
+//! ```json
+//! {
+//!     "actions": [
+//!         {"code": "...", "name": "...", "description": "...", "value": 10, "game_code": "", "platforms": ["PC"]}
+//!     ],
+//!     "rewards": [
+//!         {"code": "...", "name": "...", "description": "...", "value": 10, "reward_type_name": "...", "game_code": "", "platforms": []}
+//!     ]
+//! }
+//! ```
+//!
+//! `game_code` and `platforms` are optional, an empty value offers the entry to all games or platforms.
+
+use std::sync::Arc;
+
 use quazal::prudp::ClientRegistry;
 use quazal::rmc::types::QList;
 use quazal::rmc::Error;
 use quazal::rmc::Protocol;
 use quazal::ClientInfo;
 use quazal::Context;
+use serde::Deserialize;
 use slog::Logger;
 
+use crate::data_file::DataFile;
 use crate::login_required;
+use crate::protocols::uplay_win_service::types::UplayAction;
+use crate::protocols::uplay_win_service::types::UplayActionPlatform;
+use crate::protocols::uplay_win_service::types::UplayReward;
+use crate::protocols::uplay_win_service::types::UplayRewardPlatform;
+use crate::protocols::uplay_win_service::uplay_win_protocol::BuyRewardRequest;
+use crate::protocols::uplay_win_service::uplay_win_protocol::BuyRewardResponse;
+use crate::protocols::uplay_win_service::uplay_win_protocol::GetActionsCompletedCountRequest;
+use crate::protocols::uplay_win_service::uplay_win_protocol::GetActionsCompletedCountResponse;
 use crate::protocols::uplay_win_service::uplay_win_protocol::GetActionsCompletedRequest;
 use crate::protocols::uplay_win_service::uplay_win_protocol::GetActionsCompletedResponse;
+use crate::protocols::uplay_win_service::uplay_win_protocol::GetActionsCountRequest;
+use crate::protocols::uplay_win_service::uplay_win_protocol::GetActionsCountResponse;
+use crate::protocols::uplay_win_service::uplay_win_protocol::GetActionsRequest;
+use crate::protocols::uplay_win_service::uplay_win_protocol::GetActionsResponse;
 use crate::protocols::uplay_win_service::uplay_win_protocol::GetRewardsPurchasedRequest;
 use crate::protocols::uplay_win_service::uplay_win_protocol::GetRewardsPurchasedResponse;
+use crate::protocols::uplay_win_service::uplay_win_protocol::GetRewardsRequest;
+use crate::protocols::uplay_win_service::uplay_win_protocol::GetRewardsResponse;
+use crate::protocols::uplay_win_service::uplay_win_protocol::GetVirtualCurrencyUserBalanceRequest;
+use crate::protocols::uplay_win_service::uplay_win_protocol::GetVirtualCurrencyUserBalanceResponse;
+use crate::protocols::uplay_win_service::uplay_win_protocol::SetActionCompletedRequest;
+use crate::protocols::uplay_win_service::uplay_win_protocol::SetActionCompletedResponse;
+use crate::protocols::uplay_win_service::uplay_win_protocol::SetActionsCompletedRequest;
+use crate::protocols::uplay_win_service::uplay_win_protocol::SetActionsCompletedResponse;
 use crate::protocols::uplay_win_service::uplay_win_protocol::UplayWelcomeRequest;
 use crate::protocols::uplay_win_service::uplay_win_protocol::UplayWelcomeResponse;
 use crate::protocols::uplay_win_service::uplay_win_protocol::UplayWinProtocolServer;
 use crate::protocols::uplay_win_service::uplay_win_protocol::UplayWinProtocolServerTrait;
+use crate::storage::Storage;
+
+/// The actions and rewards offered to players.
+#[derive(Debug, Default, Deserialize)]
+struct Catalogue {
+    #[serde(default)]
+    actions: Vec<ActionDefinition>,
+    #[serde(default)]
+    rewards: Vec<RewardDefinition>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ActionDefinition {
+    code: String,
+    name: String,
+    description: String,
+    /// Units earned by completing the action.
+    value: i32,
+    /// Empty for all games.
+    #[serde(default)]
+    game_code: String,
+    /// Empty for all platforms.
+    #[serde(default)]
+    platforms: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RewardDefinition {
+    code: String,
+    name: String,
+    description: String,
+    /// Units needed to purchase the reward.
+    value: i32,
+    reward_type_name: String,
+    /// Empty for all games.
+    #[serde(default)]
+    game_code: String,
+    /// Empty for all platforms.
+    #[serde(default)]
+    platforms: Vec<String>,
+}
+
+/// Checks if a catalogue entry is offered for a game and platform.
+fn is_available(game_code: &str, platforms: &[String], requested_game_code: &str, requested_platform_code: &str) -> bool {
+    (game_code.is_empty() || requested_game_code.is_empty() || game_code == requested_game_code) && (platforms.is_empty() || platforms.iter().any(|p| p == requested_platform_code))
+}
+
+impl ActionDefinition {
+    fn to_action(&self, platform_code: &str, game_code: &str, completed: bool) -> UplayAction {
+        UplayAction {
+            code: self.code.clone(),
+            name: self.name.clone(),
+            description: self.description.clone(),
+            value: self.value,
+            game_code: if self.game_code.is_empty() { game_code.into() } else { self.game_code.clone() },
+            platforms: QList(vec![UplayActionPlatform {
+                platform_code: platform_code.into(),
+                completed,
+                specific_key: String::new(),
+            }]),
+        }
+    }
+}
+
+impl RewardDefinition {
+    fn to_reward(&self, platform_code: &str, game_code: &str, purchased: bool) -> UplayReward {
+        UplayReward {
+            code: self.code.clone(),
+            name: self.name.clone(),
+            description: self.description.clone(),
+            value: self.value,
+            reward_type_name: self.reward_type_name.clone(),
+            game_code: if self.game_code.is_empty() { game_code.into() } else { self.game_code.clone() },
+            platforms: QList(vec![UplayRewardPlatform {
+                platform_code: platform_code.into(),
+                purchased,
+            }]),
+        }
+    }
+}
+
+/// Returns the requested rows. A maximum of 0 or less returns all remaining rows.
+fn page<T: std::fmt::Debug>(items: Vec<T>, start_row_index: i32, maximum_rows: i32) -> QList<T> {
+    let items = items.into_iter().skip(usize::try_from(start_row_index).unwrap_or(0));
+    match usize::try_from(maximum_rows) {
+        Ok(max) if max > 0 => items.take(max).collect(),
+        _ => items.collect(),
+    }
+}
 
 /// Implementation of the `UplayWinProtocolServerTrait` for handling Uplay-related requests.
-struct UplayWinProtocolServerImpl;
+struct UplayWinProtocolServerImpl {
+    storage: Arc<Storage>,
+    catalogue: DataFile<Catalogue>,
+}
+
+impl UplayWinProtocolServerImpl {
+    /// Loads the catalogue, which is empty if the file is missing or invalid.
+    fn catalogue(&self, logger: &Logger) -> Arc<Catalogue> {
+        match self.catalogue.load() {
+            Ok(catalogue) => catalogue.unwrap_or_default(),
+            Err(e) => {
+                error!(logger, "Invalid Uplay catalogue {}: {e}", self.catalogue.path().display());
+                Arc::default()
+            }
+        }
+    }
+
+    /// Returns the actions offered for a game and platform, and whether the user completed them.
+    fn actions(&self, logger: &Logger, user_id: u32, platform_code: &str, game_code: &str) -> Result<Vec<(UplayAction, bool)>, Error> {
+        let completed = rmc_err!(self.storage.list_uplay_actions_completed(user_id, platform_code), logger, "error listing completed actions")?;
+        Ok(self
+            .catalogue(logger)
+            .actions
+            .iter()
+            .filter(|a| is_available(&a.game_code, &a.platforms, game_code, platform_code))
+            .map(|a| {
+                let is_completed = completed.contains(&a.code);
+                (a.to_action(platform_code, game_code, is_completed), is_completed)
+            })
+            .collect())
+    }
+
+    /// Returns the rewards offered for a game and platform, and whether the user purchased them.
+    fn rewards(&self, logger: &Logger, user_id: u32, platform_code: &str, game_code: &str) -> Result<Vec<(UplayReward, bool)>, Error> {
+        let purchased = rmc_err!(self.storage.list_uplay_rewards_purchased(user_id, platform_code), logger, "error listing purchased rewards")?;
+        Ok(self
+            .catalogue(logger)
+            .rewards
+            .iter()
+            .filter(|r| is_available(&r.game_code, &r.platforms, game_code, platform_code))
+            .map(|r| {
+                let is_purchased = purchased.contains(&r.code);
+                (r.to_reward(platform_code, game_code, is_purchased), is_purchased)
+            })
+            .collect())
+    }
+
+    /// Completes an action, returns `None` if it's unknown.
+    fn complete_action(&self, logger: &Logger, user_id: u32, action_code: &str, platform_code: &str, game_code: &str) -> Result<Option<UplayAction>, Error> {
+        let catalogue = self.catalogue(logger);
+        let Some(action) = catalogue
+            .actions
+            .iter()
+            .find(|a| a.code == action_code && is_available(&a.game_code, &a.platforms, game_code, platform_code))
+        else {
+            warn!(logger, "Unknown Uplay action {action_code} for {game_code} on {platform_code}");
+            return Ok(None);
+        };
+        let newly_completed = rmc_err!(
+            self.storage.complete_uplay_action(user_id, action_code, platform_code, action.value),
+            logger,
+            "error completing action"
+        )?;
+        if newly_completed {
+            info!(logger, "User {user_id} completed Uplay action {action_code}, earning {} units", action.value);
+        }
+        Ok(Some(action.to_action(platform_code, game_code, true)))
+    }
+}
 
 impl<CI> UplayWinProtocolServerTrait<CI> for UplayWinProtocolServerImpl {
-    /// Handles the `UplayWelcome` request.
-    ///
-    /// This function requires the client to be logged in. It currently returns an empty list of actions.
-    fn uplay_welcome(
+    /// Handles the `GetActions` request, returning the actions offered with the user's progress.
+    fn get_actions(
         &self,
-        _logger: &Logger,
+        logger: &Logger,
         _ctx: &Context,
         ci: &mut ClientInfo<CI>,
-        _request: UplayWelcomeRequest,
+        request: GetActionsRequest,
         _client_registry: &ClientRegistry<CI>,
         _socket: &std::net::UdpSocket,
-    ) -> Result<UplayWelcomeResponse, Error> {
-        login_required(&*ci)?;
-        Ok(UplayWelcomeResponse { action_list: QList::default() })
+    ) -> Result<GetActionsResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let actions = self
+            .actions(logger, user_id, &request.platform_code, &request.game_code)?
+            .into_iter()
+            .map(|(a, _)| a)
+            .collect();
+        Ok(GetActionsResponse {
+            action_list: page(actions, request.start_row_index, request.maximum_rows),
+        })
     }
 
-    /// Handles the `GetActionsCompleted` request.
-    ///
-    /// This function requires the client to be logged in. It currently returns an empty list of completed actions.
+    /// Handles the `GetActionsCompleted` request, returning the actions the user completed.
     fn get_actions_completed(
         &self,
-        _logger: &Logger,
+        logger: &Logger,
         _ctx: &Context,
         ci: &mut ClientInfo<CI>,
-        _request: GetActionsCompletedRequest,
+        request: GetActionsCompletedRequest,
         _client_registry: &ClientRegistry<CI>,
         _socket: &std::net::UdpSocket,
     ) -> Result<GetActionsCompletedResponse, Error> {
-        login_required(&*ci)?;
-        Ok(GetActionsCompletedResponse { action_list: QList::default() })
+        let user_id = login_required(&*ci)?;
+        let actions = self
+            .actions(logger, user_id, &request.platform_code, &request.game_code)?
+            .into_iter()
+            .filter_map(|(a, completed)| completed.then_some(a))
+            .collect();
+        Ok(GetActionsCompletedResponse {
+            action_list: page(actions, request.start_row_index, request.maximum_rows),
+        })
     }
 
-    /// Handles the `GetRewardsPurchased` request.
-    ///
-    /// This function requires the client to be logged in. It currently returns an empty list of purchased rewards.
+    /// Handles the `GetActionsCount` request.
+    fn get_actions_count(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetActionsCountRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetActionsCountResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let actions = self.actions(logger, user_id, &request.platform_code, &request.game_code)?;
+        Ok(GetActionsCountResponse {
+            actions_count: i32::try_from(actions.len()).unwrap_or(i32::MAX),
+        })
+    }
+
+    /// Handles the `GetActionsCompletedCount` request.
+    fn get_actions_completed_count(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetActionsCompletedCountRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetActionsCompletedCountResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let actions = self.actions(logger, user_id, &request.platform_code, &request.game_code)?;
+        Ok(GetActionsCompletedCountResponse {
+            actions_count: i32::try_from(actions.iter().filter(|(_, completed)| *completed).count()).unwrap_or(i32::MAX),
+        })
+    }
+
+    /// Handles the `GetRewards` request, returning the rewards offered and whether the user purchased them.
+    fn get_rewards(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetRewardsRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetRewardsResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let rewards = self
+            .rewards(logger, user_id, &request.platform_code, &request.game_code)?
+            .into_iter()
+            .map(|(r, _)| r)
+            .collect();
+        Ok(GetRewardsResponse {
+            reward_list: page(rewards, request.start_row_index, request.maximum_rows),
+        })
+    }
+
+    /// Handles the `GetRewardsPurchased` request, returning the rewards the user unlocked.
     fn get_rewards_purchased(
         &self,
-        _logger: &Logger,
+        logger: &Logger,
         _ctx: &Context,
         ci: &mut ClientInfo<CI>,
-        _request: GetRewardsPurchasedRequest,
+        request: GetRewardsPurchasedRequest,
         _client_registry: &ClientRegistry<CI>,
         _socket: &std::net::UdpSocket,
     ) -> Result<GetRewardsPurchasedResponse, Error> {
-        login_required(&*ci)?;
-        Ok(GetRewardsPurchasedResponse { reward_list: QList::default() })
+        let user_id = login_required(&*ci)?;
+        let rewards = self
+            .rewards(logger, user_id, &request.platform_code, &request.game_code)?
+            .into_iter()
+            .filter_map(|(r, purchased)| purchased.then_some(r))
+            .collect();
+        Ok(GetRewardsPurchasedResponse {
+            reward_list: page(rewards, request.start_row_index, request.maximum_rows),
+        })
+    }
+
+    /// Handles the `UplayWelcome` request, returning all actions offered with the user's progress.
+    fn uplay_welcome(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: UplayWelcomeRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<UplayWelcomeResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let action_list = self
+            .actions(logger, user_id, &request.platform_code, &request.game_code)?
+            .into_iter()
+            .map(|(a, _)| a)
+            .collect();
+        Ok(UplayWelcomeResponse { action_list })
+    }
+
+    /// Handles the `SetActionCompleted` request, returning the completed action.
+    fn set_action_completed(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: SetActionCompletedRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<SetActionCompletedResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let unlocked_action = self
+            .complete_action(logger, user_id, &request.action_code, &request.platform_code, &request.game_code)?
+            .ok_or(Error::AccessDenied)?;
+        Ok(SetActionCompletedResponse { unlocked_action })
+    }
+
+    /// Handles the `SetActionsCompleted` request, returning the completed actions. Unknown actions are skipped.
+    fn set_actions_completed(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: SetActionsCompletedRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<SetActionsCompletedResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let mut action_list = QList::default();
+        for action_code in request.action_code_list.iter() {
+            if let Some(action) = self.complete_action(logger, user_id, action_code, &request.platform_code, &request.game_code)? {
+                action_list.0.push(action);
+            }
+        }
+        Ok(SetActionsCompletedResponse { action_list })
+    }
+
+    /// Handles the `GetVirtualCurrencyUserBalance` request, returning the units the user can spend.
+    fn get_virtual_currency_user_balance(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        _request: GetVirtualCurrencyUserBalanceRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetVirtualCurrencyUserBalanceResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let virtual_currency_user_balance = rmc_err!(self.storage.uplay_balance(user_id), logger, "error getting balance")?;
+        Ok(GetVirtualCurrencyUserBalanceResponse { virtual_currency_user_balance })
+    }
+
+    /// Handles the `BuyReward` request, returning the remaining units.
+    ///
+    /// Access is denied for unknown rewards and if the user can't afford it.
+    fn buy_reward(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: BuyRewardRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<BuyRewardResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let catalogue = self.catalogue(logger);
+        let Some(reward) = catalogue
+            .rewards
+            .iter()
+            .find(|r| r.code == request.reward_code && is_available(&r.game_code, &r.platforms, "", &request.platform_code))
+        else {
+            warn!(logger, "Unknown Uplay reward {} on {}", request.reward_code, request.platform_code);
+            return Err(Error::AccessDenied);
+        };
+        let balance = rmc_err!(
+            self.storage.purchase_uplay_reward(user_id, &reward.code, &request.platform_code, reward.value),
+            logger,
+            "error purchasing reward"
+        )?;
+        let Some(virtual_currency_user_balance) = balance else {
+            warn!(logger, "User {user_id} can't afford Uplay reward {}", reward.code);
+            return Err(Error::AccessDenied);
+        };
+        info!(logger, "User {user_id} purchased Uplay reward {}", reward.code);
+        Ok(BuyRewardResponse { virtual_currency_user_balance })
     }
 }
 
@@ -73,6 +451,141 @@ impl<CI> UplayWinProtocolServerTrait<CI> for UplayWinProtocolServerImpl {
 ///
 /// This function is typically used to register the Uplay protocol
 /// with the server's protocol dispatcher.
-pub fn new_protocol<T: 'static>() -> Box<dyn Protocol<T>> {
-    Box::new(UplayWinProtocolServer::new(UplayWinProtocolServerImpl))
+pub fn new_protocol<T: 'static>(storage: Arc<Storage>) -> Box<dyn Protocol<T>> {
+    Box::new(UplayWinProtocolServer::new(UplayWinProtocolServerImpl {
+        storage,
+        catalogue: DataFile::new("data/uplay_win.json"),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use quazal::rmc::basic::FromStream;
+    use quazal::rmc::basic::ToStream;
+
+    use super::*;
+    use crate::protocols::uplay_win_service::uplay_win_protocol::UplayWinProtocolMethod;
+    use crate::test_util;
+
+    const CATALOGUE: &str = r#"{
+        "actions": [
+            {"code": "TEST_ACTION_1", "name": "First", "description": "", "value": 20},
+            {"code": "TEST_ACTION_2", "name": "Second", "description": "", "value": 30, "platforms": ["PC", "PS3"]}
+        ],
+        "rewards": [
+            {"code": "TEST_REWARD", "name": "Reward", "description": "", "value": 20, "reward_type_name": "InGameItem"}
+        ]
+    }"#;
+
+    /// Sends a request as user 1000 (`Foo`) with the test catalogue.
+    fn call(storage: &Arc<Storage>, method: UplayWinProtocolMethod, parameters: &[u8]) -> Result<Vec<u8>, Error> {
+        let catalogue_path = std::env::temp_dir().join(format!("uplay_win_{}_{:?}.json", std::process::id(), std::thread::current().id()));
+        std::fs::write(&catalogue_path, CATALOGUE).unwrap();
+        let prot = UplayWinProtocolServer::new(UplayWinProtocolServerImpl {
+            storage: Arc::clone(storage),
+            catalogue: DataFile::new(&catalogue_path),
+        });
+        let resp = test_util::call(&prot, Some(1000), method as u32, parameters);
+        std::fs::remove_file(catalogue_path).unwrap();
+        resp
+    }
+
+    fn complete(storage: &Arc<Storage>, action_code: &str) -> Result<Vec<u8>, Error> {
+        let request = SetActionCompletedRequest {
+            action_code: action_code.into(),
+            culture_name: String::from("en-US"),
+            platform_code: String::from("PC"),
+            game_code: String::from("SCBL"),
+        };
+        call(storage, UplayWinProtocolMethod::SetActionCompleted, &request.to_bytes())
+    }
+
+    fn buy(storage: &Arc<Storage>, reward_code: &str) -> Result<Vec<u8>, Error> {
+        let request = BuyRewardRequest {
+            reward_code: reward_code.into(),
+            platform_code: String::from("PC"),
+        };
+        call(storage, UplayWinProtocolMethod::BuyReward, &request.to_bytes())
+    }
+
+    /// `GetVirtualCurrencyUserBalance` for platform `PC`
+    const GET_BALANCE: &[u8] = b"\x03\x00PC\x00";
+
+    #[test]
+    fn complete_action() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let resp = complete(&storage, "TEST_ACTION_1").unwrap();
+        let resp = SetActionCompletedResponse::from_bytes(&resp).unwrap();
+        assert_eq!(resp.unlocked_action.code, "TEST_ACTION_1");
+        assert_eq!(resp.unlocked_action.game_code, "SCBL");
+        assert!(resp.unlocked_action.platforms[0].completed);
+
+        // completing an action twice earns its units once
+        complete(&storage, "TEST_ACTION_1").unwrap();
+        let resp = call(&storage, UplayWinProtocolMethod::GetVirtualCurrencyUserBalance, GET_BALANCE).unwrap();
+        assert_eq!(resp, 20i32.to_le_bytes());
+
+        assert!(matches!(complete(&storage, "UNKNOWN"), Err(Error::AccessDenied)));
+    }
+
+    #[test]
+    fn actions_completed() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        complete(&storage, "TEST_ACTION_2").unwrap();
+        let request = GetActionsCompletedRequest {
+            start_row_index: 0,
+            maximum_rows: 0,
+            sort_expression: String::new(),
+            culture_name: String::from("en-US"),
+            platform_code: String::from("PC"),
+            game_code: String::from("SCBL"),
+        };
+        let resp = call(&storage, UplayWinProtocolMethod::GetActionsCompleted, &request.to_bytes()).unwrap();
+        let resp = GetActionsCompletedResponse::from_bytes(&resp).unwrap();
+        assert_eq!(resp.action_list.iter().map(|a| a.code.as_str()).collect::<Vec<_>>(), ["TEST_ACTION_2"]);
+
+        // completions are per platform
+        let request = GetActionsCompletedRequest {
+            platform_code: String::from("PS3"),
+            ..request
+        };
+        let resp = call(&storage, UplayWinProtocolMethod::GetActionsCompleted, &request.to_bytes()).unwrap();
+        assert_eq!(resp, b"\x00\x00\x00\x00");
+    }
+
+    #[test]
+    fn buy_reward() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        assert!(matches!(buy(&storage, "TEST_REWARD"), Err(Error::AccessDenied)));
+
+        complete(&storage, "TEST_ACTION_2").unwrap();
+        let resp = buy(&storage, "TEST_REWARD").unwrap();
+        assert_eq!(resp, 10i32.to_le_bytes());
+        // already purchased rewards are free
+        let resp = buy(&storage, "TEST_REWARD").unwrap();
+        assert_eq!(resp, 10i32.to_le_bytes());
+
+        let request = GetRewardsPurchasedRequest {
+            start_row_index: 0,
+            maximum_rows: 10,
+            sort_expression: String::new(),
+            culture_name: String::from("en-US"),
+            platform_code: String::from("PC"),
+            game_code: String::from("SCBL"),
+        };
+        let resp = call(&storage, UplayWinProtocolMethod::GetRewardsPurchased, &request.to_bytes()).unwrap();
+        let resp = GetRewardsPurchasedResponse::from_bytes(&resp).unwrap();
+        assert_eq!(resp.reward_list.iter().map(|r| r.code.as_str()).collect::<Vec<_>>(), ["TEST_REWARD"]);
+        assert!(resp.reward_list[0].platforms[0].purchased);
+
+        assert!(matches!(buy(&storage, "UNKNOWN"), Err(Error::AccessDenied)));
+    }
+
+    #[test]
+    fn paging() {
+        let items: Vec<u32> = (0..5).collect();
+        assert_eq!(page(items.clone(), 1, 2).0, [1, 2]);
+        assert_eq!(page(items.clone(), 3, 0).0, [3, 4]);
+        assert_eq!(page(items, -1, -1).0, [0, 1, 2, 3, 4]);
+    }
 }
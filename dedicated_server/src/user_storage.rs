@@ -1,4 +1,8 @@
+use std::sync::Arc;
+
 use quazal::prudp::ClientRegistry;
+use quazal::rmc::basic::FromStream;
+use quazal::rmc::basic::ToStream;
 use quazal::rmc::types::DateTime;
 use quazal::rmc::types::QList;
 use quazal::rmc::types::Variant;
@@ -8,90 +12,837 @@ use quazal::ClientInfo;
 use quazal::Context;
 use slog::Logger;
 
+use crate::config::UserStorageConfig;
 use crate::login_required;
 use crate::protocols::user_storage::types::ContentProperty;
 use crate::protocols::user_storage::types::UserContent;
 use crate::protocols::user_storage::types::UserContentKey;
 use crate::protocols::user_storage::types::UserContentURL;
+use crate::protocols::user_storage::types::UserSlotCount;
+use crate::protocols::user_storage::types::UserStorageQuery;
+use crate::protocols::user_storage::types::WeightedTag;
+use crate::protocols::user_storage::user_storage_protocol::DeleteContentRequest;
+use crate::protocols::user_storage::user_storage_protocol::DeleteContentResponse;
+use crate::protocols::user_storage::user_storage_protocol::GetContentDbRequest;
+use crate::protocols::user_storage::user_storage_protocol::GetContentDbResponse;
 use crate::protocols::user_storage::user_storage_protocol::GetContentUrlRequest;
 use crate::protocols::user_storage::user_storage_protocol::GetContentUrlResponse;
+use crate::protocols::user_storage::user_storage_protocol::GetFavouritesRequest;
+use crate::protocols::user_storage::user_storage_protocol::GetFavouritesResponse;
+use crate::protocols::user_storage::user_storage_protocol::GetMetaDataRequest;
+use crate::protocols::user_storage::user_storage_protocol::GetMetaDataResponse;
+use crate::protocols::user_storage::user_storage_protocol::GetMostPopularTagsRequest;
+use crate::protocols::user_storage::user_storage_protocol::GetMostPopularTagsResponse;
+use crate::protocols::user_storage::user_storage_protocol::GetOwnContentsRequest;
+use crate::protocols::user_storage::user_storage_protocol::GetOwnContentsResponse;
+use crate::protocols::user_storage::user_storage_protocol::GetSlotCountRequest;
+use crate::protocols::user_storage::user_storage_protocol::GetSlotCountResponse;
+use crate::protocols::user_storage::user_storage_protocol::GetTagsRequest;
+use crate::protocols::user_storage::user_storage_protocol::GetTagsResponse;
+use crate::protocols::user_storage::user_storage_protocol::IncrementPlayCountRequest;
+use crate::protocols::user_storage::user_storage_protocol::IncrementPlayCountResponse;
+use crate::protocols::user_storage::user_storage_protocol::IsLikedRequest;
+use crate::protocols::user_storage::user_storage_protocol::IsLikedResponse;
+use crate::protocols::user_storage::user_storage_protocol::LikeRequest;
+use crate::protocols::user_storage::user_storage_protocol::LikeResponse;
+use crate::protocols::user_storage::user_storage_protocol::MakeFavouriteRequest;
+use crate::protocols::user_storage::user_storage_protocol::MakeFavouriteResponse;
+use crate::protocols::user_storage::user_storage_protocol::RemoveFromFavouritesRequest;
+use crate::protocols::user_storage::user_storage_protocol::RemoveFromFavouritesResponse;
+use crate::protocols::user_storage::user_storage_protocol::ReportInappropriateRequest;
+use crate::protocols::user_storage::user_storage_protocol::ReportInappropriateResponse;
+use crate::protocols::user_storage::user_storage_protocol::SaveContentAndGetUploadInfoRequest;
+use crate::protocols::user_storage::user_storage_protocol::SaveContentAndGetUploadInfoResponse;
+use crate::protocols::user_storage::user_storage_protocol::SaveContentDbRequest;
+use crate::protocols::user_storage::user_storage_protocol::SaveContentDbResponse;
+use crate::protocols::user_storage::user_storage_protocol::SaveMetaDataRequest;
+use crate::protocols::user_storage::user_storage_protocol::SaveMetaDataResponse;
+use crate::protocols::user_storage::user_storage_protocol::SearchContentsByPlayersRequest;
+use crate::protocols::user_storage::user_storage_protocol::SearchContentsByPlayersResponse;
+use crate::protocols::user_storage::user_storage_protocol::SearchContentsByPlayersWithTotalRequest;
+use crate::protocols::user_storage::user_storage_protocol::SearchContentsByPlayersWithTotalResponse;
 use crate::protocols::user_storage::user_storage_protocol::SearchContentsRequest;
 use crate::protocols::user_storage::user_storage_protocol::SearchContentsResponse;
+use crate::protocols::user_storage::user_storage_protocol::SearchContentsWithTotalRequest;
+use crate::protocols::user_storage::user_storage_protocol::SearchContentsWithTotalResponse;
+use crate::protocols::user_storage::user_storage_protocol::TagContentRequest;
+use crate::protocols::user_storage::user_storage_protocol::TagContentResponse;
+use crate::protocols::user_storage::user_storage_protocol::UnlikeRequest;
+use crate::protocols::user_storage::user_storage_protocol::UnlikeResponse;
+use crate::protocols::user_storage::user_storage_protocol::UpdateCustomStatRequest;
+use crate::protocols::user_storage::user_storage_protocol::UpdateCustomStatResponse;
+use crate::protocols::user_storage::user_storage_protocol::UploadEndRequest;
+use crate::protocols::user_storage::user_storage_protocol::UploadEndResponse;
 use crate::protocols::user_storage::user_storage_protocol::UserStorageProtocolServer;
 use crate::protocols::user_storage::user_storage_protocol::UserStorageProtocolServerTrait;
+use crate::simple_http::USER_CONTENT_PATH;
+use crate::simple_http::USER_CONTENT_UPLOAD_PATH;
+use crate::storage::Content;
+use crate::storage::ContentOrder;
+use crate::storage::Storage;
+
+/// Ids of content properties with a known meaning.
+pub(crate) mod property_ids {
+    /// Type of the content. `SaveMetaData` and `SaveContentDb` don't take a key, so the game has to send it as property.
+    /// Guessed from the field order of `UserContent`.
+    pub const TYPE_ID: u32 = 1;
+    /// Id of the content, see [`TYPE_ID`].
+    pub const CONTENT_ID: u32 = 2;
+    pub const CREATED: u32 = 4;
+    pub const MODIFIED: u32 = 5;
+    pub const SIZE: u32 = 6;
+    /// Upper case hex md5 of the data.
+    pub const HASH: u32 = 7;
+
+    /// Returns whether the property is maintained by the server and not stored as sent by the game.
+    pub fn is_managed(id: u32) -> bool {
+        matches!(id, TYPE_ID | CONTENT_ID | CREATED | MODIFIED | SIZE | HASH)
+    }
+}
+
+/// Query ids selecting the sort order of content searches.
+///
+/// The ids the game uses for its listings haven't been mapped yet, unknown ids return the newest contents first.
+mod query_ids {
+    pub const MOST_LIKED: u32 = 1;
+    pub const MOST_PLAYED: u32 = 2;
+}
+
+/// The content served by the server itself, the `storage_path` file of the content server.
+///
+/// It's the only content of its type, players can't save contents of that type.
+mod builtin {
+    pub const TYPE_ID: u32 = 0x8000_0002;
+    pub const CONTENT_ID: u64 = 1;
+    pub const PID: u32 = 0x0000_045f;
+}
+
+/// Returns whether `key` addresses the [`builtin`] content.
+fn is_builtin(key: &UserContentKey) -> bool {
+    key.type_id == builtin::TYPE_ID && key.content_id == builtin::CONTENT_ID
+}
+
+/// Builds the [`builtin`] content, with the properties of the file shipped with the server.
+fn builtin_content() -> UserContent {
+    #![allow(clippy::unreadable_literal)]
+
+    UserContent {
+        key: UserContentKey {
+            type_id: builtin::TYPE_ID,
+            content_id: builtin::CONTENT_ID,
+        },
+        pid: builtin::PID,
+        properties: QList(vec![
+            ContentProperty {
+                id: property_ids::SIZE,
+                value: Variant::I64(0x274),
+            },
+            ContentProperty {
+                id: property_ids::CREATED,
+                value: Variant::DateTime(DateTime(0x1f768edbd6)),
+            },
+            ContentProperty {
+                id: property_ids::MODIFIED,
+                value: Variant::DateTime(DateTime(0x1f768f13f9)),
+            },
+            ContentProperty {
+                id: property_ids::HASH,
+                value: Variant::String("A6E32CFD0C2B2CFFE2D0C785830B7C49".to_string()),
+            },
+        ]),
+    }
+}
+
+/// Returns the integer value of a property, if it is set.
+fn property_u64(properties: &QList<ContentProperty>, id: u32) -> Option<u64> {
+    properties.0.iter().find(|p| p.id == id).and_then(|p| match p.value {
+        Variant::I64(value) => u64::try_from(value).ok(),
+        Variant::U64(value) => Some(value),
+        _ => None,
+    })
+}
+
+/// Encodes the properties sent by the game for storing them.
+pub(crate) fn encode_properties(properties: &QList<ContentProperty>) -> Vec<(u32, Vec<u8>)> {
+    properties
+        .0
+        .iter()
+        .filter(|p| !property_ids::is_managed(p.id))
+        .map(|p| (p.id, p.value.to_bytes()))
+        .collect()
+}
+
+/// Converts a protocol content id to the one used in storage. Ids out of range can't exist.
+pub(crate) fn storage_id(key: &UserContentKey) -> Option<i64> {
+    i64::try_from(key.content_id).ok().filter(|id| *id > 0)
+}
+
+pub(crate) fn to_key(type_id: u32, content_id: i64) -> UserContentKey {
+    UserContentKey {
+        type_id,
+        content_id: content_id.unsigned_abs(),
+    }
+}
+
+/// Builds the content the game expects for a stored content, including the server maintained properties.
+pub(crate) fn to_user_content(content: Content) -> UserContent {
+    let mut properties = vec![
+        ContentProperty {
+            id: property_ids::SIZE,
+            value: Variant::I64(content.size),
+        },
+        ContentProperty {
+            id: property_ids::CREATED,
+            value: Variant::DateTime(content.created_at.parse().unwrap_or_default()),
+        },
+        ContentProperty {
+            id: property_ids::MODIFIED,
+            value: Variant::DateTime(content.updated_at.parse().unwrap_or_default()),
+        },
+        ContentProperty {
+            id: property_ids::HASH,
+            value: Variant::String(content.hash),
+        },
+    ];
+    properties.extend(
+        content
+            .properties
+            .into_iter()
+            .filter_map(|(id, value)| Variant::from_bytes(&value).ok().map(|value| ContentProperty { id, value })),
+    );
+    UserContent {
+        key: to_key(content.type_id, content.id),
+        pid: content.owner_id,
+        properties: QList(properties),
+    }
+}
+
+/// Returns the address of a path on the content server.
+pub(crate) fn content_url(ctx: &Context, path: String) -> UserContentURL {
+    let protocol = ctx.settings.get("content_protocol").map_or("http://", String::as_str).to_owned();
+    let host = ctx.settings.get("storage_host").expect("missing storage_host setting").to_owned();
+    UserContentURL { protocol, host, path }
+}
 
 /// Implementation of the `UserStorageProtocolServerTrait` for handling user storage requests.
-struct UserStorageProtocolServerImpl;
+struct UserStorageProtocolServerImpl {
+    storage: Arc<Storage>,
+    config: UserStorageConfig,
+}
+
+impl UserStorageProtocolServerImpl {
+    /// Returns a page of finalized contents of a type, optionally restricted to the given owners, and the total number of matches.
+    fn search(&self, logger: &Logger, query: &UserStorageQuery, owners: Option<&[u32]>) -> Result<(QList<UserContent>, u32), Error> {
+        if query.type_id == builtin::TYPE_ID {
+            let matches = owners.is_none_or(|owners| owners.contains(&builtin::PID));
+            let results = if matches && query.result_range.offset == 0 { vec![builtin_content()] } else { vec![] };
+            return Ok((QList(results), u32::from(matches)));
+        }
+
+        let order = match query.query_id {
+            query_ids::MOST_LIKED => ContentOrder::Likes,
+            query_ids::MOST_PLAYED => ContentOrder::Plays,
+            _ => ContentOrder::Newest,
+        };
+        let range = &query.result_range;
+        let (contents, total) = rmc_err!(
+            self.storage.search_contents(query.type_id, owners, order, range.offset, range.size),
+            logger,
+            "Error searching contents"
+        )?;
+        Ok((contents.into_iter().map(to_user_content).collect(), total))
+    }
+
+    /// Loads a content visible to `user_id`.
+    fn find(&self, logger: &Logger, user_id: u32, key: &UserContentKey) -> Result<Content, Error> {
+        let content = match storage_id(key) {
+            Some(content_id) => rmc_err!(self.storage.find_content(content_id, Some(user_id)), logger, "Error loading content")?,
+            None => None,
+        };
+        match content {
+            Some(content) if content.type_id == key.type_id => Ok(content),
+            _ => {
+                warn!(logger, "Content {:?} not found", key);
+                Err(Error::AccessDenied)
+            }
+        }
+    }
+
+    /// Creates a new content or updates an existing one owned by `user_id`.
+    ///
+    /// New contents are only created if the user has a free slot for the type. Banned users can't save contents.
+    fn save(&self, logger: &Logger, user_id: u32, key: &UserContentKey, properties: &QList<ContentProperty>, data: Option<&[u8]>) -> Result<i64, Error> {
+        if key.type_id == builtin::TYPE_ID {
+            warn!(logger, "User {user_id} tried to save a content of the built-in type");
+            return Err(Error::AccessDenied);
+        }
+        if let Some(data) = data {
+            if data.len() > usize::try_from(self.config.max_content_size).unwrap_or(usize::MAX) {
+                warn!(logger, "Content of {} bytes exceeds the maximum size", data.len());
+                return Err(Error::AccessDenied);
+            }
+        }
+
+        let ban = rmc_err!(self.storage.find_content_ban(user_id, key.type_id), logger, "Error checking content ban")?;
+        if let Some(ban) = ban {
+            warn!(logger, "User {user_id} is banned from content type {}: {}", key.type_id, ban.reason);
+            return Err(Error::AccessDenied);
+        }
+
+        let content_id = if key.content_id == 0 {
+            let used = rmc_err!(self.storage.count_contents(user_id, key.type_id), logger, "Error counting contents")?;
+            if used >= self.config.slots {
+                warn!(logger, "User {user_id} has no free slot for content type {}", key.type_id);
+                return Err(Error::AccessDenied);
+            }
+            None
+        } else {
+            Some(storage_id(key).ok_or(Error::AccessDenied)?)
+        };
+
+        let content_id = rmc_err!(
+            self.storage.save_content(user_id, key.type_id, content_id, &encode_properties(properties), data),
+            logger,
+            "Error saving content"
+        )?;
+        content_id.ok_or(Error::AccessDenied)
+    }
+
+    /// Builds the key of a content addressed by its properties, see [`property_ids::TYPE_ID`].
+    fn key_from_properties(logger: &Logger, properties: &QList<ContentProperty>) -> Result<UserContentKey, Error> {
+        let Some(type_id) = property_u64(properties, property_ids::TYPE_ID).and_then(|id| u32::try_from(id).ok()) else {
+            warn!(logger, "Content type missing in properties {:?}", properties);
+            return Err(Error::InternalError);
+        };
+        Ok(UserContentKey {
+            type_id,
+            content_id: property_u64(properties, property_ids::CONTENT_ID).unwrap_or_default(),
+        })
+    }
+}
 
 impl<CI> UserStorageProtocolServerTrait<CI> for UserStorageProtocolServerImpl {
-    /// Handles the `SearchContents` request, returning a list of user content.
+    /// Handles the `SearchContents` request, returning finalized contents of the requested type.
     ///
-    /// This function requires the client to be logged in. It currently returns a hardcoded
-    /// list of user content for a specific query type.
+    /// This function requires the client to be logged in.
     fn search_contents(
         &self,
-        _logger: &Logger,
+        logger: &Logger,
         _ctx: &Context,
         ci: &mut ClientInfo<CI>,
         request: SearchContentsRequest,
         _client_registry: &ClientRegistry<CI>,
         _socket: &std::net::UdpSocket,
     ) -> Result<SearchContentsResponse, Error> {
-        #![allow(clippy::unreadable_literal)]
+        login_required(&*ci)?;
+        let (search_results, _) = self.search(logger, &request.query, None)?;
+        Ok(SearchContentsResponse { search_results })
+    }
 
+    /// Handles the `SearchContentsWithTotal` request, like `SearchContents` but also returning the number of matches.
+    ///
+    /// This function requires the client to be logged in.
+    fn search_contents_with_total(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: SearchContentsWithTotalRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<SearchContentsWithTotalResponse, Error> {
         login_required(&*ci)?;
-        if request.query.type_id == 0x8000_0002 {
-            let search_results = QList(vec![UserContent {
-                key: UserContentKey {
-                    type_id: 0x8000_0002,
-                    content_id: 1,
-                },
-                pid: 0x0000_045f,
-                properties: QList(vec![
-                    ContentProperty {
-                        id: 6,
-                        value: Variant::I64(0x274),
-                    },
-                    ContentProperty {
-                        id: 4,
-                        value: Variant::DateTime(DateTime(0x1f768edbd6)),
-                    },
-                    ContentProperty {
-                        id: 5,
-                        value: Variant::DateTime(DateTime(0x1f768f13f9)),
-                    },
-                    ContentProperty {
-                        id: 7,
-                        value: Variant::String("A6E32CFD0C2B2CFFE2D0C785830B7C49".to_string()),
-                    },
-                ]),
-            }]);
-            Ok(SearchContentsResponse { search_results })
-        } else {
-            Ok(SearchContentsResponse { search_results: QList::default() })
+        let (search_results, total_results) = self.search(logger, &request.query, None)?;
+        Ok(SearchContentsWithTotalResponse { search_results, total_results })
+    }
+
+    /// Handles the `SearchContentsByPlayers` request, returning finalized contents of the given players.
+    ///
+    /// This function requires the client to be logged in.
+    fn search_contents_by_players(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: SearchContentsByPlayersRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<SearchContentsByPlayersResponse, Error> {
+        login_required(&*ci)?;
+        let (search_results, _) = self.search(logger, &request.query, Some(request.pids.0.as_slice()))?;
+        Ok(SearchContentsByPlayersResponse { search_results })
+    }
+
+    /// Handles the `SearchContentsByPlayersWithTotal` request, like `SearchContentsByPlayers` but also returning the number of matches.
+    ///
+    /// This function requires the client to be logged in.
+    fn search_contents_by_players_with_total(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: SearchContentsByPlayersWithTotalRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<SearchContentsByPlayersWithTotalResponse, Error> {
+        login_required(&*ci)?;
+        let (search_results, total_results) = self.search(logger, &request.query, Some(request.pids.0.as_slice()))?;
+        Ok(SearchContentsByPlayersWithTotalResponse { search_results, total_results })
+    }
+
+    /// Handles the `DeleteContent` request, deleting a content of the client.
+    ///
+    /// This function requires the client to be logged in.
+    fn delete_content(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: DeleteContentRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<DeleteContentResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let content_id = storage_id(&request.content_key).ok_or(Error::AccessDenied)?;
+        if !rmc_err!(self.storage.delete_content(user_id, content_id), logger, "Error deleting content")? {
+            warn!(logger, "User {user_id} tried to delete unknown content {:?}", request.content_key);
+            return Err(Error::AccessDenied);
+        }
+        Ok(DeleteContentResponse)
+    }
+
+    /// Handles the `SaveMetaData` request, creating or updating a content without data.
+    ///
+    /// This function requires the client to be logged in. The content is addressed by its properties.
+    fn save_meta_data(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: SaveMetaDataRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<SaveMetaDataResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let key = Self::key_from_properties(logger, &request.properties)?;
+        let content_id = self.save(logger, user_id, &key, &request.properties, None)?;
+        Ok(SaveMetaDataResponse {
+            content_key: to_key(key.type_id, content_id),
+        })
+    }
+
+    /// Handles the `SaveContentDb` request, creating or updating a content with data sent inline.
+    ///
+    /// This function requires the client to be logged in. The content is addressed by its properties.
+    fn save_content_db(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: SaveContentDbRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<SaveContentDbResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let key = Self::key_from_properties(logger, &request.properties)?;
+        let content_id = self.save(logger, user_id, &key, &request.properties, Some(&request.data))?;
+        Ok(SaveContentDbResponse {
+            content_key: to_key(key.type_id, content_id),
+        })
+    }
+
+    /// Handles the `SaveContentAndGetUploadInfo` request, saving the properties of a content and
+    /// returning where to upload its data.
+    ///
+    /// This function requires the client to be logged in. The data is uploaded to the content server
+    /// with a `PUT` request and the content is finalized with `UploadEnd`.
+    fn save_content_and_get_upload_info(
+        &self,
+        logger: &Logger,
+        ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: SaveContentAndGetUploadInfoRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<SaveContentAndGetUploadInfoResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        if request.size > self.config.max_content_size {
+            warn!(logger, "Content of {} bytes exceeds the maximum size", request.size);
+            return Err(Error::AccessDenied);
         }
+        let content_id = self.save(logger, user_id, &request.content_key, &request.properties, None)?;
+        let pending_id = rmc_err!(self.storage.create_content_upload(content_id, request.size), logger, "Error creating content upload")?;
+
+        Ok(SaveContentAndGetUploadInfoResponse {
+            upload_info: content_url(ctx, format!("{USER_CONTENT_UPLOAD_PATH}{pending_id}")),
+            pending_id: pending_id.unsigned_abs(),
+            headers: vec![String::from("Content-Type: application/octet-stream")],
+        })
+    }
+
+    /// Handles the `UploadEnd` request, finalizing a content with the uploaded data.
+    ///
+    /// This function requires the client to be logged in. Failed uploads are discarded.
+    fn upload_end(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: UploadEndRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<UploadEndResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let pending_id = i64::try_from(request.pending_id).map_err(|_| Error::AccessDenied)?;
+        // moderators finish uploads started with `UserStorageAdmin.UpdateContentAndGetUploadInfo` for other players' contents
+        let is_moderator = rmc_err!(self.storage.is_moderator(user_id), logger, "Error checking moderator")?;
+        let finished = rmc_err!(
+            self.storage.finish_content_upload((!is_moderator).then_some(user_id), pending_id, request.result),
+            logger,
+            "Error finishing content upload"
+        )?;
+        let Some((type_id, content_id)) = finished else {
+            warn!(logger, "Unknown upload {pending_id} for user {user_id}");
+            return Err(Error::AccessDenied);
+        };
+        Ok(UploadEndResponse {
+            content_key: to_key(type_id, content_id),
+        })
+    }
+
+    /// Handles the `GetContentDb` request, returning the data of a content.
+    ///
+    /// This function requires the client to be logged in.
+    fn get_content_db(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetContentDbRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetContentDbResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let content = self.find(logger, user_id, &request.content_key)?;
+        let data = rmc_err!(self.storage.find_content_data(content.id), logger, "Error loading content data")?;
+        Ok(GetContentDbResponse { data: data.unwrap_or_default() })
     }
 
     /// Handles the `GetContentUrl` request, returning the URL for a piece of user content.
     ///
-    /// This function requires the client to be logged in. It constructs the URL from
-    /// the server's configuration.
+    /// This function requires the client to be logged in. Stored contents are downloaded from the content server,
+    /// the built-in content is its `storage_path` file.
     fn get_content_url(
         &self,
-        _logger: &Logger,
+        logger: &Logger,
         ctx: &Context,
         ci: &mut ClientInfo<CI>,
-        _request: GetContentUrlRequest,
+        request: GetContentUrlRequest,
         _client_registry: &ClientRegistry<CI>,
         _socket: &std::net::UdpSocket,
     ) -> Result<GetContentUrlResponse, Error> {
-        login_required(&*ci)?;
-        let protocol = ctx.settings.get("content_protocol").map_or("http://", String::as_str).to_owned();
-        let host = ctx.settings.get("storage_host").expect("missing storage_host setting").to_owned();
-        let path = ctx.settings.get("storage_path").expect("missing storage_path setting").to_owned();
-
+        let user_id = login_required(&*ci)?;
+        if is_builtin(&request.content_key) {
+            let path = ctx.settings.get("storage_path").expect("missing storage_path setting").to_owned();
+            return Ok(GetContentUrlResponse {
+                download_info: content_url(ctx, path),
+            });
+        }
+        let content = self.find(logger, user_id, &request.content_key)?;
         Ok(GetContentUrlResponse {
-            download_info: UserContentURL { protocol, host, path },
+            download_info: content_url(ctx, format!("{USER_CONTENT_PATH}{}", content.id)),
+        })
+    }
+
+    /// Handles the `GetSlotCount` request, returning the used and available slots of the client.
+    ///
+    /// This function requires the client to be logged in.
+    fn get_slot_count(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetSlotCountRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetSlotCountResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let used_slots = rmc_err!(self.storage.count_contents(user_id, request.type_id), logger, "Error counting contents")?;
+        Ok(GetSlotCountResponse {
+            slot_count: UserSlotCount {
+                pid: user_id,
+                type_id: request.type_id,
+                used_slots,
+                total_slots: self.config.slots,
+            },
+        })
+    }
+
+    /// Handles the `GetMetaData` request, returning a content with its properties.
+    ///
+    /// This function requires the client to be logged in.
+    fn get_meta_data(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetMetaDataRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetMetaDataResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        if is_builtin(&request.content_key) {
+            return Ok(GetMetaDataResponse { content: builtin_content() });
+        }
+        let content = self.find(logger, user_id, &request.content_key)?;
+        Ok(GetMetaDataResponse {
+            content: to_user_content(content),
+        })
+    }
+
+    /// Handles the `Like` request, liking a content for the client.
+    ///
+    /// This function requires the client to be logged in.
+    fn like(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: LikeRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<LikeResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let content = self.find(logger, user_id, &request.content_key)?;
+        rmc_err!(self.storage.set_content_liked(user_id, content.id, true), logger, "Error liking content")?;
+        Ok(LikeResponse)
+    }
+
+    /// Handles the `Unlike` request, removing the like of the client from a content.
+    ///
+    /// This function requires the client to be logged in.
+    fn unlike(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: UnlikeRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<UnlikeResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let content = self.find(logger, user_id, &request.content_key)?;
+        rmc_err!(self.storage.set_content_liked(user_id, content.id, false), logger, "Error unliking content")?;
+        Ok(UnlikeResponse)
+    }
+
+    /// Handles the `IsLiked` request, returning whether the client likes a content.
+    ///
+    /// This function requires the client to be logged in.
+    fn is_liked(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: IsLikedRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<IsLikedResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let content = self.find(logger, user_id, &request.content_key)?;
+        let liked = rmc_err!(self.storage.is_content_liked(user_id, content.id), logger, "Error checking like")?;
+        Ok(IsLikedResponse { liked })
+    }
+
+    /// Handles the `GetFavourites` request, returning the favourite contents of the client with the given types.
+    ///
+    /// This function requires the client to be logged in.
+    fn get_favourites(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetFavouritesRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetFavouritesResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let contents = rmc_err!(self.storage.list_favourite_contents(user_id, &request.content_types.0), logger, "Error listing favourites")?;
+        Ok(GetFavouritesResponse {
+            favourites: contents.into_iter().map(to_user_content).collect(),
+        })
+    }
+
+    /// Handles the `MakeFavourite` request, adding a content to the favourites of the client.
+    ///
+    /// This function requires the client to be logged in.
+    fn make_favourite(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: MakeFavouriteRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<MakeFavouriteResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let content = self.find(logger, user_id, &request.content_key)?;
+        rmc_err!(self.storage.set_content_favourite(user_id, content.id, true), logger, "Error adding favourite")?;
+        Ok(MakeFavouriteResponse)
+    }
+
+    /// Handles the `RemoveFromFavourites` request, removing a content from the favourites of the client.
+    ///
+    /// This function requires the client to be logged in.
+    fn remove_from_favourites(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: RemoveFromFavouritesRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<RemoveFromFavouritesResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let content_id = storage_id(&request.content_key).ok_or(Error::AccessDenied)?;
+        // no visibility check, removing a favourite must work even if the content got hidden in the meantime
+        rmc_err!(self.storage.set_content_favourite(user_id, content_id, false), logger, "Error removing favourite")?;
+        Ok(RemoveFromFavouritesResponse)
+    }
+
+    /// Handles the `ReportInappropriate` request, putting a content into the moderation queue.
+    ///
+    /// This function requires the client to be logged in.
+    fn report_inappropriate(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: ReportInappropriateRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<ReportInappropriateResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let content = self.find(logger, user_id, &request.content_key)?;
+        rmc_err!(self.storage.report_content(user_id, content.id, &request.reason), logger, "Error reporting content")?;
+        Ok(ReportInappropriateResponse)
+    }
+
+    /// Handles the `IncrementPlayCount` request, counting a play of a content.
+    ///
+    /// This function requires the client to be logged in.
+    fn increment_play_count(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: IncrementPlayCountRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<IncrementPlayCountResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let content = self.find(logger, user_id, &request.content_key)?;
+        rmc_err!(self.storage.increment_content_play_count(content.id), logger, "Error incrementing play count")?;
+        Ok(IncrementPlayCountResponse)
+    }
+
+    /// Handles the `UpdateCustomStat` request, adding to a game defined stat of a content.
+    ///
+    /// This function requires the client to be logged in.
+    fn update_custom_stat(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: UpdateCustomStatRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<UpdateCustomStatResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let content = self.find(logger, user_id, &request.content_key)?;
+        rmc_err!(
+            self.storage.update_content_stat(content.id, request.stat_id, request.inc_value),
+            logger,
+            "Error updating custom stat"
+        )?;
+        Ok(UpdateCustomStatResponse)
+    }
+
+    /// Handles the `GetMostPopularTags` request, returning the tags of a content with how often they were set.
+    ///
+    /// This function requires the client to be logged in.
+    fn get_most_popular_tags(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetMostPopularTagsRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetMostPopularTagsResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let content = self.find(logger, user_id, &request.content_key)?;
+        let tags = rmc_err!(self.storage.list_content_tags(content.id), logger, "Error listing tags")?;
+        Ok(GetMostPopularTagsResponse {
+            total_number_of_taggings: tags.iter().map(|(_, count)| count).sum(),
+            tags: tags.into_iter().map(|(id, number_of_occurences)| WeightedTag { id, number_of_occurences }).collect(),
+        })
+    }
+
+    /// Handles the `GetTags` request, returning all tags set on a content.
+    ///
+    /// This function requires the client to be logged in.
+    fn get_tags(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetTagsRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetTagsResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let content = self.find(logger, user_id, &request.content_key)?;
+        let tags = rmc_err!(self.storage.list_content_tags(content.id), logger, "Error listing tags")?;
+        Ok(GetTagsResponse {
+            tag_ids: tags.into_iter().map(|(id, _)| id).collect(),
+        })
+    }
+
+    /// Handles the `TagContent` request, adding the tags of the client to a content.
+    ///
+    /// This function requires the client to be logged in.
+    fn tag_content(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: TagContentRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<TagContentResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let content = self.find(logger, user_id, &request.content_key)?;
+        rmc_err!(self.storage.tag_content(user_id, content.id, &request.new_tag_ids.0), logger, "Error tagging content")?;
+        Ok(TagContentResponse)
+    }
+
+    /// Handles the `GetOwnContents` request, returning all contents of the client of a type.
+    ///
+    /// This function requires the client to be logged in.
+    fn get_own_contents(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetOwnContentsRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetOwnContentsResponse, Error> {
+        let user_id = login_required(&*ci)?;
+        let contents = rmc_err!(self.storage.list_own_contents(user_id, request.type_id), logger, "Error listing contents")?;
+        Ok(GetOwnContentsResponse {
+            results: contents.into_iter().map(to_user_content).collect(),
         })
     }
 }
@@ -100,6 +851,284 @@ impl<CI> UserStorageProtocolServerTrait<CI> for UserStorageProtocolServerImpl {
 ///
 /// This function is typically used to register the user storage protocol
 /// with the server's protocol dispatcher.
-pub fn new_protocol<T: 'static>() -> Box<dyn Protocol<T>> {
-    Box::new(UserStorageProtocolServer::new(UserStorageProtocolServerImpl))
+pub fn new_protocol<T: 'static>(storage: Arc<Storage>, config: UserStorageConfig) -> Box<dyn Protocol<T>> {
+    Box::new(UserStorageProtocolServer::new(UserStorageProtocolServerImpl { storage, config }))
+}
+
+#[cfg(test)]
+mod tests {
+    use quazal::rmc::types::ResultRange;
+
+    use super::*;
+    use crate::protocols::user_storage::user_storage_protocol::UserStorageProtocolMethod;
+    use crate::test_util;
+
+    const TYPE_ID: u32 = 0x8000_0001;
+
+    /// Context of the secure server, with the content server settings.
+    fn context() -> Context {
+        let mut ctx = Context::default();
+        ctx.settings.insert(String::from("storage_host"), String::from("127.0.0.1:8000"));
+        ctx.settings.insert(String::from("storage_path"), String::from("/content.bin"));
+        ctx
+    }
+
+    fn key(type_id: u32, content_id: u64) -> UserContentKey {
+        UserContentKey { type_id, content_id }
+    }
+
+    fn query(type_id: u32, query_id: u32) -> UserStorageQuery {
+        UserStorageQuery {
+            type_id,
+            query_id,
+            result_range: ResultRange { offset: 0, size: 10 },
+            parameters: QList::default(),
+        }
+    }
+
+    /// Returns a storage with two contents of user 1001.
+    fn storage_with_contents() -> Arc<Storage> {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        for data in [b"first", b"other"] {
+            storage.save_content(1001, TYPE_ID, None, &[], Some(data)).unwrap();
+        }
+        storage
+    }
+
+    /// Returns the content ids of the search results of a query.
+    fn search(prot: &dyn Protocol<()>, query_id: u32) -> Vec<u64> {
+        let request = SearchContentsRequest { query: query(TYPE_ID, query_id) }.to_bytes();
+        let resp = test_util::call(prot, Some(1000), UserStorageProtocolMethod::SearchContents as u32, &request).unwrap();
+        SearchContentsResponse::from_bytes(&resp)
+            .unwrap()
+            .search_results
+            .0
+            .iter()
+            .map(|c| c.key.content_id)
+            .collect()
+    }
+
+    #[test]
+    fn upload() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let prot = new_protocol::<()>(Arc::clone(&storage), UserStorageConfig::default());
+        let ctx = context();
+        let call = |user_id, method: UserStorageProtocolMethod, parameters: Vec<u8>| test_util::call_in(&*prot, &ctx, Some(user_id), method as u32, &parameters);
+
+        let request = SaveContentAndGetUploadInfoRequest {
+            properties: QList::default(),
+            size: 4,
+            content_key: key(TYPE_ID, 0),
+        };
+        let resp = call(1000, UserStorageProtocolMethod::SaveContentAndGetUploadInfo, request.to_bytes()).unwrap();
+        let resp = SaveContentAndGetUploadInfoResponse::from_bytes(&resp).unwrap();
+        assert_eq!(resp.upload_info.host, "127.0.0.1:8000");
+        assert_eq!(resp.upload_info.path, format!("{USER_CONTENT_UPLOAD_PATH}{}", resp.pending_id));
+        let pending_id = resp.pending_id;
+
+        // hidden from other players until the upload is finished
+        let meta_data = GetMetaDataRequest { content_key: key(TYPE_ID, 1) }.to_bytes();
+        assert!(matches!(call(1001, UserStorageProtocolMethod::GetMetaData, meta_data), Err(Error::AccessDenied)));
+
+        // the PUT to the content server
+        let upload = i64::try_from(pending_id).unwrap();
+        assert!(!storage.store_content_upload(upload, b"too long").unwrap());
+        assert!(storage.store_content_upload(upload, b"data").unwrap());
+
+        let upload_end = UploadEndRequest { pending_id, result: true }.to_bytes();
+        assert!(matches!(call(1001, UserStorageProtocolMethod::UploadEnd, upload_end.clone()), Err(Error::AccessDenied)));
+        let resp = call(1000, UserStorageProtocolMethod::UploadEnd, upload_end).unwrap();
+        let content_key = UploadEndResponse::from_bytes(&resp).unwrap().content_key;
+        assert_eq!((content_key.type_id, content_key.content_id), (TYPE_ID, 1));
+
+        let resp = call(
+            1001,
+            UserStorageProtocolMethod::GetContentUrl,
+            GetContentUrlRequest { content_key: key(TYPE_ID, 1) }.to_bytes(),
+        )
+        .unwrap();
+        let download_info = GetContentUrlResponse::from_bytes(&resp).unwrap().download_info;
+        assert_eq!(download_info.host, "127.0.0.1:8000");
+        assert_eq!(download_info.path, format!("{USER_CONTENT_PATH}1"));
+
+        let resp = call(
+            1001,
+            UserStorageProtocolMethod::GetContentDb,
+            GetContentDbRequest { content_key: key(TYPE_ID, 1) }.to_bytes(),
+        )
+        .unwrap();
+        assert_eq!(GetContentDbResponse::from_bytes(&resp).unwrap().data, b"data");
+
+        let resp = call(
+            1001,
+            UserStorageProtocolMethod::SearchContents,
+            SearchContentsRequest { query: query(TYPE_ID, 0) }.to_bytes(),
+        )
+        .unwrap();
+        let results = SearchContentsResponse::from_bytes(&resp).unwrap().search_results;
+        assert_eq!(results.0.len(), 1);
+        assert_eq!(results.0[0].pid, 1000);
+        let hash = results.0[0].properties.0.iter().find(|p| p.id == property_ids::HASH).unwrap();
+        assert!(matches!(&hash.value, Variant::String(hash) if hash == "8D777F385D3DFEC8815D20F7496026DC"));
+    }
+
+    #[test]
+    fn abandoned_upload() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let prot = new_protocol::<()>(Arc::clone(&storage), UserStorageConfig::default());
+        let ctx = context();
+        let call = |method: UserStorageProtocolMethod, parameters: Vec<u8>| test_util::call_in(&*prot, &ctx, Some(1000), method as u32, &parameters);
+
+        let request = SaveContentAndGetUploadInfoRequest {
+            properties: QList::default(),
+            size: 4,
+            content_key: key(TYPE_ID, 0),
+        };
+        let resp = call(UserStorageProtocolMethod::SaveContentAndGetUploadInfo, request.to_bytes()).unwrap();
+        let pending_id = SaveContentAndGetUploadInfoResponse::from_bytes(&resp).unwrap().pending_id;
+
+        assert_eq!(storage.prune_content_uploads("2000-01-01 00:00:00").unwrap(), 0);
+        assert_eq!(storage.prune_content_uploads("9999-01-01 00:00:00").unwrap(), 1);
+
+        let upload_end = UploadEndRequest { pending_id, result: true }.to_bytes();
+        assert!(matches!(call(UserStorageProtocolMethod::UploadEnd, upload_end), Err(Error::AccessDenied)));
+        let resp = call(UserStorageProtocolMethod::GetSlotCount, GetSlotCountRequest { type_id: TYPE_ID }.to_bytes()).unwrap();
+        assert_eq!(GetSlotCountResponse::from_bytes(&resp).unwrap().slot_count.used_slots, 0);
+    }
+
+    #[test]
+    fn builtin_content() {
+        let prot = new_protocol::<()>(Arc::new(Storage::in_memory().unwrap()), UserStorageConfig::default());
+        let ctx = context();
+        let call = |method: UserStorageProtocolMethod, parameters: Vec<u8>| test_util::call_in(&*prot, &ctx, Some(1000), method as u32, &parameters);
+
+        let resp = call(
+            UserStorageProtocolMethod::SearchContents,
+            SearchContentsRequest {
+                query: query(builtin::TYPE_ID, 0),
+            }
+            .to_bytes(),
+        )
+        .unwrap();
+        let results = SearchContentsResponse::from_bytes(&resp).unwrap().search_results;
+        assert_eq!(results.0.len(), 1);
+        assert_eq!((results.0[0].key.content_id, results.0[0].pid), (builtin::CONTENT_ID, builtin::PID));
+
+        let content_key = key(builtin::TYPE_ID, builtin::CONTENT_ID);
+        let resp = call(UserStorageProtocolMethod::GetContentUrl, GetContentUrlRequest { content_key }.to_bytes()).unwrap();
+        assert_eq!(GetContentUrlResponse::from_bytes(&resp).unwrap().download_info.path, "/content.bin");
+
+        let properties = QList(vec![ContentProperty {
+            id: property_ids::TYPE_ID,
+            value: Variant::I64(builtin::TYPE_ID.into()),
+        }]);
+        let resp = call(UserStorageProtocolMethod::SaveMetaData, SaveMetaDataRequest { properties }.to_bytes());
+        assert!(matches!(resp, Err(Error::AccessDenied)));
+    }
+
+    #[test]
+    fn likes() {
+        let prot = new_protocol::<()>(storage_with_contents(), UserStorageConfig::default());
+        let call = |user_id, method: UserStorageProtocolMethod, content_id| {
+            let request = LikeRequest {
+                content_key: key(TYPE_ID, content_id),
+            }
+            .to_bytes();
+            test_util::call(&*prot, Some(user_id), method as u32, &request).unwrap()
+        };
+
+        call(1000, UserStorageProtocolMethod::Like, 2);
+        assert_eq!(call(1000, UserStorageProtocolMethod::IsLiked, 2), b"\x01");
+        assert_eq!(call(1002, UserStorageProtocolMethod::IsLiked, 2), b"\x00");
+        assert_eq!(search(&*prot, query_ids::MOST_LIKED), [2, 1]);
+
+        call(1000, UserStorageProtocolMethod::Unlike, 2);
+        assert_eq!(call(1000, UserStorageProtocolMethod::IsLiked, 2), b"\x00");
+    }
+
+    #[test]
+    fn favourites() {
+        let prot = new_protocol::<()>(storage_with_contents(), UserStorageConfig::default());
+        let call = |method: UserStorageProtocolMethod, parameters: Vec<u8>| test_util::call(&*prot, Some(1000), method as u32, &parameters).unwrap();
+        let favourites = || {
+            let resp = call(
+                UserStorageProtocolMethod::GetFavourites,
+                GetFavouritesRequest {
+                    content_types: QList(vec![TYPE_ID]),
+                }
+                .to_bytes(),
+            );
+            GetFavouritesResponse::from_bytes(&resp)
+                .unwrap()
+                .favourites
+                .0
+                .iter()
+                .map(|c| c.key.content_id)
+                .collect::<Vec<_>>()
+        };
+
+        call(UserStorageProtocolMethod::MakeFavourite, MakeFavouriteRequest { content_key: key(TYPE_ID, 2) }.to_bytes());
+        assert_eq!(favourites(), [2]);
+        call(
+            UserStorageProtocolMethod::RemoveFromFavourites,
+            RemoveFromFavouritesRequest { content_key: key(TYPE_ID, 2) }.to_bytes(),
+        );
+        assert!(favourites().is_empty());
+    }
+
+    #[test]
+    fn tags() {
+        let prot = new_protocol::<()>(storage_with_contents(), UserStorageConfig::default());
+        let tag = |user_id, tag_ids: Vec<u32>| {
+            let request = TagContentRequest {
+                content_key: key(TYPE_ID, 1),
+                new_tag_ids: QList(tag_ids),
+            };
+            test_util::call(&*prot, Some(user_id), UserStorageProtocolMethod::TagContent as u32, &request.to_bytes()).unwrap();
+        };
+
+        tag(1000, vec![5, 6]);
+        tag(1002, vec![6]);
+        // a player tags a content only once with the same tag
+        tag(1000, vec![5]);
+
+        let request = GetTagsRequest { content_key: key(TYPE_ID, 1) }.to_bytes();
+        let resp = test_util::call(&*prot, Some(1000), UserStorageProtocolMethod::GetTags as u32, &request).unwrap();
+        assert_eq!(GetTagsResponse::from_bytes(&resp).unwrap().tag_ids.0, [6, 5]);
+
+        let request = GetMostPopularTagsRequest { content_key: key(TYPE_ID, 1) }.to_bytes();
+        let resp = test_util::call(&*prot, Some(1000), UserStorageProtocolMethod::GetMostPopularTags as u32, &request).unwrap();
+        let resp = GetMostPopularTagsResponse::from_bytes(&resp).unwrap();
+        assert_eq!(resp.total_number_of_taggings, 3);
+        let tags = resp.tags.0.iter().map(|t| (t.id, t.number_of_occurences)).collect::<Vec<_>>();
+        assert_eq!(tags, [(6, 2), (5, 1)]);
+    }
+
+    #[test]
+    fn play_count() {
+        let prot = new_protocol::<()>(storage_with_contents(), UserStorageConfig::default());
+        let request = IncrementPlayCountRequest { content_key: key(TYPE_ID, 2) }.to_bytes();
+        test_util::call(&*prot, Some(1000), UserStorageProtocolMethod::IncrementPlayCount as u32, &request).unwrap();
+        assert_eq!(search(&*prot, query_ids::MOST_PLAYED), [2, 1]);
+    }
+
+    #[test]
+    fn report() {
+        let storage = storage_with_contents();
+        let prot = new_protocol::<()>(Arc::clone(&storage), UserStorageConfig::default());
+        let report = |reason: &str| {
+            let request = ReportInappropriateRequest {
+                content_key: key(TYPE_ID, 1),
+                reason: reason.to_string(),
+            };
+            test_util::call(&*prot, Some(1000), UserStorageProtocolMethod::ReportInappropriate as u32, &request.to_bytes()).unwrap();
+        };
+
+        report("offensive");
+        // reporting again replaces the reason, it doesn't count twice
+        report("spam");
+        let (contents, total) = storage.list_contents_to_moderate(Some(TYPE_ID), 1, 0, 10).unwrap();
+        assert_eq!((contents[0].id, total), (1, 1));
+        assert_eq!(storage.list_contents_to_moderate(Some(TYPE_ID), 2, 0, 10).unwrap().1, 0);
+    }
 }
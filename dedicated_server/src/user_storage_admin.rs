@@ -0,0 +1,498 @@
+//! Implements the `UserStorageAdminProtocolServer` for moderating user contents from within the game.
+//!
+//! Only users flagged as moderator in the database may use it. The same operations are available
+//! to server admins through the `ContentAdmin` gRPC service. The protocol is only registered with
+//! `user_storage.admin_protocol` set, as its id isn't confirmed yet.
+
+use std::sync::Arc;
+
+use quazal::prudp::ClientRegistry;
+use quazal::rmc::types::DateTime;
+use quazal::rmc::types::QList;
+use quazal::rmc::Error;
+use quazal::rmc::Protocol;
+use quazal::ClientInfo;
+use quazal::Context;
+use slog::Logger;
+
+use crate::moderator_required;
+use crate::protocols::user_storage::types::UserContent;
+use crate::protocols::user_storage::types::UserContentKey;
+use crate::protocols::user_storage::types::WeightedTag;
+use crate::protocols::user_storage_admin::types::AdminContent;
+use crate::protocols::user_storage_admin::types::BannedUser;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::BanContentRequest;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::BanContentResponse;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::BanUserFromContentTypeRequest;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::BanUserFromContentTypeResponse;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::BanUserRequest;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::BanUserResponse;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::BrowseContentsRequest;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::BrowseContentsResponse;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::DeleteContentRequest;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::DeleteContentResponse;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::FlagContentAsVerifiedRequest;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::FlagContentAsVerifiedResponse;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::GetBannedUsersRequest;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::GetBannedUsersResponse;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::GetContentsToModerateRequest;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::GetContentsToModerateResponse;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::GetContentsToModerateWithThresholdRequest;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::GetContentsToModerateWithThresholdResponse;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::IsUserbannedRequest;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::IsUserbannedResponse;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::UnbanUserFromContentTypeRequest;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::UnbanUserFromContentTypeResponse;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::UnbanUserRequest;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::UnbanUserResponse;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::UpdateContentAndGetUploadInfoRequest;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::UpdateContentAndGetUploadInfoResponse;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::UpdateContentDbRequest;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::UpdateContentDbResponse;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::UpdateMetaDataRequest;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::UpdateMetaDataResponse;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::UserStorageAdminProtocolServer;
+use crate::protocols::user_storage_admin::user_storage_admin_protocol::UserStorageAdminProtocolServerTrait;
+use crate::simple_http::USER_CONTENT_UPLOAD_PATH;
+use crate::storage::Content;
+use crate::storage::ContentBan;
+use crate::storage::Storage;
+use crate::user_storage::content_url;
+use crate::user_storage::encode_properties;
+use crate::user_storage::storage_id;
+use crate::user_storage::to_user_content;
+
+/// Converts the expiration date sent by the game to a sqlite timestamp. A date of 0 never expires.
+fn expiration(date: DateTime) -> Option<String> {
+    (date.0 != 0).then(|| date.to_string())
+}
+
+fn to_banned_user(ban: ContentBan) -> BannedUser {
+    BannedUser {
+        pid: ban.user_id,
+        reason: ban.reason,
+        date_banned: ban.created_at.parse().unwrap_or_default(),
+        expiration: ban.expires_at.and_then(|ts| ts.parse().ok()).unwrap_or_default(),
+    }
+}
+
+/// Implementation of the `UserStorageAdminProtocolServerTrait` for moderating user contents.
+struct UserStorageAdminProtocolServerImpl {
+    storage: Arc<Storage>,
+}
+
+impl UserStorageAdminProtocolServerImpl {
+    /// Loads any content, including unfinished and banned ones.
+    fn find(&self, logger: &Logger, key: &UserContentKey) -> Result<Content, Error> {
+        let content = match storage_id(key) {
+            Some(content_id) => rmc_err!(self.storage.find_content(content_id, None), logger, "Error loading content")?,
+            None => None,
+        };
+        match content {
+            Some(content) if content.type_id == key.type_id => Ok(content),
+            _ => {
+                warn!(logger, "Content {:?} not found", key);
+                Err(Error::AccessDenied)
+            }
+        }
+    }
+
+    /// Lists the contents waiting for moderation with at least `threshold` reports.
+    fn to_moderate(&self, logger: &Logger, type_id: u32, threshold: u32, offset: u32, size: u32) -> Result<(QList<UserContent>, u32), Error> {
+        let (contents, total) = rmc_err!(
+            self.storage.list_contents_to_moderate(Some(type_id), threshold, offset, size),
+            logger,
+            "Error listing contents to moderate"
+        )?;
+        Ok((contents.into_iter().map(to_user_content).collect(), total))
+    }
+
+    /// Overwrites properties and optionally the data of any content.
+    fn update(&self, logger: &Logger, key: &UserContentKey, properties: &[(u32, Vec<u8>)], data: Option<&[u8]>) -> Result<Content, Error> {
+        let content = self.find(logger, key)?;
+        rmc_err!(
+            self.storage.save_content(content.owner_id, content.type_id, Some(content.id), properties, data),
+            logger,
+            "Error updating content"
+        )?;
+        Ok(content)
+    }
+
+    fn moderate(&self, logger: &Logger, key: &UserContentKey, banned: bool) -> Result<(), Error> {
+        let content = self.find(logger, key)?;
+        rmc_err!(self.storage.moderate_content(content.id, banned), logger, "Error moderating content")?;
+        Ok(())
+    }
+
+    fn ban(&self, logger: &Logger, type_id: u32, pid: u32, reason: &str, ban_contents: bool, expire_date: DateTime) -> Result<(), Error> {
+        rmc_err!(
+            self.storage.ban_content_user(pid, type_id, reason, expiration(expire_date).as_deref(), ban_contents),
+            logger,
+            "Error banning user"
+        )
+    }
+}
+
+impl<CI> UserStorageAdminProtocolServerTrait<CI> for UserStorageAdminProtocolServerImpl {
+    /// Handles the `GetContentsToModerate` request, listing reported contents of a type.
+    ///
+    /// This function requires the client to be a moderator.
+    fn get_contents_to_moderate(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetContentsToModerateRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetContentsToModerateResponse, Error> {
+        moderator_required(logger, &self.storage, ci)?;
+        let (contents, total_results) = self.to_moderate(logger, request.type_id, 1, request.offset, request.size)?;
+        Ok(GetContentsToModerateResponse { contents, total_results })
+    }
+
+    /// Handles the `FlagContentAsVerified` request, accepting a content and closing its reports.
+    ///
+    /// This function requires the client to be a moderator.
+    fn flag_content_as_verified(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: FlagContentAsVerifiedRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<FlagContentAsVerifiedResponse, Error> {
+        moderator_required(logger, &self.storage, ci)?;
+        self.moderate(logger, &request.content_key, false)?;
+        Ok(FlagContentAsVerifiedResponse)
+    }
+
+    /// Handles the `BanContent` request, hiding a content from other players and closing its reports.
+    ///
+    /// This function requires the client to be a moderator.
+    fn ban_content(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: BanContentRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<BanContentResponse, Error> {
+        moderator_required(logger, &self.storage, ci)?;
+        self.moderate(logger, &request.content_key, true)?;
+        Ok(BanContentResponse)
+    }
+
+    /// Handles the `BanUser` request, banning a player from saving contents of any type.
+    ///
+    /// This function requires the client to be a moderator.
+    fn ban_user(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: BanUserRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<BanUserResponse, Error> {
+        moderator_required(logger, &self.storage, ci)?;
+        self.ban(logger, 0, request.pid, &request.reason, request.ban_contents, request.expire_date)?;
+        Ok(BanUserResponse)
+    }
+
+    /// Handles the `BanUserFromContentType` request, banning a player from saving contents of a type.
+    ///
+    /// This function requires the client to be a moderator.
+    fn ban_user_from_content_type(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: BanUserFromContentTypeRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<BanUserFromContentTypeResponse, Error> {
+        moderator_required(logger, &self.storage, ci)?;
+        self.ban(logger, request.type_id, request.pid, &request.reason, request.ban_contents, request.expire_date)?;
+        Ok(BanUserFromContentTypeResponse)
+    }
+
+    /// Handles the `UnbanUser` request, lifting a ban for all content types.
+    ///
+    /// This function requires the client to be a moderator.
+    fn unban_user(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: UnbanUserRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<UnbanUserResponse, Error> {
+        moderator_required(logger, &self.storage, ci)?;
+        rmc_err!(self.storage.unban_content_user(request.pid, 0), logger, "Error unbanning user")?;
+        Ok(UnbanUserResponse)
+    }
+
+    /// Handles the `UnbanUserFromContentType` request, lifting a ban for a content type.
+    ///
+    /// This function requires the client to be a moderator.
+    fn unban_user_from_content_type(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: UnbanUserFromContentTypeRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<UnbanUserFromContentTypeResponse, Error> {
+        moderator_required(logger, &self.storage, ci)?;
+        rmc_err!(self.storage.unban_content_user(request.pid, request.type_id), logger, "Error unbanning user")?;
+        Ok(UnbanUserFromContentTypeResponse)
+    }
+
+    /// Handles the `GetContentsToModerateWithThreshold` request, listing contents of a type with at least `threshold` reports.
+    ///
+    /// This function requires the client to be a moderator.
+    fn get_contents_to_moderate_with_threshold(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetContentsToModerateWithThresholdRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetContentsToModerateWithThresholdResponse, Error> {
+        moderator_required(logger, &self.storage, ci)?;
+        let (contents, total_results) = self.to_moderate(logger, request.type_id, request.threshold, request.offset, request.size)?;
+        Ok(GetContentsToModerateWithThresholdResponse { contents, total_results })
+    }
+
+    /// Handles the `UpdateMetaData` request, overwriting properties of any content.
+    ///
+    /// This function requires the client to be a moderator.
+    fn update_meta_data(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: UpdateMetaDataRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<UpdateMetaDataResponse, Error> {
+        moderator_required(logger, &self.storage, ci)?;
+        self.update(logger, &request.content_key, &encode_properties(&request.properties), None)?;
+        Ok(UpdateMetaDataResponse)
+    }
+
+    /// Handles the `UpdateContentDb` request, overwriting properties and data of any content.
+    ///
+    /// This function requires the client to be a moderator.
+    fn update_content_db(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: UpdateContentDbRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<UpdateContentDbResponse, Error> {
+        moderator_required(logger, &self.storage, ci)?;
+        self.update(logger, &request.content_key, &encode_properties(&request.properties), Some(request.data.as_bytes()))?;
+        Ok(UpdateContentDbResponse)
+    }
+
+    /// Handles the `UpdateContentAndGetUploadInfo` request, overwriting properties of any content and
+    /// returning where to upload its new data.
+    ///
+    /// This function requires the client to be a moderator. The upload is finished with `UserStorage.UploadEnd`.
+    fn update_content_and_get_upload_info(
+        &self,
+        logger: &Logger,
+        ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: UpdateContentAndGetUploadInfoRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<UpdateContentAndGetUploadInfoResponse, Error> {
+        moderator_required(logger, &self.storage, ci)?;
+        let content = self.update(logger, &request.content_key, &encode_properties(&request.properties), None)?;
+        let pending_id = rmc_err!(self.storage.create_content_upload(content.id, request.size), logger, "Error creating content upload")?;
+        Ok(UpdateContentAndGetUploadInfoResponse {
+            upload_info: content_url(ctx, format!("{USER_CONTENT_UPLOAD_PATH}{pending_id}")),
+            pending_id: pending_id.unsigned_abs(),
+            headers: vec![String::from("Content-Type: application/octet-stream")],
+        })
+    }
+
+    /// Handles the `DeleteContent` request, deleting any content.
+    ///
+    /// This function requires the client to be a moderator.
+    fn delete_content(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: DeleteContentRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<DeleteContentResponse, Error> {
+        moderator_required(logger, &self.storage, ci)?;
+        let content = self.find(logger, &request.content_key)?;
+        rmc_err!(self.storage.delete_content_by_id(content.id), logger, "Error deleting content")?;
+        Ok(DeleteContentResponse)
+    }
+
+    /// Handles the `BrowseContents` request, listing all finalized contents of a type with their tags.
+    ///
+    /// This function requires the client to be a moderator.
+    fn browse_contents(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: BrowseContentsRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<BrowseContentsResponse, Error> {
+        moderator_required(logger, &self.storage, ci)?;
+        let (contents, total_results) = rmc_err!(
+            self.storage.browse_contents(request.type_id, request.offset, request.size),
+            logger,
+            "Error browsing contents"
+        )?;
+        let mut admin_contents = Vec::with_capacity(contents.len());
+        for content in contents {
+            let tags = rmc_err!(self.storage.list_content_tags(content.id), logger, "Error listing tags")?;
+            admin_contents.push(AdminContent {
+                user_content: to_user_content(content),
+                tags: tags.into_iter().map(|(id, number_of_occurences)| WeightedTag { id, number_of_occurences }).collect(),
+            });
+        }
+        Ok(BrowseContentsResponse {
+            contents: QList(admin_contents),
+            total_results,
+        })
+    }
+
+    /// Handles the `IsUserbanned` request, returning whether a player is banned from a content type.
+    ///
+    /// This function requires the client to be a moderator.
+    fn is_userbanned(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: IsUserbannedRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<IsUserbannedResponse, Error> {
+        moderator_required(logger, &self.storage, ci)?;
+        let ban = rmc_err!(self.storage.find_content_ban(request.pid, request.type_id), logger, "Error checking content ban")?;
+        Ok(IsUserbannedResponse {
+            banned: ban.is_some(),
+            reason: ban.map(|ban| ban.reason).unwrap_or_default(),
+        })
+    }
+
+    /// Handles the `GetBannedUsers` request, listing the players banned from a content type.
+    ///
+    /// This function requires the client to be a moderator.
+    fn get_banned_users(
+        &self,
+        logger: &Logger,
+        _ctx: &Context,
+        ci: &mut ClientInfo<CI>,
+        request: GetBannedUsersRequest,
+        _client_registry: &ClientRegistry<CI>,
+        _socket: &std::net::UdpSocket,
+    ) -> Result<GetBannedUsersResponse, Error> {
+        moderator_required(logger, &self.storage, ci)?;
+        let (bans, total_banned_users) = rmc_err!(
+            self.storage.list_content_bans(request.type_id, request.offset, request.size),
+            logger,
+            "Error listing banned users"
+        )?;
+        Ok(GetBannedUsersResponse {
+            banned_users: bans.into_iter().map(to_banned_user).collect(),
+            total_banned_users,
+        })
+    }
+}
+
+/// Creates a new boxed `UserStorageAdminProtocolServer` instance.
+pub fn new_protocol<T: 'static>(storage: Arc<Storage>) -> Box<dyn Protocol<T>> {
+    Box::new(UserStorageAdminProtocolServer::new(UserStorageAdminProtocolServerImpl { storage }))
+}
+
+#[cfg(test)]
+mod tests {
+    use quazal::rmc::basic::FromStream;
+    use quazal::rmc::basic::ToStream;
+
+    use super::*;
+    use crate::protocols::user_storage_admin::user_storage_admin_protocol::UserStorageAdminProtocolMethod;
+    use crate::test_util;
+
+    const TYPE_ID: u32 = 0x8000_0001;
+
+    /// Stores a finalized content of user 1001 and returns its key.
+    fn content(storage: &Storage) -> UserContentKey {
+        let content_id = storage.save_content(1001, TYPE_ID, None, &[], Some(b"data")).unwrap().unwrap();
+        UserContentKey {
+            type_id: TYPE_ID,
+            content_id: content_id.unsigned_abs(),
+        }
+    }
+
+    fn to_moderate(prot: &dyn Protocol<()>) -> u32 {
+        let request = GetContentsToModerateRequest {
+            type_id: TYPE_ID,
+            offset: 0,
+            size: 10,
+        }
+        .to_bytes();
+        let resp = test_util::call(prot, Some(1000), UserStorageAdminProtocolMethod::GetContentsToModerate as u32, &request).unwrap();
+        GetContentsToModerateResponse::from_bytes(&resp).unwrap().total_results
+    }
+
+    #[test]
+    fn requires_moderator() {
+        let prot = new_protocol::<()>(Arc::new(Storage::in_memory().unwrap()));
+        let request = GetContentsToModerateRequest {
+            type_id: TYPE_ID,
+            offset: 0,
+            size: 10,
+        }
+        .to_bytes();
+        let resp = test_util::call(&*prot, Some(1000), UserStorageAdminProtocolMethod::GetContentsToModerate as u32, &request);
+        assert!(matches!(resp, Err(Error::AccessDenied)));
+    }
+
+    #[test]
+    fn moderate() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let prot = new_protocol::<()>(Arc::clone(&storage));
+        storage.set_moderator(1000).unwrap();
+        let banned = content(&storage);
+        let verified = content(&storage);
+        for key in [&banned, &verified] {
+            storage.report_content(1000, storage_id(key).unwrap(), "offensive").unwrap();
+        }
+        assert_eq!(to_moderate(&*prot), 2);
+
+        let request = BanContentRequest { content_key: banned }.to_bytes();
+        test_util::call(&*prot, Some(1000), UserStorageAdminProtocolMethod::BanContent as u32, &request).unwrap();
+        assert_eq!(to_moderate(&*prot), 1);
+        let request = FlagContentAsVerifiedRequest { content_key: verified }.to_bytes();
+        test_util::call(&*prot, Some(1000), UserStorageAdminProtocolMethod::FlagContentAsVerified as u32, &request).unwrap();
+        assert_eq!(to_moderate(&*prot), 0);
+
+        // banned contents are hidden from other players, but not from their owner
+        assert!(storage.find_content(1, Some(1000)).unwrap().is_none());
+        assert!(storage.find_content(1, Some(1001)).unwrap().is_some());
+        assert!(storage.find_content(2, Some(1000)).unwrap().is_some_and(|content| content.verified));
+    }
+}
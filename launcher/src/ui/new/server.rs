@@ -21,6 +21,7 @@ use imgui::TableColumnFlags;
 use imgui::TableColumnSetup;
 use itertools::Itertools;
 use serde::Deserialize;
+use server_api::content;
 use server_api::games;
 use server_api::games::Game;
 use server_api::users;
@@ -30,6 +31,8 @@ use tonic::Request;
 use tracing::error;
 use tracing::info;
 
+use super::super::icons::ICON_BAN;
+use super::super::icons::ICON_CHECK;
 use super::super::icons::ICON_REPEAT;
 use super::super::icons::ICON_TRASH;
 use super::colors::ORANGE;
@@ -54,6 +57,7 @@ pub struct ServerMenu {
     users: Option<Vec<User>>,
     selected_log_level: usize,
     games: Option<Vec<Game>>,
+    contents: Option<Vec<content::Content>>,
     download_text: AnimatedText<'static>,
     public_ip: Option<IpAddr>,
     download_progress: Arc<AtomicUsize>,
@@ -61,7 +65,11 @@ pub struct ServerMenu {
     server_version: Option<Version>,
     latest_version: BackgroundValue<Option<Version>>,
     show_delete_user_confirmation: Option<String>,
+    show_ban_user_confirmation: Option<String>,
+    ban_reason: String,
+    ban_ip: bool,
     show_delete_game_confirmation: Option<u32>,
+    show_delete_content_confirmation: Option<i64>,
 }
 
 impl ServerMenu {
@@ -120,6 +128,7 @@ impl ServerMenu {
             users: None,
             selected_log_level: LogLevel::Info as _,
             games: None,
+            contents: None,
             download_text: AnimatedText::new(&["Downloading...", "Downloading..", "Downloading"], Duration::from_millis(200)),
             public_ip,
             download_progress: Arc::new(AtomicUsize::new(0)),
@@ -127,7 +136,11 @@ impl ServerMenu {
             server_version,
             latest_version,
             show_delete_user_confirmation: None,
+            show_ban_user_confirmation: None,
+            ban_reason: String::new(),
+            ban_ip: false,
             show_delete_game_confirmation: None,
+            show_delete_content_confirmation: None,
         }
     }
 
@@ -250,6 +263,10 @@ impl ServerMenu {
                     ui.child_window("GamesWindow").build(|| self.games_table(ui));
                     item.end();
                 }
+                if let Some(item) = ui.tab_item("Content") {
+                    ui.child_window("ContentWindow").build(|| self.contents_table(ui));
+                    item.end();
+                }
             }
             tabbar.end();
         }
@@ -424,6 +441,13 @@ impl ServerMenu {
                     ui.table_next_column();
                     ui.text(user.ips.join(", "));
                     ui.table_next_column();
+                    if ui.button(format!("{}##ban{}", ICON_BAN, user.id)) {
+                        self.show_ban_user_confirmation = Some(user.id.clone());
+                    }
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Ban user");
+                    }
+                    ui.same_line();
                     if ui.button(format!("{}##{}", ICON_TRASH, user.id)) {
                         self.show_delete_user_confirmation = Some(user.id.clone());
                     }
@@ -508,6 +532,109 @@ impl ServerMenu {
         }
     }
 
+    fn contents_table(&mut self, ui: &imgui::Ui) {
+        if ui.button(format!("{ICON_REPEAT}")) || self.contents.is_none() {
+            tokio::runtime::Runtime::new().unwrap().block_on(async {
+                self.contents = load_reported_contents(format!("http://{}", self.api_server()))
+                    .await
+                    .inspect_err(|e| {
+                        error!("Error loading reported contents: {e}");
+                    })
+                    .ok();
+            });
+        }
+        if ui.is_item_hovered() {
+            ui.tooltip_text("Reload reported contents");
+        }
+
+        if let Some(table) = ui.begin_table_header(
+            "ContentTable",
+            [
+                TableColumnSetup {
+                    name: "Content ID",
+                    flags: TableColumnFlags::WIDTH_FIXED,
+                    init_width_or_weight: ui.calc_text_size("999999")[0],
+                    ..Default::default()
+                },
+                TableColumnSetup {
+                    name: "Type",
+                    flags: TableColumnFlags::WIDTH_FIXED,
+                    init_width_or_weight: ui.calc_text_size("999")[0],
+                    ..Default::default()
+                },
+                TableColumnSetup {
+                    name: "Owner",
+                    flags: TableColumnFlags::WIDTH_STRETCH,
+                    ..Default::default()
+                },
+                TableColumnSetup {
+                    name: "Reports",
+                    flags: TableColumnFlags::WIDTH_STRETCH,
+                    ..Default::default()
+                },
+                TableColumnSetup {
+                    name: "Actions",
+                    flags: TableColumnFlags::WIDTH_FIXED,
+                    init_width_or_weight: 100.0,
+                    ..Default::default()
+                },
+            ],
+        ) {
+            let mut moderated = None;
+            if let Some(contents) = self.contents.as_ref() {
+                for content in contents {
+                    ui.table_next_row();
+                    ui.table_next_column();
+                    ui.text(format!("{}", content.id));
+                    ui.table_next_column();
+                    ui.text(format!("{}", content.type_id));
+                    ui.table_next_column();
+                    ui.text(&content.owner);
+                    ui.table_next_column();
+                    ui.text(content.reports.iter().map(|r| format!("{}: {}", r.reporter, r.reason)).join(", "));
+                    ui.table_next_column();
+                    if ui.button(format!("{}##verify{}", ICON_CHECK, content.id)) {
+                        moderated = Some((content.id, false));
+                    }
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Verify content");
+                    }
+                    ui.same_line();
+                    if ui.button(format!("{}##ban{}", ICON_BAN, content.id)) {
+                        moderated = Some((content.id, true));
+                    }
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Ban content");
+                    }
+                    ui.same_line();
+                    if ui.button(format!("{}##delete{}", ICON_TRASH, content.id)) {
+                        self.show_delete_content_confirmation = Some(content.id);
+                    }
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Delete content");
+                    }
+                }
+            }
+            table.end();
+
+            if let Some((content_id, banned)) = moderated {
+                let api_url = format!("http://{}", self.api_server());
+                info!("Moderating content {content_id}, banned: {banned}");
+                let moderated = tokio::runtime::Runtime::new().unwrap().block_on(async {
+                    moderate_content(api_url, content_id, banned)
+                        .await
+                        .inspect_err(|e| {
+                            error!("Error moderating content {}: {}", content_id, e);
+                        })
+                        .is_ok()
+                });
+                if moderated {
+                    self.contents = None;
+                }
+            }
+        }
+    }
+
     fn render_delete_confirmation_modals(&mut self, ui: &imgui::Ui) {
         if let Some(user_id) = self.show_delete_user_confirmation.clone() {
             let popup_name = "Delete User?";
@@ -540,6 +667,40 @@ impl ServerMenu {
             });
         }
 
+        if let Some(user_id) = self.show_ban_user_confirmation.clone() {
+            let popup_name = "Ban User?";
+            ui.open_popup(popup_name);
+            ui.modal_popup_config(popup_name).always_auto_resize(true).build(|| {
+                ui.text(format!("Are you sure you want to ban user {}?", user_id));
+                ui.input_text("Reason", &mut self.ban_reason).build();
+                ui.checkbox("Ban last IP", &mut self.ban_ip);
+                ui.separator();
+                if ui.button("Yes") {
+                    let api_url = format!("http://{}", self.api_server());
+                    info!("Banning user {user_id}");
+                    let banned = tokio::runtime::Runtime::new().unwrap().block_on(async {
+                        ban_user(api_url.clone(), user_id.clone(), self.ban_reason.clone(), self.ban_ip)
+                            .await
+                            .inspect_err(|e| {
+                                error!("Error banning user {}: {}", user_id, e);
+                            })
+                            .is_ok()
+                    });
+                    if banned {
+                        self.users = None;
+                    }
+                    self.show_ban_user_confirmation = None;
+                    self.ban_reason.clear();
+                    ui.close_current_popup();
+                }
+                ui.same_line();
+                if ui.button("No") {
+                    self.show_ban_user_confirmation = None;
+                    ui.close_current_popup();
+                }
+            });
+        }
+
         if let Some(game_id) = self.show_delete_game_confirmation {
             let popup_name = "Delete Game?";
             ui.open_popup(popup_name);
@@ -569,6 +730,36 @@ impl ServerMenu {
                 }
             });
         }
+
+        if let Some(content_id) = self.show_delete_content_confirmation {
+            let popup_name = "Delete Content?";
+            ui.open_popup(popup_name);
+            ui.modal_popup_config(popup_name).always_auto_resize(true).build(|| {
+                ui.text(format!("Are you sure you want to delete content {}?", content_id));
+                ui.separator();
+                if ui.button("Yes") {
+                    let api_url = format!("http://{}", self.api_server());
+                    let deleted = tokio::runtime::Runtime::new().unwrap().block_on(async {
+                        delete_content(api_url.clone(), content_id)
+                            .await
+                            .inspect_err(|e| {
+                                error!("Error deleting content {}: {}", content_id, e);
+                            })
+                            .is_ok()
+                    });
+                    if deleted {
+                        self.contents = None;
+                    }
+                    self.show_delete_content_confirmation = None;
+                    ui.close_current_popup();
+                }
+                ui.same_line();
+                if ui.button("No") {
+                    self.show_delete_content_confirmation = None;
+                    ui.close_current_popup();
+                }
+            });
+        }
     }
 
     fn download_server_modal(&mut self, ui: &imgui::Ui) {
@@ -675,6 +866,17 @@ async fn games_admin_client(
     }))
 }
 
+async fn content_admin_client(
+    api_server_url: String,
+) -> anyhow::Result<content::content_admin_client::ContentAdminClient<tonic::service::interceptor::InterceptedService<Channel, impl tonic::service::Interceptor>>> {
+    let channel = tonic::transport::Channel::from_shared(api_server_url)?.connect().await?;
+
+    Ok(content::content_admin_client::ContentAdminClient::with_interceptor(channel, |mut req: Request<()>| {
+        req.metadata_mut().insert("authorization", unsafe { ADMIN_TOKEN.parse().unwrap() });
+        Ok(req)
+    }))
+}
+
 async fn load_users(api_server_url: String) -> anyhow::Result<Vec<User>> {
     let mut client = users_admin_client(api_server_url).await?;
     let resp = client.list(users::ListRequest::default()).await?;
@@ -688,6 +890,20 @@ async fn delete_user(api_server_url: String, user_id: String) -> anyhow::Result<
     Ok(())
 }
 
+async fn ban_user(api_server_url: String, user_id: String, reason: String, ban_ip: bool) -> anyhow::Result<()> {
+    let mut client = users_admin_client(api_server_url).await?;
+    let _resp = client
+        .ban(users::BanRequest {
+            id: user_id,
+            reason,
+            banned_by: String::from("launcher"),
+            expires_at: String::new(),
+            ban_ip,
+        })
+        .await?;
+    Ok(())
+}
+
 async fn load_games(api_server_url: String) -> anyhow::Result<Vec<Game>> {
     let mut client = games_admin_client(api_server_url).await?;
     let resp = client.list(games::ListRequest::default()).await?;
@@ -700,3 +916,26 @@ async fn delete_game(api_server_url: String, game_id: u32) -> anyhow::Result<()>
     let _resp = client.delete(games::DeleteRequest { id: game_id }).await?;
     Ok(())
 }
+
+async fn load_reported_contents(api_server_url: String) -> anyhow::Result<Vec<content::Content>> {
+    let mut client = content_admin_client(api_server_url).await?;
+    let resp = client.list_reported(content::ListReportedRequest { type_id: 0, threshold: 1 }).await?;
+    let contents = resp.into_inner().contents;
+    Ok(contents)
+}
+
+async fn moderate_content(api_server_url: String, content_id: i64, banned: bool) -> anyhow::Result<()> {
+    let mut client = content_admin_client(api_server_url).await?;
+    if banned {
+        let _resp = client.ban(content::BanRequest { id: content_id }).await?;
+    } else {
+        let _resp = client.verify(content::VerifyRequest { id: content_id }).await?;
+    }
+    Ok(())
+}
+
+async fn delete_content(api_server_url: String, content_id: i64) -> anyhow::Result<()> {
+    let mut client = content_admin_client(api_server_url).await?;
+    let _resp = client.delete(content::DeleteRequest { id: content_id }).await?;
+    Ok(())
+}
@@ -43,6 +43,12 @@ impl From<ConnectionID> for u32 {
     }
 }
 
+impl From<u32> for ConnectionID {
+    fn from(value: u32) -> Self {
+        ConnectionID(value)
+    }
+}
+
 impl FromStr for ConnectionID {
     type Err = std::num::ParseIntError;
 
@@ -55,6 +61,33 @@ impl FromStr for ConnectionID {
 #[derive(Hash, PartialEq, Eq, Clone, Copy, Debug)]
 struct Signature(u32);
 
+/// Number of sequence IDs the handlers of a sent response wait for its ack.
+///
+/// Handlers of older responses are dropped as lost. The window is far smaller than the range of the wrapping sequence
+/// ID, so a lost response's handlers never run for a later packet that got the same sequence ID.
+const ACK_WINDOW: u16 = 0x100;
+
+/// A callback waiting for a client to acknowledge a response.
+type AckHandler = Box<dyn FnOnce() + Send>;
+
+/// The ack handlers of a client.
+#[derive(Default)]
+struct AckHandlers {
+    /// Handlers registered while handling the current request.
+    current: Vec<AckHandler>,
+    /// Handlers of sent responses, by the sequence ID of the response's last packet.
+    sent: HashMap<u16, Vec<AckHandler>>,
+}
+
+impl std::fmt::Debug for AckHandlers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AckHandlers")
+            .field("current", &self.current.len())
+            .field("sent", &self.sent.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
 /// Holds information about a client connection.
 #[derive(Debug)]
 pub struct ClientInfo<T = ()> {
@@ -76,6 +109,8 @@ pub struct ClientInfo<T = ()> {
     address: SocketAddr,
     /// The time the client was last seen.
     last_seen: Instant,
+    /// Callbacks run once responses were acknowledged.
+    ack_handlers: AckHandlers,
     /// The client's connection ID, if available.
     pub connection_id: Option<ConnectionID>,
     /// The client's user ID, if available.
@@ -103,6 +138,7 @@ impl<T> ClientInfo<T> {
             address,
             additional: Default::default(),
             last_seen: std::time::Instant::now(),
+            ack_handlers: AckHandlers::default(),
             connection_id: None,
         }
     }
@@ -116,4 +152,91 @@ impl<T> ClientInfo<T> {
     pub fn seen(&mut self) {
         self.last_seen = std::time::Instant::now();
     }
+
+    /// Runs `handler` once the client acknowledged the response to the request that's being handled.
+    ///
+    /// The handler is dropped without being run if the request fails or its response is lost.
+    pub fn on_response_ack(&mut self, handler: impl FnOnce() + Send + 'static) {
+        self.ack_handlers.current.push(Box::new(handler));
+    }
+
+    /// Waits for the ack of the packet with the given sequence ID to run the handlers of the current request.
+    ///
+    /// Handlers of responses that fell out of the [`ACK_WINDOW`] are dropped.
+    pub(crate) fn response_sent(&mut self, sequence: u16) {
+        self.ack_handlers.sent.retain(|sent, _| sequence.wrapping_sub(*sent) < ACK_WINDOW);
+        let handlers = std::mem::take(&mut self.ack_handlers.current);
+        if !handlers.is_empty() {
+            self.ack_handlers.sent.insert(sequence, handlers);
+        }
+    }
+
+    /// Drops the handlers of the current request that weren't bound to a sent response.
+    pub(crate) fn drop_ack_handlers(&mut self) {
+        self.ack_handlers.current.clear();
+    }
+
+    /// Runs the handlers waiting for the ack of the packet with the given sequence ID.
+    pub(crate) fn acknowledged(&mut self, sequence: u16) {
+        for handler in self.ack_handlers.sent.remove(&sequence).into_iter().flatten() {
+            handler();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU32;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn response_ack() {
+        let mut ci = ClientInfo::<()>::new("127.0.0.1:2".parse().unwrap());
+        let acked = Arc::new(AtomicU32::new(0));
+        let register = |ci: &mut ClientInfo, value| {
+            let acked = Arc::clone(&acked);
+            ci.on_response_ack(move || acked.store(value, Ordering::SeqCst));
+        };
+
+        register(&mut ci, 1);
+        ci.drop_ack_handlers();
+        register(&mut ci, 2);
+        ci.response_sent(7);
+        ci.acknowledged(6);
+        assert_eq!(acked.load(Ordering::SeqCst), 0);
+        ci.acknowledged(7);
+        assert_eq!(acked.load(Ordering::SeqCst), 2);
+        // handlers run only once
+        acked.store(0, Ordering::SeqCst);
+        ci.acknowledged(7);
+        assert_eq!(acked.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn lost_response_ack() {
+        let mut ci = ClientInfo::<()>::new("127.0.0.1:2".parse().unwrap());
+        let acked = Arc::new(AtomicU32::new(0));
+        let register = |ci: &mut ClientInfo, value| {
+            let acked = Arc::clone(&acked);
+            ci.on_response_ack(move || acked.store(value, Ordering::SeqCst));
+        };
+
+        // the ack of the response with sequence ID 0xfff0 is lost
+        register(&mut ci, 1);
+        ci.response_sent(0xfff0);
+        register(&mut ci, 2);
+        ci.response_sent(0xfff0_u16.wrapping_add(ACK_WINDOW - 1));
+        register(&mut ci, 3);
+        ci.response_sent(0xfff0_u16.wrapping_add(ACK_WINDOW));
+        assert_eq!(ci.ack_handlers.sent.len(), 2);
+
+        // a later packet with the same sequence ID doesn't run its handlers
+        ci.acknowledged(0xfff0);
+        assert_eq!(acked.load(Ordering::SeqCst), 0);
+        ci.acknowledged(0xfff0_u16.wrapping_add(ACK_WINDOW - 1));
+        assert_eq!(acked.load(Ordering::SeqCst), 2);
+    }
 }
@@ -1,4 +1,5 @@
 /// This module handles the PRUDP protocol, which is a custom reliable UDP protocol.
+pub mod client;
 pub mod packet;
 
 use std::cell::RefCell;
@@ -8,6 +9,7 @@ use std::net::SocketAddr;
 use std::net::UdpSocket;
 use std::net::{self};
 use std::sync::atomic::AtomicU32;
+use std::sync::mpsc::Receiver;
 use std::time::Duration;
 use std::time::Instant;
 
@@ -22,6 +24,7 @@ use self::packet::PacketType;
 use self::packet::QPacket;
 use self::packet::StreamHandler;
 use self::packet::StreamHandlerRegistry;
+use self::packet::StreamType;
 use self::packet::VPort;
 use crate::kerberos::KerberosTicketInternal;
 use crate::rmc::basic::ReadStream;
@@ -47,6 +50,12 @@ impl<T> ClientRegistry<T> {
     pub fn client_by_connection_id(&self, conn_id: ConnectionID) -> Option<&RefCell<ClientInfo<T>>> {
         self.connection_id_session_ids.get(&conn_id).and_then(|sig| self.clients.get(&sig.0))
     }
+
+    /// Returns the number of connected clients.
+    #[must_use]
+    pub fn client_count(&self) -> usize {
+        self.clients.len()
+    }
 }
 
 /// A PRUDP server.
@@ -67,6 +76,8 @@ where
     pub expired_client_handler: Option<ECH>,
     /// A handler for disconnected clients.
     pub disconnect_handler: Option<DH>,
+    /// Receives ids of users whose connections should be closed.
+    pub kick_receiver: Option<Receiver<u32>>,
     next_conn_id: AtomicU32,
 }
 
@@ -93,6 +104,7 @@ where
             user_handler: None,
             expired_client_handler: None,
             disconnect_handler: None,
+            kick_receiver: None,
             next_conn_id: AtomicU32::new(0x3AAA_AAAA),
         }
     }
@@ -109,12 +121,18 @@ where
         Ok(())
     }
 
+    /// Returns the address the server is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.as_ref().expect("UDP socket required").local_addr()
+    }
+
     /// Starts the server's main loop.
     pub fn serve(mut self) {
         let socket = self.socket.as_ref().expect("UDP socket required").try_clone().expect("Couldn't clone socket");
         socket.set_read_timeout(Some(Duration::from_secs(1))).expect("error setting read timeout");
         let mut buf = vec![0u8; 1024];
         'outer: loop {
+            self.kick_clients();
             let (nread, client) = match socket.recv_from(&mut buf) {
                 Ok(x) => x,
                 Err(e) => {
@@ -157,6 +175,11 @@ where
         debug!(logger, "packet: {:?}", packet);
         if packet.flags.contains(PacketFlag::Ack) {
             debug!(logger, "Received ACK");
+            if matches!(packet.packet_type, PacketType::Data) {
+                if let Some(ci) = self.client_registry.clients.get(&packet.signature) {
+                    ci.borrow_mut().acknowledged(packet.sequence);
+                }
+            }
             return;
         }
         match packet.packet_type {
@@ -255,10 +278,14 @@ where
                         fragment_id: Some(fid as u8),
                         ..Default::default()
                     };
+                    let sequence = ci.server_sequence_id;
                     if let Err(e) = self.send_response(&logger, &client, resp, ci) {
                         error!(logger, "Error sending response"; "error" => %e);
                     } else {
                         trace!(logger, "Send response");
+                        if fid == 0 {
+                            ci.response_sent(sequence);
+                        }
                     }
                 }
             }
@@ -269,6 +296,7 @@ where
                 error!(logger, "Handler failed");
             }
         }
+        ci.drop_ack_handlers();
     }
 
     /// Handles a SYN packet.
@@ -391,6 +419,52 @@ where
         self.send_packet(logger, src, resp)
     }
 
+    /// Disconnects the clients of users received on the kick channel.
+    ///
+    /// Kicked clients are handed to the disconnect handler like clients that disconnected on their own.
+    fn kick_clients(&mut self) {
+        let Some(receiver) = self.kick_receiver.as_ref() else {
+            return;
+        };
+        let user_ids: Vec<u32> = receiver.try_iter().collect();
+        if user_ids.is_empty() {
+            return;
+        }
+        let kicked: Vec<_> = self
+            .client_registry
+            .clients
+            .extract_if(|_k, v| v.try_borrow().map(|ci| ci.user_id.is_some_and(|uid| user_ids.contains(&uid))).unwrap_or(false))
+            .map(|(_, ci)| ci.into_inner())
+            .collect();
+        for mut ci in kicked {
+            let logger = self.logger.new(o!("client" => ci.address, "pid" => ci.user_id));
+            info!(logger, "Kicking client");
+            if let Some(conn_id) = ci.connection_id {
+                self.client_registry.connection_id_session_ids.remove(&conn_id);
+            }
+            let packet = QPacket {
+                source: VPort {
+                    port: self.ctx.vport,
+                    stream_type: StreamType::RVSec,
+                },
+                // the port clients connect from
+                destination: VPort {
+                    port: 15,
+                    stream_type: StreamType::RVSec,
+                },
+                packet_type: PacketType::Disconnect,
+                ..Default::default()
+            };
+            let address = ci.address;
+            if let Err(e) = self.send_response(&logger, &address, packet, &mut ci) {
+                error!(logger, "Error sending disconnect"; "error" => %e);
+            }
+            if let Some(handler) = self.disconnect_handler.as_mut() {
+                (handler)(ci);
+            }
+        }
+    }
+
     /// Clears expired clients from the client registry.
     fn clear_clients(&mut self) {
         let now = Instant::now();
@@ -0,0 +1,301 @@
+/// This module implements a minimal, blocking PRUDP client.
+///
+/// It opens anonymous connections (without a Kerberos ticket) and calls RMC methods one at a time. Lost packets are
+/// not retransmitted, so it is meant for probes and tests rather than for playing.
+use std::collections::BTreeMap;
+use std::io;
+use std::net::ToSocketAddrs;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use derive_more::Display;
+use derive_more::Error as DeriveError;
+use derive_more::From;
+
+use super::packet::PacketFlag;
+use super::packet::PacketType;
+use super::packet::QPacket;
+use super::packet::StreamType;
+use super::packet::VPort;
+use crate::rmc;
+use crate::rmc::Packet;
+use crate::rmc::Request;
+use crate::Context;
+
+const CLIENT_PORT: u8 = 15;
+
+/// Errors that can occur on a client connection.
+#[derive(Debug, Display, DeriveError, From)]
+pub enum Error {
+    /// An I/O error occurred, including timeouts while waiting for the service.
+    #[display("I/O error {_0}")]
+    IO(#[error(source)] io::Error),
+
+    /// A received packet couldn't be parsed.
+    #[display("Invalid packet: {_0}")]
+    #[from(ignore)]
+    InvalidPacket(#[error(ignore)] String),
+
+    /// The service didn't accept the connection.
+    #[display("Handshake failed: {_0}")]
+    #[from(ignore)]
+    Handshake(#[error(ignore)] &'static str),
+
+    /// The RMC response couldn't be parsed or reported a known error.
+    #[display("RMC error {_0}")]
+    Rmc(#[error(source)] rmc::Error),
+
+    /// The RMC response reported an unknown error code.
+    #[display("Error code {_0:#x}")]
+    #[from(ignore)]
+    ErrorCode(#[error(ignore)] u32),
+}
+
+/// An anonymous PRUDP connection to a service.
+pub struct Client {
+    ctx: Context,
+    socket: UdpSocket,
+    session_id: u8,
+    signature: u32,
+    sequence: u16,
+    call_id: u32,
+}
+
+impl Client {
+    /// Connects to a service with the SYN/CONNECT handshake.
+    ///
+    /// `timeout` bounds the wait for every reply of the service.
+    pub fn connect<A: ToSocketAddrs>(ctx: Context, addr: A, timeout: Duration) -> Result<Self, Error> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(addr)?;
+        socket.set_read_timeout(Some(timeout))?;
+        let mut client = Self {
+            ctx,
+            socket,
+            session_id: rand::random(),
+            signature: 0,
+            sequence: 0,
+            call_id: 0,
+        };
+
+        client.send(QPacket {
+            packet_type: PacketType::Syn,
+            flags: PacketFlag::NeedAck.into(),
+            conn_signature: Some(0),
+            ..client.packet()
+        })?;
+        let syn_ack = client.recv()?;
+        if syn_ack.packet_type != PacketType::Syn || !syn_ack.flags.contains(PacketFlag::Ack) {
+            return Err(Error::Handshake("invalid syn ack"));
+        }
+        client.signature = syn_ack.conn_signature.ok_or(Error::Handshake("missing connection signature"))?;
+
+        client.send(QPacket {
+            packet_type: PacketType::Connect,
+            flags: PacketFlag::NeedAck.into(),
+            conn_signature: Some(rand::random()),
+            ..client.packet()
+        })?;
+        let connect_ack = client.recv()?;
+        if connect_ack.packet_type != PacketType::Connect || !connect_ack.flags.contains(PacketFlag::Ack) {
+            return Err(Error::Handshake("invalid connect ack"));
+        }
+        Ok(client)
+    }
+
+    /// Calls an RMC method and returns the data of the response.
+    ///
+    /// Every data packet of the response is acknowledged, which runs the ack handlers registered on the server.
+    pub fn call(&mut self, protocol_id: u16, method_id: u32, parameters: Vec<u8>) -> Result<Vec<u8>, Error> {
+        self.call_id += 1;
+        let request = Packet::Request(Request {
+            protocol_id,
+            call_id: self.call_id,
+            method_id,
+            parameters,
+        });
+        self.send(QPacket {
+            packet_type: PacketType::Data,
+            flags: PacketFlag::NeedAck | PacketFlag::Reliable,
+            fragment_id: Some(0),
+            payload: request.to_bytes(),
+            ..self.packet()
+        })?;
+
+        // the ack of the request and the fragments of the response can arrive in any order, the last fragment has the id 0
+        let mut fragments = BTreeMap::new();
+        loop {
+            let packet = self.recv()?;
+            if packet.packet_type != PacketType::Data || packet.flags.contains(PacketFlag::Ack) {
+                continue;
+            }
+            self.send(QPacket {
+                packet_type: PacketType::Data,
+                flags: PacketFlag::Ack.into(),
+                fragment_id: packet.fragment_id,
+                sequence: packet.sequence,
+                ..self.packet()
+            })?;
+            let last = packet.fragment_id.unwrap_or_default() == 0;
+            fragments.entry(packet.sequence).or_insert(packet.payload);
+            if last {
+                break;
+            }
+        }
+
+        let payload = fragments.into_values().flatten().collect::<Vec<u8>>();
+        let Packet::Response(response) = Packet::from_bytes(&payload)? else {
+            return Err(Error::Rmc(rmc::Error::InvalidPacketType));
+        };
+        match response.result {
+            Ok(data) => Ok(data.data),
+            Err(e) => Err(rmc::Error::from_error_code(e.error_code).map_or_else(Error::ErrorCode, Error::Rmc)),
+        }
+    }
+
+    /// Closes the connection, not waiting for the service to confirm.
+    pub fn close(mut self) -> Result<(), Error> {
+        self.send(QPacket {
+            packet_type: PacketType::Disconnect,
+            fragment_id: Some(0),
+            ..self.packet()
+        })
+    }
+
+    fn packet(&self) -> QPacket {
+        QPacket {
+            source: VPort {
+                port: CLIENT_PORT,
+                stream_type: StreamType::RVSec,
+            },
+            destination: VPort {
+                port: self.ctx.vport,
+                stream_type: StreamType::RVSec,
+            },
+            ..Default::default()
+        }
+    }
+
+    fn send(&mut self, mut packet: QPacket) -> Result<(), Error> {
+        packet.session_id = self.session_id;
+        packet.signature = self.signature;
+        if !packet.flags.contains(PacketFlag::Ack) {
+            packet.sequence = self.sequence;
+            self.sequence += 1;
+        }
+        self.socket.send(&packet.to_bytes(&self.ctx))?;
+        Ok(())
+    }
+
+    fn recv(&self) -> Result<QPacket, Error> {
+        let mut buf = vec![0u8; 2048];
+        let n = self.socket.recv(&mut buf)?;
+        let data = &buf[..n];
+        let (packet, _) = QPacket::from_bytes(&self.ctx, data).map_err(|e| Error::InvalidPacket(e.to_string()))?;
+        packet.validate(&self.ctx, data).map_err(|e| Error::InvalidPacket(e.to_string()))?;
+        Ok(packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::mpsc;
+    use std::sync::mpsc::Sender;
+
+    use slog::o;
+    use slog::Logger;
+
+    use super::*;
+    use crate::prudp::packet::StreamHandlerRegistry;
+    use crate::prudp::ClientRegistry;
+    use crate::prudp::Server;
+    use crate::rmc::Protocol;
+    use crate::rmc::RVSecHandler;
+    use crate::ClientInfo;
+
+    const ECHO_PROTOCOL_ID: u16 = 0x42;
+
+    /// Answers method 1 with its parameters repeated `REPEAT` times, enough to span several fragments.
+    struct EchoProtocol {
+        acked: Sender<u32>,
+    }
+
+    const REPEAT: usize = 500;
+
+    impl Protocol<()> for EchoProtocol {
+        fn id(&self) -> u16 {
+            ECHO_PROTOCOL_ID
+        }
+
+        fn name(&self) -> String {
+            "EchoProtocol".into()
+        }
+
+        fn num_methods(&self) -> u32 {
+            1
+        }
+
+        fn handle(
+            &self,
+            _logger: &Logger,
+            _ctx: &Context,
+            ci: &mut ClientInfo<()>,
+            request: &Request,
+            _client_registry: &ClientRegistry<()>,
+            _socket: &UdpSocket,
+        ) -> Result<Vec<u8>, rmc::Error> {
+            if request.method_id != 1 {
+                return Err(rmc::Error::UnknownMethod);
+            }
+            let acked = self.acked.clone();
+            let call_id = request.call_id;
+            ci.on_response_ack(move || {
+                let _ = acked.send(call_id);
+            });
+            Ok(request.parameters.repeat(REPEAT))
+        }
+
+        fn method_name(&self, method_id: u32) -> Option<String> {
+            (method_id == 1).then(|| "Echo".into())
+        }
+    }
+
+    fn start_server(acked: Sender<u32>) -> (&'static Context, SocketAddr) {
+        let ctx: &'static Context = Box::leak(Box::new(Context::splinter_cell_blacklist()));
+        let (addr_tx, addr_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let logger = Logger::root(slog::Discard, o!());
+            let mut handler = RVSecHandler::<()>::new(logger.clone());
+            handler.register_protocol(Box::new(EchoProtocol { acked }));
+            let mut registry = StreamHandlerRegistry::new(logger.clone());
+            registry.register(
+                VPort {
+                    port: ctx.vport,
+                    stream_type: StreamType::RVSec,
+                },
+                Box::new(handler),
+            );
+            let mut server: Server<fn(ClientInfo), fn(ClientInfo)> = Server::new(logger, ctx, registry);
+            server.bind("127.0.0.1:0").unwrap();
+            addr_tx.send(server.local_addr().unwrap()).unwrap();
+            server.serve();
+        });
+        (ctx, addr_rx.recv().unwrap())
+    }
+
+    #[test]
+    fn loopback() {
+        let (acked_tx, acked_rx) = mpsc::channel();
+        let (ctx, addr) = start_server(acked_tx);
+        let mut client = Client::connect(ctx.clone(), addr, Duration::from_secs(5)).unwrap();
+
+        let data = client.call(ECHO_PROTOCOL_ID, 1, b"0123456789".to_vec()).unwrap();
+        assert_eq!(data, b"0123456789".repeat(REPEAT));
+        assert_eq!(acked_rx.recv_timeout(Duration::from_secs(5)).unwrap(), 1);
+
+        assert!(matches!(client.call(ECHO_PROTOCOL_ID, 2, Vec::new()), Err(Error::Rmc(rmc::Error::UnknownProtocol))));
+        assert!(matches!(client.call(ECHO_PROTOCOL_ID + 1, 1, Vec::new()), Err(Error::Rmc(rmc::Error::UnknownProtocol))));
+        client.close().unwrap();
+    }
+}
@@ -32,6 +32,12 @@ pub enum StationURLParseError {
     InvalidPort(#[error(source)] std::num::ParseIntError),
 }
 
+#[derive(Debug, derive_more::Error, derive_more::Display, derive_more::From)]
+pub enum DateTimeParseError {
+    InvalidFormat,
+    InvalidNumber(#[error(source)] std::num::ParseIntError),
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct StationURL {
     pub scheme: String,
@@ -164,6 +170,15 @@ impl<V, K> Any<V, K> {
     pub fn new(type_name: K, data: Vec<u8>) -> Self {
         Self { type_name, data, pd: PhantomData }
     }
+
+    pub fn type_name(&self) -> &K {
+        &self.type_name
+    }
+
+    /// Returns the serialized object without the class name.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
 }
 
 impl<V, K: ToString> Any<V, K> {
@@ -198,9 +213,69 @@ impl<V: std::fmt::Debug, K: FromStream> FromStream for Any<V, K> {
     }
 }
 
-#[derive(Clone, Copy, Default, Deserialize)]
+/// Packed date and time. Ordering follows the time, as the fields are packed from year to second.
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub struct DateTime(pub u64);
 
+impl DateTime {
+    #[must_use]
+    pub fn new(year: u64, month: u64, day: u64, hour: u64, minute: u64, second: u64) -> Self {
+        Self((year << 26) | (month << 22) | (day << 17) | (hour << 12) | (minute << 6) | second)
+    }
+
+    /// Returns the current time in UTC.
+    #[must_use]
+    pub fn now() -> Self {
+        let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        Self::from_unix_timestamp(secs)
+    }
+
+    /// Converts seconds since the unix epoch to a UTC date and time.
+    #[must_use]
+    pub fn from_unix_timestamp(secs: u64) -> Self {
+        // days to civil date, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let days = secs / 86_400 + 719_468;
+        let era = days / 146_097;
+        let day_of_era = days % 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = era * 400 + year_of_era + u64::from(month <= 2);
+        let secs = secs % 86_400;
+        Self::new(year, month, day, secs / 3600, secs / 60 % 60, secs % 60)
+    }
+}
+
+/// Parses timestamps in the `YYYY-MM-DD HH:MM:SS` format (e.g. sqlite's `CURRENT_TIMESTAMP`).
+impl FromStr for DateTime {
+    type Err = DateTimeParseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (date, time) = value.split_once(' ').ok_or(DateTimeParseError::InvalidFormat)?;
+        let date = date.split('-').map(str::parse).collect::<Result<Vec<u64>, _>>()?;
+        let time = time.split(':').map(str::parse).collect::<Result<Vec<u64>, _>>()?;
+        let ([year, month, day], [hour, minute, second]) = (date.as_slice(), time.as_slice()) else {
+            return Err(DateTimeParseError::InvalidFormat);
+        };
+        Ok(Self::new(*year, *month, *day, *hour, *minute, *second))
+    }
+}
+
+/// Formats in the `YYYY-MM-DD HH:MM:SS` format, the inverse of the `FromStr` implementation.
+impl Display for DateTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let second = self.0 & 0b11_1111;
+        let minute = (self.0 >> 6) & 0b11_1111;
+        let hour = (self.0 >> 12) & 0b1_1111;
+        let day = (self.0 >> 17) & 0b1_1111;
+        let month = (self.0 >> 22) & 0b1111;
+        let year = self.0 >> 26;
+        write!(f, "{year:04}-{month:02}-{day:02} {hour:02}:{minute:02}:{second:02}")
+    }
+}
+
 impl Debug for DateTime {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let second = self.0 & 0b1_1111;
@@ -238,6 +313,29 @@ impl FromStream for DateTime {
 #[derive(Debug, FromStream, ToStream)]
 pub struct Data;
 
+/// Raw bytes up to the end of the stream, without a length prefix.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BufferTail(pub Vec<u8>);
+
+impl ToStream for BufferTail {
+    fn to_stream<W>(&self, stream: &mut WriteStream<W>) -> io::Result<usize>
+    where
+        W: WriteBytesExt,
+    {
+        stream.write_n_bytes(&self.0)
+    }
+}
+
+impl FromStream for BufferTail {
+    fn from_stream<R>(stream: &mut ReadStream<R>) -> Result<Self, FromStreamError>
+    where
+        R: ReadBytesExt,
+        Self: Sized,
+    {
+        Ok(Self(stream.read_all()?))
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub enum Variant {
     None,
@@ -433,6 +531,32 @@ mod tests {
         assert_eq!(parsed.params.get("type").map(String::as_str), Some("3"));
     }
 
+    #[test]
+    fn parse_datetime() {
+        let parsed: DateTime = "2013-08-20 13:37:42".parse().unwrap();
+        assert_eq!(parsed.0, DateTime::new(2013, 8, 20, 13, 37, 42).0);
+        assert_eq!(parsed.0 >> 26, 2013);
+        assert_eq!((parsed.0 >> 12) & 0b1_1111, 13);
+        assert_eq!(parsed.to_string(), "2013-08-20 13:37:42");
+        assert!("2013-08-20".parse::<DateTime>().is_err());
+        assert!("2013-08-xx 13:37:42".parse::<DateTime>().is_err());
+    }
+
+    #[test]
+    fn datetime_from_unix_timestamp() {
+        assert_eq!(DateTime::from_unix_timestamp(0).to_string(), "1970-01-01 00:00:00");
+        assert_eq!(DateTime::from_unix_timestamp(951_782_400).to_string(), "2000-02-29 00:00:00");
+        assert_eq!(DateTime::from_unix_timestamp(1_376_998_662).to_string(), "2013-08-20 11:37:42");
+        assert!(DateTime::from_unix_timestamp(1_376_998_662) < DateTime::now());
+    }
+
+    #[test]
+    fn buffer_tail() {
+        let tail = BufferTail(vec![1, 2, 3]);
+        assert_eq!(tail.to_bytes(), [1, 2, 3]);
+        assert_eq!(BufferTail::from_bytes(&[4, 5]).unwrap(), BufferTail(vec![4, 5]));
+    }
+
     #[test]
     fn any_type_registration() {
         let mut registry = ClassRegistry::default();